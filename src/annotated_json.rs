@@ -0,0 +1,270 @@
+use crate::error::AnnotatedJsonError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Where each field lives in an annotation export, as JSON pointers (RFC 6901)
+///
+/// `records` points at an array in the file. Without `annotations`, each record is one
+/// annotation and every other pointer is relative to it; annotations are grouped into
+/// books by title and author. With `annotations`, each record is a document: `title` and
+/// `author` are relative to the document and the rest to each of its annotations.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonMapping {
+    #[serde(default)]
+    pub records: String,
+    #[serde(default)]
+    pub annotations: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub text: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub page: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl JsonMapping {
+    /// Built-in mapping by name
+    pub fn preset(name: &str) -> Result<JsonMapping, AnnotatedJsonError> {
+        match name.to_lowercase().as_str() {
+            // Polar's annotation export: one document with its docInfo and annotations
+            "polar" => Ok(JsonMapping {
+                records: String::new(),
+                annotations: Some("/annotations".to_string()),
+                title: "/docInfo/title".to_string(),
+                author: Some("/docInfo/authors".to_string()),
+                text: "/text".to_string(),
+                note: Some("/comment".to_string()),
+                page: Some("/pageNum".to_string()),
+                created_at: Some("/created".to_string()),
+            }),
+            other => Err(AnnotatedJsonError::UnknownPreset(other.to_string())),
+        }
+    }
+
+    /// Load a mapping from a JSON file
+    pub fn from_file(path: &Path) -> Result<JsonMapping, AnnotatedJsonError> {
+        let content = fs::read_to_string(path).map_err(|e| AnnotatedJsonError::ReadError(path.to_path_buf(), e))?;
+        serde_json::from_str(&content).map_err(|e| AnnotatedJsonError::InvalidMapping(e.to_string()))
+    }
+}
+
+/// Import an annotation export file using a mapping
+pub fn parse_file(path: &Path, mapping: &JsonMapping) -> Result<Vec<Book>, AnnotatedJsonError> {
+    if !path.exists() {
+        return Err(AnnotatedJsonError::FileNotFound(path.to_path_buf()));
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| AnnotatedJsonError::ReadError(path.to_path_buf(), e))?;
+    let value: Value = serde_json::from_str(&content)?;
+
+    apply_mapping(&value, mapping)
+}
+
+/// Convert parsed JSON into books according to the mapping
+pub fn apply_mapping(value: &Value, mapping: &JsonMapping) -> Result<Vec<Book>, AnnotatedJsonError> {
+    let records = value
+        .pointer(&mapping.records)
+        .ok_or_else(|| AnnotatedJsonError::InvalidMapping(format!("no value at records pointer {:?}", mapping.records)))?;
+
+    // A single object stands for a one-element list
+    let records = match records {
+        Value::Array(items) => items.iter().collect::<Vec<_>>(),
+        other => vec![other],
+    };
+
+    let mut books: Vec<Book> = Vec::new();
+
+    for record in records {
+        let title = string_at(record, &mapping.title);
+        let author = mapping.author.as_deref().and_then(|p| string_at(record, p));
+
+        let annotations: Vec<&Value> = match mapping.annotations.as_deref() {
+            Some(pointer) => match record.pointer(pointer) {
+                Some(Value::Array(items)) => items.iter().collect(),
+                _ => Vec::new(),
+            },
+            None => vec![record],
+        };
+
+        for annotation in annotations {
+            let Some(text) = string_at(annotation, &mapping.text) else {
+                continue;
+            };
+            let Some(title) = title.clone() else {
+                continue;
+            };
+
+            let index = match books.iter().position(|b| b.title == title && b.author == author) {
+                Some(index) => index,
+                None => {
+                    let mut book = Book::new(title, author.clone());
+                    book.sources = vec![Source::AnnotatedJson];
+                    books.push(book);
+                    books.len() - 1
+                }
+            };
+
+            let position = mapping
+                .page
+                .as_deref()
+                .and_then(|p| string_at(annotation, p))
+                .map(|page| format!("Page {}", page));
+
+            let book = &mut books[index];
+            book.highlights.push(Highlight {
+                id: generate_highlight_id(&book.id, &text, position.as_deref()),
+                text,
+                note: mapping.note.as_deref().and_then(|p| string_at(annotation, p)),
+                location: Location {
+                    chapter: None,
+                    position,
+                    page: None,
+                    raw: None,
+                },
+                created_at: mapping
+                    .created_at
+                    .as_deref()
+                    .and_then(|p| annotation.pointer(p))
+                    .and_then(parse_date),
+                sources: vec![Source::AnnotatedJson],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+    }
+
+    Ok(books)
+}
+
+/// Trimmed, non-empty text at a pointer; numbers are formatted and arrays of strings joined
+fn string_at(value: &Value, pointer: &str) -> Option<String> {
+    let text = match value.pointer(pointer)? {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => return None,
+    };
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// RFC 3339, "YYYY-MM-DD HH:MM:SS", or a Unix timestamp in seconds or milliseconds
+fn parse_date(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s.trim())
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|dt| dt.and_utc())
+            }),
+        Value::Number(n) => {
+            let ts = n.as_i64()?;
+            // Anything past the year 5138 in seconds is really milliseconds
+            if ts > 100_000_000_000 {
+                DateTime::from_timestamp_millis(ts)
+            } else {
+                DateTime::from_timestamp(ts, 0)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    #[test]
+    fn test_polar_preset() {
+        let export = json!({
+            "docInfo": {"title": "Attention Is All You Need", "authors": ["Vaswani", "Shazeer"]},
+            "annotations": [
+                {"type": "TEXT_HIGHLIGHT", "text": "The dominant sequence transduction models", "pageNum": 1,
+                 "comment": "Intro", "created": "2024-03-01T10:00:00Z"},
+                {"type": "AREA_HIGHLIGHT", "pageNum": 3},
+                {"type": "TEXT_HIGHLIGHT", "text": "Scaled Dot-Product Attention", "pageNum": 4, "created": 1709287200000i64}
+            ]
+        });
+
+        let books = apply_mapping(&export, &JsonMapping::preset("polar").unwrap()).unwrap();
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.author.as_deref(), Some("Vaswani, Shazeer"));
+        assert_eq!(book.sources, vec![Source::AnnotatedJson]);
+        assert_eq!(book.highlights.len(), 2);
+
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("Page 1"));
+        assert_eq!(book.highlights[0].note.as_deref(), Some("Intro"));
+        assert_eq!(book.highlights[0].created_at, Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).single());
+        assert_eq!(book.highlights[1].created_at, Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).single());
+    }
+
+    #[test]
+    fn test_flat_records_grouped_by_book() {
+        let mapping: JsonMapping = serde_json::from_value(json!({
+            "records": "/items",
+            "title": "/book/name",
+            "author": "/book/by",
+            "text": "/quote",
+            "page": "/p"
+        }))
+        .unwrap();
+
+        let export = json!({"items": [
+            {"book": {"name": "SICP", "by": "Abelson"}, "quote": "Programs must be written for people to read", "p": 22},
+            {"book": {"name": "SICP", "by": "Abelson"}, "quote": "  "},
+            {"book": {"name": "TAOCP", "by": "Knuth"}, "quote": "Premature optimization", "p": "xii"}
+        ]});
+
+        let books = apply_mapping(&export, &mapping).unwrap();
+
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].highlights.len(), 1);
+        assert_eq!(books[0].highlights[0].location.position.as_deref(), Some("Page 22"));
+        assert_eq!(books[1].highlights[0].location.position.as_deref(), Some("Page xii"));
+        assert_eq!(books[1].highlights[0].note, None);
+    }
+
+    #[test]
+    fn test_missing_records_pointer() {
+        let mapping = JsonMapping::preset("polar").unwrap();
+        let mapping = JsonMapping {
+            records: "/nope".to_string(),
+            ..mapping
+        };
+
+        let result = apply_mapping(&json!({}), &mapping);
+        assert!(matches!(result, Err(AnnotatedJsonError::InvalidMapping(_))));
+    }
+
+    #[test]
+    fn test_unknown_preset() {
+        assert!(matches!(JsonMapping::preset("nope"), Err(AnnotatedJsonError::UnknownPreset(_))));
+    }
+}
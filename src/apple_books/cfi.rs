@@ -0,0 +1,206 @@
+//! EPUB CFIs, the locations Apple Books stores in ZANNOTATIONLOCATION
+//!
+//! A CFI is a path of child indexes through the package document to a spine item, then
+//! (after `!`) through that item's XHTML to the highlighted text, e.g.
+//! `epubcfi(/6/12[chapter7]!/4/2/10,/1:0,/1:22)`: spine item 5 (`chapter7`), the 5th
+//! element of the body's first child, characters 0 to 22 of its text. Elements have
+//! even indexes and text nodes odd ones; `[...]` assertions and `:n` character offsets
+//! can follow any step. Only what's needed to place a highlight is kept.
+
+use std::fmt;
+
+/// The parts of an EPUB CFI that place a highlight in the book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfi {
+    /// 0-based index in the spine
+    pub spine_index: usize,
+    /// The itemref's id, when the CFI carries it as an assertion
+    pub idref: Option<String>,
+    /// Child indexes from the content document's root to the start of the highlight
+    pub path: Vec<u32>,
+    /// Character offset of the start in its text node
+    pub start_offset: Option<u32>,
+    /// Character offset of the end, for a range
+    pub end_offset: Option<u32>,
+}
+
+/// One step of a CFI path
+struct Step {
+    index: u32,
+    assertion: Option<String>,
+    offset: Option<u32>,
+}
+
+impl Cfi {
+    /// Parse an `epubcfi(...)`; None for anything else (e.g. a PDF location)
+    pub fn parse(cfi: &str) -> Option<Self> {
+        let inner = cfi.trim().strip_prefix("epubcfi(")?.strip_suffix(')')?;
+        // A range is "parent,start,end", with start and end relative to the parent
+        let parts = split_range(inner);
+        let parent = parts.first()?;
+        let (start, end) = (parts.get(1).copied(), parts.get(2).copied());
+
+        let mut indirections = parent.split('!');
+        let package = parse_steps(indirections.next()?)?;
+        let mut path = Vec::new();
+        for content in indirections {
+            path.extend(parse_steps(content)?);
+        }
+
+        // The first package step is the spine element, the second the itemref
+        let itemref = package.get(1)?;
+        // Elements have even indexes, starting at 2
+        if itemref.index < 2 || !itemref.index.is_multiple_of(2) {
+            return None;
+        }
+
+        let mut start_offset = path.last().and_then(|step| step.offset);
+        if let Some(start) = start {
+            let steps = parse_steps(start)?;
+            start_offset = steps.last().and_then(|step| step.offset);
+            path.extend(steps);
+        }
+        let end_offset = match end {
+            Some(end) => parse_steps(end)?.last().and_then(|step| step.offset),
+            None => None,
+        };
+
+        Some(Cfi {
+            spine_index: itemref.index as usize / 2 - 1,
+            idref: itemref
+                .assertion
+                .as_deref()
+                .map(|a| a.split(';').next().unwrap_or_default().to_string())
+                .filter(|a| !a.is_empty()),
+            path: path.iter().map(|step| step.index).collect(),
+            start_offset,
+            end_offset,
+        })
+    }
+
+    /// Key that orders CFIs as their highlights appear in the book
+    pub fn sort_key(&self) -> Vec<u32> {
+        std::iter::once(self.spine_index as u32)
+            .chain(self.path.iter().copied())
+            .chain(std::iter::once(self.start_offset.unwrap_or(0)))
+            .collect()
+    }
+
+    /// Which element of its parent the highlight starts in, counting from 1; the text
+    /// node steps after it don't count
+    pub fn paragraph(&self) -> Option<u32> {
+        self.path.iter().rev().find(|index| index.is_multiple_of(2)).map(|index| index / 2)
+    }
+}
+
+/// Compact display, e.g. "ch. 6, ¶5": the spine item and the paragraph in it
+impl fmt::Display for Cfi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ch. {}", self.spine_index + 1)?;
+        if let Some(paragraph) = self.paragraph() {
+            write!(f, ", ¶{}", paragraph)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a range CFI at its commas, leaving those inside assertions alone
+fn split_range(cfi: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut escaped, mut start) = (0, false, 0);
+    for (i, c) in cfi.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '^' => escaped = true,
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&cfi[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&cfi[start..]);
+    parts
+}
+
+/// The steps of a path like `/4/2[body]/10/1:22`
+fn parse_steps(path: &str) -> Option<Vec<Step>> {
+    path.split('/').skip(1).map(parse_step).collect()
+}
+
+fn parse_step(step: &str) -> Option<Step> {
+    let digits = step.find(|c: char| !c.is_ascii_digit()).unwrap_or(step.len());
+    let index = step[..digits].parse().ok()?;
+    let mut rest = &step[digits..];
+
+    let mut offset = None;
+    if let Some(after) = rest.strip_prefix(':') {
+        let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+        offset = Some(after[..digits].parse().ok()?);
+        rest = &after[digits..];
+    }
+    // Text assertions can hold escaped brackets; only an itemref's id is used
+    let assertion = rest
+        .strip_prefix('[')
+        .and_then(|a| a.split_once(']'))
+        .map(|(assertion, _)| assertion.to_string());
+
+    Some(Step { index, assertion, offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_with_assertions() {
+        let cfi = Cfi::parse("epubcfi(/6/12[chapter7]!/4/2/10,/1:0,/1:22)").unwrap();
+
+        assert_eq!(cfi.spine_index, 5);
+        assert_eq!(cfi.idref.as_deref(), Some("chapter7"));
+        assert_eq!(cfi.path, vec![4, 2, 10, 1]);
+        assert_eq!((cfi.start_offset, cfi.end_offset), (Some(0), Some(22)));
+        assert_eq!(cfi.to_string(), "ch. 6, ¶5");
+
+        let cfi = Cfi::parse("epubcfi(/6/24[x9780141_ch3]!/4/2[ch3]/10,/1:0,/3:12)").unwrap();
+        assert_eq!((cfi.spine_index, cfi.idref.as_deref()), (11, Some("x9780141_ch3")));
+        assert_eq!(cfi.end_offset, Some(12));
+        assert_eq!(Cfi::parse("epubcfi(/6/2[cover;s=a]!/4)").unwrap().idref.as_deref(), Some("cover"));
+    }
+
+    #[test]
+    fn test_parse_without_assertions_or_range() {
+        let cfi = Cfi::parse("epubcfi(/6/26!/4/2/1:37)").unwrap();
+
+        assert_eq!((cfi.spine_index, cfi.idref.as_deref()), (12, None));
+        assert_eq!(cfi.path, vec![4, 2, 1]);
+        assert_eq!((cfi.start_offset, cfi.end_offset), (Some(37), None));
+        assert_eq!(cfi.to_string(), "ch. 13, ¶1");
+
+        // Offsets before a text assertion
+        let cfi = Cfi::parse("epubcfi(/6/4!/4/8/3:5[yes,%20sir])").unwrap();
+        assert_eq!((cfi.path, cfi.start_offset), (vec![4, 8, 3], Some(5)));
+
+        assert_eq!(Cfi::parse("epubcfi(/6/7!/4)"), None);
+        assert_eq!(Cfi::parse("epubcfi(/6/4!/4/x)"), None);
+        assert_eq!(Cfi::parse("page 12"), None);
+    }
+
+    #[test]
+    fn test_sort_key_follows_reading_order() {
+        let cfis = [
+            "epubcfi(/6/4!/4/2/1:0)",
+            "epubcfi(/6/4!/4/2/1:15)",
+            "epubcfi(/6/4[ch1]!/4/10,/1:3,/1:9)",
+            "epubcfi(/6/12!/4/2/1:0)",
+            "epubcfi(/6/26!/4/2/1:0)",
+        ];
+        let mut shuffled = cfis.to_vec();
+        shuffled.reverse();
+        shuffled.sort_by_key(|cfi| Cfi::parse(cfi).unwrap().sort_key());
+
+        assert_eq!(shuffled, cfis);
+    }
+}
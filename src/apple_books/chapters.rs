@@ -0,0 +1,422 @@
+//! Chapter names for Apple Books highlights
+//!
+//! Recent versions of Apple Books leave ZFUTUREPROOFING5 empty, so the chapter has to
+//! come from the highlight's EPUB CFI (ZANNOTATIONLOCATION). Its first steps name the
+//! spine item the highlight is in, e.g. `epubcfi(/6/26[chap12]!/4/2/1:0)` is the 12th
+//! item (0-based), `chap12`. When the book's EPUB is on this Mac (ZPATH in the library,
+//! an unpacked `.epub` directory or a zipped one) that item is looked up in the table
+//! of contents; otherwise the chapter is "Section 13", which still groups and orders
+//! highlights.
+
+use super::cfi::Cfi;
+use crate::model::Highlight;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Fill in the chapters Apple Books didn't record, from the CFIs and, when found, the
+/// book's table of contents at `epub`
+pub fn fill_chapters(highlights: &mut [Highlight], epub: Option<&Path>) {
+    if highlights.iter().all(|h| h.location.chapter.is_some()) {
+        return;
+    }
+
+    let toc = epub.and_then(TableOfContents::load);
+    for highlight in highlights.iter_mut().filter(|h| h.location.chapter.is_none()) {
+        let Some(cfi) = highlight.location.raw.as_deref().and_then(Cfi::parse) else {
+            continue;
+        };
+        let chapter = toc
+            .as_ref()
+            .and_then(|toc| toc.chapter(&cfi))
+            .unwrap_or_else(|| format!("Section {}", cfi.spine_index + 1));
+        highlight.location.chapter = Some(chapter);
+    }
+}
+
+/// A book's spine and the titles its table of contents gives to spine items
+#[derive(Debug, Default)]
+pub struct TableOfContents {
+    /// (idref, file path from the book's root) in reading order
+    spine: Vec<(String, String)>,
+    /// The first table-of-contents title pointing into each file
+    titles: HashMap<String, String>,
+}
+
+impl TableOfContents {
+    /// Read an EPUB's table of contents: the EPUB 3 nav document, else the NCX
+    ///
+    /// None when the book can't be read or has neither.
+    pub fn load(epub: &Path) -> Option<Self> {
+        let book = EpubFiles::open(epub)?;
+        let container = book.read("META-INF/container.xml")?;
+        let opf_path = attr(&container, "full-path")?;
+        let opf = book.read(&opf_path)?;
+        let opf_dir = parent_dir(&opf_path);
+
+        // Manifest items by id: (path from the root, media type, properties)
+        let mut manifest: HashMap<String, (String, String, String)> = HashMap::new();
+        for attrs in tags("item", &opf) {
+            if let (Some(id), Some(href)) = (attr(&attrs, "id"), attr(&attrs, "href")) {
+                manifest.insert(
+                    id,
+                    (
+                        resolve(&opf_dir, &href),
+                        attr(&attrs, "media-type").unwrap_or_default(),
+                        attr(&attrs, "properties").unwrap_or_default(),
+                    ),
+                );
+            }
+        }
+
+        let spine: Vec<(String, String)> = tags("itemref", &opf)
+            .iter()
+            .filter_map(|attrs| attr(attrs, "idref"))
+            .filter_map(|idref| manifest.get(&idref).map(|(path, _, _)| (idref.clone(), path.clone())))
+            .collect();
+
+        let nav = manifest
+            .values()
+            .find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "nav"));
+        let ncx_id = tags("spine", &opf).first().and_then(|attrs| attr(attrs, "toc"));
+        let ncx = ncx_id
+            .and_then(|id| manifest.get(&id))
+            .or_else(|| manifest.values().find(|(_, media_type, _)| media_type == "application/x-dtbncx+xml"));
+
+        let entries = match (nav, ncx) {
+            (Some((path, _, _)), _) => nav_entries(&book.read(path)?, &parent_dir(path)),
+            (None, Some((path, _, _))) => ncx_entries(&book.read(path)?, &parent_dir(path)),
+            (None, None) => return None,
+        };
+
+        let mut titles = HashMap::new();
+        for (path, title) in entries {
+            titles.entry(path).or_insert(title);
+        }
+        Some(Self { spine, titles })
+    }
+
+    /// The chapter a spine position is in: the title of its spine item, or of the
+    /// nearest item before it that has one (a chapter split over several files)
+    pub fn chapter(&self, cfi: &Cfi) -> Option<String> {
+        let index = cfi
+            .idref
+            .as_ref()
+            .and_then(|idref| self.spine.iter().position(|(id, _)| id == idref))
+            .unwrap_or(cfi.spine_index);
+        let items = self.spine.get(..=index)?;
+        items.iter().rev().find_map(|(_, path)| self.titles.get(path).cloned())
+    }
+}
+
+/// The files of an EPUB, unpacked (as Apple Books keeps them) or zipped
+enum EpubFiles {
+    Dir(PathBuf),
+    Zip(PathBuf),
+}
+
+impl EpubFiles {
+    fn open(path: &Path) -> Option<Self> {
+        if path.is_dir() {
+            Some(Self::Dir(path.to_path_buf()))
+        } else if path.is_file() {
+            Some(Self::Zip(path.to_path_buf()))
+        } else {
+            None
+        }
+    }
+
+    /// A file by its path from the book's root
+    fn read(&self, name: &str) -> Option<String> {
+        match self {
+            Self::Dir(dir) => fs::read_to_string(dir.join(name)).ok(),
+            Self::Zip(path) => {
+                let mut archive = zip::ZipArchive::new(fs::File::open(path).ok()?).ok()?;
+                let mut file = archive.by_name(name).ok()?;
+                let mut content = String::new();
+                file.read_to_string(&mut content).ok()?;
+                Some(content)
+            }
+        }
+    }
+}
+
+/// (file path, title) of each entry in an EPUB 3 nav document's table of contents
+fn nav_entries(nav: &str, nav_dir: &str) -> Vec<(String, String)> {
+    // The toc nav, not the landmarks or page list
+    static TOC: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let toc = TOC
+        .get_or_init(|| {
+            Regex::new(r#"(?s)<nav\b[^>]*epub:type\s*=\s*["'][^"']*\btoc\b[^"']*["'][^>]*>(.*?)</nav>"#).unwrap()
+        })
+        .captures(nav)
+        .map(|c| c[1].to_string())
+        .unwrap_or_else(|| nav.to_string());
+
+    let link = LINK.get_or_init(|| Regex::new(r#"(?s)<a\b([^>]*)>(.*?)</a>"#).unwrap());
+    link.captures_iter(&toc)
+        .filter_map(|c| {
+            let href = attr(&c[1], "href")?;
+            let title = text_content(&c[2]);
+            (!title.is_empty()).then(|| (resolve(nav_dir, &href), title))
+        })
+        .collect()
+}
+
+/// (file path, title) of each navPoint in an NCX, in document order
+fn ncx_entries(ncx: &str, ncx_dir: &str) -> Vec<(String, String)> {
+    static POINT: OnceLock<Regex> = OnceLock::new();
+    let point = POINT
+        .get_or_init(|| Regex::new(r#"(?s)<navLabel>\s*<text>(.*?)</text>\s*</navLabel>\s*<content\b([^>]*)>"#).unwrap());
+    point
+        .captures_iter(ncx)
+        .filter_map(|c| {
+            let src = attr(&c[2], "src")?;
+            let title = text_content(&c[1]);
+            (!title.is_empty()).then(|| (resolve(ncx_dir, &src), title))
+        })
+        .collect()
+}
+
+/// The attributes of every `<name ...>` tag, with or without a namespace prefix
+fn tags(name: &str, xml: &str) -> Vec<String> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    TAG.get_or_init(|| Regex::new(r"<(?:\w+:)?(\w+)\b([^>]*)>").unwrap())
+        .captures_iter(xml)
+        .filter(|c| &c[1] == name)
+        .map(|c| c[2].to_string())
+        .collect()
+}
+
+/// The value of the first `name="..."` or `name='...'` attribute in a tag's attributes
+/// (or any markup)
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    ATTR.get_or_init(|| Regex::new(r#"(?:^|\s)([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap())
+        .captures_iter(attrs)
+        .find(|c| &c[1] == name)
+        .and_then(|c| c.get(2).or_else(|| c.get(3)))
+        .map(|value| unescape_xml(value.as_str()))
+}
+
+/// Text with tags stripped and whitespace collapsed
+fn text_content(markup: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let text = TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap()).replace_all(markup, " ");
+    unescape_xml(&text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#160;", " ")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// The directory part of a path inside the book, e.g. "OEBPS/" for "OEBPS/content.opf"
+fn parent_dir(path: &str) -> String {
+    match path.rfind('/') {
+        Some(i) => path[..=i].to_string(),
+        None => String::new(),
+    }
+}
+
+/// A link relative to `base_dir` as a path from the book's root, without its fragment
+fn resolve(base_dir: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    percent_decode(&parts.join("/"))
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{HighlightKind, HighlightStyle, Location, Source};
+
+    #[test]
+    fn test_resolve_paths() {
+        assert_eq!(resolve("OEBPS/", "Text/ch01.xhtml#start"), "OEBPS/Text/ch01.xhtml");
+        assert_eq!(resolve("OEBPS/Nav/", "../Text/ch%2001.xhtml"), "OEBPS/Text/ch 01.xhtml");
+        assert_eq!(resolve("", "./ch02.xhtml"), "ch02.xhtml");
+    }
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+        <container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0">
+          <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+        </container>"#;
+
+    fn write_book(dir: &Path, files: &[(&str, &str)]) {
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    fn highlight(cfi: &str) -> Highlight {
+        Highlight {
+            id: cfi.to_string(),
+            text: "text".to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: Some(cfi.to_string()),
+            },
+            created_at: None,
+            sources: vec![Source::AppleBooks],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    #[test]
+    fn test_chapters_from_nav_document() {
+        let dir = std::env::temp_dir().join(format!("readingsync_epub_{}", uuid::Uuid::new_v4()));
+        write_book(
+            &dir,
+            &[
+                ("META-INF/container.xml", CONTAINER),
+                (
+                    "OEBPS/content.opf",
+                    r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                      <manifest>
+                        <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                        <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+                        <item id="ch1" href="Text/ch1.xhtml" media-type="application/xhtml+xml"/>
+                        <item id="ch1b" href="Text/ch1-part2.xhtml" media-type="application/xhtml+xml"/>
+                        <item id="ch2" href="Text/ch2.xhtml" media-type="application/xhtml+xml"/>
+                      </manifest>
+                      <spine><itemref idref="cover"/><itemref idref="ch1"/><itemref idref="ch1b"/><itemref idref="ch2"/></spine>
+                    </package>"#,
+                ),
+                (
+                    "OEBPS/nav.xhtml",
+                    r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+                      <nav epub:type="toc"><ol>
+                        <li><a href="Text/ch1.xhtml">Economy</a></li>
+                        <li><a href="Text/ch2.xhtml#top"><span>Where I Lived, &amp; What</span>
+                            I Lived For</a></li>
+                      </ol></nav>
+                      <nav epub:type="landmarks"><ol><li><a href="Text/cover.xhtml">Cover</a></li></ol></nav>
+                    </body></html>"#,
+                ),
+            ],
+        );
+
+        let mut highlights = vec![
+            highlight("epubcfi(/6/6!/4/2/1:0)"),
+            highlight("epubcfi(/6/98[ch2]!/4/2/1:0)"),
+            highlight("epubcfi(/6/2!/4/1:0)"),
+            highlight("epubcfi(/6/40!/4/1:0)"),
+        ];
+        fill_chapters(&mut highlights, Some(&dir));
+        let _ = fs::remove_dir_all(&dir);
+
+        let chapters: Vec<Option<&str>> = highlights.iter().map(|h| h.location.chapter.as_deref()).collect();
+        assert_eq!(
+            chapters,
+            vec![
+                // The second file of chapter 1
+                Some("Economy"),
+                // The idref wins over a wrong index
+                Some("Where I Lived, & What I Lived For"),
+                // Before the first chapter: nothing to name it after
+                Some("Section 1"),
+                Some("Section 20"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chapters_from_zipped_ncx() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let path = std::env::temp_dir().join(format!("readingsync_epub_{}.epub", uuid::Uuid::new_v4()));
+        {
+            let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+            let files = [
+                ("META-INF/container.xml", CONTAINER),
+                (
+                    "OEBPS/content.opf",
+                    r#"<opf:package xmlns:opf="http://www.idpf.org/2007/opf" version="2.0">
+                      <opf:manifest>
+                        <opf:item href="toc.ncx" id="ncx" media-type="application/x-dtbncx+xml"/>
+                        <opf:item href="book1.html" id="b1" media-type="application/xhtml+xml"/>
+                        <opf:item href="book2.html" id="b2" media-type="application/xhtml+xml"/>
+                      </opf:manifest>
+                      <opf:spine toc="ncx"><opf:itemref idref="b1"/><opf:itemref idref="b2"/></opf:spine>
+                    </opf:package>"#,
+                ),
+                (
+                    "OEBPS/toc.ncx",
+                    r#"<ncx><navMap>
+                      <navPoint id="p1" playOrder="1"><navLabel><text>Book One</text></navLabel><content src="book1.html"/></navPoint>
+                      <navPoint id="p2" playOrder="2"><navLabel><text>Book Two</text></navLabel><content src="book2.html#s1"/></navPoint>
+                    </navMap></ncx>"#,
+                ),
+            ];
+            for (name, content) in files {
+                zip.start_file(name, SimpleFileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+
+        let toc = TableOfContents::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let toc = toc.unwrap();
+        assert_eq!(toc.chapter(&Cfi::parse("epubcfi(/6/4!/4/2/1:0)").unwrap()).as_deref(), Some("Book Two"));
+        assert_eq!(toc.chapter(&Cfi::parse("epubcfi(/6/2!/4/2/1:0)").unwrap()).as_deref(), Some("Book One"));
+    }
+
+    #[test]
+    fn test_recorded_chapters_are_kept() {
+        let mut highlights = vec![highlight("epubcfi(/6/26!/4/2/1:0)")];
+        highlights[0].location.chapter = Some("Book 10".to_string());
+        fill_chapters(&mut highlights, None);
+        assert_eq!(highlights[0].location.chapter.as_deref(), Some("Book 10"));
+    }
+}
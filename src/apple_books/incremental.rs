@@ -0,0 +1,222 @@
+//! Incremental Apple Books exports
+//!
+//! The annotation database can hold thousands of rows of which a sync changes a few. An
+//! incremental pass remembers the latest ZANNOTATIONMODIFICATIONDATE it saw, next time
+//! reads only the annotations modified after it (`AppleBooksOptions::modified_after`),
+//! and applies them to the previous export by ZANNOTATIONUUID, the `Highlight.id`. Notes
+//! edited since count as modified, so they replace the exported highlight.
+
+use super::sort_highlights;
+use crate::error::AppleBooksError;
+use crate::model::{Book, Highlight, Source};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the last export of Apple Books got to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncrementalState {
+    /// The annotation database read; another one needs a full pass
+    pub annotation_db: PathBuf,
+    /// Latest modification date of the annotations read
+    pub last_modified: DateTime<Utc>,
+}
+
+/// The state file for an export: `library.json` → `library.apple-books-state.json`
+pub fn state_path(output: &Path) -> PathBuf {
+    output.with_extension("apple-books-state.json")
+}
+
+impl IncrementalState {
+    /// Load the state from a file; None if there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>, AppleBooksError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| AppleBooksError::StateError(path.to_path_buf(), e.to_string()))?;
+
+        serde_json::from_str(&content).map_err(|e| AppleBooksError::StateError(path.to_path_buf(), e.to_string()))
+    }
+
+    /// Save the state to a file
+    pub fn save(&self, path: &Path) -> Result<(), AppleBooksError> {
+        let content =
+            serde_json::to_string(self).map_err(|e| AppleBooksError::StateError(path.to_path_buf(), e.to_string()))?;
+
+        fs::write(path, content).map_err(|e| AppleBooksError::StateError(path.to_path_buf(), e.to_string()))
+    }
+}
+
+/// Remove the state file of an export, so the next pass reads everything
+///
+/// For when the export no longer holds the whole library, e.g. after a filtered run.
+pub fn invalidate(output: &Path) -> Result<(), AppleBooksError> {
+    let path = state_path(output);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    fs::remove_file(&path).map_err(|e| AppleBooksError::StateError(path.clone(), e.to_string()))
+}
+
+/// Apply the books of an incremental pass to the previous export
+///
+/// Each book gets its previously exported Apple Books highlights, with the changed ones
+/// replaced by ID, new ones added, and deleted ones dropped unless `include_deleted`.
+/// The result stands in for a full pass, so merging it with the previous export flags
+/// the deletions as `deleted_upstream` just as a full pass would. Highlights already
+/// flagged are left to that merge.
+///
+/// None if a book isn't in the previous export (new, or dropped from it), whose other
+/// highlights only a full pass can read.
+pub fn apply_delta(previous: &[Book], delta: Vec<Book>, include_deleted: bool) -> Option<Vec<Book>> {
+    delta
+        .into_iter()
+        .map(|mut book| {
+            let asset_id = book.source_ids.get("apple_books");
+            let exported = previous
+                .iter()
+                .find(|p| asset_id.is_some() && p.source_ids.get("apple_books") == asset_id)
+                .or_else(|| previous.iter().find(|p| p.id == book.id))?;
+
+            let mut changed = std::mem::take(&mut book.highlights);
+            let mut highlights: Vec<Highlight> = exported
+                .highlights
+                .iter()
+                .filter(|h| h.sources.first() == Some(&Source::AppleBooks) && !h.deleted_upstream)
+                .map(|h| match changed.iter().position(|c| c.id == h.id) {
+                    Some(i) => changed.remove(i),
+                    None => h.clone(),
+                })
+                .collect();
+            highlights.append(&mut changed);
+            highlights.retain(|h| include_deleted || !h.deleted_upstream);
+
+            sort_highlights(&mut highlights);
+            book.highlights = highlights;
+            Some(book)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::SubsecRound;
+    use crate::model::{HighlightKind, HighlightStyle, Location};
+    use std::collections::BTreeMap;
+
+    fn make_highlight(id: &str, text: &str, source: Source, cfi: &str) -> Highlight {
+        Highlight {
+            id: id.to_string(),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: Some(cfi.to_string()),
+            },
+            created_at: None,
+            sources: vec![source],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    fn make_book(highlights: Vec<Highlight>) -> Book {
+        Book {
+            id: "walden".to_string(),
+            title: "Walden".to_string(),
+            author: None,
+            sources: vec![Source::AppleBooks],
+            highlights,
+            finished: None,
+            finished_at: None,
+            url: None,
+            isbn: None,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated: false,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids: BTreeMap::from([("apple_books".to_string(), "A1".to_string())]),
+            document_type: None,
+            progress: None,
+            last_opened_at: None,
+            language: None,
+            genre: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let previous = vec![make_book(vec![
+            make_highlight("U1", "Simplify, simplify.", Source::AppleBooks, "epubcfi(/6/4!/4/2/1:0)"),
+            make_highlight("U2", "I went to the woods", Source::AppleBooks, "epubcfi(/6/8!/4/2/1:0)"),
+            make_highlight("K1", "Kindle copy", Source::Kindle, "epubcfi(/6/2!/4/2/1:0)"),
+        ])];
+
+        let mut edited = make_highlight("U2", "I went to the woods", Source::AppleBooks, "epubcfi(/6/8!/4/2/1:0)");
+        edited.note = Some("Edited".to_string());
+        let mut deleted = make_highlight("U1", "Simplify, simplify.", Source::AppleBooks, "epubcfi(/6/4!/4/2/1:0)");
+        deleted.deleted_upstream = true;
+        let added = make_highlight("U3", "Sounds", Source::AppleBooks, "epubcfi(/6/6!/4/2/1:0)");
+        let delta = vec![make_book(vec![added, deleted, edited])];
+
+        let books = apply_delta(&previous, delta.clone(), false).unwrap();
+        let ids: Vec<&str> = books[0].highlights.iter().map(|h| h.id.as_str()).collect();
+        // The edit replaces the exported highlight; other sources are left to the merge
+        assert_eq!(ids, vec!["U3", "U2"]);
+        assert_eq!(books[0].highlights[1].note.as_deref(), Some("Edited"));
+
+        let books = apply_delta(&previous, delta, true).unwrap();
+        assert_eq!(books[0].highlights.len(), 3);
+        assert!(books[0].highlights[0].deleted_upstream);
+    }
+
+    #[test]
+    fn test_apply_delta_needs_every_book() {
+        let previous = vec![make_book(vec![make_highlight(
+            "U1",
+            "Simplify, simplify.",
+            Source::AppleBooks,
+            "epubcfi(/6/4!/4/2/1:0)",
+        )])];
+
+        // E.g. left out of the export by a filter; its unchanged highlights would be lost
+        let mut other = make_book(vec![make_highlight("U9", "Call me Ishmael", Source::AppleBooks, "epubcfi(/6/2!/4/2/1:0)")]);
+        other.id = "moby-dick".to_string();
+        other.source_ids.insert("apple_books".to_string(), "A2".to_string());
+
+        assert!(apply_delta(&previous, vec![other], false).is_none());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = std::env::temp_dir().join(format!("readingsync_incremental_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = state_path(&dir.join("library.json"));
+        assert_eq!(path.file_name().unwrap(), "library.apple-books-state.json");
+        assert_eq!(IncrementalState::load(&path).unwrap(), None);
+
+        let state = IncrementalState {
+            annotation_db: PathBuf::from("/tmp/AEAnnotation.sqlite"),
+            last_modified: Utc::now().trunc_subsecs(0),
+        };
+        state.save(&path).unwrap();
+        let loaded = IncrementalState::load(&path).unwrap();
+        invalidate(&dir.join("library.json")).unwrap();
+        let invalidated = IncrementalState::load(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded, Some(state));
+        assert_eq!(invalidated, None);
+    }
+}
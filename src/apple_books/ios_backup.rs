@@ -0,0 +1,218 @@
+use super::{extract_from_databases, AppleBooksExtract, AppleBooksOptions};
+use crate::error::AppleBooksError;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extract Apple Books highlights from an unencrypted iPhone or iPad backup
+///
+/// Backups store each file under a hashed name, listed in `Manifest.db` with its domain
+/// and original path. The iBooks library and annotation databases (plus any write-ahead
+/// logs) are copied out under their real names and read like the Mac databases; the
+/// report names the files stored in the backup. The options' database paths are ignored.
+pub fn extract(backup_dir: &Path, options: &AppleBooksOptions) -> Result<AppleBooksExtract, AppleBooksError> {
+    let manifest = backup_dir.join("Manifest.db");
+    if !manifest.exists() {
+        return Err(AppleBooksError::BackupManifestNotFound(backup_dir.to_path_buf()));
+    }
+
+    let conn = Connection::open_with_flags(&manifest, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let library = find_file(&conn, "BKLibrary")?.ok_or(AppleBooksError::BackupDatabaseNotFound("BKLibrary"))?;
+    let annotations =
+        find_file(&conn, "AEAnnotation")?.ok_or(AppleBooksError::BackupDatabaseNotFound("AEAnnotation"))?;
+
+    let temp_dir = std::env::temp_dir().join(format!("readingsync_ios_backup_{}", std::process::id()));
+    fs::create_dir_all(&temp_dir).map_err(AppleBooksError::TempCopyFailed)?;
+
+    let result = (|| {
+        let library_db = copy_out(&conn, backup_dir, &library, &temp_dir)?;
+        let annotation_db = copy_out(&conn, backup_dir, &annotations, &temp_dir)?;
+        extract_from_databases(&library_db, &annotation_db, options)
+    })();
+
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    let mut extracted = result?;
+    extracted.report.library_dbs = vec![stored_path(backup_dir, &library.file_id)];
+    extracted.report.annotation_dbs = vec![stored_path(backup_dir, &annotations.file_id)];
+    Ok(extracted)
+}
+
+/// A file listed in the backup manifest
+#[derive(Debug)]
+struct BackupFile {
+    file_id: String,
+    relative_path: String,
+}
+
+/// Find an iBooks database whose file name starts with `prefix`
+fn find_file(conn: &Connection, prefix: &str) -> Result<Option<BackupFile>, AppleBooksError> {
+    let file = conn
+        .query_row(
+            r#"
+            SELECT fileID, relativePath
+            FROM Files
+            WHERE domain LIKE '%iBooks%'
+              AND relativePath LIKE '%/' || ?1 || '%.sqlite'
+            ORDER BY relativePath
+            LIMIT 1
+            "#,
+            [prefix],
+            |row| {
+                Ok(BackupFile {
+                    file_id: row.get(0)?,
+                    relative_path: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(map_manifest_error)?;
+
+    Ok(file)
+}
+
+/// Copy a database and its `-wal` log out of the backup, returning the copied database path
+fn copy_out(
+    conn: &Connection,
+    backup_dir: &Path,
+    file: &BackupFile,
+    temp_dir: &Path,
+) -> Result<PathBuf, AppleBooksError> {
+    let name = Path::new(&file.relative_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.file_id.clone());
+    let target = temp_dir.join(&name);
+
+    fs::copy(stored_path(backup_dir, &file.file_id), &target).map_err(AppleBooksError::TempCopyFailed)?;
+
+    let wal: Option<String> = conn
+        .query_row(
+            "SELECT fileID FROM Files WHERE relativePath = ?1",
+            [format!("{}-wal", file.relative_path)],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(wal_id) = wal {
+        let wal_path = stored_path(backup_dir, &wal_id);
+        if wal_path.exists() {
+            fs::copy(wal_path, temp_dir.join(format!("{}-wal", name))).map_err(AppleBooksError::TempCopyFailed)?;
+        }
+    }
+
+    Ok(target)
+}
+
+/// Where a backup stores a file: `<first two characters of the ID>/<ID>`
+fn stored_path(backup_dir: &Path, file_id: &str) -> PathBuf {
+    backup_dir.join(&file_id[..2.min(file_id.len())]).join(file_id)
+}
+
+/// Encrypted backups have an unreadable manifest
+fn map_manifest_error(error: rusqlite::Error) -> AppleBooksError {
+    match error.sqlite_error_code() {
+        Some(rusqlite::ErrorCode::NotADatabase) => AppleBooksError::BackupEncrypted,
+        _ => AppleBooksError::Database(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Source;
+
+    const LIBRARY_ID: &str = "3b1ad2a1c0ffee00000000000000000000000001";
+    const ANNOTATION_ID: &str = "7e2cd4b2c0ffee00000000000000000000000002";
+
+    fn store(backup: &Path, file_id: &str, sql: &str) {
+        let path = stored_path(backup, file_id);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        Connection::open(path).unwrap().execute_batch(sql).unwrap();
+    }
+
+    fn make_backup(backup: &Path) {
+        fs::create_dir_all(backup).unwrap();
+
+        let manifest = Connection::open(backup.join("Manifest.db")).unwrap();
+        manifest
+            .execute_batch(&format!(
+                r#"
+                CREATE TABLE Files (fileID TEXT PRIMARY KEY, domain TEXT, relativePath TEXT, flags INTEGER, file BLOB);
+                INSERT INTO Files VALUES ('{LIBRARY_ID}', 'AppDomainGroup-group.com.apple.iBooks',
+                    'Documents/BKLibrary/BKLibrary-1-091020131601.sqlite', 1, NULL);
+                INSERT INTO Files VALUES ('{ANNOTATION_ID}', 'AppDomainGroup-group.com.apple.iBooks',
+                    'Documents/AEAnnotation/AEAnnotation_v10312011_1727_local.sqlite', 1, NULL);
+                INSERT INTO Files VALUES ('0000000000000000000000000000000000000003', 'HomeDomain',
+                    'Library/Notes/notes.sqlite', 1, NULL);
+                "#
+            ))
+            .unwrap();
+
+        store(
+            backup,
+            LIBRARY_ID,
+            r#"
+            CREATE TABLE ZBKLIBRARYASSET (
+                ZASSETID TEXT, ZTITLE TEXT, ZAUTHOR TEXT, ZISFINISHED INTEGER, ZDATEFINISHED REAL,
+                ZREADINGPROGRESS REAL, ZLASTOPENDATE REAL, ZLANGUAGE TEXT, ZGENRE TEXT, ZSTOREID TEXT, ZISSAMPLE INTEGER
+            );
+            INSERT INTO ZBKLIBRARYASSET VALUES ('A1', 'Walden', 'Henry David Thoreau', 0, NULL, NULL, NULL, NULL, NULL, NULL, 0);
+            "#,
+        );
+        store(
+            backup,
+            ANNOTATION_ID,
+            r#"
+            CREATE TABLE ZAEANNOTATION (
+                ZANNOTATIONUUID TEXT, ZANNOTATIONASSETID TEXT, ZANNOTATIONSELECTEDTEXT TEXT,
+                ZANNOTATIONNOTE TEXT, ZFUTUREPROOFING5 TEXT, ZANNOTATIONLOCATION TEXT,
+                ZANNOTATIONCREATIONDATE REAL, ZANNOTATIONDELETED INTEGER, ZPLLOCATIONRANGESTART INTEGER,
+                ZANNOTATIONSTYLE INTEGER
+            );
+            INSERT INTO ZAEANNOTATION VALUES ('U1', 'A1', 'Simplify, simplify.', NULL, 'Where I Lived',
+                'epubcfi(/6/12!/4/2/1:0)', 700000000.0, 0, 1, 3);
+            INSERT INTO ZAEANNOTATION VALUES ('U2', 'A1', 'Deleted on the phone', NULL, NULL, NULL, NULL, 1, 2, 1);
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_extract_backup() {
+        let backup = std::env::temp_dir().join(format!("readingsync_backup_{}", uuid::Uuid::new_v4()));
+        make_backup(&backup);
+
+        let extracted = extract(&backup, &AppleBooksOptions::default()).unwrap();
+        let _ = fs::remove_dir_all(&backup);
+        let books = extracted.books;
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "Walden");
+        assert_eq!(book.sources, vec![Source::AppleBooks]);
+        assert_eq!(book.highlights.len(), 1);
+        assert_eq!(book.highlights[0].id, "U1");
+        assert_eq!(book.highlights[0].location.chapter.as_deref(), Some("Where I Lived"));
+        assert_eq!(extracted.report.library_dbs, vec![stored_path(&backup, LIBRARY_ID)]);
+        assert_eq!(extracted.report.annotations, 1);
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let result = extract(Path::new("/nonexistent/backup"), &AppleBooksOptions::default());
+        assert!(matches!(result, Err(AppleBooksError::BackupManifestNotFound(_))));
+    }
+
+    #[test]
+    fn test_encrypted_manifest() {
+        let backup = std::env::temp_dir().join(format!("readingsync_backup_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&backup).unwrap();
+        fs::write(backup.join("Manifest.db"), vec![0x5a; 4096]).unwrap();
+
+        let result = extract(&backup, &AppleBooksOptions::default());
+        let _ = fs::remove_dir_all(&backup);
+
+        assert!(matches!(result, Err(AppleBooksError::BackupEncrypted)));
+    }
+}
@@ -0,0 +1,1560 @@
+pub mod cfi;
+pub mod chapters;
+pub mod incremental;
+pub mod ios_backup;
+#[cfg(test)]
+mod testing;
+
+use crate::error::AppleBooksError;
+use cfi::Cfi;
+use crate::model::{generate_book_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, TimeZone, Utc};
+use glob::glob;
+use regex::Regex;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// CoreData epoch offset (2001-01-01 00:00:00 UTC)
+const CORE_DATA_EPOCH_OFFSET: i64 = 978307200;
+
+/// Where Apple Books keeps its databases, tried in order
+///
+/// The iBooksX container is current; libraries from older macOS versions, or from
+/// before Books moved into a container, are under BKAgentService or Application Support.
+pub const LIBRARY_DB_PATTERNS: [&str; 3] = [
+    "~/Library/Containers/com.apple.iBooksX/Data/Documents/BKLibrary/BKLibrary*.sqlite",
+    "~/Library/Containers/com.apple.BKAgentService/Data/Documents/iBooks/BKLibrary/BKLibrary*.sqlite",
+    "~/Library/Application Support/iBooks/BKLibrary/BKLibrary*.sqlite",
+];
+pub const ANNOTATION_DB_PATTERNS: [&str; 3] = [
+    "~/Library/Containers/com.apple.iBooksX/Data/Documents/AEAnnotation/AEAnnotation*.sqlite",
+    "~/Library/Containers/com.apple.BKAgentService/Data/Documents/iBooks/AEAnnotation/AEAnnotation*.sqlite",
+    "~/Library/Application Support/iBooks/AEAnnotation/AEAnnotation*.sqlite",
+];
+
+/// Glob patterns to look for each database with, in order of preference
+#[derive(Debug, Clone)]
+pub struct DatabaseSearch {
+    pub library: Vec<String>,
+    pub annotation: Vec<String>,
+}
+
+impl DatabaseSearch {
+    /// Extra patterns (e.g. from config), tried before the built-in locations
+    pub fn with_extra(library: &[String], annotation: &[String]) -> Self {
+        let chain = |extra: &[String], defaults: &[&str]| {
+            extra.iter().cloned().chain(defaults.iter().map(|p| p.to_string())).collect()
+        };
+        Self {
+            library: chain(library, &LIBRARY_DB_PATTERNS),
+            annotation: chain(annotation, &ANNOTATION_DB_PATTERNS),
+        }
+    }
+}
+
+impl Default for DatabaseSearch {
+    fn default() -> Self {
+        Self::with_extra(&[], &[])
+    }
+}
+
+/// A database file matched by one of the search patterns
+#[derive(Debug, Clone)]
+pub struct DatabaseCandidate {
+    pub pattern: String,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+/// Every database file the patterns match, in pattern order and newest first within
+/// a pattern, leaving out SQLite's `-wal` and `-shm` files
+pub fn find_candidates(patterns: &[String]) -> Vec<DatabaseCandidate> {
+    let mut candidates = Vec::new();
+    for pattern in patterns {
+        let Ok(paths) = glob(&shellexpand::tilde(pattern)) else {
+            continue;
+        };
+        let mut matched: Vec<DatabaseCandidate> = paths
+            .filter_map(|r| r.ok())
+            .filter(|p| !p.to_string_lossy().contains("-wal") && !p.to_string_lossy().contains("-shm"))
+            .map(|path| DatabaseCandidate {
+                pattern: pattern.clone(),
+                modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+                path,
+            })
+            .collect();
+        // A library migrated more than once can leave stale copies next to the live one
+        matched.sort_by_key(|c| std::cmp::Reverse(c.modified));
+        candidates.extend(matched);
+    }
+    candidates
+}
+
+/// The databases to read: every file the patterns match, newest first
+///
+/// A library migrated more than once, or synced from another Mac, can spread its assets
+/// over several files; they're all read, and newer files win where they disagree.
+fn find_databases(patterns: &[String], database: &'static str) -> Result<Vec<PathBuf>, AppleBooksError> {
+    let mut candidates = find_candidates(patterns);
+    // Stable, so files with equal or unknown dates keep their pattern order
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.modified));
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for candidate in candidates {
+        // Overlapping patterns can match a file twice
+        if !paths.contains(&candidate.path) {
+            paths.push(candidate.path);
+        }
+    }
+    if paths.is_empty() {
+        return Err(AppleBooksError::NoDatabasesFound {
+            database,
+            searched: patterns.to_vec(),
+        });
+    }
+    Ok(paths)
+}
+
+/// Copies of the databases to read, in a temp directory of their own that's removed
+/// when dropped
+///
+/// Apple Books keeps its databases open, so they're read from copies to avoid lock
+/// issues; a directory per extraction keeps concurrent runs from reading each other's.
+struct TempCopies {
+    dir: PathBuf,
+}
+
+impl TempCopies {
+    fn new() -> Result<Self, AppleBooksError> {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_books_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).map_err(AppleBooksError::TempCopyFailed)?;
+        Ok(Self { dir })
+    }
+
+    /// Copy a database in as `<name>.sqlite`, so two databases with one file name don't clash
+    fn copy(&self, source: &Path, name: &str) -> Result<PathBuf, AppleBooksError> {
+        let target = self.dir.join(format!("{}.sqlite", name));
+        fs::copy(source, &target).map_err(AppleBooksError::TempCopyFailed)?;
+        Ok(target)
+    }
+}
+
+impl Drop for TempCopies {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// What to read from Apple Books, and where from
+///
+/// The default reads the Mac's own databases, found with the built-in `DatabaseSearch`,
+/// leaving out deleted annotations and Book Store samples; the setters below chain, e.g.
+/// `AppleBooksOptions::default().include_samples(true)`.
+#[derive(Debug, Clone)]
+pub struct AppleBooksOptions {
+    /// Library database to read instead of searching for one
+    pub library_db: Option<PathBuf>,
+    /// Annotation database to read instead of searching for one
+    pub annotation_db: Option<PathBuf>,
+    /// Where to look for the databases that aren't given
+    pub search: DatabaseSearch,
+    /// Read from an unencrypted iPhone or iPad backup instead of the Mac
+    pub ios_backup: Option<PathBuf>,
+    /// Keep annotations deleted in Apple Books, flagged `deleted_upstream`
+    pub include_deleted: bool,
+    /// Keep Book Store samples and their annotations
+    pub include_samples: bool,
+    /// Read the library's collections into `Book::collections`
+    pub include_collections: bool,
+    /// Only read annotations modified after this, deleted ones included (see `incremental`)
+    pub modified_after: Option<DateTime<Utc>>,
+}
+
+impl Default for AppleBooksOptions {
+    fn default() -> Self {
+        Self {
+            library_db: None,
+            annotation_db: None,
+            search: DatabaseSearch::default(),
+            ios_backup: None,
+            include_deleted: false,
+            include_samples: false,
+            include_collections: true,
+            modified_after: None,
+        }
+    }
+}
+
+impl AppleBooksOptions {
+    pub fn library_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.library_db = Some(path.into());
+        self
+    }
+
+    pub fn annotation_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.annotation_db = Some(path.into());
+        self
+    }
+
+    pub fn search(mut self, search: DatabaseSearch) -> Self {
+        self.search = search;
+        self
+    }
+
+    pub fn ios_backup(mut self, backup_dir: impl Into<PathBuf>) -> Self {
+        self.ios_backup = Some(backup_dir.into());
+        self
+    }
+
+    pub fn include_deleted(mut self, include: bool) -> Self {
+        self.include_deleted = include;
+        self
+    }
+
+    pub fn include_samples(mut self, include: bool) -> Self {
+        self.include_samples = include;
+        self
+    }
+
+    pub fn include_collections(mut self, include: bool) -> Self {
+        self.include_collections = include;
+        self
+    }
+
+    pub fn modified_after(mut self, since: DateTime<Utc>) -> Self {
+        self.modified_after = Some(since);
+        self
+    }
+}
+
+/// Books read from Apple Books, with what was read to get them
+#[derive(Debug, Clone)]
+pub struct AppleBooksExtract {
+    pub books: Vec<Book>,
+    pub report: ExtractionReport,
+}
+
+/// What an extraction read and left out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractionReport {
+    /// The databases read, newest first: the files found or given, not the copies
+    /// actually opened
+    pub library_dbs: Vec<PathBuf>,
+    pub annotation_dbs: Vec<PathBuf>,
+    /// Annotations kept
+    pub annotations: usize,
+    /// Kept annotations of books the library has no title for, e.g. still in iCloud
+    pub orphaned: usize,
+    /// Annotations read that were deleted in Apple Books: kept with `include_deleted`, and
+    /// read by incremental passes to drop them
+    pub deleted: usize,
+    /// Annotations also found in a newer annotation database, read from there instead
+    pub duplicate_annotations: usize,
+    /// Annotations of samples left out
+    pub skipped_samples: usize,
+    /// Rows with neither text nor a note, e.g. EPUB bookmarks
+    pub skipped_empty: usize,
+    /// Whether only annotations changed since `modified_after` were read; databases
+    /// without modification dates are always read in full
+    pub incremental: bool,
+    /// Latest modification date of the annotations read
+    pub latest_modification: Option<DateTime<Utc>>,
+}
+
+/// Read Apple Books from the Mac's databases, or from an iOS backup
+pub fn extract(options: &AppleBooksOptions) -> Result<AppleBooksExtract, AppleBooksError> {
+    if let Some(backup_dir) = &options.ios_backup {
+        return ios_backup::extract(backup_dir, options);
+    }
+
+    // Find or use provided database paths
+    let library_dbs = match &options.library_db {
+        Some(path) if !path.exists() => return Err(AppleBooksError::LibraryDbNotFound(path.clone())),
+        Some(path) => vec![path.clone()],
+        None => find_databases(&options.search.library, "library")?,
+    };
+
+    let annotation_dbs = match &options.annotation_db {
+        Some(path) if !path.exists() => return Err(AppleBooksError::AnnotationDbNotFound(path.clone())),
+        Some(path) => vec![path.clone()],
+        None => find_databases(&options.search.annotation, "annotation")?,
+    };
+
+    let copies = TempCopies::new()?;
+    let temp_library_dbs = library_dbs
+        .iter()
+        .enumerate()
+        .map(|(i, path)| copies.copy(path, &format!("BKLibrary-{}", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let temp_annotation_dbs = annotation_dbs
+        .iter()
+        .enumerate()
+        .map(|(i, path)| copies.copy(path, &format!("AEAnnotation-{}", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut extracted = extract_from_database_sets(&temp_library_dbs, &temp_annotation_dbs, options)?;
+    extracted.report.library_dbs = library_dbs;
+    extracted.report.annotation_dbs = annotation_dbs;
+    Ok(extracted)
+}
+
+/// Full extraction with proper asset_id handling
+#[deprecated(note = "use `extract` with `AppleBooksOptions`")]
+pub fn extract_full(
+    library_db_path: Option<PathBuf>,
+    annotation_db_path: Option<PathBuf>,
+    search: &DatabaseSearch,
+    include_samples: bool,
+) -> Result<Vec<Book>, AppleBooksError> {
+    let options = AppleBooksOptions {
+        library_db: library_db_path,
+        annotation_db: annotation_db_path,
+        search: search.clone(),
+        include_samples,
+        ..AppleBooksOptions::default()
+    };
+    Ok(extract(&options)?.books)
+}
+
+/// Read books and highlights from a library and an annotation database
+///
+/// The databases are opened in place, so callers working with live files should pass copies.
+/// The options' database paths and backup are ignored.
+pub fn extract_from_databases(
+    library_db: &Path,
+    annotation_db: &Path,
+    options: &AppleBooksOptions,
+) -> Result<AppleBooksExtract, AppleBooksError> {
+    extract_from_database_sets(&[library_db.to_path_buf()], &[annotation_db.to_path_buf()], options)
+}
+
+/// Read books and highlights from several library and annotation databases, newest first
+///
+/// An asset in more than one library takes its entry from the newest library that has a
+/// title for it, and an annotation in more than one annotation database is read from the
+/// newest. Which copy of an annotation wins can't be told from the changes alone, so
+/// `modified_after` only applies to a single annotation database. Like
+/// `extract_from_databases`, the databases are opened in place.
+pub fn extract_from_database_sets(
+    library_dbs: &[PathBuf],
+    annotation_dbs: &[PathBuf],
+    options: &AppleBooksOptions,
+) -> Result<AppleBooksExtract, AppleBooksError> {
+    let mut options = options.clone();
+    if annotation_dbs.len() > 1 {
+        options.modified_after = None;
+    }
+    let options = &options;
+    let mut report = ExtractionReport {
+        library_dbs: library_dbs.to_vec(),
+        annotation_dbs: annotation_dbs.to_vec(),
+        ..ExtractionReport::default()
+    };
+    let mut assets = LibraryAssets::default();
+    let mut paths = HashMap::new();
+    for library_db in library_dbs {
+        assets.merge_older(read_books(library_db, options)?);
+        // Older libraries, and those in iOS backups, have no usable book paths
+        for (asset_id, path) in read_book_paths(library_db).unwrap_or_default() {
+            paths.entry(asset_id).or_insert(path);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for annotation_db in annotation_dbs {
+        read_annotations(annotation_db, &mut assets, &mut seen, options, &mut report)?;
+    }
+
+    // Untitled library entries are only worth keeping for their highlights
+    let mut books_by_asset = assets.books;
+    books_by_asset.retain(|asset_id, book| !assets.untitled.contains(asset_id) || !book.highlights.is_empty());
+
+    for (asset_id, book) in &mut books_by_asset {
+        chapters::fill_chapters(&mut book.highlights, paths.get(asset_id).map(PathBuf::as_path));
+        sort_highlights(&mut book.highlights);
+    }
+
+    // Give books a stable order too
+    let mut books: Vec<Book> = books_by_asset.into_values().collect();
+    books.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()).then(a.id.cmp(&b.id)));
+    Ok(AppleBooksExtract { books, report })
+}
+
+/// Sort highlights as they appear in the book
+///
+/// ZPLLOCATIONRANGESTART is missing or 0 for some annotations, so the query's order is
+/// only a fallback for highlights without a CFI or a PDF page.
+fn sort_highlights(highlights: &mut [Highlight]) {
+    highlights.sort_by_cached_key(|h| {
+        let key = h.location.raw.as_deref().and_then(Cfi::parse).map(|cfi| cfi.sort_key());
+        (key.is_none(), key, h.location.reading_order())
+    });
+}
+
+/// The assets of a BKLibrary database
+#[derive(Debug, Default)]
+struct LibraryAssets {
+    /// Books keyed by asset ID
+    books: HashMap<String, Book>,
+    /// Assets with no title, whose books are named after the asset ID
+    untitled: HashSet<String>,
+    /// Store samples that were left out, whose annotations are dropped too
+    skipped: HashSet<String>,
+    /// PDFs, whose annotations are located by page rather than by CFI
+    pdfs: HashSet<String>,
+}
+
+impl LibraryAssets {
+    /// Add the assets of an older library: an asset both have keeps this library's entry,
+    /// unless only the older one has a title
+    fn merge_older(&mut self, older: LibraryAssets) {
+        for (asset_id, book) in older.books {
+            let keep_newer = match self.books.contains_key(&asset_id) {
+                true => !self.untitled.contains(&asset_id) || older.untitled.contains(&asset_id),
+                // A sample here was left out on purpose
+                false => self.skipped.contains(&asset_id),
+            };
+            if keep_newer {
+                continue;
+            }
+            if older.untitled.contains(&asset_id) {
+                self.untitled.insert(asset_id.clone());
+            } else {
+                self.untitled.remove(&asset_id);
+            }
+            if older.pdfs.contains(&asset_id) {
+                self.pdfs.insert(asset_id.clone());
+            } else {
+                self.pdfs.remove(&asset_id);
+            }
+            self.books.insert(asset_id, book);
+        }
+        for asset_id in older.skipped {
+            if !self.books.contains_key(&asset_id) {
+                self.skipped.insert(asset_id);
+            }
+        }
+    }
+}
+
+/// ZCONTENTTYPE of a PDF; EPUBs are 1
+const PDF_CONTENT_TYPE: i64 = 3;
+
+/// Books from a BKLibrary database
+fn read_books(library_db: &Path, options: &AppleBooksOptions) -> Result<LibraryAssets, AppleBooksError> {
+    let conn = Connection::open(library_db)?;
+    // Only newer libraries record whether an asset is an EPUB or a PDF
+    let content_type_column = if has_column(&conn, "ZBKLIBRARYASSET", "ZCONTENTTYPE")? {
+        "ZCONTENTTYPE"
+    } else {
+        "NULL"
+    };
+    let mut stmt = conn.prepare(&format!(
+        r#"
+        SELECT
+            ZASSETID,
+            ZTITLE,
+            ZAUTHOR,
+            ZISFINISHED,
+            ZDATEFINISHED,
+            ZREADINGPROGRESS,
+            ZLASTOPENDATE,
+            ZLANGUAGE,
+            ZGENRE,
+            CAST(ZSTOREID AS TEXT),
+            ZISSAMPLE,
+            {content_type_column}
+        FROM ZBKLIBRARYASSET
+        WHERE ZASSETID IS NOT NULL
+        "#,
+    ))?;
+
+    let mut assets = LibraryAssets::default();
+
+    let rows = stmt.query_map([], |row| {
+        let asset_id: String = row.get(0)?;
+        let title: Option<String> = row.get(1)?;
+        let author: Option<String> = row.get(2)?;
+        let is_finished: Option<i64> = row.get(3)?;
+        let finished_timestamp: Option<f64> = row.get(4)?;
+        let progress: Option<f64> = row.get(5)?;
+        let last_opened_timestamp: Option<f64> = row.get(6)?;
+        let language: Option<String> = row.get(7)?;
+        let genre: Option<String> = row.get(8)?;
+        let store_id: Option<String> = row.get(9)?;
+        let is_sample: Option<i64> = row.get(10)?;
+        let is_pdf = row.get::<_, Option<i64>>(11)? == Some(PDF_CONTENT_TYPE);
+
+        let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut source_ids = BTreeMap::from([("apple_books".to_string(), asset_id.clone())]);
+        // Sideloaded books have no store ID, stored as NULL or 0
+        if let Some(store_id) = non_empty(store_id).filter(|id| id != "0") {
+            source_ids.insert("apple_books_store".to_string(), store_id);
+        }
+
+        // Cloud-only books can lack a title until they're downloaded
+        let (title, untitled) = match non_empty(title) {
+            Some(title) => (title, false),
+            None => (untitled_title(&asset_id), true),
+        };
+
+        let book = Book {
+            id: generate_book_id(&title, author.as_deref()),
+            title,
+            author,
+            sources: vec![Source::AppleBooks],
+            highlights: Vec::new(),
+            finished: Some(is_finished.unwrap_or(0) == 1),
+            finished_at: finished_timestamp.and_then(core_data_date),
+            url: None,
+            isbn: None,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated: false,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids,
+            document_type: None,
+            // Never-opened books store 0
+            progress: progress.filter(|p| *p > 0.0).map(|p| p.min(1.0) as f32),
+            last_opened_at: last_opened_timestamp.and_then(core_data_date),
+            language: non_empty(language),
+            genre: non_empty(genre),
+        };
+
+        Ok((asset_id, book, untitled, is_sample.unwrap_or(0) != 0, is_pdf))
+    })?;
+
+    for row_result in rows {
+        let (asset_id, book, untitled, is_sample, is_pdf) = row_result?;
+        if is_sample && !options.include_samples {
+            assets.skipped.insert(asset_id);
+            continue;
+        }
+        if untitled {
+            assets.untitled.insert(asset_id.clone());
+        }
+        if is_pdf {
+            assets.pdfs.insert(asset_id.clone());
+        }
+        assets.books.insert(asset_id, book);
+    }
+
+    drop(stmt);
+
+    // Older libraries have no collection tables
+    let memberships = match options.include_collections {
+        true => read_collections(&conn).ok(),
+        false => None,
+    };
+    if let Some(memberships) = memberships {
+        for (asset_id, collection) in memberships {
+            if let Some(book) = assets.books.get_mut(&asset_id) {
+                if !book.collections.contains(&collection) {
+                    book.collections.push(collection);
+                }
+            }
+        }
+    }
+
+    Ok(assets)
+}
+
+/// Where each book's EPUB is stored, by asset ID
+fn read_book_paths(library_db: &Path) -> Result<HashMap<String, PathBuf>, AppleBooksError> {
+    let conn = Connection::open(library_db)?;
+    let mut stmt = conn.prepare("SELECT ZASSETID, ZPATH FROM ZBKLIBRARYASSET WHERE ZPATH IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut paths = HashMap::new();
+    for row_result in rows {
+        let (asset_id, path) = row_result?;
+        paths.insert(asset_id, PathBuf::from(path));
+    }
+    Ok(paths)
+}
+
+/// (asset ID, collection name) pairs for "Want to Read", "Finished" and user collections
+///
+/// Other built-in collections ("Books", "PDFs", "Samples", ...) only mirror the library
+/// and are skipped.
+fn read_collections(conn: &Connection) -> Result<Vec<(String, String)>, AppleBooksError> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT m.ZASSETID, c.ZCOLLECTIONID, c.ZTITLE
+        FROM ZBKCOLLECTIONMEMBER m
+        JOIN ZBKCOLLECTION c ON c.Z_PK = m.ZCOLLECTION
+        WHERE m.ZASSETID IS NOT NULL
+          AND (c.ZDELETEDFLAG IS NULL OR c.ZDELETEDFLAG = 0)
+        ORDER BY c.ZSORTKEY, m.ZSORTKEY
+        "#,
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+
+    let mut memberships = Vec::new();
+
+    for row_result in rows {
+        let (asset_id, collection_id, title) = row_result?;
+
+        let name = match collection_id.as_deref() {
+            Some("com.apple.iBooks.collection.WantToRead") => Some("Want to Read".to_string()),
+            Some("com.apple.iBooks.collection.Finished") => Some("Finished".to_string()),
+            Some(id) if id.starts_with("com.apple.iBooks.collection.") => None,
+            _ => title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+        };
+
+        if let Some(name) = name {
+            memberships.push((asset_id, name));
+        }
+    }
+
+    Ok(memberships)
+}
+
+/// An annotation as stored, before it's matched to its book
+struct AnnotationRow {
+    id: String,
+    asset_id: String,
+    text: String,
+    note: Option<String>,
+    chapter: Option<String>,
+    location: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    style: Option<i64>,
+    is_underline: bool,
+    range_start: Option<i64>,
+    deleted: bool,
+    modified_at: Option<DateTime<Utc>>,
+    context: Option<String>,
+}
+
+/// Add highlights from an AEAnnotation database to their books, counting them in `report`
+///
+/// Annotations sync through iCloud ahead of the books they belong to, so an asset can
+/// have annotations but no library entry; it gets an untitled book of its own.
+fn read_annotations(
+    annotation_db: &Path,
+    assets: &mut LibraryAssets,
+    seen: &mut HashSet<String>,
+    options: &AppleBooksOptions,
+    report: &mut ExtractionReport,
+) -> Result<(), AppleBooksError> {
+    let conn = Connection::open(annotation_db)?;
+    let modified_column = if has_column(&conn, "ZAEANNOTATION", "ZANNOTATIONMODIFICATIONDATE")? {
+        "ZANNOTATIONMODIFICATIONDATE"
+    } else {
+        "NULL"
+    };
+    let modified_after = options.modified_after.filter(|_| modified_column != "NULL");
+    let context_column = if has_column(&conn, "ZAEANNOTATION", "ZANNOTATIONREPRESENTATIVETEXT")? {
+        "ZANNOTATIONREPRESENTATIVETEXT"
+    } else {
+        "NULL"
+    };
+    report.incremental = modified_after.is_some();
+    // An incremental pass needs the deletions too, to drop them from the last export
+    let include_deleted = options.include_deleted || report.incremental;
+
+    // Older databases predate the underline flag and mark underlines by style alone
+    let underline_column = if has_column(&conn, "ZAEANNOTATION", "ZANNOTATIONISUNDERLINE")? {
+        "ZANNOTATIONISUNDERLINE"
+    } else {
+        "0"
+    };
+    let mut stmt = conn.prepare(&format!(
+        r#"
+        SELECT
+            ZANNOTATIONUUID,
+            ZANNOTATIONASSETID,
+            ZANNOTATIONSELECTEDTEXT,
+            ZANNOTATIONNOTE,
+            ZFUTUREPROOFING5,
+            ZANNOTATIONLOCATION,
+            ZANNOTATIONCREATIONDATE,
+            ZANNOTATIONSTYLE,
+            {underline_column},
+            ZPLLOCATIONRANGESTART,
+            ZANNOTATIONDELETED,
+            {modified_column},
+            {context_column}
+        FROM ZAEANNOTATION
+        WHERE ?1 IS NULL OR {modified_column} > ?1
+        ORDER BY ZANNOTATIONASSETID, ZPLLOCATIONRANGESTART
+        "#,
+    ))?;
+
+    let threshold = modified_after.map(|since| (since.timestamp() - CORE_DATA_EPOCH_OFFSET) as f64);
+    let annotation_rows = stmt.query_map(rusqlite::params![threshold], |row| {
+        Ok(AnnotationRow {
+            id: row.get(0)?,
+            asset_id: row.get(1)?,
+            text: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            note: row.get::<_, Option<String>>(3)?.filter(|n| !n.is_empty()),
+            // Empty on recent macOS; `chapters::fill_chapters` fills it in from the CFI
+            chapter: row.get::<_, Option<String>>(4)?.filter(|c| !c.trim().is_empty()),
+            location: row.get(5)?,
+            created_at: row.get::<_, Option<f64>>(6)?.and_then(core_data_date),
+            style: row.get(7)?,
+            is_underline: row.get::<_, Option<i64>>(8)?.unwrap_or(0) != 0,
+            range_start: row.get(9)?,
+            deleted: row.get::<_, Option<i64>>(10)?.unwrap_or(0) != 0,
+            modified_at: row.get::<_, Option<f64>>(11)?.and_then(core_data_date),
+            context: row.get(12)?,
+        })
+    })?;
+
+    for row_result in annotation_rows {
+        let row = row_result?;
+        report.latest_modification = report.latest_modification.max(row.modified_at);
+        // Annotation databases are read newest first; deleted rows are read regardless,
+        // so one deleted in a newer database isn't brought back by an older one
+        if !seen.insert(row.id.clone()) {
+            report.duplicate_annotations += 1;
+            continue;
+        }
+        if row.deleted && !include_deleted {
+            continue;
+        }
+        let asset_id = row.asset_id;
+        if assets.skipped.contains(&asset_id) {
+            report.skipped_samples += 1;
+            continue;
+        }
+        // PDF highlights can be a bare rect on the page, with no text
+        let is_pdf = assets.pdfs.contains(&asset_id);
+        if row.text.is_empty() && row.note.is_none() && !is_pdf {
+            report.skipped_empty += 1;
+            continue;
+        }
+
+        if !assets.books.contains_key(&asset_id) {
+            assets.books.insert(asset_id.clone(), untitled_book(&asset_id));
+            assets.untitled.insert(asset_id.clone());
+        }
+        report.annotations += 1;
+        if assets.untitled.contains(&asset_id) {
+            report.orphaned += 1;
+        }
+        if row.deleted {
+            report.deleted += 1;
+        }
+
+        // An EPUB note attached to a point in the text, with nothing selected
+        let kind = if row.text.is_empty() && !is_pdf {
+            HighlightKind::Note
+        } else {
+            HighlightKind::Highlight
+        };
+        let (color, style) = match kind {
+            HighlightKind::Highlight if row.is_underline || row.style == Some(0) => {
+                (row.style.and_then(style_color), HighlightStyle::Underline)
+            }
+            HighlightKind::Highlight => (row.style.and_then(style_color), HighlightStyle::Highlight),
+            _ => (None, HighlightStyle::Highlight),
+        };
+        // Only worth keeping when it says more than the selection
+        let context = row
+            .context
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty() && *c != row.text.trim());
+        let location = if is_pdf {
+            pdf_location(row.chapter, row.location, row.range_start)
+        } else {
+            cfi_location(row.chapter, row.location)
+        };
+
+        if let Some(book) = assets.books.get_mut(&asset_id) {
+            book.highlights.push(Highlight {
+                id: row.id,
+                text: row.text,
+                note: row.note,
+                location,
+                created_at: row.created_at,
+                sources: vec![Source::AppleBooks],
+                color: color.map(String::from),
+                kind,
+                style,
+                context,
+                deleted_upstream: row.deleted,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// A highlight's location from its ZANNOTATIONLOCATION: an EPUB CFI is kept in `raw`
+/// and shown as e.g. "ch. 6, ¶5"; anything else is kept as the position
+fn cfi_location(chapter: Option<String>, location: Option<String>) -> Location {
+    match location.as_deref().and_then(Cfi::parse) {
+        Some(cfi) => Location {
+            chapter,
+            position: Some(cfi.to_string()),
+            page: None,
+            raw: location,
+        },
+        None => Location {
+            chapter,
+            position: location,
+            page: None,
+            raw: None,
+        },
+    }
+}
+
+/// A PDF highlight's location: "Page <n>" from the page named in ZANNOTATIONLOCATION,
+/// else from ZPLLOCATIONRANGESTART, which holds the 0-based page index for PDFs
+fn pdf_location(chapter: Option<String>, location: Option<String>, range_start: Option<i64>) -> Location {
+    let page = location
+        .as_deref()
+        .and_then(pdf_page)
+        .or_else(|| range_start.filter(|index| *index >= 0).map(|index| index as u64 + 1));
+    Location {
+        chapter,
+        position: page.map(|page| format!("Page {}", page)),
+        page: None,
+        raw: location.filter(|l| !l.trim().is_empty()),
+    }
+}
+
+/// The 1-based page in a PDF annotation's location payload: a `page`/`pageNumber`
+/// entry, a 0-based `pageIndex` entry or a bare page index
+fn pdf_page(payload: &str) -> Option<u64> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let payload = payload.trim();
+    if let Ok(index) = payload.parse::<u64>() {
+        return Some(index + 1);
+    }
+    let re = RE.get_or_init(|| Regex::new(r#"(?i)"?page(index|number)?"?\s*[:=]\s*"?(\d+)"#).unwrap());
+    let caps = re.captures(payload)?;
+    let number: u64 = caps[2].parse().ok()?;
+    match caps.get(1) {
+        Some(kind) if kind.as_str().eq_ignore_ascii_case("index") => Some(number + 1),
+        _ => Some(number),
+    }
+}
+
+/// Title for an asset the library has no title for
+fn untitled_title(asset_id: &str) -> String {
+    format!("Unknown book ({})", asset_id)
+}
+
+/// A book for annotations whose asset isn't in the library at all
+fn untitled_book(asset_id: &str) -> Book {
+    let mut book = Book::new(untitled_title(asset_id), None);
+    book.sources.push(Source::AppleBooks);
+    book.source_ids.insert("apple_books".to_string(), asset_id.to_string());
+    // Book Store purchases use their store ID as the asset ID
+    if !asset_id.is_empty() && asset_id.chars().all(|c| c.is_ascii_digit()) {
+        book.source_ids.insert("apple_books_store".to_string(), asset_id.to_string());
+    }
+    book
+}
+
+/// A CoreData timestamp (seconds since 2001-01-01) as a UTC date
+fn core_data_date(timestamp: f64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(timestamp as i64 + CORE_DATA_EPOCH_OFFSET, 0).single()
+}
+
+/// Whether a table has a column, for columns only some Apple Books versions have
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, AppleBooksError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name?.eq_ignore_ascii_case(column) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Highlight color for a ZANNOTATIONSTYLE value; 0 is an underline, with no color
+fn style_color(style: i64) -> Option<&'static str> {
+    match style {
+        1 => Some("green"),
+        2 => Some("blue"),
+        3 => Some("yellow"),
+        4 => Some("pink"),
+        5 => Some("purple"),
+        _ => None,
+    }
+}
+
+// Use shellexpand for tilde expansion
+mod shellexpand {
+    pub fn tilde(path: &str) -> String {
+        if path.starts_with("~/") {
+            if let Some(home) = dirs::home_dir() {
+                return format!("{}{}", home.display(), &path[1..]);
+            }
+        }
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::{Annotation, Asset, Fixture};
+
+    fn make_databases(dir: &Path, with_collections: bool) -> (PathBuf, PathBuf) {
+        fs::create_dir_all(dir).unwrap();
+        let library_db = dir.join("BKLibrary.sqlite");
+        let annotation_db = dir.join("AEAnnotation.sqlite");
+
+        let library = Connection::open(&library_db).unwrap();
+        library
+            .execute_batch(
+                r#"
+                CREATE TABLE ZBKLIBRARYASSET (
+                    ZASSETID TEXT, ZTITLE TEXT, ZAUTHOR TEXT, ZISFINISHED INTEGER, ZDATEFINISHED REAL,
+                    ZREADINGPROGRESS REAL, ZLASTOPENDATE REAL, ZLANGUAGE TEXT, ZGENRE TEXT, ZSTOREID INTEGER, ZISSAMPLE INTEGER
+                );
+                INSERT INTO ZBKLIBRARYASSET VALUES ('A1', 'Meditations', 'Marcus Aurelius', 0, NULL, 0.42, 710000000.0,
+                    'en', 'Philosophy', 1234567890, 0);
+                INSERT INTO ZBKLIBRARYASSET VALUES ('A2', 'Walden', 'Henry David Thoreau', 1, 700000000.0, 0.0, NULL,
+                    '', NULL, 0, 0);
+                INSERT INTO ZBKLIBRARYASSET VALUES ('A3', 'Dune', 'Frank Herbert', 0, NULL, 0.1, NULL,
+                    'en', 'Sci-Fi & Fantasy', 987654321, 1);
+                "#,
+            )
+            .unwrap();
+
+        if with_collections {
+            library
+                .execute_batch(
+                    r#"
+                    CREATE TABLE ZBKCOLLECTION (Z_PK INTEGER PRIMARY KEY, ZCOLLECTIONID TEXT, ZTITLE TEXT, ZDELETEDFLAG INTEGER, ZSORTKEY INTEGER);
+                    CREATE TABLE ZBKCOLLECTIONMEMBER (Z_PK INTEGER PRIMARY KEY, ZCOLLECTION INTEGER, ZASSETID TEXT, ZSORTKEY INTEGER);
+                    INSERT INTO ZBKCOLLECTION VALUES (1, 'com.apple.iBooks.collection.WantToRead', NULL, 0, 1);
+                    INSERT INTO ZBKCOLLECTION VALUES (2, 'com.apple.iBooks.collection.Books', 'Books', 0, 2);
+                    INSERT INTO ZBKCOLLECTION VALUES (3, '5C1F0D2E-0000-4000-8000-000000000001', 'Philosophy', 0, 3);
+                    INSERT INTO ZBKCOLLECTION VALUES (4, '5C1F0D2E-0000-4000-8000-000000000002', 'Old', 1, 4);
+                    INSERT INTO ZBKCOLLECTIONMEMBER VALUES (1, 1, 'A1', 1);
+                    INSERT INTO ZBKCOLLECTIONMEMBER VALUES (2, 2, 'A1', 1);
+                    INSERT INTO ZBKCOLLECTIONMEMBER VALUES (3, 3, 'A1', 1);
+                    INSERT INTO ZBKCOLLECTIONMEMBER VALUES (4, 4, 'A2', 1);
+                    "#,
+                )
+                .unwrap();
+        }
+
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                CREATE TABLE ZAEANNOTATION (
+                    ZANNOTATIONUUID TEXT, ZANNOTATIONASSETID TEXT, ZANNOTATIONSELECTEDTEXT TEXT,
+                    ZANNOTATIONNOTE TEXT, ZFUTUREPROOFING5 TEXT, ZANNOTATIONLOCATION TEXT,
+                    ZANNOTATIONCREATIONDATE REAL, ZANNOTATIONDELETED INTEGER, ZPLLOCATIONRANGESTART INTEGER,
+                    ZANNOTATIONSTYLE INTEGER
+                );
+                INSERT INTO ZAEANNOTATION VALUES ('U2', 'A1', 'Waste no more time arguing.', NULL, 'Book 12',
+                    'epubcfi(/6/30!/4/2/1:0)', 690000000.0, 0, 5, 0);
+                INSERT INTO ZAEANNOTATION VALUES ('U1', 'A1', 'You have power over your mind.', NULL, 'Book 10',
+                    'epubcfi(/6/26[book10]!/4/2/6,/1:0,/1:30)', 700000000.0, 0, 1, 3);
+                "#,
+            )
+            .unwrap();
+
+        (library_db, annotation_db)
+    }
+
+    fn extract_books(library_db: &Path, annotation_db: &Path) -> Vec<Book> {
+        extract_from_databases(library_db, annotation_db, &AppleBooksOptions::default()).unwrap().books
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_default_options_match_extract_full() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, true);
+
+        let options = AppleBooksOptions::default().library_db(&library_db).annotation_db(&annotation_db);
+        assert!(options.include_collections && !options.include_samples && !options.include_deleted);
+        let extracted = extract(&options).unwrap();
+        let full = extract_full(Some(library_db.clone()), Some(annotation_db.clone()), &DatabaseSearch::default(), false)
+            .unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(serde_json::to_value(&extracted.books).unwrap(), serde_json::to_value(&full).unwrap());
+        // The report names the databases found, not the temporary copies
+        assert_eq!(extracted.report.library_dbs, vec![library_db]);
+        assert_eq!(extracted.report.annotation_dbs, vec![annotation_db]);
+        assert_eq!(extracted.report.annotations, 2);
+    }
+
+    #[test]
+    fn test_extract_report_and_options() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, true);
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZAEANNOTATION VALUES ('D1', 'A2', 'Deleted on the phone', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 1, 1, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('S1', 'A3', 'Fear is the mind-killer.', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 0, 1, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('B1', 'A2', '', NULL, NULL,
+                    'epubcfi(/6/12!/4/2/1:0)', 700000000.0, 0, 2, 0);
+                INSERT INTO ZAEANNOTATION VALUES ('O1', 'A9', 'Call me Ishmael.', NULL, NULL,
+                    'epubcfi(/6/4!/4/2/1:0)', 700000000.0, 0, 1, 3);
+                "#,
+            )
+            .unwrap();
+
+        let default = extract_from_databases(&library_db, &annotation_db, &AppleBooksOptions::default()).unwrap();
+        let options = AppleBooksOptions::default().include_deleted(true).include_collections(false);
+        let with_deleted = extract_from_databases(&library_db, &annotation_db, &options).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let report = &default.report;
+        assert_eq!((report.annotations, report.orphaned, report.deleted), (3, 1, 0));
+        assert_eq!((report.skipped_samples, report.skipped_empty), (1, 1));
+        assert!(!default.books.iter().any(|b| b.highlights.iter().any(|h| h.id == "D1")));
+
+        assert_eq!((with_deleted.report.annotations, with_deleted.report.deleted), (4, 1));
+        let walden = with_deleted.books.iter().find(|b| b.title == "Walden").unwrap();
+        assert!(walden.highlights[0].deleted_upstream);
+        assert!(with_deleted.books.iter().all(|b| b.collections.is_empty()));
+    }
+
+    #[test]
+    fn test_extract_modified_after() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                ALTER TABLE ZAEANNOTATION ADD COLUMN ZANNOTATIONMODIFICATIONDATE REAL;
+                UPDATE ZAEANNOTATION SET ZANNOTATIONMODIFICATIONDATE = 700000000.0;
+                UPDATE ZAEANNOTATION SET ZANNOTATIONMODIFICATIONDATE = 720000000.5, ZANNOTATIONNOTE = 'Edited'
+                    WHERE ZANNOTATIONUUID = 'U2';
+                INSERT INTO ZAEANNOTATION VALUES ('D1', 'A2', 'Deleted on the phone', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 1, 1, 3, 715000000.0);
+                "#,
+            )
+            .unwrap();
+
+        let since = Utc.timestamp_opt(710000000 + CORE_DATA_EPOCH_OFFSET, 0).unwrap();
+        let options = AppleBooksOptions::default().modified_after(since);
+        let delta = extract_from_databases(&library_db, &annotation_db, &options).unwrap();
+        let full = extract_from_databases(&library_db, &annotation_db, &AppleBooksOptions::default()).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        // The edit and the deletion, which incremental passes need to drop it
+        let ids: Vec<&str> = delta.books.iter().flat_map(|b| &b.highlights).map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["U2", "D1"]);
+        assert!(delta.books.iter().flat_map(|b| &b.highlights).any(|h| h.id == "D1" && h.deleted_upstream));
+        assert!(delta.report.incremental);
+        assert_eq!(delta.report.latest_modification, Utc.timestamp_opt(720000000 + CORE_DATA_EPOCH_OFFSET, 0).single());
+
+        assert!(!full.report.incremental);
+        assert_eq!(full.report.annotations, 2);
+    }
+
+    #[test]
+    fn test_extract_context() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                ALTER TABLE ZAEANNOTATION ADD COLUMN ZANNOTATIONREPRESENTATIVETEXT TEXT;
+                UPDATE ZAEANNOTATION SET ZANNOTATIONREPRESENTATIVETEXT =
+                    '  Remember this. You have power over your mind, not outside events.  '
+                    WHERE ZANNOTATIONUUID = 'U1';
+                UPDATE ZAEANNOTATION SET ZANNOTATIONREPRESENTATIVETEXT = 'Waste no more time arguing. '
+                    WHERE ZANNOTATIONUUID = 'U2';
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        assert_eq!(
+            meditations.highlights[0].context.as_deref(),
+            Some("Remember this. You have power over your mind, not outside events.")
+        );
+        // Nothing more than the selection
+        assert_eq!(meditations.highlights[1].context, None);
+    }
+
+    #[test]
+    fn test_fixture_finished_and_authorless_books() {
+        let fixture = Fixture::library();
+        fixture.asset(Asset {
+            id: "UNANNOTATED",
+            title: Some("Dune"),
+            finished: true,
+            ..Asset::default()
+        });
+        let books = extract_books(&fixture.library_db, &fixture.annotation_db);
+
+        // Kept for the reading log without any highlights
+        let dune = books.iter().find(|b| b.title == "Dune").unwrap();
+        assert_eq!((dune.finished, dune.highlights.len()), (Some(true), 0));
+
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert_eq!(walden.finished, Some(true));
+        assert_eq!(walden.finished_at, Utc.timestamp_opt(700000000 + CORE_DATA_EPOCH_OFFSET, 0).single());
+        assert_eq!(walden.progress, Some(1.0));
+
+        let cloud = books.iter().find(|b| b.title == "The Cloud of Unknowing").unwrap();
+        assert_eq!((cloud.author.as_deref(), cloud.finished), (None, Some(false)));
+        assert_eq!(cloud.id, generate_book_id("The Cloud of Unknowing", None));
+        assert_eq!(cloud.highlights.len(), 1);
+    }
+
+    #[test]
+    fn test_fixture_orphans_and_deleted_rows() {
+        let fixture = Fixture::library();
+        let options = AppleBooksOptions::default();
+        let extracted = extract_from_databases(&fixture.library_db, &fixture.annotation_db, &options).unwrap();
+        let with_deleted = extract_from_databases(
+            &fixture.library_db,
+            &fixture.annotation_db,
+            &AppleBooksOptions::default().include_deleted(true),
+        )
+        .unwrap();
+
+        let orphan = extracted.books.iter().find(|b| b.title == "Unknown book (ORPHAN)").unwrap();
+        assert_eq!(orphan.highlights[0].id, "O1");
+        assert_eq!((extracted.report.orphaned, extracted.report.deleted), (1, 0));
+
+        let ids = |books: &[Book]| -> Vec<String> {
+            let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+            meditations.highlights.iter().map(|h| h.id.clone()).collect()
+        };
+        assert_eq!(ids(&extracted.books), vec!["M1"]);
+        assert_eq!(ids(&with_deleted.books), vec!["M1", "M2"]);
+        assert_eq!(with_deleted.report.deleted, 1);
+    }
+
+    #[test]
+    fn test_extract_searches_and_copies_databases() {
+        let fixture = Fixture::library();
+        let pattern = |name: &str| vec![format!("{}/{}*.sqlite", fixture.dir.display(), name)];
+        let search = DatabaseSearch {
+            library: pattern("BKLibrary"),
+            annotation: pattern("AEAnnotation"),
+        };
+
+        // Runs side by side read their own copies
+        let (first, second) = std::thread::scope(|scope| {
+            let runs = [(); 2].map(|_| scope.spawn(|| extract(&AppleBooksOptions::default().search(search.clone()))));
+            let [first, second] = runs.map(|run| run.join().unwrap().unwrap());
+            (first, second)
+        });
+        assert_eq!(first.report.library_dbs, vec![fixture.library_db.clone()]);
+        assert_eq!(first.report.annotation_dbs, vec![fixture.annotation_db.clone()]);
+        assert_eq!(first.books.len(), 4);
+        assert_eq!(serde_json::to_value(&first.books).unwrap(), serde_json::to_value(&second.books).unwrap());
+
+        let missing = fixture.dir.join("missing.sqlite");
+        let result = extract(&AppleBooksOptions::default().library_db(&missing).annotation_db(&fixture.annotation_db));
+        assert!(matches!(result, Err(AppleBooksError::LibraryDbNotFound(path)) if path == missing));
+        let result = extract(&AppleBooksOptions::default().library_db(&fixture.library_db).annotation_db(&missing));
+        assert!(matches!(result, Err(AppleBooksError::AnnotationDbNotFound(_))));
+    }
+
+    #[test]
+    fn test_extract_collections() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, true);
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        assert_eq!(meditations.collections, vec!["Want to Read", "Philosophy"]);
+        // In book order, not insertion or date order
+        let texts: Vec<&str> = meditations.highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, vec!["You have power over your mind.", "Waste no more time arguing."]);
+        assert_eq!(meditations.highlights[0].color.as_deref(), Some("yellow"));
+        assert_eq!(meditations.source_ids["apple_books"], "A1");
+
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert!(walden.collections.is_empty());
+        assert_eq!(walden.finished, Some(true));
+    }
+
+    #[test]
+    fn test_extract_keeps_annotations_of_untitled_books() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&library_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZBKLIBRARYASSET VALUES ('A4', NULL, 'Mary Shelley', 0, NULL, NULL, NULL, NULL, NULL, NULL, 0);
+                INSERT INTO ZBKLIBRARYASSET VALUES ('A5', NULL, NULL, 0, NULL, NULL, NULL, NULL, NULL, NULL, 0);
+                "#,
+            )
+            .unwrap();
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZAEANNOTATION VALUES ('U4', 'A4', 'Beware; for I am fearless.', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 0, 1, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('U5', '1234', 'It was a bright cold day in April.', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 0, 1, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('U6', 'A3', 'Fear is the mind-killer.', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 0, 1, 3);
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let titles: Vec<&str> = books.iter().map(|b| b.title.as_str()).collect();
+        // A5 has nothing to keep it for, and A3 is a sample
+        assert_eq!(titles, vec!["Meditations", "Unknown book (1234)", "Unknown book (A4)", "Walden"]);
+
+        let shelley = &books[2];
+        assert_eq!(shelley.author.as_deref(), Some("Mary Shelley"));
+        assert_eq!(shelley.highlights[0].text, "Beware; for I am fearless.");
+
+        let missing = &books[1];
+        assert_eq!(missing.sources, vec![Source::AppleBooks]);
+        assert_eq!(missing.source_ids["apple_books_store"], "1234");
+        assert_eq!(missing.highlights.len(), 1);
+    }
+
+    #[test]
+    fn test_extract_metadata_and_samples() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+
+        let books = extract_books(&library_db, &annotation_db);
+        let with_samples = extract_from_databases(&library_db, &annotation_db, &AppleBooksOptions::default().include_samples(true))
+            .unwrap()
+            .books;
+        let _ = fs::remove_dir_all(&dir);
+
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        assert_eq!(meditations.language.as_deref(), Some("en"));
+        assert_eq!(meditations.genre.as_deref(), Some("Philosophy"));
+        assert_eq!(meditations.source_ids["apple_books_store"], "1234567890");
+
+        // Sideloaded: no language, genre or store ID
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert_eq!((walden.language.as_deref(), walden.genre.as_deref()), (None, None));
+        assert!(!walden.source_ids.contains_key("apple_books_store"));
+
+        assert!(!books.iter().any(|b| b.title == "Dune"));
+        assert!(with_samples.iter().any(|b| b.title == "Dune"));
+    }
+
+    #[test]
+    fn test_extract_progress_and_last_opened() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        assert_eq!(meditations.progress, Some(0.42));
+        assert_eq!(meditations.last_opened_at, Utc.timestamp_opt(710000000 + CORE_DATA_EPOCH_OFFSET, 0).single());
+
+        // Never opened
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert_eq!((walden.progress, walden.last_opened_at), (None, None));
+    }
+
+    #[test]
+    fn test_extract_orders_by_cfi() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        // Annotations without a ZPLLOCATIONRANGESTART sort first in the query
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZAEANNOTATION VALUES ('U3', 'A1', 'The best revenge is not to be like your enemy.', NULL, NULL,
+                    'epubcfi(/6/30!/4/2/8/1:4)', 700000000.0, 0, NULL, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('U4', 'A1', 'Dwell on the beauty of life.', NULL, NULL,
+                    'Page 7', 700000000.0, 0, NULL, 3);
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        let ids: Vec<&str> = meditations.highlights.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["U1", "U2", "U3", "U4"]);
+
+        let location = &meditations.highlights[0].location;
+        assert_eq!(location.position.as_deref(), Some("ch. 13, ¶3"));
+        assert_eq!(location.raw.as_deref(), Some("epubcfi(/6/26[book10]!/4/2/6,/1:0,/1:30)"));
+        // Not a CFI: kept as it is
+        assert_eq!(meditations.highlights[3].location.position.as_deref(), Some("Page 7"));
+        assert_eq!(meditations.highlights[3].location.raw, None);
+    }
+
+    #[test]
+    fn test_extract_underlines() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                ALTER TABLE ZAEANNOTATION ADD COLUMN ZANNOTATIONISUNDERLINE INTEGER;
+                UPDATE ZAEANNOTATION SET ZANNOTATIONISUNDERLINE = 0;
+                INSERT INTO ZAEANNOTATION VALUES ('U3', 'A2', 'Simplify, simplify.', NULL, NULL,
+                    'epubcfi(/6/8!/4/2/1:0)', 700000000.0, 0, 1, 2, 1);
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        // Style 0 is an underline even where the flag says otherwise
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        let styles: Vec<HighlightStyle> = meditations.highlights.iter().map(|h| h.style).collect();
+        assert_eq!(styles, vec![HighlightStyle::Highlight, HighlightStyle::Underline]);
+        assert_eq!(meditations.highlights[1].color, None);
+
+        // A colored underline keeps its color
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert_eq!(walden.highlights[0].style, HighlightStyle::Underline);
+        assert_eq!(walden.highlights[0].color.as_deref(), Some("blue"));
+    }
+
+    #[test]
+    fn test_extract_standalone_notes() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZAEANNOTATION VALUES ('N1', 'A2', NULL, 'Economy: what is really needed.', 'Economy',
+                    'epubcfi(/6/4!/4/2/1:0)', 700000000.0, 0, 1, 0);
+                INSERT INTO ZAEANNOTATION VALUES ('N2', 'A2', '', 'Sounds: the railroad.', 'Sounds',
+                    'epubcfi(/6/10!/4/2/1:0)', 700000000.0, 0, 2, 0);
+                INSERT INTO ZAEANNOTATION VALUES ('B1', 'A2', '', '', 'Solitude',
+                    'epubcfi(/6/12!/4/2/1:0)', 700000000.0, 0, 3, 0);
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        // Rows with neither text nor a note are left out
+        let walden = books.iter().find(|b| b.title == "Walden").unwrap();
+        assert_eq!(walden.highlights.len(), 2);
+        let note = &walden.highlights[0];
+        assert_eq!(note.kind, HighlightKind::Note);
+        assert_eq!(note.text, "");
+        assert_eq!(note.note.as_deref(), Some("Economy: what is really needed."));
+        assert_eq!((note.color.as_deref(), note.style), (None, HighlightStyle::Highlight));
+        assert_eq!(walden.highlights[1].location.chapter.as_deref(), Some("Sounds"));
+    }
+
+    #[test]
+    fn test_extract_pdf_annotations() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+        Connection::open(&library_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                ALTER TABLE ZBKLIBRARYASSET ADD COLUMN ZCONTENTTYPE INTEGER;
+                UPDATE ZBKLIBRARYASSET SET ZCONTENTTYPE = 1;
+                INSERT INTO ZBKLIBRARYASSET VALUES ('P', 'Design Patterns', 'Erich Gamma', 0, NULL, NULL, NULL,
+                    NULL, NULL, NULL, 0, 3);
+                "#,
+            )
+            .unwrap();
+        // A rect-only highlight on a scanned page has no text, and ZPLLOCATIONRANGESTART
+        // can be 0 whatever the page
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO ZAEANNOTATION VALUES ('P1', 'P', NULL, 'Compare with Strategy.', NULL,
+                    '{"pageIndex": 11}', 700000000.0, 0, 0, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('P2', 'P', 'Program to an interface, not an implementation.', NULL, NULL,
+                    NULL, 700000000.0, 0, 2, 3);
+                INSERT INTO ZAEANNOTATION VALUES ('P3', 'P', NULL, NULL, NULL,
+                    '4', 700000000.0, 0, 4, 3);
+                "#,
+            )
+            .unwrap();
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let patterns = books.iter().find(|b| b.title == "Design Patterns").unwrap();
+        let ids: Vec<&str> = patterns.highlights.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["P2", "P3", "P1"]);
+        let positions: Vec<Option<&str>> = patterns.highlights.iter().map(|h| h.location.position.as_deref()).collect();
+        assert_eq!(positions, vec![Some("Page 3"), Some("Page 5"), Some("Page 12")]);
+
+        let rect = &patterns.highlights[2];
+        assert_eq!((rect.kind, rect.text.as_str()), (HighlightKind::Highlight, ""));
+        assert_eq!(rect.note.as_deref(), Some("Compare with Strategy."));
+        assert_eq!(rect.location.raw.as_deref(), Some(r#"{"pageIndex": 11}"#));
+
+        // EPUBs in the same library are still located by CFI
+        let meditations = books.iter().find(|b| b.title == "Meditations").unwrap();
+        assert_eq!(meditations.highlights[0].location.position.as_deref(), Some("ch. 13, ¶3"));
+    }
+
+    #[test]
+    fn test_pdf_page() {
+        assert_eq!(pdf_page("0"), Some(1));
+        assert_eq!(pdf_page(r#"{"pageIndex": 11}"#), Some(12));
+        assert_eq!(pdf_page("page=7"), Some(7));
+        assert_eq!(pdf_page(r#"{"pageNumber":"42","rect":"{{1, 2}, {3, 4}}"}"#), Some(42));
+        assert_eq!(pdf_page("epubcfi(/6/4!/4/2/1:0)"), None);
+    }
+
+    #[test]
+    fn test_extract_merges_databases_newest_first() {
+        let current = Fixture::library();
+        // A stale copy from before a migration: an older title for Walden, the orphan's
+        // library entry, and old copies of two annotations
+        let stale = Fixture::new();
+        stale
+            .asset(Asset {
+                id: "FINISHED",
+                title: Some("Walden; or, Life in the Woods"),
+                ..Asset::default()
+            })
+            .asset(Asset {
+                id: "ORPHAN",
+                title: Some("1984"),
+                author: Some("George Orwell"),
+                ..Asset::default()
+            })
+            .annotation(Annotation {
+                id: "W1",
+                asset_id: "FINISHED",
+                text: Some("Simplify."),
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "M2",
+                asset_id: "READING",
+                text: Some("Deleted on the phone"),
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "X1",
+                asset_id: "ORPHAN",
+                text: Some("War is peace."),
+                ..Annotation::default()
+            });
+        let day_ago = SystemTime::now() - std::time::Duration::from_secs(86400);
+        for path in [&stale.library_db, &stale.annotation_db] {
+            fs::File::options().write(true).open(path).unwrap().set_modified(day_ago).unwrap();
+        }
+
+        // The stale directory's pattern comes first, but the newest file wins
+        let pattern = |name: &str| {
+            [&stale, &current]
+                .iter()
+                .map(|f| format!("{}/{}*.sqlite", f.dir.display(), name))
+                .collect()
+        };
+        let search = DatabaseSearch {
+            library: pattern("BKLibrary"),
+            annotation: pattern("AEAnnotation"),
+        };
+        let extracted = extract(&AppleBooksOptions::default().search(search.clone())).unwrap();
+        let report = &extracted.report;
+        assert_eq!(report.library_dbs, vec![current.library_db.clone(), stale.library_db.clone()]);
+        assert_eq!(report.annotation_dbs, vec![current.annotation_db.clone(), stale.annotation_db.clone()]);
+        assert_eq!(report.duplicate_annotations, 2);
+        assert_eq!(report.orphaned, 0);
+
+        let book = |title: &str| extracted.books.iter().find(|b| b.title == title).unwrap();
+        assert_eq!(book("Walden").highlights[0].text, "Simplify, simplify.");
+        assert!(!extracted.books.iter().any(|b| b.title.starts_with("Walden;")));
+        let ids = |title: &str| book(title).highlights.iter().map(|h| h.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids("1984"), vec!["O1", "X1"]);
+        // Deleted in the newer database, so the older copy isn't brought back
+        assert_eq!(ids("Meditations"), vec!["M1"]);
+
+        // Several annotation databases are always read in full
+        let since = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        let later = extract(&AppleBooksOptions::default().search(search).modified_after(since)).unwrap();
+        assert!(!later.report.incremental);
+        assert_eq!(later.report.annotations, extracted.report.annotations);
+    }
+
+    #[test]
+    fn test_find_databases_newest_first() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (current, old) = (dir.join("iBooksX"), dir.join("iBooks"));
+        fs::create_dir_all(&current).unwrap();
+        fs::create_dir_all(&old).unwrap();
+        let touch = |path: PathBuf, age_secs: u64| {
+            let file = fs::File::create(&path).unwrap();
+            file.set_modified(SystemTime::now() - std::time::Duration::from_secs(age_secs)).unwrap();
+            path
+        };
+        let stale = touch(current.join("BKLibrary-1.sqlite"), 3600);
+        let live = touch(current.join("BKLibrary-2.sqlite"), 60);
+        touch(current.join("BKLibrary-2.sqlite-wal"), 0);
+        let legacy = touch(old.join("BKLibrary-1.sqlite"), 0);
+
+        let patterns: Vec<String> = [&current, &old, &dir.join("missing")]
+            .iter()
+            .map(|d| d.join("BKLibrary*.sqlite").to_string_lossy().to_string())
+            .collect();
+        let candidates: Vec<PathBuf> = find_candidates(&patterns).into_iter().map(|c| c.path).collect();
+        let found = find_databases(&patterns, "library").unwrap();
+        let fallback = find_databases(&patterns[1..], "library").unwrap();
+        let overlapping = find_databases(&[patterns[1].clone(), patterns[1].clone()], "library").unwrap();
+        let missing = find_databases(&patterns[2..], "library");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(candidates, vec![live.clone(), stale.clone(), legacy.clone()]);
+        assert_eq!(found, vec![legacy.clone(), live, stale]);
+        assert_eq!(fallback, vec![legacy.clone()]);
+        assert_eq!(overlapping, vec![legacy]);
+        let error = missing.unwrap_err().to_string();
+        assert!(error.starts_with("No Apple Books library database found (searched "), "{}", error);
+        assert!(error.contains("missing"));
+    }
+
+    #[test]
+    fn test_extract_without_collection_tables() {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_{}", uuid::Uuid::new_v4()));
+        let (library_db, annotation_db) = make_databases(&dir, false);
+
+        let books = extract_books(&library_db, &annotation_db);
+        let _ = fs::remove_dir_all(&dir);
+
+        let titles: Vec<&str> = books.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(titles, vec!["Meditations", "Walden"]);
+        assert!(books.iter().all(|b| b.collections.is_empty()));
+    }
+}
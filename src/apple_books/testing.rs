@@ -0,0 +1,256 @@
+//! Miniature Apple Books databases for tests
+//!
+//! `Fixture` builds a BKLibrary and an AEAnnotation database in a temp directory, with
+//! every column the queries read, optional ones included, so the extractor can be tested
+//! without a Mac. Rows are described with `Asset` and `Annotation`, whose defaults are an
+//! unremarkable EPUB and a yellow highlight; `Fixture::library` is a small library with
+//! the cases that matter most.
+
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::PathBuf;
+
+/// A library and an annotation database, removed when dropped
+pub(crate) struct Fixture {
+    pub dir: PathBuf,
+    pub library_db: PathBuf,
+    pub annotation_db: PathBuf,
+}
+
+/// A ZBKLIBRARYASSET row
+#[derive(Debug, Clone)]
+pub(crate) struct Asset {
+    pub id: &'static str,
+    pub title: Option<&'static str>,
+    pub author: Option<&'static str>,
+    pub finished: bool,
+    /// Core Data timestamp
+    pub finished_at: Option<f64>,
+    pub progress: Option<f64>,
+    pub last_opened: Option<f64>,
+    pub language: Option<&'static str>,
+    pub genre: Option<&'static str>,
+    pub store_id: Option<i64>,
+    pub sample: bool,
+    /// 1 for EPUBs, 3 for PDFs
+    pub content_type: i64,
+}
+
+impl Default for Asset {
+    fn default() -> Self {
+        Self {
+            id: "A1",
+            title: Some("Walden"),
+            author: Some("Henry David Thoreau"),
+            finished: false,
+            finished_at: None,
+            progress: None,
+            last_opened: None,
+            language: None,
+            genre: None,
+            store_id: None,
+            sample: false,
+            content_type: 1,
+        }
+    }
+}
+
+/// A ZAEANNOTATION row
+#[derive(Debug, Clone)]
+pub(crate) struct Annotation {
+    pub id: &'static str,
+    pub asset_id: &'static str,
+    pub text: Option<&'static str>,
+    pub note: Option<&'static str>,
+    pub chapter: Option<&'static str>,
+    pub location: Option<&'static str>,
+    /// Core Data timestamps
+    pub created: Option<f64>,
+    pub modified: Option<f64>,
+    pub deleted: bool,
+    pub range_start: Option<i64>,
+    pub style: Option<i64>,
+    pub underline: bool,
+    pub context: Option<&'static str>,
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Self {
+            id: "U1",
+            asset_id: "A1",
+            text: Some("Simplify, simplify."),
+            note: None,
+            chapter: None,
+            location: Some("epubcfi(/6/4!/4/2/1:0)"),
+            created: Some(700000000.0),
+            modified: Some(700000000.0),
+            deleted: false,
+            range_start: Some(1),
+            style: Some(3),
+            underline: false,
+            context: None,
+        }
+    }
+}
+
+impl Fixture {
+    /// Empty databases with the current schema
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("readingsync_apple_fixture_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        // Named like the real files, so the default search patterns' globs match them
+        let library_db = dir.join("BKLibrary-1-091020131601.sqlite");
+        let annotation_db = dir.join("AEAnnotation_v10312011_1727_local.sqlite");
+
+        Connection::open(&library_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                CREATE TABLE ZBKLIBRARYASSET (
+                    ZASSETID TEXT, ZTITLE TEXT, ZAUTHOR TEXT, ZISFINISHED INTEGER, ZDATEFINISHED REAL,
+                    ZREADINGPROGRESS REAL, ZLASTOPENDATE REAL, ZLANGUAGE TEXT, ZGENRE TEXT, ZSTOREID INTEGER,
+                    ZISSAMPLE INTEGER, ZCONTENTTYPE INTEGER, ZPATH TEXT
+                );
+                CREATE TABLE ZBKCOLLECTION (Z_PK INTEGER PRIMARY KEY, ZCOLLECTIONID TEXT, ZTITLE TEXT, ZDELETEDFLAG INTEGER, ZSORTKEY INTEGER);
+                CREATE TABLE ZBKCOLLECTIONMEMBER (Z_PK INTEGER PRIMARY KEY, ZCOLLECTION INTEGER, ZASSETID TEXT, ZSORTKEY INTEGER);
+                "#,
+            )
+            .unwrap();
+        Connection::open(&annotation_db)
+            .unwrap()
+            .execute_batch(
+                r#"
+                CREATE TABLE ZAEANNOTATION (
+                    ZANNOTATIONUUID TEXT, ZANNOTATIONASSETID TEXT, ZANNOTATIONSELECTEDTEXT TEXT,
+                    ZANNOTATIONNOTE TEXT, ZFUTUREPROOFING5 TEXT, ZANNOTATIONLOCATION TEXT,
+                    ZANNOTATIONCREATIONDATE REAL, ZANNOTATIONMODIFICATIONDATE REAL, ZANNOTATIONDELETED INTEGER,
+                    ZPLLOCATIONRANGESTART INTEGER, ZANNOTATIONSTYLE INTEGER, ZANNOTATIONISUNDERLINE INTEGER,
+                    ZANNOTATIONREPRESENTATIVETEXT TEXT
+                );
+                "#,
+            )
+            .unwrap();
+
+        Self {
+            dir,
+            library_db,
+            annotation_db,
+        }
+    }
+
+    /// A finished book, a book without an author, a book being read with a deleted
+    /// annotation, and an annotation of an asset missing from the library
+    pub fn library() -> Self {
+        let fixture = Self::new();
+        fixture
+            .asset(Asset {
+                id: "FINISHED",
+                finished: true,
+                finished_at: Some(700000000.0),
+                progress: Some(1.0),
+                ..Asset::default()
+            })
+            .asset(Asset {
+                id: "NO_AUTHOR",
+                title: Some("The Cloud of Unknowing"),
+                author: None,
+                ..Asset::default()
+            })
+            .asset(Asset {
+                id: "READING",
+                title: Some("Meditations"),
+                author: Some("Marcus Aurelius"),
+                progress: Some(0.42),
+                ..Asset::default()
+            })
+            .annotation(Annotation {
+                id: "W1",
+                asset_id: "FINISHED",
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "C1",
+                asset_id: "NO_AUTHOR",
+                text: Some("By love may He be gotten and holden; but by thought never."),
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "M1",
+                asset_id: "READING",
+                text: Some("You have power over your mind."),
+                location: Some("epubcfi(/6/26!/4/2/1:0)"),
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "M2",
+                asset_id: "READING",
+                text: Some("Deleted on the phone"),
+                location: Some("epubcfi(/6/30!/4/2/1:0)"),
+                deleted: true,
+                ..Annotation::default()
+            })
+            .annotation(Annotation {
+                id: "O1",
+                asset_id: "ORPHAN",
+                text: Some("It was a bright cold day in April."),
+                ..Annotation::default()
+            });
+        fixture
+    }
+
+    pub fn asset(&self, asset: Asset) -> &Self {
+        Connection::open(&self.library_db)
+            .unwrap()
+            .execute(
+                "INSERT INTO ZBKLIBRARYASSET VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, NULL)",
+                params![
+                    asset.id,
+                    asset.title,
+                    asset.author,
+                    asset.finished,
+                    asset.finished_at,
+                    asset.progress,
+                    asset.last_opened,
+                    asset.language,
+                    asset.genre,
+                    asset.store_id,
+                    asset.sample,
+                    asset.content_type,
+                ],
+            )
+            .unwrap();
+        self
+    }
+
+    pub fn annotation(&self, annotation: Annotation) -> &Self {
+        Connection::open(&self.annotation_db)
+            .unwrap()
+            .execute(
+                "INSERT INTO ZAEANNOTATION VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    annotation.id,
+                    annotation.asset_id,
+                    annotation.text,
+                    annotation.note,
+                    annotation.chapter,
+                    annotation.location,
+                    annotation.created,
+                    annotation.modified,
+                    annotation.deleted,
+                    annotation.range_start,
+                    annotation.style,
+                    annotation.underline,
+                    annotation.context,
+                ],
+            )
+            .unwrap();
+        self
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::error::CalibreError;
+use crate::model::{Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Highlight payload stored in the `annot_data` column
+#[derive(Debug, Deserialize)]
+struct AnnotationData {
+    uuid: Option<String>,
+    highlighted_text: Option<String>,
+    notes: Option<String>,
+    start_cfi: Option<String>,
+    #[serde(default)]
+    toc_family_titles: Vec<String>,
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    removed: bool,
+}
+
+/// Extract viewer highlights from a Calibre library
+///
+/// Accepts the library folder or its `metadata.db` directly. Books get the same IDs as
+/// other sources so they merge with Kindle and Apple Books copies.
+pub fn extract(library: &Path) -> Result<Vec<Book>, CalibreError> {
+    let db_path = if library.is_dir() {
+        library.join("metadata.db")
+    } else {
+        library.to_path_buf()
+    };
+
+    if !db_path.exists() {
+        return Err(CalibreError::DatabaseNotFound(db_path));
+    }
+
+    let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let authors = load_authors(&conn)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT a.book, b.title, a.annot_id, a.annot_data, a.timestamp
+        FROM annotations a
+        JOIN books b ON b.id = a.book
+        WHERE a.annot_type = 'highlight'
+        ORDER BY a.book, a.timestamp
+        "#,
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<f64>>(4)?,
+        ))
+    })?;
+
+    let mut books: Vec<Book> = Vec::new();
+    let mut index_by_book: HashMap<i64, usize> = HashMap::new();
+
+    for row_result in rows {
+        let (book_id, title, annot_id, annot_data, timestamp) = row_result?;
+
+        let data: AnnotationData = serde_json::from_str(&annot_data)
+            .map_err(|e| CalibreError::InvalidAnnotation(annot_id.clone(), e))?;
+
+        let text = data.highlighted_text.as_deref().map(str::trim).unwrap_or_default();
+        if data.removed || text.is_empty() {
+            continue;
+        }
+
+        let index = *index_by_book.entry(book_id).or_insert_with(|| {
+            let mut book = Book::new(title, authors.get(&book_id).cloned());
+            book.sources = vec![Source::Calibre];
+            books.push(book);
+            books.len() - 1
+        });
+
+        books[index].highlights.push(Highlight {
+            id: data.uuid.unwrap_or(annot_id),
+            text: text.to_string(),
+            note: data.notes.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()),
+            location: Location {
+                chapter: data.toc_family_titles.last().cloned(),
+                position: data.start_cfi,
+                page: None,
+                raw: None,
+            },
+            created_at: data.timestamp.or_else(|| {
+                timestamp.and_then(|ts| DateTime::from_timestamp_millis((ts * 1000.0) as i64))
+            }),
+            sources: vec![Source::Calibre],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    Ok(books)
+}
+
+/// Authors per book, joined with " & " in Calibre's author order
+///
+/// Not ", ", which `normalize_author` reads as one "Last, First" name, giving the book
+/// another ID than the same book from other sources.
+fn load_authors(conn: &Connection) -> Result<HashMap<i64, String>, CalibreError> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT l.book, a.name
+        FROM books_authors_link l
+        JOIN authors a ON a.id = l.author
+        ORDER BY l.book, l.id
+        "#,
+    )?;
+
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut authors: HashMap<i64, Vec<String>> = HashMap::new();
+    for row_result in rows {
+        let (book, name) = row_result?;
+        authors.entry(book).or_default().push(name);
+    }
+
+    Ok(authors
+        .into_iter()
+        .map(|(book, names)| (book, names.join(" & ")))
+        .collect())
+}
+
+/// Default Calibre library location
+pub fn default_library_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join("Calibre Library"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::generate_book_id;
+    use chrono::TimeZone;
+
+    fn make_library(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        let conn = Connection::open(dir.join("metadata.db")).unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE books (id INTEGER PRIMARY KEY, title TEXT);
+            CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE books_authors_link (id INTEGER PRIMARY KEY, book INTEGER, author INTEGER);
+            CREATE TABLE annotations (
+                id INTEGER PRIMARY KEY, book INTEGER, format TEXT, user_type TEXT, user TEXT,
+                timestamp REAL, annot_id TEXT, annot_type TEXT, annot_data TEXT, searchable_text TEXT
+            );
+            INSERT INTO books VALUES (1, 'Good Omens');
+            INSERT INTO authors VALUES (1, 'Terry Pratchett'), (2, 'Neil Gaiman');
+            INSERT INTO books_authors_link VALUES (1, 1, 1), (2, 1, 2);
+            INSERT INTO annotations VALUES (1, 1, 'EPUB', 'local', 'viewer', 1620000000.0, 'a1', 'highlight',
+                '{"type":"highlight","uuid":"a1","highlighted_text":"Kindly remove your hand.","notes":"Ha","start_cfi":"/2/4/2/6:0","toc_family_titles":["Part One","In the Beginning"],"timestamp":"2021-05-03T00:00:00.000Z"}', '');
+            INSERT INTO annotations VALUES (2, 1, 'EPUB', 'local', 'viewer', 1620000100.0, 'a2', 'highlight',
+                '{"type":"highlight","uuid":"a2","highlighted_text":"Gone","removed":true}', '');
+            INSERT INTO annotations VALUES (3, 1, 'EPUB', 'local', 'viewer', 1620000200.0, 'a3', 'bookmark',
+                '{"type":"bookmark","title":"Bookmark 1"}', '');
+            "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_extract_library() {
+        let dir = std::env::temp_dir().join(format!("readingsync_calibre_{}", uuid::Uuid::new_v4()));
+        make_library(&dir);
+
+        let books = extract(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.author.as_deref(), Some("Terry Pratchett & Neil Gaiman"));
+        assert_eq!(book.id, generate_book_id("Good Omens", Some("Terry Pratchett & Neil Gaiman")));
+        assert_eq!(book.sources, vec![Source::Calibre]);
+        assert_eq!(book.highlights.len(), 1);
+
+        let highlight = &book.highlights[0];
+        assert_eq!(highlight.id, "a1");
+        assert_eq!(highlight.note.as_deref(), Some("Ha"));
+        assert_eq!(highlight.location.chapter.as_deref(), Some("In the Beginning"));
+        assert_eq!(highlight.location.position.as_deref(), Some("/2/4/2/6:0"));
+        assert_eq!(highlight.created_at, Utc.with_ymd_and_hms(2021, 5, 3, 0, 0, 0).single());
+    }
+
+    #[test]
+    fn test_multiple_authors_match_other_sources() {
+        let dir = std::env::temp_dir().join(format!("readingsync_calibre_{}", uuid::Uuid::new_v4()));
+        make_library(&dir);
+
+        let books = extract(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Another source listing the same authors semicolon-separated, in another order
+        assert_eq!(books[0].id, generate_book_id("Good Omens", Some("Neil Gaiman; Terry Pratchett")));
+        // Joined with ", " they would read as one "Last, First" name and get another ID
+        assert_ne!(books[0].id, generate_book_id("Good Omens", Some("Terry Pratchett, Neil Gaiman")));
+
+        let clippings = crate::kindle::clippings::parse_clippings_content(
+            "Good Omens (Neil Gaiman;Terry Pratchett)\n- Your Highlight on Location 10-12 | Added on Monday, May 3, 2021 12:00:00 AM\n\nIt may help to understand human affairs.\n==========\n",
+        )
+        .unwrap();
+        let merged = crate::merge::merge_books(vec![books, clippings]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sources, vec![Source::Calibre, Source::Kindle]);
+        assert_eq!(merged[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_library() {
+        let result = extract(Path::new("/nonexistent/Calibre Library"));
+        assert!(matches!(result, Err(CalibreError::DatabaseNotFound(_))));
+    }
+}
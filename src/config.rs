@@ -1,5 +1,6 @@
 use crate::error::ConfigError;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -15,6 +16,9 @@ pub struct Config {
 
     /// Kindle configuration
     pub kindle: KindleConfig,
+
+    /// Network settings for every HTTP request
+    pub http: HttpConfig,
 }
 
 impl Default for Config {
@@ -23,6 +27,7 @@ impl Default for Config {
             output_path: default_output_path(),
             apple_books: AppleBooksConfig::default(),
             kindle: KindleConfig::default(),
+            http: HttpConfig::default(),
         }
     }
 }
@@ -39,6 +44,12 @@ pub struct AppleBooksConfig {
 
     /// Override path for the annotation database
     pub annotation_db: Option<PathBuf>,
+
+    /// Extra glob patterns to look for the library database with, before the built-in locations
+    pub library_db_patterns: Vec<String>,
+
+    /// Extra glob patterns to look for the annotation database with, before the built-in locations
+    pub annotation_db_patterns: Vec<String>,
 }
 
 impl Default for AppleBooksConfig {
@@ -47,6 +58,8 @@ impl Default for AppleBooksConfig {
             enabled: true,
             library_db: None,
             annotation_db: None,
+            library_db_patterns: Vec::new(),
+            annotation_db_patterns: Vec::new(),
         }
     }
 }
@@ -64,8 +77,49 @@ pub struct KindleConfig {
     /// Path to cookies file for Amazon scraping
     pub cookies_path: Option<PathBuf>,
 
-    /// Amazon region code (us, uk, de, fr, etc.)
+    /// Amazon region code (us, uk, de, fr, etc.), or "auto" to detect the account's
     pub region: String,
+
+    /// Clippings titles to rename, e.g. a sideloaded file name to the book's real title.
+    /// Keys match the raw or cleaned-up title, ignoring case.
+    pub title_aliases: BTreeMap<String, String>,
+
+    /// Browser sync settings
+    pub browser: KindleBrowserSettings,
+}
+
+/// Browser sync settings, the `[kindle.browser]` table
+///
+/// Unset values fall back to the `kindle` command's flags, then the built-in defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KindleBrowserSettings {
+    /// Chrome, Chromium, Edge or Brave binary to use instead of searching for one
+    pub chrome_path: Option<PathBuf>,
+
+    /// Run the browser without a window
+    pub headless: Option<bool>,
+
+    /// Timeout for page loads in seconds
+    pub timeout_secs: Option<u64>,
+
+    /// How long to wait for a book's highlights after opening it, in seconds
+    pub per_book_timeout_secs: Option<u64>,
+
+    /// Pause between books in milliseconds
+    pub inter_book_delay_ms: Option<u64>,
+
+    /// Most pages of highlights read per book
+    pub max_pages: Option<usize>,
+
+    /// Browser window size as [width, height]
+    pub window_size: Option<(u32, u32)>,
+
+    /// Chrome profile directory holding the Amazon session
+    pub user_data_dir: Option<PathBuf>,
+
+    /// Tabs scraping books at once (at most 4)
+    pub concurrency: Option<usize>,
 }
 
 impl Default for KindleConfig {
@@ -74,11 +128,30 @@ impl Default for KindleConfig {
             enabled: true,
             clippings_path: None,
             cookies_path: None,
-            region: "us".to_string(),
+            region: "auto".to_string(),
+            title_aliases: BTreeMap::new(),
+            browser: KindleBrowserSettings::default(),
         }
     }
 }
 
+/// Network settings, the `[http]` table
+///
+/// Used by the cookie scraper, cover downloads and the Hypothesis and Pocket APIs.
+/// The `--proxy`, `--ca-cert` and `--user-agent` flags override them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Proxy for all requests, e.g. "http://proxy.corp:3128"; defaults to HTTPS_PROXY / HTTP_PROXY
+    pub proxy: Option<String>,
+
+    /// PEM or DER root certificates to trust besides the system's, e.g. a TLS-intercepting proxy's
+    pub ca_certs: Vec<PathBuf>,
+
+    /// User-Agent header; the cookie scraper otherwise sends a desktop Chrome one
+    pub user_agent: Option<String>,
+}
+
 /// Get the default output path
 fn default_output_path() -> PathBuf {
     dirs::data_local_dir()
@@ -146,15 +219,24 @@ impl Config {
         if let Some(ref mut path) = self.kindle.cookies_path {
             *path = expand_tilde(path);
         }
+        if let Some(ref mut path) = self.kindle.browser.chrome_path {
+            *path = expand_tilde(path);
+        }
+        if let Some(ref mut path) = self.kindle.browser.user_data_dir {
+            *path = expand_tilde(path);
+        }
+        for path in &mut self.http.ca_certs {
+            *path = expand_tilde(path);
+        }
     }
 }
 
 /// Expand tilde in a path
 fn expand_tilde(path: &Path) -> PathBuf {
     let path_str = path.to_string_lossy();
-    if path_str.starts_with("~/") {
+    if let Some(rest) = path_str.strip_prefix("~/") {
         if let Some(home) = dirs::home_dir() {
-            return home.join(&path_str[2..]);
+            return home.join(rest);
         }
     }
     path.to_path_buf()
@@ -169,7 +251,7 @@ mod tests {
         let config = Config::default();
         assert!(config.apple_books.enabled);
         assert!(config.kindle.enabled);
-        assert_eq!(config.kindle.region, "us");
+        assert_eq!(config.kindle.region, "auto");
     }
 
     #[test]
@@ -190,4 +272,21 @@ mod tests {
 
         assert_eq!(parsed.kindle.region, config.kindle.region);
     }
+
+    #[test]
+    fn test_kindle_browser_section() {
+        let config: Config = toml::from_str("[kindle.browser]\nchrome_path = \"/opt/homebrew/bin/chromium\"\n").unwrap();
+        assert_eq!(config.kindle.browser.chrome_path, Some(PathBuf::from("/opt/homebrew/bin/chromium")));
+        assert_eq!(config.kindle.browser.max_pages, None);
+        assert_eq!(config.kindle.region, "auto");
+
+        let config: Config = toml::from_str(
+            "[kindle.browser]\nheadless = true\nper_book_timeout_secs = 45\nmax_pages = 500\nwindow_size = [1600, 1000]\n",
+        )
+        .unwrap();
+        assert_eq!(config.kindle.browser.headless, Some(true));
+        assert_eq!(config.kindle.browser.per_book_timeout_secs, Some(45));
+        assert_eq!(config.kindle.browser.max_pages, Some(500));
+        assert_eq!(config.kindle.browser.window_size, Some((1600, 1000)));
+    }
 }
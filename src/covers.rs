@@ -0,0 +1,149 @@
+//! Download cover images so exports can show them offline
+//!
+//! Covers are saved as `<ASIN or book ID>.<ext>` in one directory, so a file already
+//! there from an earlier run is reused instead of fetched again.
+
+use crate::config::HttpConfig;
+use crate::error::Error;
+use crate::model::Book;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// What a cover download pass did
+#[derive(Debug, Default)]
+pub struct CoverDownloads {
+    pub downloaded: usize,
+    /// Covers already in the directory from an earlier run
+    pub reused: usize,
+    /// (book title, error) for covers that couldn't be fetched; they keep their URL
+    pub failed: Vec<(String, String)>,
+}
+
+/// Download every book's remote cover into `dir`, pointing `cover_url` at the file
+///
+/// Paths are recorded relative to `relative_to` (the output file's directory) when
+/// `dir` is inside it, so the library and its covers can be moved together.
+pub fn download_covers(
+    books: &mut [Book],
+    dir: &Path,
+    relative_to: &Path,
+    http: &HttpConfig,
+) -> Result<CoverDownloads, Error> {
+    let client = crate::http::blocking_client_builder(http)?
+        .timeout(Duration::from_secs(30))
+        .build()?;
+
+    let fetch = |url: &str| -> Result<Vec<u8>, String> {
+        let response = client
+            .get(url)
+            .send()
+            .map_err(|e| crate::http::request_error(&e, url, http).to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        response.bytes().map(|b| b.to_vec()).map_err(|e| e.to_string())
+    };
+
+    download_covers_with(books, dir, relative_to, &fetch)
+}
+
+fn download_covers_with(
+    books: &mut [Book],
+    dir: &Path,
+    relative_to: &Path,
+    fetch: &dyn Fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<CoverDownloads, Error> {
+    let mut downloads = CoverDownloads::default();
+
+    for book in books.iter_mut() {
+        // Books without a cover, or whose cover is already a local file, are left alone
+        let Some(url) = book.cover_url.clone().filter(|u| u.starts_with("http://") || u.starts_with("https://"))
+        else {
+            continue;
+        };
+
+        let name = format!("{}.{}", book.asin().unwrap_or(&book.id), cover_extension(&url));
+        let path = dir.join(&name);
+
+        if path.exists() {
+            downloads.reused += 1;
+        } else {
+            match fetch(&url) {
+                Ok(bytes) => {
+                    fs::create_dir_all(dir)?;
+                    fs::write(&path, bytes)?;
+                    downloads.downloaded += 1;
+                }
+                Err(e) => {
+                    downloads.failed.push((book.title.clone(), e));
+                    continue;
+                }
+            }
+        }
+
+        let recorded = path.strip_prefix(relative_to).unwrap_or(&path);
+        book.cover_url = Some(recorded.to_string_lossy().replace('\\', "/"));
+    }
+
+    Ok(downloads)
+}
+
+/// Image file extension from a cover URL, defaulting to "jpg"
+fn cover_extension(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+    ["png", "gif", "webp", "jpeg"]
+        .into_iter()
+        .find(|ext| path.ends_with(&format!(".{}", ext)))
+        .unwrap_or("jpg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_download_covers_reuses_earlier_files() {
+        let root = std::env::temp_dir().join(format!("readingsync_covers_{}", uuid::Uuid::new_v4()));
+        let dir = root.join("covers");
+
+        let mut dune = Book::new("Dune".to_string(), None);
+        dune.source_ids.insert("asin".to_string(), "B00B7NPRY8".to_string());
+        dune.cover_url = Some("https://m.media-amazon.com/images/I/81.SY160.png?x=1".to_string());
+        let mut walden = Book::new("Walden".to_string(), None);
+        walden.cover_url = Some("https://example.com/walden".to_string());
+        let mut emma = Book::new("Emma".to_string(), None);
+        emma.cover_url = Some("https://example.com/missing.jpg".to_string());
+        let uncovered = Book::new("Middlemarch".to_string(), None);
+        let mut books = vec![dune, walden, emma, uncovered];
+
+        let fetches = Cell::new(0);
+        let fetch = |url: &str| {
+            fetches.set(fetches.get() + 1);
+            if url.contains("missing") {
+                Err("HTTP 404 Not Found".to_string())
+            } else {
+                Ok(url.as_bytes().to_vec())
+            }
+        };
+
+        let first = download_covers_with(&mut books, &dir, &root, &fetch).unwrap();
+        assert_eq!(first.downloaded, 2);
+        assert_eq!(first.failed, vec![("Emma".to_string(), "HTTP 404 Not Found".to_string())]);
+        assert_eq!(books[0].cover_url.as_deref(), Some("covers/B00B7NPRY8.png"));
+        assert_eq!(books[1].cover_url, Some(format!("covers/{}.jpg", books[1].id)));
+        assert!(books[2].cover_url.as_deref().unwrap().starts_with("https://"));
+        assert_eq!(books[3].cover_url, None);
+
+        // A fresh scrape has URLs again; the files on disk are reused
+        books[0].cover_url = Some("https://m.media-amazon.com/images/I/81.SY160.png".to_string());
+        let second = download_covers_with(&mut books, &dir, &root, &fetch).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(second.reused, 1);
+        assert_eq!(second.downloaded, 0);
+        assert_eq!(fetches.get(), 4);
+        assert_eq!(books[0].cover_url.as_deref(), Some("covers/B00B7NPRY8.png"));
+    }
+}
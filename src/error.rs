@@ -10,14 +10,97 @@ pub enum Error {
     #[error("Kindle error: {0}")]
     Kindle(#[from] KindleError),
 
+    #[error("Kobo error: {0}")]
+    Kobo(#[from] KoboError),
+
+    #[error("Google Play Books error: {0}")]
+    PlayBooks(#[from] PlayBooksError),
+
+    #[error("Pocket error: {0}")]
+    Pocket(#[from] PocketError),
+
+    #[error("Instapaper error: {0}")]
+    Instapaper(#[from] InstapaperError),
+
+    #[error("KOReader error: {0}")]
+    KoReader(#[from] KoReaderError),
+
+    #[error("Moon+ Reader error: {0}")]
+    MoonReader(#[from] MoonReaderError),
+
+    #[error("Calibre error: {0}")]
+    Calibre(#[from] CalibreError),
+
+    #[error("Hypothesis error: {0}")]
+    Hypothesis(#[from] HypothesisError),
+
+    #[error("Readwise error: {0}")]
+    Readwise(#[from] ReadwiseError),
+
+    #[error("Libby error: {0}")]
+    Libby(#[from] LibbyError),
+
+    #[error("Goodreads error: {0}")]
+    Goodreads(#[from] GoodreadsError),
+
+    #[error("Annotation JSON error: {0}")]
+    AnnotatedJson(#[from] AnnotatedJsonError),
+
+    #[error("Import command error: {0}")]
+    ImportCmd(#[from] ImportCmdError),
+
     #[error("Config error: {0}")]
     Config(#[from] ConfigError),
 
+    #[error("Export error: {0}")]
+    Export(#[from] ExportError),
+
+    #[error("Failed to read library file {0}: {1}")]
+    LibraryRead(PathBuf, std::io::Error),
+
+    #[error("Failed to parse library file {0}: {1}")]
+    LibraryParse(PathBuf, serde_json::Error),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+}
+
+/// Errors setting up an HTTP client or reaching a server, saying whether the proxy or
+/// the server failed
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    #[error("Invalid proxy URL {0}: {1}")]
+    InvalidProxy(String, String),
+
+    #[error("Failed to read CA certificate {0}: {1}")]
+    CertificateRead(PathBuf, std::io::Error),
+
+    #[error("Invalid CA certificate {0}: {1}")]
+    InvalidCertificate(PathBuf, String),
+
+    #[error("Invalid user agent {0:?}")]
+    InvalidUserAgent(String),
+
+    #[error("Failed to set up the HTTP client: {0}")]
+    Client(String),
+
+    #[error("Could not connect to proxy {proxy} (for {url}): {message}")]
+    ProxyConnect { proxy: String, url: String, message: String },
+
+    #[error("Proxy {proxy} would not connect to {url}: {message}")]
+    ProxyRefused { proxy: String, url: String, message: String },
+
+    #[error("Request to {url} failed: {message}")]
+    Upstream { url: String, message: String },
 }
 
 /// Errors specific to Apple Books extraction
@@ -35,8 +118,20 @@ pub enum AppleBooksError {
     #[error("Failed to copy database to temp location: {0}")]
     TempCopyFailed(std::io::Error),
 
-    #[error("No Apple Books databases found")]
-    NoDatabasesFound,
+    #[error("No Apple Books {database} database found (searched {})", searched.join(", "))]
+    NoDatabasesFound { database: &'static str, searched: Vec<String> },
+
+    #[error("No Manifest.db in {0}; is this a device backup folder?")]
+    BackupManifestNotFound(PathBuf),
+
+    #[error("Backup is encrypted; make an unencrypted backup to import Apple Books highlights")]
+    BackupEncrypted,
+
+    #[error("Backup does not contain the Apple Books {0} database")]
+    BackupDatabaseNotFound(&'static str),
+
+    #[error("Apple Books sync state {0}: {1}")]
+    StateError(PathBuf, String),
 }
 
 /// Errors specific to Kindle extraction
@@ -60,14 +155,245 @@ pub enum KindleError {
     #[error("HTTP request failed: {0}")]
     HttpError(#[from] reqwest::Error),
 
+    #[error("HTTP {0} from {1}")]
+    HttpStatus(u16, String),
+
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+
+    #[error("Failed to start the HTTP runtime: {0}")]
+    RuntimeError(std::io::Error),
+
     #[error("Failed to parse Amazon page: {0}")]
     ParseError(String),
 
-    #[error("Not authenticated with Amazon. Please provide valid cookies.")]
+    #[error("Amazon did not accept the cookies (signed out or expired). Log in to the Kindle notebook (read.amazon.com or your region's) in a browser, export its cookies in Netscape cookies.txt format, e.g. with a \"Get cookies.txt LOCALLY\" extension, and pass the file with --cookies")]
     NotAuthenticated,
 
+    #[error("No cookies given. Pass --cookies with a cookies.txt exported from a browser logged in to Amazon, --cookies-from to read them from an installed browser, or set kindle.cookies_path in the config")]
+    NoCookies,
+
+    #[error("No {0} cookie store found (looked for {})", .1.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    CookieStoreNotFound(&'static str, Vec<PathBuf>),
+
+    #[error("Can't read {0}'s cookie store: {1}")]
+    CookieStoreUnreadable(&'static str, String),
+
+    #[error("Can't decrypt {0}'s cookies: {1}")]
+    CookiesEncrypted(&'static str, String),
+
+    #[error("{0} has no cookies for {1}. Log in to the Kindle notebook in {0} first")]
+    NoBrowserCookies(&'static str, String),
+
+    #[error("Amazon login required. Run `readingsync kindle` once without --headless or --non-interactive to log in; the session is then kept in the browser profile")]
+    LoginRequired,
+
+    #[error("No Chrome-compatible browser found (tried {}). Install Chrome or Chromium, or set kindle.browser.chrome_path / --chrome-path", .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    BrowserNotFound(Vec<PathBuf>),
+
+    #[error("The saved Amazon session has expired. Run `readingsync kindle` without --headless to log in again")]
+    SessionExpired,
+
+    #[error("Amazon is asking for a two-step verification code. Run without --headless to complete 2FA in the browser window")]
+    TwoFactorRequired,
+
+    #[error("Amazon is showing a CAPTCHA. Run without --headless to solve it in the browser window")]
+    CaptchaRequired,
+
+    #[error("Amazon is asking which account to use. Run without --headless to choose one in the browser window")]
+    AccountChoiceRequired,
+
     #[error("Invalid Amazon region: {0}")]
     InvalidRegion(String),
+
+    #[error("Your Amazon account appears to be registered on {detected}, not {expected} — retry with --region {code}")]
+    WrongRegion {
+        expected: String,
+        detected: String,
+        code: String,
+    },
+
+    #[error("Could not tell the Amazon region from the cookies: none are for an Amazon store. Pass --region, e.g. --region uk")]
+    RegionUndetected,
+
+    #[error("Kindle app content folder not found. Pass --path to 'My Kindle Content'")]
+    LocalContentNotFound,
+
+    #[error("Failed to read {0}: {1}")]
+    LocalReadError(PathBuf, std::io::Error),
+
+    #[error("Bookcision export not found: {0}")]
+    BookcisionNotFound(PathBuf),
+
+    #[error("Failed to parse Bookcision export {0}: {1}")]
+    BookcisionParseError(PathBuf, serde_json::Error),
+
+    #[error("Notebook export not found: {0}")]
+    NotebookExportNotFound(PathBuf),
+
+    #[error("Kindle sync state {0}: {1}")]
+    SyncStateError(PathBuf, String),
+
+    #[error("Scrape report {0}: {1}")]
+    ReportError(PathBuf, String),
+
+    #[error("Kindle sync checkpoint {0}: {1}")]
+    CheckpointError(PathBuf, String),
+
+    #[error("{0} books failed to scrape after retries")]
+    BooksFailed(usize),
+
+    #[error("This build has no {0} backend. Rebuild with `cargo install --features {0}`, or use --backend chrome")]
+    BackendUnavailable(&'static str),
+
+    #[error("The WebDriver browser starts with a temporary profile, so no Amazon login is kept between runs. Pass --user-data-dir with a Firefox profile to keep one, or run with a visible window to log in each time")]
+    SessionNotPersisted,
+}
+
+impl KindleError {
+    /// Whether trying again later could succeed: rate limiting (429), server errors,
+    /// timeouts and pages that didn't render. Other 4xx responses and anything that
+    /// needs the user to log in are final.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            KindleError::HttpStatus(status, _) => *status == 429 || *status >= 500,
+            KindleError::NotAuthenticated
+            | KindleError::LoginRequired
+            | KindleError::SessionExpired
+            | KindleError::TwoFactorRequired
+            | KindleError::CaptchaRequired
+            | KindleError::AccountChoiceRequired
+            | KindleError::WrongRegion { .. }
+            | KindleError::RegionUndetected => false,
+            _ => true,
+        }
+    }
+}
+
+/// Errors specific to Kobo extraction
+#[derive(Error, Debug)]
+pub enum KoboError {
+    #[error("Kobo database not found at {0}")]
+    DatabaseNotFound(PathBuf),
+
+    #[error("No mounted Kobo device found. Connect your Kobo or pass --path to KoboReader.sqlite")]
+    DeviceNotFound,
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// Errors specific to Google Play Books import
+#[derive(Error, Debug)]
+pub enum PlayBooksError {
+    #[error("Play Books export directory not found: {0}")]
+    DirectoryNotFound(PathBuf),
+
+    #[error("Failed to read {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+}
+
+/// Errors specific to Pocket import
+#[derive(Error, Debug)]
+pub enum PocketError {
+    #[error("Pocket export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to read Pocket export: {0}")]
+    ReadError(std::io::Error),
+
+    #[error("Failed to read Pocket export archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("Failed to parse Pocket JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Failed to parse Pocket CSV: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("HTTP request failed: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+
+    #[error("Pocket API error: {0}")]
+    ApiError(String),
+
+    #[error("Pass a Pocket export file, or both --consumer-key and --access-token")]
+    MissingCredentials,
+}
+
+/// Errors specific to Instapaper import
+#[derive(Error, Debug)]
+pub enum InstapaperError {
+    #[error("Instapaper export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to parse Instapaper CSV: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+/// Errors specific to KOReader import
+#[derive(Error, Debug)]
+pub enum KoReaderError {
+    #[error("Directory not found: {0}")]
+    DirectoryNotFound(PathBuf),
+
+    #[error("Failed to read {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+}
+
+/// Errors specific to Moon+ Reader import
+#[derive(Error, Debug)]
+pub enum MoonReaderError {
+    #[error("Moon+ Reader export not found: {0}")]
+    NotFound(PathBuf),
+
+    #[error("Failed to read {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+}
+
+/// Errors specific to Calibre extraction
+#[derive(Error, Debug)]
+pub enum CalibreError {
+    #[error("Calibre library database not found at {0}")]
+    DatabaseNotFound(PathBuf),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("Invalid annotation data for {0}: {1}")]
+    InvalidAnnotation(String, serde_json::Error),
+}
+
+/// Errors specific to Hypothesis import
+#[derive(Error, Debug)]
+pub enum HypothesisError {
+    #[error("HTTP request failed: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+
+    #[error("Hypothesis API error: {0}")]
+    ApiError(String),
+
+    #[error("Failed to parse Hypothesis response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Hypothesis rejected the API token. Generate one at https://hypothes.is/account/developer")]
+    NotAuthenticated,
+}
+
+/// Errors specific to Readwise import
+#[derive(Error, Debug)]
+pub enum ReadwiseError {
+    #[error("Readwise export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to parse Readwise CSV: {0}")]
+    Csv(#[from] csv::Error),
 }
 
 /// Errors specific to configuration
@@ -86,4 +412,96 @@ pub enum ConfigError {
     InvalidValue(String),
 }
 
+/// Errors specific to exporting the library
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Failed to write archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("Failed to write CSV: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Failed to read library file {0}: {1}")]
+    LibraryRead(PathBuf, std::io::Error),
+
+    #[error("Failed to parse library file {0}: {1}")]
+    LibraryParse(PathBuf, serde_json::Error),
+
+    #[error("A filtered export would replace the library at {0} with only the matching books. Write it elsewhere with -o, or pass --no-merge-existing")]
+    FilteredOverLibrary(PathBuf),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to Libby import
+#[derive(Error, Debug)]
+pub enum LibbyError {
+    #[error("Libby export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to read Libby export: {0}")]
+    ReadError(#[from] std::io::Error),
+
+    #[error("Failed to parse Libby export: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Failed to parse Libby CSV: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+/// Errors specific to Goodreads import
+#[derive(Error, Debug)]
+pub enum GoodreadsError {
+    #[error("Goodreads export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to parse Goodreads CSV: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+/// Errors specific to mapped annotation JSON import
+#[derive(Error, Debug)]
+pub enum AnnotatedJsonError {
+    #[error("Annotation export not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Failed to read {0}: {1}")]
+    ReadError(PathBuf, std::io::Error),
+
+    #[error("Failed to parse annotation export: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid mapping: {0}")]
+    InvalidMapping(String),
+
+    #[error("Unknown preset: {0} (available: polar)")]
+    UnknownPreset(String),
+}
+
+/// Errors from running an external import command
+#[derive(Error, Debug)]
+pub enum ImportCmdError {
+    #[error("No command given")]
+    NoCommand,
+
+    #[error("Failed to run {0}: {1}")]
+    SpawnFailed(String, std::io::Error),
+
+    #[error("{0} exited with {1}")]
+    CommandFailed(String, String),
+
+    #[error("Command output is not valid UTF-8")]
+    InvalidUtf8,
+
+    #[error("Command output is not valid JSON: {0}")]
+    InvalidJson(String),
+
+    #[error("Invalid entry {0}: {1}")]
+    Invalid(String, String),
+}
@@ -0,0 +1,193 @@
+use crate::error::ExportError;
+use crate::model::{parse_labelled_position, HighlightKind, Library, Source};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// One row of `books.csv`
+#[derive(Debug, Serialize)]
+struct BookRow<'a> {
+    book_id: &'a str,
+    title: &'a str,
+    author: Option<&'a str>,
+    sources: String,
+    highlight_count: usize,
+    note_count: usize,
+    finished: Option<bool>,
+    finished_at: Option<String>,
+    progress: Option<f32>,
+    last_opened_at: Option<String>,
+    language: Option<&'a str>,
+    genre: Option<&'a str>,
+}
+
+/// One row of `highlights.csv`
+#[derive(Debug, Serialize)]
+struct HighlightRow<'a> {
+    highlight_id: &'a str,
+    book_id: &'a str,
+    text: &'a str,
+    note: Option<&'a str>,
+    has_note: bool,
+    char_count: usize,
+    word_count: usize,
+    chapter: Option<&'a str>,
+    position: Option<&'a str>,
+    page: Option<&'a str>,
+    location_start: Option<u64>,
+    created_at: Option<String>,
+    sources: String,
+    kind: HighlightKind,
+}
+
+/// Write a flattened analytics bundle (`books.csv` and `highlights.csv`) into a directory
+///
+/// Highlights reference books through the `book_id` column. Dates are RFC3339 and
+/// booleans are `true`/`false`, so the files load with typed columns in DuckDB/Polars.
+pub fn to_analytics(library: &Library, dir: &Path) -> Result<(), ExportError> {
+    fs::create_dir_all(dir)?;
+
+    let mut books = csv::Writer::from_path(dir.join("books.csv"))?;
+    let mut highlights = csv::Writer::from_path(dir.join("highlights.csv"))?;
+
+    for book in &library.books {
+        books.serialize(BookRow {
+            book_id: &book.id,
+            title: &book.title,
+            author: book.author.as_deref(),
+            sources: book
+                .sources
+                .iter()
+                .map(Source::as_str)
+                .collect::<Vec<_>>()
+                .join(";"),
+            highlight_count: book.highlights.len(),
+            note_count: book.highlights.iter().filter(|h| h.note.is_some()).count(),
+            finished: book.finished,
+            finished_at: book.finished_at.as_ref().map(rfc3339),
+            progress: book.progress,
+            last_opened_at: book.last_opened_at.as_ref().map(rfc3339),
+            language: book.language.as_deref(),
+            genre: book.genre.as_deref(),
+        })?;
+
+        for highlight in &book.highlights {
+            let position = highlight.location.position.as_deref();
+
+            highlights.serialize(HighlightRow {
+                highlight_id: &highlight.id,
+                book_id: &book.id,
+                text: &highlight.text,
+                note: highlight.note.as_deref(),
+                has_note: highlight.note.is_some(),
+                char_count: highlight.text.chars().count(),
+                word_count: highlight.text.split_whitespace().count(),
+                chapter: highlight.location.chapter.as_deref(),
+                position,
+                page: highlight.location.page.as_deref(),
+                // Pages count something else; they're in `page` or `position`
+                location_start: position
+                    .and_then(parse_labelled_position)
+                    .filter(|(is_page, _)| !is_page)
+                    .map(|(_, start)| start),
+                created_at: highlight.created_at.as_ref().map(rfc3339),
+                sources: highlight
+                    .sources
+                    .iter()
+                    .map(Source::as_str)
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                kind: highlight.kind,
+            })?;
+        }
+    }
+
+    books.flush()?;
+    highlights.flush()?;
+
+    Ok(())
+}
+
+/// Format a timestamp as RFC3339
+fn rfc3339(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Book, Highlight, HighlightStyle, Location};
+
+    #[test]
+    fn test_to_analytics_writes_both_files() {
+        let mut book = Book::new("Test Book".to_string(), None);
+        book.sources.push(Source::Kindle);
+        book.progress = Some(0.25);
+        book.highlights.push(Highlight {
+            id: "h1".to_string(),
+            text: "Two words".to_string(),
+            note: Some("a note".to_string()),
+            location: Location {
+                chapter: None,
+                position: Some("10-12".to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        let dir = std::env::temp_dir().join(format!("readingsync_analytics_{}", uuid::Uuid::new_v4()));
+        to_analytics(&library, &dir).unwrap();
+
+        let books_csv = fs::read_to_string(dir.join("books.csv")).unwrap();
+        let highlights_csv = fs::read_to_string(dir.join("highlights.csv")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(books_csv.starts_with("book_id,title,author,sources,highlight_count"));
+        assert!(books_csv.contains(",kindle,1,1,,,0.25,"));
+        assert!(highlights_csv.contains("Two words,a note,true,9,2,,10-12,,10,,kindle,highlight"));
+    }
+
+    #[test]
+    fn test_to_analytics_leaves_pages_out_of_location_start() {
+        let mut book = Book::new("Test Book".to_string(), None);
+        book.highlights.push(Highlight {
+            id: "h1".to_string(),
+            text: "On a page".to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: Some("Page 57".to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kobo],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        let dir = std::env::temp_dir().join(format!("readingsync_analytics_{}", uuid::Uuid::new_v4()));
+        to_analytics(&library, &dir).unwrap();
+        let highlights_csv = fs::read_to_string(dir.join("highlights.csv")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(highlights_csv.contains("On a page,,false,9,3,,Page 57,,,,kobo,highlight"));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::model::{Book, Highlight, HighlightKind, Library};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Separator between entries in My Clippings.txt
+const SEPARATOR: &str = "==========";
+
+/// Render the library in Kindle's My Clippings.txt format
+///
+/// Highlights become `Your Highlight` entries and attached notes follow as separate
+/// `Your Note` entries at the same location, as the Kindle itself writes them.
+/// Lines end with CRLF like the files written by the device.
+pub fn to_clippings(library: &Library) -> String {
+    let mut out = String::new();
+
+    for book in &library.books {
+        for highlight in &book.highlights {
+            match highlight.kind {
+                HighlightKind::Note => push_entry(&mut out, book, highlight, "Note", highlight.body()),
+                HighlightKind::Bookmark => push_entry(&mut out, book, highlight, "Bookmark", ""),
+                HighlightKind::Highlight => {
+                    push_entry(&mut out, book, highlight, "Highlight", &highlight.text);
+
+                    if let Some(ref note) = highlight.note {
+                        push_entry(&mut out, book, highlight, "Note", note);
+                    }
+                }
+            }
+        }
+    }
+
+    out
+}
+
+fn push_entry(out: &mut String, book: &Book, highlight: &Highlight, kind: &str, content: &str) {
+    let header = match book.author {
+        Some(ref author) => format!("{} ({})", book.title, author),
+        None => book.title.clone(),
+    };
+
+    let mut metadata = format!("- Your {}", kind);
+    let page = highlight.location.page.as_ref().map(|page| format!("page {}", page));
+    let location = highlight.location.position.as_deref().and_then(format_location);
+    match (page, location) {
+        (Some(page), Some(location)) => metadata.push_str(&format!(" on {} | {}", page, location)),
+        (Some(position), None) | (None, Some(position)) => metadata.push_str(&format!(" on {}", position)),
+        (None, None) => {}
+    }
+    if let Some(created_at) = highlight.created_at {
+        metadata.push_str(&format!(
+            " | Added on {}",
+            created_at.format("%A, %B %-d, %Y %-I:%M:%S %p")
+        ));
+    }
+
+    for line in [header.as_str(), metadata.as_str(), "", content, SEPARATOR] {
+        out.push_str(line);
+        out.push_str("\r\n");
+    }
+}
+
+/// Convert a stored position into the clippings location phrase
+///
+/// "123-145" and "Location 123" become "Location 123-145"/"Location 123", "Page 57"
+/// becomes "page 57". Positions that aren't numeric (e.g. EPUB CFIs) are omitted.
+fn format_location(position: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"(?i)^(location|loc\.|page)?\s*([\d,]+(?:\s*-\s*[\d,]+)?)$").unwrap()
+    });
+    let caps = re.captures(position.trim())?;
+
+    let range: String = caps[2].chars().filter(|c| !c.is_whitespace() && *c != ',').collect();
+
+    match caps.get(1).map(|m| m.as_str().to_lowercase()) {
+        Some(ref word) if word == "page" => Some(format!("page {}", range)),
+        _ => Some(format!("Location {}", range)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::clippings::parse_clippings_content;
+    use crate::model::{HighlightStyle, Location, Source};
+    use chrono::{TimeZone, Utc};
+
+    fn make_highlight(text: &str, position: &str) -> Highlight {
+        Highlight {
+            id: uuid::Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: Some(position.to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: Utc.with_ymd_and_hms(2024, 1, 1, 13, 5, 0).single(),
+            sources: vec![Source::AppleBooks],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    #[test]
+    fn test_format_location() {
+        assert_eq!(format_location("123-145"), Some("Location 123-145".to_string()));
+        assert_eq!(format_location("Location 1,234"), Some("Location 1234".to_string()));
+        assert_eq!(format_location("Page 57"), Some("page 57".to_string()));
+        assert_eq!(format_location("epubcfi(/6/4!/4/2/1:0)"), None);
+    }
+
+    #[test]
+    fn test_to_clippings_entry_format() {
+        let mut book = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        book.highlights.push(make_highlight("I went to the woods.", "200-210"));
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        assert_eq!(
+            to_clippings(&library),
+            "Walden (Henry David Thoreau)\r\n\
+             - Your Highlight on Location 200-210 | Added on Monday, January 1, 2024 1:05:00 PM\r\n\
+             \r\n\
+             I went to the woods.\r\n\
+             ==========\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_clippings_standalone_note() {
+        let mut book = Book::new("Walden".to_string(), None);
+        let mut note = make_highlight("", "450");
+        note.note = Some("Reread this chapter".to_string());
+        note.kind = HighlightKind::Note;
+        book.highlights.push(note);
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        let clippings = to_clippings(&library);
+        assert_eq!(clippings.matches(SEPARATOR).count(), 1);
+        assert!(clippings.contains("- Your Note on Location 450"));
+
+        let parsed = parse_clippings_content(&clippings).unwrap();
+        assert_eq!(parsed[0].highlights[0].kind, HighlightKind::Note);
+        assert_eq!(parsed[0].highlights[0].note.as_deref(), Some("Reread this chapter"));
+    }
+
+    #[test]
+    fn test_to_clippings_round_trip() {
+        let mut gatsby = Book::new("The Great Gatsby".to_string(), Some("F. Scott Fitzgerald".to_string()));
+        gatsby.highlights.push(make_highlight("In my younger and more vulnerable years.", "30"));
+        gatsby.highlights.push(make_highlight("So we beat on, boats against the current.", "1500-1510"));
+        gatsby.highlights[0].location.page = Some("1".to_string());
+
+        let mut untitled = Book::new("Notes Without Author".to_string(), None);
+        untitled.highlights.push(make_highlight("Standalone text", "page 12"));
+
+        let mut library = Library::new();
+        library.books = vec![untitled, gatsby];
+
+        let mut parsed = parse_clippings_content(&to_clippings(&library)).unwrap();
+        parsed.sort_by(|a, b| a.title.cmp(&b.title));
+
+        assert_eq!(parsed.len(), 2);
+
+        for (original, parsed) in library.books.iter().zip(parsed.iter()) {
+            assert_eq!(original.id, parsed.id);
+            assert_eq!(original.title, parsed.title);
+            assert_eq!(original.author, parsed.author);
+
+            let original_texts: Vec<&str> = original.highlights.iter().map(|h| h.text.as_str()).collect();
+            let parsed_texts: Vec<&str> = parsed.highlights.iter().map(|h| h.text.as_str()).collect();
+            assert_eq!(original_texts, parsed_texts);
+        }
+
+        assert_eq!(parsed[0].highlights[0].location.position, None);
+        assert_eq!(parsed[0].highlights[0].location.page.as_deref(), Some("12"));
+        assert_eq!(parsed[1].highlights[0].location.position.as_deref(), Some("30"));
+        assert_eq!(parsed[1].highlights[0].location.page.as_deref(), Some("1"));
+        assert_eq!(parsed[1].highlights[1].location.position.as_deref(), Some("1500-1510"));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::error::ExportError;
+use crate::export::{bookmark_label, escape_xml};
+use crate::model::{Book, HighlightKind, Library};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Title used for the generated EPUB
+const EPUB_TITLE: &str = "My Highlights";
+
+/// Build a minimal EPUB 3 document from the library
+///
+/// Layout:
+/// ```text
+/// mimetype                  (first entry, stored uncompressed)
+/// META-INF/container.xml
+/// OEBPS/content.opf         (metadata, manifest, spine)
+/// OEBPS/nav.xhtml           (table of contents)
+/// OEBPS/book-<id>.xhtml     (one chapter per book)
+/// ```
+pub fn to_epub(library: &Library) -> Result<Vec<u8>, ExportError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry and must not be compressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(render_opf(library).as_bytes())?;
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(render_nav(library).as_bytes())?;
+
+    for book in &library.books {
+        zip.start_file(format!("OEBPS/{}", chapter_file_name(book)), deflated)?;
+        zip.write_all(render_chapter(book).as_bytes())?;
+    }
+
+    let cursor = zip.finish()?;
+    Ok(cursor.into_inner())
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// File name of the chapter for a book
+fn chapter_file_name(book: &Book) -> String {
+    format!("book-{}.xhtml", book.id)
+}
+
+/// Render the OPF package document
+fn render_opf(library: &Library) -> String {
+    let modified = library.exported_at.format("%Y-%m-%dT%H:%M:%SZ");
+
+    let mut manifest = String::from(
+        r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+"#,
+    );
+    let mut spine = String::new();
+
+    for book in &library.books {
+        manifest.push_str(&format!(
+            "    <item id=\"book-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            book.id,
+            chapter_file_name(book)
+        ));
+        spine.push_str(&format!("    <itemref idref=\"book-{}\"/>\n", book.id));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:readingsync:{uid}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+    <dc:date>{modified}</dc:date>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+    <itemref idref="nav"/>
+{spine}  </spine>
+</package>
+"#,
+        uid = library.exported_at.timestamp(),
+        title = EPUB_TITLE,
+        modified = modified,
+        manifest = manifest,
+        spine = spine,
+    )
+}
+
+/// Render the navigation document (table of contents)
+fn render_nav(library: &Library) -> String {
+    let mut items = String::new();
+    for book in &library.books {
+        items.push_str(&format!(
+            "        <li><a href=\"{}\">{}</a></li>\n",
+            chapter_file_name(book),
+            escape_xml(&book_heading(book))
+        ));
+    }
+
+    xhtml_document(
+        EPUB_TITLE,
+        &format!(
+            r#"    <nav epub:type="toc" id="toc">
+      <h1>{}</h1>
+      <ol>
+{}      </ol>
+    </nav>
+"#,
+            EPUB_TITLE, items
+        ),
+    )
+}
+
+/// Render one chapter containing all highlights of a book
+fn render_chapter(book: &Book) -> String {
+    let mut body = format!("    <h1>{}</h1>\n", escape_xml(&book.title));
+
+    if let Some(ref author) = book.author {
+        body.push_str(&format!("    <p class=\"author\">{}</p>\n", escape_xml(author)));
+    }
+
+    for highlight in &book.highlights {
+        if highlight.kind == HighlightKind::Bookmark {
+            body.push_str(&format!(
+                "    <p class=\"bookmark\">{}</p>\n",
+                escape_xml(&bookmark_label(highlight))
+            ));
+            continue;
+        }
+
+        if highlight.kind != HighlightKind::Note {
+            body.push_str(&format!(
+                "    <blockquote><p>{}</p></blockquote>\n",
+                escape_xml(&highlight.text)
+            ));
+        }
+
+        if let Some(ref note) = highlight.note {
+            body.push_str(&format!("    <p class=\"note\">{}</p>\n", escape_xml(note)));
+        }
+
+        if let Some(label) = highlight.location.label() {
+            body.push_str(&format!(
+                "    <p class=\"location\">{}</p>\n",
+                escape_xml(&label)
+            ));
+        }
+    }
+
+    xhtml_document(&book.title, &body)
+}
+
+/// Heading for a book in the table of contents
+fn book_heading(book: &Book) -> String {
+    match book.author {
+        Some(ref author) => format!("{} — {}", book.title, author),
+        None => book.title.clone(),
+    }
+}
+
+/// Wrap body markup in an XHTML document
+fn xhtml_document(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="en" lang="en">
+  <head>
+    <title>{}</title>
+  </head>
+  <body>
+{}  </body>
+</html>
+"#,
+        escape_xml(title),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Highlight, HighlightStyle, Location, Source};
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn make_library() -> Library {
+        let mut book = Book::new("Dune & Sons".to_string(), Some("Frank Herbert".to_string()));
+        book.sources.push(Source::Kindle);
+        book.highlights.push(Highlight {
+            id: "h1".to_string(),
+            text: "Fear is the <mind-killer>.".to_string(),
+            note: Some("Classic".to_string()),
+            location: Location {
+                chapter: None,
+                position: Some("Location 123".to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+
+        let mut library = Library::new();
+        library.books.push(book);
+        library
+    }
+
+    #[test]
+    fn test_epub_container_structure() {
+        let library = make_library();
+        let bytes = to_epub(&library).unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        // mimetype must be first and stored
+        {
+            let mut mimetype = archive.by_index(0).unwrap();
+            assert_eq!(mimetype.name(), "mimetype");
+            assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+            let mut content = String::new();
+            mimetype.read_to_string(&mut content).unwrap();
+            assert_eq!(content, "application/epub+zip");
+        }
+
+        assert!(archive.by_name("META-INF/container.xml").is_ok());
+        assert!(archive.by_name("OEBPS/nav.xhtml").is_ok());
+
+        let mut opf = String::new();
+        archive
+            .by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)
+            .unwrap();
+        assert!(opf.contains("<dc:title>My Highlights</dc:title>"));
+        assert!(opf.contains(&format!("book-{}.xhtml", library.books[0].id)));
+    }
+
+    #[test]
+    fn test_epub_chapter_escapes_text() {
+        let library = make_library();
+        let chapter = render_chapter(&library.books[0]);
+
+        assert!(chapter.contains("<h1>Dune &amp; Sons</h1>"));
+        assert!(chapter.contains("Fear is the &lt;mind-killer&gt;."));
+        assert!(chapter.contains("<p class=\"note\">Classic</p>"));
+    }
+}
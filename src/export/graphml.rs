@@ -0,0 +1,113 @@
+use crate::export::{escape_xml, short_hash};
+use crate::model::{Book, Library};
+use std::collections::BTreeMap;
+
+/// Render the library as a GraphML graph of books and authors
+///
+/// Books and authors are nodes, with an edge from each book to its author. Book nodes
+/// carry highlight counts and finished status. Node and edge order is sorted so the
+/// output is stable across runs.
+pub fn to_graphml(library: &Library) -> String {
+    let mut books: Vec<&Book> = library.books.iter().collect();
+    books.sort_by(|a, b| a.id.cmp(&b.id));
+
+    // Author node ID -> display name, sorted by ID
+    let mut authors: BTreeMap<String, &str> = BTreeMap::new();
+    for book in &books {
+        if let Some(ref author) = book.author {
+            authors.insert(author_node_id(author), author);
+        }
+    }
+
+    let mut out = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="kind" for="node" attr.name="kind" attr.type="string"/>
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="highlights" for="node" attr.name="highlight_count" attr.type="int"/>
+  <key id="finished" for="node" attr.name="finished" attr.type="boolean"/>
+  <graph id="library" edgedefault="directed">
+"#,
+    );
+
+    for book in &books {
+        out.push_str(&format!("    <node id=\"book:{}\">\n", escape_xml(&book.id)));
+        out.push_str("      <data key=\"kind\">book</data>\n");
+        out.push_str(&format!("      <data key=\"label\">{}</data>\n", escape_xml(&book.title)));
+        out.push_str(&format!("      <data key=\"highlights\">{}</data>\n", book.highlights.len()));
+        if let Some(finished) = book.finished {
+            out.push_str(&format!("      <data key=\"finished\">{}</data>\n", finished));
+        }
+        out.push_str("    </node>\n");
+    }
+
+    for (id, name) in &authors {
+        let highlight_count: usize = books
+            .iter()
+            .filter(|b| b.author.as_deref().map(author_node_id).as_ref() == Some(id))
+            .map(|b| b.highlights.len())
+            .sum();
+
+        out.push_str(&format!("    <node id=\"{}\">\n", id));
+        out.push_str("      <data key=\"kind\">author</data>\n");
+        out.push_str(&format!("      <data key=\"label\">{}</data>\n", escape_xml(name)));
+        out.push_str(&format!("      <data key=\"highlights\">{}</data>\n", highlight_count));
+        out.push_str("    </node>\n");
+    }
+
+    for book in &books {
+        if let Some(ref author) = book.author {
+            out.push_str(&format!(
+                "    <edge source=\"book:{}\" target=\"{}\"/>\n",
+                escape_xml(&book.id),
+                author_node_id(author)
+            ));
+        }
+    }
+
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+/// Stable node ID for an author, derived from the normalized name
+fn author_node_id(author: &str) -> String {
+    format!("author:{}", short_hash(&author.trim().to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graphml_nodes_and_edges() {
+        let mut book1 = Book::new("Tender Is the Night".to_string(), Some("F. Scott Fitzgerald".to_string()));
+        book1.finished = Some(true);
+        let book2 = Book::new("The Great Gatsby".to_string(), Some("F. Scott Fitzgerald".to_string()));
+        let book3 = Book::new("Anonymous <Notes> & More".to_string(), None);
+
+        let mut library = Library::new();
+        library.books = vec![book1, book2, book3];
+
+        let graphml = to_graphml(&library);
+
+        assert_eq!(graphml.matches("<data key=\"kind\">book</data>").count(), 3);
+        assert_eq!(graphml.matches("<data key=\"kind\">author</data>").count(), 1);
+        // The book without an author has no edge
+        assert_eq!(graphml.matches("<edge ").count(), 2);
+        assert!(graphml.contains("Anonymous &lt;Notes&gt; &amp; More"));
+        assert!(graphml.contains("<data key=\"finished\">true</data>"));
+    }
+
+    #[test]
+    fn test_graphml_is_deterministic() {
+        let book1 = Book::new("A".to_string(), Some("X".to_string()));
+        let book2 = Book::new("B".to_string(), Some("Y".to_string()));
+
+        let mut forward = Library::new();
+        forward.books = vec![book1.clone(), book2.clone()];
+        let mut reversed = Library::new();
+        reversed.books = vec![book2, book1];
+
+        assert_eq!(to_graphml(&forward), to_graphml(&reversed));
+    }
+}
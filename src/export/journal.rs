@@ -0,0 +1,149 @@
+use crate::export::bookmark_label;
+use crate::model::{Book, Highlight, HighlightKind, Library};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+/// Render a chronological reading journal as a single Markdown document
+///
+/// Top-level headings are the local dates (in `tz`) on which highlights were made,
+/// with highlights grouped by book underneath. Highlights without a timestamp are
+/// collected in a final "Undated" section.
+pub fn to_journal(library: &Library, tz: Tz) -> String {
+    let mut dated: BTreeMap<NaiveDate, Vec<(&Book, &Highlight)>> = BTreeMap::new();
+    let mut undated: Vec<(&Book, &Highlight)> = Vec::new();
+
+    for book in &library.books {
+        for highlight in &book.highlights {
+            match highlight.created_at {
+                Some(created_at) => {
+                    let day = created_at.with_timezone(&tz).date_naive();
+                    dated.entry(day).or_default().push((book, highlight));
+                }
+                None => undated.push((book, highlight)),
+            }
+        }
+    }
+
+    let mut out = String::from("# Reading Journal\n");
+
+    for (day, mut entries) in dated {
+        entries.sort_by_key(|(_, h)| h.created_at);
+        out.push_str(&format!("\n# {}\n", day.format("%Y-%m-%d")));
+        render_by_book(&mut out, &entries);
+    }
+
+    if !undated.is_empty() {
+        out.push_str("\n# Undated\n");
+        render_by_book(&mut out, &undated);
+    }
+
+    out
+}
+
+/// Render entries grouped by book, keeping books in order of first appearance
+fn render_by_book(out: &mut String, entries: &[(&Book, &Highlight)]) {
+    let mut order: Vec<&str> = Vec::new();
+    let mut by_book: BTreeMap<&str, (&Book, Vec<&Highlight>)> = BTreeMap::new();
+
+    for (book, highlight) in entries {
+        by_book
+            .entry(book.id.as_str())
+            .or_insert_with(|| {
+                order.push(book.id.as_str());
+                (book, Vec::new())
+            })
+            .1
+            .push(highlight);
+    }
+
+    for id in order {
+        let (book, highlights) = &by_book[id];
+
+        match book.author {
+            Some(ref author) => out.push_str(&format!("\n## {} — {}\n", book.title, author)),
+            None => out.push_str(&format!("\n## {}\n", book.title)),
+        }
+
+        for highlight in highlights {
+            if highlight.kind == HighlightKind::Bookmark {
+                out.push_str(&format!("\n*{}*\n", bookmark_label(highlight)));
+                continue;
+            }
+
+            if highlight.kind != HighlightKind::Note {
+                out.push('\n');
+                for line in highlight.text.lines() {
+                    out.push_str(&format!("> {}\n", line));
+                }
+            }
+            if let Some(ref note) = highlight.note {
+                out.push_str(&format!("\n**Note:** {}\n", note));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{HighlightStyle, Location, Source};
+    use chrono::{TimeZone, Utc};
+
+    fn make_highlight(text: &str, created_at: Option<chrono::DateTime<Utc>>) -> Highlight {
+        Highlight {
+            id: uuid::Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at,
+            sources: vec![Source::AppleBooks],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    fn make_library() -> Library {
+        let mut book = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        // 2024-03-06 02:30 UTC is still March 5th in New York
+        book.highlights.push(make_highlight(
+            "Late night highlight",
+            Utc.with_ymd_and_hms(2024, 3, 6, 2, 30, 0).single(),
+        ));
+        book.highlights.push(make_highlight("No date", None));
+
+        let mut library = Library::new();
+        library.books.push(book);
+        library
+    }
+
+    #[test]
+    fn test_journal_groups_by_local_date() {
+        let library = make_library();
+
+        let utc = to_journal(&library, chrono_tz::UTC);
+        assert!(utc.contains("# 2024-03-06"));
+
+        let new_york = to_journal(&library, chrono_tz::America::New_York);
+        assert!(new_york.contains("# 2024-03-05"));
+        assert!(new_york.contains("## Walden — Henry David Thoreau"));
+    }
+
+    #[test]
+    fn test_journal_undated_section_is_last() {
+        let journal = to_journal(&make_library(), chrono_tz::UTC);
+
+        let undated = journal.find("# Undated").unwrap();
+        let dated = journal.find("# 2024-03-06").unwrap();
+        assert!(undated > dated);
+        assert!(journal[undated..].contains("> No date"));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::export::bookmark_label;
+use crate::model::{Book, HighlightKind, HighlightStyle};
+
+/// Render a book and its highlights as Markdown
+///
+/// ```text
+/// # Title
+///
+/// **Author:** Author Name
+///
+/// **ASIN:** B000FC0PDA
+///
+/// > Highlighted text
+///
+/// **Context:** The passage around it, where kept
+///
+/// **Note:** My note
+///
+/// *loc 123 · yellow*
+/// ```
+pub fn book_to_markdown(book: &Book) -> String {
+    let mut out = format!("# {}\n", book.title);
+
+    if let Some(ref author) = book.author {
+        out.push_str(&format!("\n**Author:** {}\n", author));
+    }
+
+    if let Some(asin) = book.asin() {
+        out.push_str(&format!("\n**ASIN:** {}\n", asin));
+    }
+
+    for highlight in &book.highlights {
+        if highlight.kind == HighlightKind::Bookmark {
+            out.push_str(&format!("\n*{}*\n", bookmark_label(highlight)));
+            continue;
+        }
+
+        // Standalone notes have no highlighted text to quote
+        if highlight.kind != HighlightKind::Note {
+            out.push('\n');
+            for line in highlight.text.lines() {
+                out.push_str(&format!("> {}\n", line));
+            }
+        }
+
+        if let Some(ref context) = highlight.context {
+            out.push_str(&format!("\n**Context:** {}\n", context));
+        }
+
+        if let Some(ref note) = highlight.note {
+            out.push_str(&format!("\n**Note:** {}\n", note));
+        }
+
+        let underline = (highlight.style == HighlightStyle::Underline).then(|| "underline".to_string());
+        let meta: Vec<String> = highlight
+            .location
+            .label()
+            .into_iter()
+            .chain(highlight.color.clone())
+            .chain(underline)
+            .collect();
+        if !meta.is_empty() {
+            out.push_str(&format!("\n*{}*\n", meta.join(" · ")));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Highlight, Location, Source};
+
+    #[test]
+    fn test_book_to_markdown() {
+        let mut book = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        book.highlights.push(Highlight {
+            id: "h1".to_string(),
+            text: "Line one\nLine two".to_string(),
+            note: Some("Good".to_string()),
+            location: Location {
+                chapter: None,
+                position: Some("Location 42".to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: Some("yellow".to_string()),
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: Some("Before. Line one Line two. After.".to_string()),
+            deleted_upstream: false,
+        });
+
+        let markdown = book_to_markdown(&book);
+        assert!(markdown.starts_with("# Walden\n\n**Author:** Henry David Thoreau\n"));
+        assert!(markdown.contains("> Line one\n> Line two\n\n**Context:** Before. Line one Line two. After.\n\n**Note:** Good"));
+        assert!(markdown.contains("*loc 42 · yellow*"));
+    }
+
+    #[test]
+    fn test_book_to_markdown_bookmark() {
+        let mut book = Book::new("Walden".to_string(), None);
+        book.highlights.push(Highlight {
+            id: "b1".to_string(),
+            text: String::new(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: Some("300".to_string()),
+                page: Some("20".to_string()),
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Bookmark,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+
+        let markdown = book_to_markdown(&book);
+        assert!(markdown.contains("*Bookmark — p. 20 · loc 300*"));
+        assert!(!markdown.contains(">"));
+    }
+
+    #[test]
+    fn test_book_to_markdown_standalone_note() {
+        let mut book = Book::new("Walden".to_string(), None);
+        book.highlights.push(Highlight {
+            id: "n1".to_string(),
+            text: String::new(),
+            note: Some("Reread this chapter".to_string()),
+            location: Location {
+                chapter: None,
+                position: Some("450".to_string()),
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Note,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+
+        let markdown = book_to_markdown(&book);
+        assert!(markdown.contains("**Note:** Reread this chapter\n\n*loc 450*"));
+        assert!(!markdown.contains(">"));
+    }
+}
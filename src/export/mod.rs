@@ -0,0 +1,96 @@
+pub mod analytics;
+pub mod clippings;
+pub mod epub;
+pub mod graphml;
+pub mod journal;
+pub mod markdown;
+pub mod notion;
+pub mod quotes;
+pub mod reading_log;
+pub mod textbundle;
+pub mod tiddlywiki;
+
+pub use analytics::to_analytics;
+pub use clippings::to_clippings;
+pub use epub::to_epub;
+pub use graphml::to_graphml;
+pub use journal::to_journal;
+pub use markdown::book_to_markdown;
+pub use notion::to_notion;
+pub use quotes::{to_quotes, Quote, QuoteOptions};
+pub use reading_log::to_reading_log;
+pub use textbundle::to_textbundles;
+pub use tiddlywiki::{to_tiddlywiki, Tiddler};
+
+use crate::model::Highlight;
+
+/// Escape text for inclusion in XML/XHTML content and attribute values
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Display line for a bookmark, e.g. "Bookmark — p. 4 · loc 300"
+pub(crate) fn bookmark_label(highlight: &Highlight) -> String {
+    match highlight.location.label() {
+        Some(label) => format!("Bookmark — {}", label),
+        None => "Bookmark".to_string(),
+    }
+}
+
+/// Make a string safe to use as a file name on macOS, Linux, and Windows
+pub(crate) fn sanitize_filename(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') && !c.is_control())
+        .collect();
+
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_end_matches(['.', ' ']);
+
+    if trimmed.is_empty() {
+        "Untitled".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Short, stable hex hash of a string (first 8 hex characters of SHA256)
+pub(crate) fn short_hash(input: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(input.as_bytes())[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("What If?: Serious Answers"), "What If Serious Answers");
+        assert_eq!(sanitize_filename("a/b\\c..."), "abc");
+        assert_eq!(sanitize_filename("???"), "Untitled");
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml(r#"Tom & "Jerry" <3 'em"#),
+            "Tom &amp; &quot;Jerry&quot; &lt;3 &apos;em"
+        );
+    }
+}
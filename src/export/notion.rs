@@ -0,0 +1,143 @@
+use crate::error::ExportError;
+use crate::export::markdown::book_to_markdown;
+use crate::export::sanitize_filename;
+use crate::model::{Book, Library, Source};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// One row of the Notion database CSV
+#[derive(Debug, Serialize)]
+struct NotionRow<'a> {
+    #[serde(rename = "Name")]
+    name: &'a str,
+    #[serde(rename = "Author")]
+    author: &'a str,
+    #[serde(rename = "Sources")]
+    sources: String,
+    #[serde(rename = "Highlights count")]
+    highlight_count: usize,
+    #[serde(rename = "Finished")]
+    finished: &'static str,
+    #[serde(rename = "Finished date")]
+    finished_date: String,
+}
+
+/// Write a Notion import bundle: `books.csv` plus one Markdown page per book
+///
+/// Markdown file names match the `Name` column so Notion links each row to its content.
+pub fn to_notion(library: &Library, dir: &Path) -> Result<(), ExportError> {
+    let pages_dir = dir.join("books");
+    fs::create_dir_all(&pages_dir)?;
+
+    let names = page_names(&library.books);
+    let mut csv = csv::Writer::from_path(dir.join("books.csv"))?;
+
+    for (book, name) in library.books.iter().zip(names.iter()) {
+        csv.serialize(NotionRow {
+            name,
+            author: book.author.as_deref().unwrap_or_default(),
+            sources: book
+                .sources
+                .iter()
+                .map(Source::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            highlight_count: book.highlights.len(),
+            finished: if book.finished == Some(true) { "Yes" } else { "No" },
+            finished_date: book
+                .finished_at
+                .map(|dt| dt.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        })?;
+
+        fs::write(pages_dir.join(format!("{}.md", name)), book_to_markdown(book))?;
+    }
+
+    csv.flush()?;
+
+    Ok(())
+}
+
+/// Page names for each book, disambiguating duplicate titles with the author
+fn page_names(books: &[Book]) -> Vec<String> {
+    let mut title_counts: HashMap<String, usize> = HashMap::new();
+    for book in books {
+        *title_counts.entry(notion_name(&book.title).to_lowercase()).or_default() += 1;
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+
+    books
+        .iter()
+        .map(|book| {
+            let title = notion_name(&book.title);
+            let mut name = if title_counts[&title.to_lowercase()] > 1 {
+                match book.author {
+                    Some(ref author) => format!("{} ({})", title, notion_name(author)),
+                    None => title,
+                }
+            } else {
+                title
+            };
+
+            // Same title and author: fall back to the book ID
+            if !used.insert(name.to_lowercase()) {
+                name = format!("{} {}", name, &book.id[..8]);
+                used.insert(name.to_lowercase());
+            }
+
+            name
+        })
+        .collect()
+}
+
+/// Strip characters that break Notion's importer or file names
+fn notion_name(text: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| !matches!(c, '#' | '[' | ']' | '%' | '^' | '{' | '}' | '`'))
+        .collect();
+    sanitize_filename(&cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_names_disambiguate_duplicates() {
+        let books = vec![
+            Book::new("Collected Poems".to_string(), Some("W. H. Auden".to_string())),
+            Book::new("Collected Poems".to_string(), Some("Sylvia Plath".to_string())),
+            Book::new("Notes #1 [draft]".to_string(), None),
+        ];
+
+        let names = page_names(&books);
+        assert_eq!(names[0], "Collected Poems (W. H. Auden)");
+        assert_eq!(names[1], "Collected Poems (Sylvia Plath)");
+        assert_eq!(names[2], "Notes 1 draft");
+    }
+
+    #[test]
+    fn test_to_notion_writes_csv_and_pages() {
+        let mut book = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        book.sources.push(Source::AppleBooks);
+        book.finished = Some(true);
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        let dir = std::env::temp_dir().join(format!("readingsync_notion_{}", uuid::Uuid::new_v4()));
+        to_notion(&library, &dir).unwrap();
+
+        let csv = fs::read_to_string(dir.join("books.csv")).unwrap();
+        let page_exists = dir.join("books").join("Walden.md").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(csv.starts_with("Name,Author,Sources,Highlights count,Finished,Finished date\n"));
+        assert!(csv.contains("Walden,Henry David Thoreau,apple_books,0,Yes,\n"));
+        assert!(page_exists);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::model::Library;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// A single quote in the pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Quote {
+    pub text: String,
+    pub author: Option<String>,
+    pub title: String,
+}
+
+/// Options for building a quote pool
+#[derive(Debug, Clone, Default)]
+pub struct QuoteOptions {
+    /// Minimum quote length in characters
+    pub min_chars: Option<usize>,
+    /// Maximum quote length in characters
+    pub max_chars: Option<usize>,
+    /// Seed for a reproducible shuffle (random when None)
+    pub seed: Option<u64>,
+    /// Skip highlights that don't end with terminal punctuation
+    pub complete_sentences: bool,
+}
+
+/// Build a flat, shuffled pool of quotes (e.g. for a "quote of the day" display)
+///
+/// Quotes are filtered by length, deduplicated by normalized text, and shuffled.
+/// The same seed always produces the same order.
+pub fn to_quotes(library: &Library, options: &QuoteOptions) -> Vec<Quote> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut quotes = Vec::new();
+
+    for book in &library.books {
+        for highlight in &book.highlights {
+            let text = highlight.text.trim();
+            let length = text.chars().count();
+
+            if text.is_empty()
+                || options.min_chars.is_some_and(|min| length < min)
+                || options.max_chars.is_some_and(|max| length > max)
+                || (options.complete_sentences && !is_complete_sentence(text))
+            {
+                continue;
+            }
+
+            let normalized = text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ");
+            if !seen.insert(normalized) {
+                continue;
+            }
+
+            quotes.push(Quote {
+                text: text.to_string(),
+                author: book.author.clone(),
+                title: book.title.clone(),
+            });
+        }
+    }
+
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    shuffle(&mut quotes, seed);
+
+    quotes
+}
+
+/// Whether text ends like a full sentence (terminal punctuation, optionally closed by quotes)
+fn is_complete_sentence(text: &str) -> bool {
+    let trimmed = text.trim_end_matches(['"', '\'', '”', '’', ')', ']', '»']);
+    trimmed.ends_with(['.', '!', '?', '…'])
+}
+
+/// Fisher-Yates shuffle driven by a SplitMix64 generator
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+
+    fn make_library(texts: &[&str]) -> Library {
+        let mut book = Book::new("Meditations".to_string(), Some("Marcus Aurelius".to_string()));
+        for text in texts {
+            book.highlights.push(Highlight {
+                id: uuid::Uuid::new_v4().to_string(),
+                text: text.to_string(),
+                note: None,
+                location: Location {
+                    chapter: None,
+                    position: None,
+                    page: None,
+                    raw: None,
+                },
+                created_at: None,
+                sources: vec![Source::Kindle],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+
+        let mut library = Library::new();
+        library.books.push(book);
+        library
+    }
+
+    #[test]
+    fn test_quotes_filter_and_dedupe() {
+        let library = make_library(&[
+            "Short.",
+            "The impediment to action advances action.",
+            "the impediment  to action advances action.",
+            "What stands in the way becomes the way",
+        ]);
+
+        let options = QuoteOptions {
+            min_chars: Some(10),
+            max_chars: Some(60),
+            seed: Some(1),
+            complete_sentences: true,
+        };
+        let quotes = to_quotes(&library, &options);
+
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].text, "The impediment to action advances action.");
+        assert_eq!(quotes[0].author.as_deref(), Some("Marcus Aurelius"));
+    }
+
+    #[test]
+    fn test_quotes_seeded_shuffle_is_reproducible() {
+        let texts: Vec<String> = (0..20).map(|i| format!("Quote number {}.", i)).collect();
+        let library = make_library(&texts.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let options = QuoteOptions {
+            seed: Some(42),
+            ..Default::default()
+        };
+
+        let first = to_quotes(&library, &options);
+        let second = to_quotes(&library, &options);
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 20);
+        assert_ne!(first[0].text, "Quote number 0.");
+    }
+
+    #[test]
+    fn test_is_complete_sentence() {
+        assert!(is_complete_sentence("It ends here."));
+        assert!(is_complete_sentence("\"Really?\""));
+        assert!(!is_complete_sentence("and then the"));
+    }
+}
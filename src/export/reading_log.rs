@@ -0,0 +1,163 @@
+use crate::model::{Book, Library};
+use chrono::Datelike;
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+/// Render the finished books as a Markdown reading log, by year
+///
+/// ```text
+/// # Reading Log
+///
+/// ## 2024 (2 books)
+///
+/// - 2024-01-14 — *Walden* by Henry David Thoreau · 12 highlights · Philosophy
+/// - 2024-03-05 — *Meditations* by Marcus Aurelius
+///
+/// ## Finished, date unknown (1 book)
+///
+/// ## Want to Read (3 books)
+/// ```
+///
+/// Years run newest first, books in the order they were finished (local dates in `tz`).
+/// Books count as finished when a source says so or they're in Apple Books' "Finished"
+/// collection, with or without highlights.
+pub fn to_reading_log(library: &Library, tz: Tz) -> String {
+    let mut by_year: BTreeMap<i32, Vec<&Book>> = BTreeMap::new();
+    let mut undated: Vec<&Book> = Vec::new();
+    let mut want_to_read: Vec<&Book> = Vec::new();
+
+    for book in &library.books {
+        if is_finished(book) {
+            match book.finished_at {
+                Some(finished_at) => by_year.entry(finished_at.with_timezone(&tz).year()).or_default().push(book),
+                None => undated.push(book),
+            }
+        } else if book.collections.iter().any(|c| c == WANT_TO_READ) {
+            want_to_read.push(book);
+        }
+    }
+
+    let mut out = String::from("# Reading Log\n");
+
+    for (year, mut books) in by_year.into_iter().rev() {
+        books.sort_by_key(|b| b.finished_at);
+        out.push_str(&format!("\n## {} ({})\n\n", year, count(books.len())));
+        for book in books {
+            let day = book.finished_at.map(|at| at.with_timezone(&tz).format("%Y-%m-%d").to_string());
+            out.push_str(&format!("- {} — {}\n", day.unwrap_or_default(), entry(book)));
+        }
+    }
+
+    for (heading, books) in [("Finished, date unknown", undated), (WANT_TO_READ, want_to_read)] {
+        if books.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n## {} ({})\n\n", heading, count(books.len())));
+        for book in books {
+            out.push_str(&format!("- {}\n", entry(book)));
+        }
+    }
+
+    out
+}
+
+/// Apple Books' built-in collections, as `apple_books` names them
+const WANT_TO_READ: &str = "Want to Read";
+const FINISHED: &str = "Finished";
+
+fn is_finished(book: &Book) -> bool {
+    book.finished == Some(true) || book.collections.iter().any(|c| c == FINISHED)
+}
+
+/// "*Title* by Author · 12 highlights · Collection", leaving out what's missing
+fn entry(book: &Book) -> String {
+    let mut parts = vec![match book.author {
+        Some(ref author) => format!("*{}* by {}", book.title, author),
+        None => format!("*{}*", book.title),
+    }];
+    match book.highlights.len() {
+        0 => {}
+        1 => parts.push("1 highlight".to_string()),
+        n => parts.push(format!("{} highlights", n)),
+    }
+    parts.extend(
+        book.collections
+            .iter()
+            .filter(|c| *c != WANT_TO_READ && *c != FINISHED)
+            .cloned(),
+    );
+    parts.join(" · ")
+}
+
+fn count(books: usize) -> String {
+    match books {
+        1 => "1 book".to_string(),
+        n => format!("{} books", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Highlight, HighlightKind, HighlightStyle, Location, Source};
+    use chrono::{TimeZone, Utc};
+
+    fn make_book(title: &str, finished_at: Option<(i32, u32, u32)>) -> Book {
+        let mut book = Book::new(title.to_string(), Some("Author".to_string()));
+        book.finished = Some(finished_at.is_some());
+        book.finished_at = finished_at.and_then(|(y, m, d)| Utc.with_ymd_and_hms(y, m, d, 2, 30, 0).single());
+        book
+    }
+
+    #[test]
+    fn test_reading_log_by_year() {
+        let mut library = Library::new();
+        library.books.push(make_book("Later", Some((2024, 6, 1))));
+        library.books.push(make_book("Earlier", Some((2024, 1, 10))));
+        library.books.push(make_book("Last year", Some((2023, 5, 1))));
+        library.books.push(make_book("Unread", None));
+
+        let mut walden = make_book("Walden", Some((2024, 3, 6)));
+        walden.collections = vec!["Finished".to_string(), "Philosophy".to_string()];
+        walden.highlights.push(Highlight {
+            id: "h1".to_string(),
+            text: "Simplify, simplify.".to_string(),
+            note: None,
+            location: Location::default(),
+            created_at: None,
+            sources: vec![Source::AppleBooks],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+        library.books.push(walden);
+
+        let log = to_reading_log(&library, chrono_tz::America::New_York);
+
+        let years: Vec<&str> = log.lines().filter(|l| l.starts_with("## ")).collect();
+        assert_eq!(years, vec!["## 2024 (3 books)", "## 2023 (1 book)"]);
+        let earlier = log.find("*Earlier*").unwrap();
+        assert!(earlier < log.find("*Walden*").unwrap() && log.find("*Walden*").unwrap() < log.find("*Later*").unwrap());
+        // Local dates: 02:30 UTC is the day before in New York
+        assert!(log.contains("- 2024-03-05 — *Walden* by Author · 1 highlight · Philosophy\n"));
+        assert!(!log.contains("Unread"));
+    }
+
+    #[test]
+    fn test_reading_log_undated_and_want_to_read() {
+        let mut library = Library::new();
+        let mut in_collection = make_book("In the Finished collection", None);
+        in_collection.collections = vec!["Finished".to_string()];
+        library.books.push(in_collection);
+        let mut wanted = make_book("Dune", None);
+        wanted.collections = vec!["Want to Read".to_string()];
+        library.books.push(wanted);
+
+        let log = to_reading_log(&library, chrono_tz::UTC);
+
+        assert!(log.contains("## Finished, date unknown (1 book)\n\n- *In the Finished collection* by Author\n"));
+        assert!(log.contains("## Want to Read (1 book)\n\n- *Dune* by Author\n"));
+    }
+}
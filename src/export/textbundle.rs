@@ -0,0 +1,71 @@
+use crate::error::ExportError;
+use crate::export::markdown::book_to_markdown;
+use crate::export::sanitize_filename;
+use crate::model::{Book, Library};
+use std::fs;
+use std::path::Path;
+
+/// Write one `.textbundle` package per book into a directory (for Bear and similar apps)
+///
+/// Each bundle contains `text.markdown` with a `#books/<author>#` tag and an `info.json`.
+/// Bundle names include the book ID so re-exports overwrite the same bundles.
+pub fn to_textbundles(library: &Library, dir: &Path) -> Result<(), ExportError> {
+    fs::create_dir_all(dir)?;
+
+    for book in &library.books {
+        let bundle = dir.join(bundle_name(book));
+        fs::create_dir_all(&bundle)?;
+
+        fs::write(bundle.join("text.markdown"), render_text(book))?;
+
+        let info = serde_json::json!({
+            "version": 2,
+            "type": "net.daringfireball.markdown",
+            "transient": false,
+            "creatorIdentifier": "com.github.urcades.readingsync",
+        });
+        fs::write(bundle.join("info.json"), serde_json::to_string_pretty(&info)?)?;
+    }
+
+    Ok(())
+}
+
+/// Stable bundle directory name for a book
+fn bundle_name(book: &Book) -> String {
+    format!("{} {}.textbundle", sanitize_filename(&book.title), &book.id[..8])
+}
+
+/// Markdown content with a Bear tag line under the title
+fn render_text(book: &Book) -> String {
+    let markdown = book_to_markdown(book);
+    let tag = format!("#books/{}#", book.author.as_deref().unwrap_or("Unknown Author"));
+
+    match markdown.split_once('\n') {
+        Some((title, rest)) => format!("{}\n{}\n{}", title, tag, rest),
+        None => format!("{}\n{}\n", markdown, tag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_textbundles() {
+        let book = Book::new("Walden: Or, Life in the Woods".to_string(), Some("Henry David Thoreau".to_string()));
+        let mut library = Library::new();
+        library.books.push(book.clone());
+
+        let dir = std::env::temp_dir().join(format!("readingsync_textbundle_{}", uuid::Uuid::new_v4()));
+        to_textbundles(&library, &dir).unwrap();
+
+        let bundle = dir.join(bundle_name(&book));
+        let text = fs::read_to_string(bundle.join("text.markdown")).unwrap();
+        let info = fs::read_to_string(bundle.join("info.json")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(bundle_name(&book).starts_with("Walden Or, Life in the Woods "));
+        assert!(text.starts_with("# Walden: Or, Life in the Woods\n#books/Henry David Thoreau#\n"));
+        assert!(info.contains("net.daringfireball.markdown"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::export::{bookmark_label, short_hash};
+use crate::model::{Book, Highlight, HighlightKind, Library};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// A tiddler in TiddlyWiki's JSON import format
+#[derive(Debug, Clone, Serialize)]
+pub struct Tiddler {
+    pub title: String,
+    pub text: String,
+    pub tags: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    pub book: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+/// Convert the library into TiddlyWiki tiddlers, one per highlight
+///
+/// Titles are `<book title> <hash>` where the hash is derived from the book ID and
+/// highlight text, so re-importing an export updates tiddlers instead of duplicating them.
+pub fn to_tiddlywiki(library: &Library) -> Vec<Tiddler> {
+    library
+        .books
+        .iter()
+        .flat_map(|book| book.highlights.iter().map(move |h| make_tiddler(book, h)))
+        .collect()
+}
+
+fn make_tiddler(book: &Book, highlight: &Highlight) -> Tiddler {
+    let (hash, mut text) = if highlight.kind == HighlightKind::Bookmark {
+        // Bookmarks have no text, so their position tells them apart
+        let label = bookmark_label(highlight);
+        (short_hash(&format!("{}{}", book.id, label)), label)
+    } else if highlight.kind == HighlightKind::Note {
+        (short_hash(&format!("{}{}", book.id, highlight.body())), String::new())
+    } else {
+        (
+            short_hash(&format!("{}{}", book.id, highlight.text)),
+            format!("<<<\n{}\n<<<", highlight.text),
+        )
+    };
+    if let Some(ref note) = highlight.note {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(note);
+    }
+
+    let timestamp = highlight.created_at.as_ref().map(tiddlywiki_date);
+
+    Tiddler {
+        title: format!("{} {}", book.title, hash),
+        text,
+        tags: format_tag(&book.title),
+        created: timestamp.clone(),
+        modified: timestamp,
+        book: book.title.clone(),
+        author: book.author.clone(),
+    }
+}
+
+/// Format a tag, wrapping titles containing spaces in `[[...]]`
+fn format_tag(tag: &str) -> String {
+    if tag.contains(char::is_whitespace) {
+        format!("[[{}]]", tag)
+    } else {
+        tag.to_string()
+    }
+}
+
+/// Format a timestamp in TiddlyWiki's `YYYYMMDDHHMMSSmmm` UTC format
+fn tiddlywiki_date(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%d%H%M%S%3f").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{HighlightStyle, Location, Source};
+    use chrono::TimeZone;
+
+    fn make_highlight(text: &str) -> Highlight {
+        Highlight {
+            id: uuid::Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at: Utc.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).single(),
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    #[test]
+    fn test_tiddler_titles_are_unique_and_stable() {
+        let mut book = Book::new("The Great Gatsby".to_string(), None);
+        book.highlights.push(make_highlight("First"));
+        book.highlights.push(make_highlight("Second"));
+
+        let mut library = Library::new();
+        library.books.push(book);
+
+        let first = to_tiddlywiki(&library);
+        let second = to_tiddlywiki(&library);
+
+        assert_eq!(first.len(), 2);
+        assert_ne!(first[0].title, first[1].title);
+        assert_eq!(first[0].title, second[0].title);
+        assert!(first[0].title.starts_with("The Great Gatsby "));
+        assert_eq!(first[0].tags, "[[The Great Gatsby]]");
+        assert_eq!(first[0].created.as_deref(), Some("20240305140709000"));
+    }
+}
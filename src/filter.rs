@@ -0,0 +1,435 @@
+use crate::model::{Book, Highlight, HighlightKind, Library, Source};
+use chrono::{DateTime, Utc};
+
+/// Criteria for narrowing down a library before export
+///
+/// All criteria are combined with AND semantics. Text matching is case-insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct FilterSpec {
+    /// Substring of the book title, or an exact book ID or ASIN
+    pub book: Option<String>,
+    /// Substring of the author name
+    pub author: Option<String>,
+    /// Only keep books and highlights from this source
+    pub source: Option<Source>,
+    /// Only keep books in this collection (exact name, case-insensitive)
+    pub collection: Option<String>,
+    /// Only keep books in this language, e.g. "en" also matching "en-GB"
+    pub language: Option<String>,
+    /// Only keep highlights created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only keep highlights created at or before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Drop books with fewer highlights than this (after other filters)
+    pub min_highlights: Option<usize>,
+    /// Only keep books read at least this far, from 0.0 to 1.0; finished books count as
+    /// read through and books without progress are dropped
+    pub min_progress: Option<f32>,
+    /// Only keep highlights that have a note attached
+    pub notes_only: bool,
+    /// Drop standalone notes and strip note text from all highlights
+    pub highlights_only: bool,
+}
+
+impl FilterSpec {
+    /// Whether any criteria are set
+    pub fn is_empty(&self) -> bool {
+        self.book.is_none()
+            && self.author.is_none()
+            && self.source.is_none()
+            && self.collection.is_none()
+            && self.language.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.min_highlights.is_none()
+            && self.min_progress.is_none()
+            && !self.notes_only
+            && !self.highlights_only
+    }
+
+    /// Whether books left without highlights should be dropped
+    fn drops_empty_books(&self) -> bool {
+        self.since.is_some() || self.until.is_some() || self.notes_only
+    }
+
+    /// Whether a date range is set
+    fn has_date_range(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    fn matches_book(&self, book: &Book) -> bool {
+        if let Some(ref pattern) = self.book {
+            let pattern = pattern.to_lowercase();
+            let is_asin = book.asin().is_some_and(|asin| asin.eq_ignore_ascii_case(&pattern));
+            if book.id != pattern && !is_asin && !book.title.to_lowercase().contains(&pattern) {
+                return false;
+            }
+        }
+
+        if let Some(ref pattern) = self.author {
+            let pattern = pattern.to_lowercase();
+            match book.author {
+                Some(ref author) if author.to_lowercase().contains(&pattern) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ref source) = self.source {
+            if !book.sources.contains(source) {
+                return false;
+            }
+        }
+
+        if let Some(ref collection) = self.collection {
+            if !book.collections.iter().any(|c| c.eq_ignore_ascii_case(collection)) {
+                return false;
+            }
+        }
+
+        if let Some(ref language) = self.language {
+            if !book.language.as_deref().is_some_and(|l| matches_language(l, language)) {
+                return false;
+            }
+        }
+
+        if let Some(min) = self.min_progress {
+            let progress = if book.finished == Some(true) { Some(1.0) } else { book.progress };
+            if progress.is_none_or(|progress| progress < min) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn matches_highlight(&self, highlight: &Highlight) -> bool {
+        if let Some(ref source) = self.source {
+            if !highlight.sources.contains(source) {
+                return false;
+            }
+        }
+
+        if self.notes_only && highlight.note.is_none() {
+            return false;
+        }
+
+        if self.highlights_only && highlight.kind == HighlightKind::Note {
+            return false;
+        }
+
+        if self.has_date_range() {
+            let Some(created_at) = highlight.created_at else {
+                return false;
+            };
+            if self.since.is_some_and(|since| created_at < since) {
+                return false;
+            }
+            if self.until.is_some_and(|until| created_at > until) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Whether a language tag is the wanted language or a regional variant of it
+fn matches_language(tag: &str, wanted: &str) -> bool {
+    let tag = tag.replace('_', "-");
+    tag.eq_ignore_ascii_case(wanted)
+        || tag
+            .split_once('-')
+            .is_some_and(|(primary, _)| primary.eq_ignore_ascii_case(wanted))
+}
+
+impl Library {
+    /// Return a copy of the library containing only books and highlights matching the spec
+    ///
+    /// When a date range or `notes_only` is set, books are trimmed to their matching
+    /// highlights and books left without any highlights are dropped.
+    pub fn filter(&self, spec: &FilterSpec) -> Library {
+        let books = self
+            .books
+            .iter()
+            .filter(|book| spec.matches_book(book))
+            .map(|book| {
+                let mut book = book.clone();
+                book.highlights.retain(|h| spec.matches_highlight(h));
+                if spec.highlights_only {
+                    for highlight in &mut book.highlights {
+                        highlight.note = None;
+                    }
+                }
+                book
+            })
+            .filter(|book| !spec.drops_empty_books() || !book.highlights.is_empty())
+            .filter(|book| {
+                spec.min_highlights
+                    .is_none_or(|min| book.highlights.len() >= min)
+            })
+            .collect();
+
+        Library {
+            exported_at: self.exported_at,
+            books,
+        }
+    }
+
+    /// Total number of highlights across all books
+    pub fn highlight_count(&self) -> usize {
+        self.books.iter().map(|b| b.highlights.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{HighlightStyle, Location};
+    use chrono::TimeZone;
+
+    fn make_highlight(text: &str, created_at: Option<DateTime<Utc>>, source: Source) -> Highlight {
+        Highlight {
+            id: uuid::Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at,
+            sources: vec![source],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    fn make_library() -> Library {
+        let mut gatsby = Book::new("The Great Gatsby".to_string(), Some("F. Scott Fitzgerald".to_string()));
+        gatsby.sources = vec![Source::Kindle, Source::AppleBooks];
+        gatsby.source_ids.insert("asin".to_string(), "B000FC0PDA".to_string());
+        gatsby.highlights.push(make_highlight("January", Some(date(2024, 1, 15)), Source::Kindle));
+        gatsby.highlights.push(make_highlight("March", Some(date(2024, 3, 1)), Source::AppleBooks));
+        gatsby.highlights.push(make_highlight("Undated", None, Source::Kindle));
+
+        let mut walden = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        walden.sources = vec![Source::AppleBooks];
+        walden.highlights.push(make_highlight("Woods", Some(date(2023, 6, 1)), Source::AppleBooks));
+
+        let mut library = Library::new();
+        library.books = vec![gatsby, walden];
+        library
+    }
+
+    #[test]
+    fn test_filter_empty_spec_keeps_everything() {
+        let library = make_library();
+        let filtered = library.filter(&FilterSpec::default());
+
+        assert_eq!(filtered.books.len(), 2);
+        assert_eq!(filtered.highlight_count(), 4);
+    }
+
+    #[test]
+    fn test_filter_by_book_and_author_case_insensitive() {
+        let library = make_library();
+
+        let spec = FilterSpec {
+            book: Some("GATSBY".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(library.filter(&spec).books.len(), 1);
+
+        let spec = FilterSpec {
+            book: Some("b000fc0pda".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(library.filter(&spec).books[0].title, "The Great Gatsby");
+
+        let spec = FilterSpec {
+            book: Some(library.books[1].id.clone()),
+            author: Some("thoreau".to_string()),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].title, "Walden");
+
+        // AND semantics: title matches but author does not
+        let spec = FilterSpec {
+            book: Some("gatsby".to_string()),
+            author: Some("thoreau".to_string()),
+            ..Default::default()
+        };
+        assert!(library.filter(&spec).books.is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_source_trims_highlights() {
+        let library = make_library();
+        let spec = FilterSpec {
+            source: Some(Source::Kindle),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_by_collection() {
+        let mut library = make_library();
+        library.books[1].collections = vec!["Philosophy".to_string()];
+
+        let spec = FilterSpec {
+            collection: Some("philosophy".to_string()),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].title, "Walden");
+    }
+
+    #[test]
+    fn test_filter_date_range_is_inclusive() {
+        let library = make_library();
+        let spec = FilterSpec {
+            since: Some(date(2024, 1, 15)),
+            until: Some(date(2024, 3, 1)),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        // Walden is entirely out of range, Gatsby keeps both boundary highlights
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_partially_in_range() {
+        let library = make_library();
+        let spec = FilterSpec {
+            since: Some(date(2024, 2, 1)),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].highlights.len(), 1);
+        assert_eq!(filtered.books[0].highlights[0].text, "March");
+    }
+
+    #[test]
+    fn test_filter_notes_only() {
+        let mut library = make_library();
+        library.books[0].highlights[1].note = Some("My thoughts".to_string());
+
+        let spec = FilterSpec {
+            notes_only: true,
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        // Walden has no notes and is dropped entirely
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.highlight_count(), 1);
+        assert_eq!(filtered.books[0].highlights[0].text, "March");
+    }
+
+    #[test]
+    fn test_filter_highlights_only_strips_notes() {
+        let mut library = make_library();
+        library.books[0].highlights[1].note = Some("My thoughts".to_string());
+
+        let spec = FilterSpec {
+            highlights_only: true,
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.highlight_count(), 4);
+        assert!(filtered.books.iter().flat_map(|b| &b.highlights).all(|h| h.note.is_none()));
+    }
+
+    #[test]
+    fn test_filter_highlights_only_drops_standalone_notes() {
+        let mut library = make_library();
+        library.books[0].highlights[1].kind = HighlightKind::Note;
+        library.books[0].highlights[1].text = "A note of my own".to_string();
+
+        let spec = FilterSpec {
+            highlights_only: true,
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.highlight_count(), 3);
+        assert!(filtered
+            .books
+            .iter()
+            .flat_map(|b| &b.highlights)
+            .all(|h| h.kind != HighlightKind::Note));
+    }
+
+    #[test]
+    fn test_filter_min_highlights() {
+        let library = make_library();
+        let spec = FilterSpec {
+            min_highlights: Some(2),
+            ..Default::default()
+        };
+        let filtered = library.filter(&spec);
+
+        assert_eq!(filtered.books.len(), 1);
+        assert_eq!(filtered.books[0].title, "The Great Gatsby");
+    }
+
+    #[test]
+    fn test_filter_by_language() {
+        let mut library = make_library();
+        library.books[0].language = Some("en_US".to_string());
+        library.books[1].language = Some("de".to_string());
+        let titles = |language: &str| -> Vec<String> {
+            let spec = FilterSpec {
+                language: Some(language.to_string()),
+                ..Default::default()
+            };
+            library.filter(&spec).books.into_iter().map(|b| b.title).collect()
+        };
+
+        assert_eq!(titles("EN"), vec!["The Great Gatsby"]);
+        assert_eq!(titles("en-us"), vec!["The Great Gatsby"]);
+        assert_eq!(titles("de"), vec!["Walden"]);
+        assert!(titles("fr").is_empty());
+    }
+
+    #[test]
+    fn test_filter_min_progress() {
+        let mut library = make_library();
+        library.books[0].progress = Some(0.3);
+        let mut finished = Book::new("Cape Cod".to_string(), None);
+        finished.finished = Some(true);
+        library.books.push(finished);
+
+        let spec = FilterSpec {
+            min_progress: Some(0.5),
+            ..Default::default()
+        };
+        let titles: Vec<String> = library.filter(&spec).books.into_iter().map(|b| b.title).collect();
+        // Walden has no progress at all
+        assert_eq!(titles, vec!["Cape Cod"]);
+
+        library.books[0].progress = Some(0.5);
+        assert_eq!(library.filter(&spec).books.len(), 2);
+    }
+}
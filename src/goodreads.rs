@@ -0,0 +1,162 @@
+use crate::error::GoodreadsError;
+use crate::model::{Book, Source};
+use chrono::{NaiveDate, TimeZone, Utc};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Row of Goodreads' library export
+#[derive(Debug, Deserialize)]
+struct GoodreadsRow {
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Author", default)]
+    author: String,
+    #[serde(rename = "ISBN", default)]
+    isbn: String,
+    #[serde(rename = "ISBN13", default)]
+    isbn13: String,
+    #[serde(rename = "Date Read", default)]
+    date_read: String,
+    #[serde(rename = "Exclusive Shelf", default)]
+    shelf: String,
+}
+
+/// Parse a Goodreads library export into books without highlights
+///
+/// The books exist to enrich other sources when merged: read books are marked finished
+/// (as of "Date Read") and carry their ISBN. Series suffixes like "(Dune, #1)" are
+/// stripped from titles so the IDs match a Kindle or Apple Books copy.
+pub fn parse_csv(path: &Path) -> Result<Vec<Book>, GoodreadsError> {
+    if !path.exists() {
+        return Err(GoodreadsError::FileNotFound(path.to_path_buf()));
+    }
+
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut books = Vec::new();
+
+    for row in reader.deserialize() {
+        let row: GoodreadsRow = row?;
+
+        let title = strip_series(&row.title);
+        if title.is_empty() {
+            continue;
+        }
+
+        let author = Some(row.author.trim()).filter(|a| !a.is_empty());
+        let mut book = Book::new(title.to_string(), author.map(String::from));
+        book.sources = vec![Source::Goodreads];
+        book.isbn = clean_isbn(&row.isbn).or_else(|| clean_isbn(&row.isbn13));
+
+        let finished_at = NaiveDate::parse_from_str(row.date_read.trim(), "%Y/%m/%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|dt| Utc.from_utc_datetime(&dt));
+
+        if finished_at.is_some() || row.shelf.trim() == "read" {
+            book.finished = Some(true);
+            book.finished_at = finished_at;
+        }
+
+        books.push(book);
+    }
+
+    Ok(books)
+}
+
+/// Remove a trailing series annotation, e.g. "The Way of Kings (The Stormlight Archive, #1)"
+fn strip_series(title: &str) -> &str {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"\s*\((?:[^()]*#\s*\d+(?:\.\d+)?|Book\s+\d+)\)\s*$").unwrap());
+
+    let title = title.trim();
+    match re.find(title) {
+        Some(m) => &title[..m.start()],
+        None => title,
+    }
+}
+
+/// Goodreads writes ISBNs as spreadsheet formulas, e.g. `="0441013597"`
+fn clean_isbn(value: &str) -> Option<String> {
+    let isbn: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X')
+        .collect();
+
+    if isbn.is_empty() {
+        None
+    } else {
+        Some(isbn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::clippings::parse_clippings_content;
+    use crate::merge::merge_books;
+
+    const EXPORT: &str = "Book Id,Title,Author,Author l-f,Additional Authors,ISBN,ISBN13,My Rating,Average Rating,Publisher,Binding,Number of Pages,Year Published,Original Publication Year,Date Read,Date Added,Bookshelves,Bookshelves with positions,Exclusive Shelf,My Review,Spoiler,Private Notes,Read Count,Owned Copies\n\
+        234225,\"Dune (Dune, #1)\",Frank Herbert,\"Herbert, Frank\",,\"=\"\"0441013597\"\"\",\"=\"\"9780441013593\"\"\",5,4.25,Ace,Paperback,658,2005,1965,2023/08/14,2023/07/01,,,read,,,,1,0\n\
+        11,The Hitchhiker's Guide to the Galaxy,Douglas Adams,\"Adams, Douglas\",,\"=\"\"\"\"\",\"=\"\"\"\"\",0,4.22,Del Rey,Paperback,224,1995,1979,,2023/07/01,to-read,to-read (#1),to-read,,,,0,0\n";
+
+    fn write_export() -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("readingsync_goodreads_{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, EXPORT).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let path = write_export();
+        let books = parse_csv(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(books.len(), 2);
+
+        let dune = &books[0];
+        assert_eq!(dune.title, "Dune");
+        assert_eq!(dune.isbn.as_deref(), Some("0441013597"));
+        assert_eq!(dune.finished, Some(true));
+        assert_eq!(dune.finished_at, Utc.with_ymd_and_hms(2023, 8, 14, 0, 0, 0).single());
+        assert!(dune.highlights.is_empty());
+
+        let hitchhiker = &books[1];
+        assert_eq!(hitchhiker.isbn, None);
+        assert_eq!(hitchhiker.finished, None);
+    }
+
+    #[test]
+    fn test_strip_series() {
+        assert_eq!(strip_series("Dune (Dune, #1)"), "Dune");
+        assert_eq!(strip_series("Dune Messiah (Book 2)"), "Dune Messiah");
+        assert_eq!(strip_series("The Fellowship of the Ring (The Lord of the Rings, #1.5)"), "The Fellowship of the Ring");
+        assert_eq!(strip_series("Surely You're Joking, Mr. Feynman! (Adventures of a Curious Character)"), "Surely You're Joking, Mr. Feynman! (Adventures of a Curious Character)");
+    }
+
+    #[test]
+    fn test_marks_kindle_book_finished() {
+        let path = write_export();
+        let goodreads = parse_csv(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let kindle = parse_clippings_content(
+            "Dune (Frank Herbert)\n\
+             - Your Highlight on Location 100-110 | Added on Monday, January 1, 2024\n\
+             \n\
+             I must not fear.\n\
+             ==========\n",
+        )
+        .unwrap();
+
+        let merged = merge_books(vec![kindle, goodreads]);
+        let dune = merged.iter().find(|b| b.title == "Dune").unwrap();
+
+        assert_eq!(dune.sources, vec![Source::Kindle, Source::Goodreads]);
+        assert_eq!(dune.highlights.len(), 1);
+        assert_eq!(dune.finished, Some(true));
+        assert_eq!(dune.finished_at, Utc.with_ymd_and_hms(2023, 8, 14, 0, 0, 0).single());
+        assert_eq!(dune.isbn.as_deref(), Some("0441013597"));
+    }
+}
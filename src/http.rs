@@ -0,0 +1,235 @@
+//! HTTP clients that honor the `[http]` settings
+//!
+//! Every feature that talks to a server (the cookie scraper, cover downloads, the
+//! Hypothesis and Pocket APIs) builds its reqwest client here, so a proxy, extra root
+//! certificates and the user agent apply to all of them. Failed requests go through
+//! `request_error`, which says whether the proxy or the server was at fault.
+
+use crate::config::HttpConfig;
+use crate::error::NetworkError;
+use reqwest::{Certificate, NoProxy, Proxy};
+use std::error::Error as _;
+use std::fs;
+
+/// Proxy environment variables, in order of preference
+const PROXY_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
+
+/// The proxy to use: the configured one, then the first proxy environment variable set
+pub fn proxy_url(config: &HttpConfig) -> Option<String> {
+    pick_proxy(config, |var| std::env::var(var).ok())
+}
+
+fn pick_proxy(config: &HttpConfig, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    config
+        .proxy
+        .clone()
+        .or_else(|| PROXY_VARS.iter().find_map(|var| env(var)))
+        .filter(|url| !url.trim().is_empty())
+}
+
+/// A builder for an async client with the proxy, certificates and user agent applied
+pub fn client_builder(config: &HttpConfig) -> Result<reqwest::ClientBuilder, NetworkError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = proxy(config)? {
+        builder = builder.proxy(proxy);
+    }
+    for certificate in root_certificates(config)? {
+        builder = builder.add_root_certificate(certificate);
+    }
+    if let Some(user_agent) = &config.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    Ok(builder)
+}
+
+/// `client_builder` for a blocking client
+pub fn blocking_client_builder(config: &HttpConfig) -> Result<reqwest::blocking::ClientBuilder, NetworkError> {
+    let mut builder = reqwest::blocking::Client::builder();
+    if let Some(proxy) = proxy(config)? {
+        builder = builder.proxy(proxy);
+    }
+    for certificate in root_certificates(config)? {
+        builder = builder.add_root_certificate(certificate);
+    }
+    if let Some(user_agent) = &config.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    Ok(builder)
+}
+
+/// A blocking client with nothing but the `[http]` settings
+pub fn blocking_client(config: &HttpConfig) -> Result<reqwest::blocking::Client, NetworkError> {
+    blocking_client_builder(config)?
+        .build()
+        .map_err(|e| NetworkError::Client(error_chain(&e)))
+}
+
+/// Setting a proxy turns off reqwest's own environment lookup, so NO_PROXY is read
+/// here too
+fn proxy(config: &HttpConfig) -> Result<Option<Proxy>, NetworkError> {
+    let Some(url) = proxy_url(config) else {
+        return Ok(None);
+    };
+    let proxy = Proxy::all(&url).map_err(|e| NetworkError::InvalidProxy(url.clone(), error_chain(&e)))?;
+    Ok(Some(proxy.no_proxy(NoProxy::from_env())))
+}
+
+fn root_certificates(config: &HttpConfig) -> Result<Vec<Certificate>, NetworkError> {
+    config
+        .ca_certs
+        .iter()
+        .map(|path| {
+            let bytes = fs::read(path).map_err(|e| NetworkError::CertificateRead(path.clone(), e))?;
+            let is_pem = bytes.starts_with(b"-----BEGIN");
+            let certificate = if is_pem {
+                Certificate::from_pem(&bytes)
+            } else {
+                Certificate::from_der(&bytes)
+            };
+            certificate.map_err(|e| NetworkError::InvalidCertificate(path.clone(), error_chain(&e)))
+        })
+        .collect()
+}
+
+/// Describe a request to `url` that got no response
+///
+/// With a proxy, a failure to connect is blamed on the proxy unless the proxy was
+/// reached and refused the tunnel, or the tunnel was made and the server failed
+/// (e.g. its certificate isn't trusted).
+pub fn request_error(error: &reqwest::Error, url: &str, config: &HttpConfig) -> NetworkError {
+    let message = error_chain(error);
+    let url = url.to_string();
+    let Some(proxy) = proxy_url(config).filter(|_| error.is_connect()) else {
+        return NetworkError::Upstream { url, message };
+    };
+
+    if message.contains("tunnel error") {
+        if message.contains("failed to create underlying connection") {
+            NetworkError::ProxyConnect { proxy, url, message }
+        } else {
+            NetworkError::ProxyRefused { proxy, url, message }
+        }
+    } else if has_io_error(error) {
+        // Without a tunnel (plain http) the only connection made is to the proxy
+        NetworkError::ProxyConnect { proxy, url, message }
+    } else {
+        NetworkError::Upstream { url, message }
+    }
+}
+
+/// An error and its causes, e.g. "error sending request: tunnel error: unsuccessful"
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut parts = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let text = cause.to_string();
+        if !parts.last().is_some_and(|last| last.contains(&text)) {
+            parts.push(text);
+        }
+        source = cause.source();
+    }
+    parts.join(": ")
+}
+
+fn has_io_error(error: &reqwest::Error) -> bool {
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if cause.is::<std::io::Error>() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    fn with_proxy(proxy: &str) -> HttpConfig {
+        HttpConfig {
+            proxy: Some(proxy.to_string()),
+            ..HttpConfig::default()
+        }
+    }
+
+    /// A local port nothing listens on
+    fn closed_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    fn send(config: &HttpConfig, url: &str) -> NetworkError {
+        let client = blocking_client(config).unwrap();
+        let error = client.get(url).send().unwrap_err();
+        request_error(&error, url, config)
+    }
+
+    #[test]
+    fn test_proxy_precedence() {
+        let env = |var: &str| match var {
+            "https_proxy" => Some("http://lower:3128".to_string()),
+            "HTTP_PROXY" => Some("http://plain:3128".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(pick_proxy(&with_proxy("http://configured:8080"), env).as_deref(), Some("http://configured:8080"));
+        assert_eq!(pick_proxy(&HttpConfig::default(), env).as_deref(), Some("http://lower:3128"));
+        assert_eq!(pick_proxy(&HttpConfig::default(), |_| None), None);
+        assert_eq!(pick_proxy(&HttpConfig::default(), |_| Some(String::new())), None);
+    }
+
+    #[test]
+    fn test_unreachable_proxy_is_blamed() {
+        let config = with_proxy(&format!("http://127.0.0.1:{}", closed_port()));
+
+        let error = send(&config, "http://read.amazon.com/notebook");
+        assert!(matches!(error, NetworkError::ProxyConnect { .. }), "{}", error);
+        assert!(error.to_string().starts_with("Could not connect to proxy http://127.0.0.1:"), "{}", error);
+    }
+
+    #[test]
+    fn test_proxy_refusing_tunnel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+        });
+        let config = with_proxy(&format!("http://127.0.0.1:{}", port));
+
+        let error = send(&config, "https://read.amazon.com/notebook");
+        assert!(matches!(error, NetworkError::ProxyRefused { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_unreachable_server_without_proxy() {
+        let url = format!("http://127.0.0.1:{}/notebook", closed_port());
+        let config = HttpConfig {
+            // An empty proxy counts as none
+            proxy: Some(String::new()),
+            ..HttpConfig::default()
+        };
+
+        let error = send(&config, &url);
+        assert!(matches!(error, NetworkError::Upstream { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_bad_certificate_file() {
+        let path = std::env::temp_dir().join(format!("readingsync_ca_{}.pem", uuid::Uuid::new_v4()));
+        fs::write(&path, "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n").unwrap();
+        let config = HttpConfig {
+            ca_certs: vec![path.clone(), PathBuf::from("/nonexistent/ca.pem")],
+            ..HttpConfig::default()
+        };
+
+        let result = client_builder(&config);
+        let _ = fs::remove_file(&path);
+        assert!(matches!(result, Err(NetworkError::InvalidCertificate(p, _)) if p == path));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::config::HttpConfig;
+use crate::error::HypothesisError;
+use crate::model::{generate_article_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{AUTHORIZATION, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::thread;
+use std::time::Duration;
+
+const API_URL: &str = "https://api.hypothes.is/api";
+
+/// Annotations requested per page (the API maximum)
+const PAGE_SIZE: usize = 200;
+
+/// Attempts per request before giving up on rate limiting or server errors
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Deserialize)]
+struct Profile {
+    userid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    rows: Vec<Annotation>,
+}
+
+/// An annotation from `/api/search`
+#[derive(Debug, Deserialize)]
+pub struct Annotation {
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub uri: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub document: Document,
+    #[serde(default)]
+    pub target: Vec<Target>,
+    /// Set on replies to other annotations
+    #[serde(default)]
+    pub references: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Document {
+    #[serde(default)]
+    pub title: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    #[serde(default)]
+    pub selector: Vec<Selector>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Selector {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub exact: Option<String>,
+}
+
+impl Annotation {
+    /// The quoted text from the TextQuoteSelector, if any
+    fn quote(&self) -> Option<&str> {
+        self.target
+            .iter()
+            .flat_map(|t| &t.selector)
+            .find(|s| s.kind == "TextQuoteSelector")
+            .and_then(|s| s.exact.as_deref())
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+    }
+}
+
+/// Fetch all of a user's annotations and group them into one book per document
+///
+/// The user defaults to the token's owner. Pages are walked with `search_after`, and
+/// rate-limited or failed requests are retried with backoff.
+pub fn fetch(token: &str, user: Option<&str>, http: &HttpConfig) -> Result<Vec<Book>, HypothesisError> {
+    let client = crate::http::blocking_client(http)?;
+
+    let user = match user {
+        Some(user) => user.to_string(),
+        None => {
+            let response = get_with_retry(&client, http, &format!("{}/profile", API_URL), token, &[])?;
+            let profile: Profile = serde_json::from_str(&response.text()?)?;
+            profile.userid.ok_or(HypothesisError::NotAuthenticated)?
+        }
+    };
+
+    let mut annotations = Vec::new();
+    let mut search_after: Option<String> = None;
+
+    loop {
+        let limit = PAGE_SIZE.to_string();
+        let mut query = vec![
+            ("user", user.as_str()),
+            ("limit", limit.as_str()),
+            ("sort", "created"),
+            ("order", "asc"),
+        ];
+        if let Some(ref cursor) = search_after {
+            query.push(("search_after", cursor.as_str()));
+        }
+
+        let response = get_with_retry(&client, http, &format!("{}/search", API_URL), token, &query)?;
+        let page: SearchPage = serde_json::from_str(&response.text()?)?;
+        let count = page.rows.len();
+
+        search_after = page.rows.last().map(|a| a.created.to_rfc3339());
+        annotations.extend(page.rows);
+
+        if count < PAGE_SIZE {
+            break;
+        }
+    }
+
+    Ok(annotations_to_books(annotations))
+}
+
+/// GET with retries on 429 (honoring Retry-After) and 5xx responses
+fn get_with_retry(
+    client: &Client,
+    http: &HttpConfig,
+    url: &str,
+    token: &str,
+    query: &[(&str, &str)],
+) -> Result<Response, HypothesisError> {
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let response = client
+            .get(url)
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .query(query)
+            .send()
+            .map_err(|e| crate::http::request_error(&e, url, http))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(HypothesisError::NotAuthenticated);
+        }
+
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retryable || attempt >= MAX_ATTEMPTS {
+            return Err(HypothesisError::ApiError(status.to_string()));
+        }
+
+        let delay = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(1 << (attempt - 1)));
+
+        thread::sleep(delay);
+    }
+}
+
+/// Group annotations into one book per document URI
+///
+/// Replies and page notes without quoted text are skipped.
+pub fn annotations_to_books(annotations: Vec<Annotation>) -> Vec<Book> {
+    let mut books: Vec<Book> = Vec::new();
+    let mut index_by_uri: HashMap<String, usize> = HashMap::new();
+
+    for annotation in annotations {
+        if !annotation.references.is_empty() {
+            continue;
+        }
+        let Some(quote) = annotation.quote().map(String::from) else {
+            continue;
+        };
+
+        let index = *index_by_uri.entry(annotation.uri.clone()).or_insert_with(|| {
+            let title = annotation
+                .document
+                .title
+                .first()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| annotation.uri.clone());
+
+            books.push(Book {
+                id: generate_article_id(&annotation.uri),
+                title,
+                author: None,
+                sources: vec![Source::Hypothesis],
+                highlights: Vec::new(),
+                finished: None,
+                finished_at: None,
+                url: Some(annotation.uri.clone()),
+                isbn: None,
+                collections: Vec::new(),
+                unavailable_highlights: 0,
+                truncated: false,
+                original_title: None,
+                aliases: Vec::new(),
+                cover_url: None,
+                source_ids: BTreeMap::new(),
+                document_type: None,
+                progress: None,
+                last_opened_at: None,
+                language: None,
+                genre: None,
+            });
+            books.len() - 1
+        });
+
+        let note = annotation.text.trim();
+
+        books[index].highlights.push(Highlight {
+            id: annotation.id,
+            text: quote,
+            note: if note.is_empty() { None } else { Some(note.to_string()) },
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at: Some(annotation.created),
+            sources: vec![Source::Hypothesis],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    books
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH_PAGE: &str = r#"{
+        "total": 3,
+        "rows": [
+            {
+                "id": "a1",
+                "created": "2024-02-01T10:00:00.000000+00:00",
+                "uri": "https://example.com/post",
+                "text": "Good point",
+                "document": {"title": ["An Example Post"]},
+                "target": [{"source": "https://example.com/post", "selector": [
+                    {"type": "RangeSelector", "startContainer": "/p[1]"},
+                    {"type": "TextQuoteSelector", "exact": "The quoted passage", "prefix": "", "suffix": ""}
+                ]}]
+            },
+            {
+                "id": "a2",
+                "created": "2024-02-01T11:00:00.000000+00:00",
+                "uri": "https://example.com/post",
+                "text": "A reply",
+                "references": ["a1"],
+                "target": [{"source": "https://example.com/post"}]
+            },
+            {
+                "id": "a3",
+                "created": "2024-02-02T09:00:00.000000+00:00",
+                "uri": "https://example.com/post",
+                "text": "",
+                "document": {"title": ["An Example Post"]},
+                "target": [{"selector": [{"type": "TextQuoteSelector", "exact": "Another passage"}]}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_annotations_to_books() {
+        let page: SearchPage = serde_json::from_str(SEARCH_PAGE).unwrap();
+        let books = annotations_to_books(page.rows);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "An Example Post");
+        assert_eq!(book.url.as_deref(), Some("https://example.com/post"));
+        assert_eq!(book.sources, vec![Source::Hypothesis]);
+
+        // The reply is skipped
+        assert_eq!(book.highlights.len(), 2);
+        assert_eq!(book.highlights[0].text, "The quoted passage");
+        assert_eq!(book.highlights[0].note.as_deref(), Some("Good point"));
+        assert_eq!(book.highlights[1].note, None);
+    }
+}
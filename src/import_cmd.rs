@@ -0,0 +1,239 @@
+use crate::error::ImportCmdError;
+use crate::model::{generate_book_id, generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+/// A book as printed by an import command; only `title` is required
+#[derive(Debug, Deserialize)]
+struct CommandBook {
+    id: Option<String>,
+    title: Option<String>,
+    author: Option<String>,
+    sources: Option<Vec<Source>>,
+    #[serde(default)]
+    highlights: Vec<Value>,
+    finished: Option<bool>,
+    finished_at: Option<DateTime<Utc>>,
+    url: Option<String>,
+    isbn: Option<String>,
+}
+
+/// A highlight as printed by an import command; only `text` is required
+#[derive(Debug, Deserialize)]
+struct CommandHighlight {
+    id: Option<String>,
+    text: Option<String>,
+    note: Option<String>,
+    #[serde(default)]
+    location: Location,
+    created_at: Option<DateTime<Utc>>,
+    source: Option<Source>,
+    color: Option<String>,
+}
+
+/// Run an external command and import the books it prints as JSON
+///
+/// The command's stderr is passed through. Books without `sources` are attributed to a
+/// source named after the program.
+pub fn run(command: &[String]) -> Result<Vec<Book>, ImportCmdError> {
+    let (program, args) = command.split_first().ok_or(ImportCmdError::NoCommand)?;
+
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| ImportCmdError::SpawnFailed(program.clone(), e))?;
+
+    if !output.status.success() {
+        return Err(ImportCmdError::CommandFailed(program.clone(), output.status.to_string()));
+    }
+
+    let stdout = String::from_utf8(output.stdout).map_err(|_| ImportCmdError::InvalidUtf8)?;
+    let name = std::path::Path::new(program)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| program.clone());
+
+    parse_output(&stdout, &Source::Other(name))
+}
+
+/// Validate and convert an import command's output
+///
+/// Accepts an array of books or a library object with a `books` array. Missing book and
+/// highlight IDs are generated the same way as for built-in sources, highlights default
+/// to their book's first source, and unknown source names become `Source::Other`.
+/// Errors name the offending entry, e.g. `books[2].highlights[0]`.
+pub fn parse_output(output: &str, default_source: &Source) -> Result<Vec<Book>, ImportCmdError> {
+    let value: Value = serde_json::from_str(output).map_err(|e| ImportCmdError::InvalidJson(e.to_string()))?;
+
+    let entries = match value {
+        Value::Array(entries) => entries,
+        Value::Object(mut library) => match library.remove("books") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(invalid("books", "expected an array of books")),
+        },
+        _ => return Err(invalid("books", "expected an array of books")),
+    };
+
+    let mut books = Vec::with_capacity(entries.len());
+
+    for (book_index, entry) in entries.into_iter().enumerate() {
+        let path = format!("books[{}]", book_index);
+        let raw: CommandBook = serde_json::from_value(entry).map_err(|e| invalid(&path, &e.to_string()))?;
+
+        let title = raw
+            .title
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| invalid(&path, "missing title"))?;
+        let author = raw.author.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+
+        let id = raw
+            .id
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| generate_book_id(&title, author.as_deref()));
+        let sources = raw
+            .sources
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| vec![default_source.clone()]);
+
+        let mut highlights = Vec::with_capacity(raw.highlights.len());
+
+        for (highlight_index, entry) in raw.highlights.into_iter().enumerate() {
+            let path = format!("{}.highlights[{}]", path, highlight_index);
+            let raw: CommandHighlight =
+                serde_json::from_value(entry).map_err(|e| invalid(&path, &e.to_string()))?;
+
+            let text = raw
+                .text
+                .filter(|t| !t.trim().is_empty())
+                .ok_or_else(|| invalid(&path, "missing text"))?;
+
+            highlights.push(Highlight {
+                id: raw
+                    .id
+                    .filter(|id| !id.trim().is_empty())
+                    .unwrap_or_else(|| generate_highlight_id(&id, &text, raw.location.position.as_deref())),
+                text,
+                note: raw.note.filter(|n| !n.trim().is_empty()),
+                location: raw.location,
+                created_at: raw.created_at,
+                sources: vec![raw.source.unwrap_or_else(|| sources[0].clone())],
+                color: raw.color,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+
+        books.push(Book {
+            id,
+            title,
+            author,
+            sources,
+            highlights,
+            finished: raw.finished,
+            finished_at: raw.finished_at,
+            url: raw.url,
+            isbn: raw.isbn,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated: false,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids: BTreeMap::new(),
+            document_type: None,
+            progress: None,
+            last_opened_at: None,
+            language: None,
+            genre: None,
+        });
+    }
+
+    Ok(books)
+}
+
+fn invalid(path: &str, message: &str) -> ImportCmdError {
+    ImportCmdError::Invalid(path.to_string(), message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script() -> Source {
+        Source::Other("script".to_string())
+    }
+
+    #[test]
+    fn test_parse_output() {
+        let output = r#"[
+            {"title": "Walden", "author": "Henry David Thoreau", "highlights": [
+                {"text": "Simplify, simplify.", "location": {"position": "Page 12"}, "source": "my_ereader"},
+                {"text": "I went to the woods.", "id": "custom-id"}
+            ]},
+            {"id": "abc", "title": "Dune", "sources": ["kindle"]}
+        ]"#;
+
+        let books = parse_output(output, &script()).unwrap();
+
+        assert_eq!(books.len(), 2);
+        let walden = &books[0];
+        assert_eq!(walden.id, generate_book_id("Walden", Some("Henry David Thoreau")));
+        assert_eq!(walden.sources, vec![script()]);
+        assert_eq!(walden.highlights[0].sources, vec![Source::Other("my_ereader".to_string())]);
+        assert_eq!(walden.highlights[0].location.position.as_deref(), Some("Page 12"));
+        assert_eq!(walden.highlights[0].id.len(), 16);
+        assert_eq!(walden.highlights[1].id, "custom-id");
+        assert_eq!(walden.highlights[1].sources, vec![script()]);
+
+        assert_eq!(books[1].id, "abc");
+        assert_eq!(books[1].sources, vec![Source::Kindle]);
+    }
+
+    #[test]
+    fn test_accepts_library_object() {
+        let books = parse_output(r#"{"books": [{"title": "Walden"}]}"#, &script()).unwrap();
+        assert_eq!(books.len(), 1);
+    }
+
+    #[test]
+    fn test_errors_name_the_entry() {
+        let output = r#"[{"title": "Walden"}, {"title": "Dune", "highlights": [{"text": "ok"}, {"note": "no text"}]}]"#;
+
+        match parse_output(output, &script()) {
+            Err(ImportCmdError::Invalid(path, message)) => {
+                assert_eq!(path, "books[1].highlights[1]");
+                assert_eq!(message, "missing text");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match parse_output(r#"[{"title": 42}]"#, &script()) {
+            Err(ImportCmdError::Invalid(path, _)) => assert_eq!(path, "books[0]"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            r#"echo '[{"title": "Walden", "highlights": [{"text": "Simplify."}]}]'"#.to_string(),
+        ];
+
+        let books = run(&command).unwrap();
+        assert_eq!(books[0].sources, vec![Source::Other("sh".to_string())]);
+
+        let failing = vec!["sh".to_string(), "-c".to_string(), "exit 3".to_string()];
+        assert!(matches!(run(&failing), Err(ImportCmdError::CommandFailed(_, _))));
+    }
+}
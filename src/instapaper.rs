@@ -0,0 +1,129 @@
+use crate::error::InstapaperError;
+use crate::model::{generate_article_id, generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::DateTime;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Row of Instapaper's highlights CSV export
+#[derive(Debug, Deserialize)]
+struct InstapaperRow {
+    #[serde(rename = "URL")]
+    url: String,
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Selection", default)]
+    selection: String,
+    #[serde(rename = "Timestamp", default)]
+    timestamp: Option<i64>,
+}
+
+/// Parse Instapaper's highlights CSV into one book per article
+///
+/// Rows are grouped by URL (or title when the URL is blank). Rows without a selection
+/// are plain saves and are ignored.
+pub fn parse_csv(path: &Path) -> Result<Vec<Book>, InstapaperError> {
+    if !path.exists() {
+        return Err(InstapaperError::FileNotFound(path.to_path_buf()));
+    }
+
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut books: Vec<Book> = Vec::new();
+    let mut index_by_key: HashMap<String, usize> = HashMap::new();
+
+    for row in reader.deserialize() {
+        let row: InstapaperRow = row?;
+
+        let text = row.selection.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let url = row.url.trim();
+        let key = if url.is_empty() { row.title.trim() } else { url };
+
+        let index = *index_by_key.entry(key.to_string()).or_insert_with(|| {
+            let title = row.title.trim();
+            books.push(Book {
+                id: generate_article_id(key),
+                title: if title.is_empty() { url.to_string() } else { title.to_string() },
+                author: None,
+                sources: vec![Source::Instapaper],
+                highlights: Vec::new(),
+                finished: None,
+                finished_at: None,
+                url: if url.is_empty() { None } else { Some(url.to_string()) },
+                isbn: None,
+                collections: Vec::new(),
+                unavailable_highlights: 0,
+                truncated: false,
+                original_title: None,
+                aliases: Vec::new(),
+                cover_url: None,
+                source_ids: BTreeMap::new(),
+                document_type: None,
+                progress: None,
+                last_opened_at: None,
+                language: None,
+                genre: None,
+            });
+            books.len() - 1
+        });
+
+        let book = &mut books[index];
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, text, None),
+            text: text.to_string(),
+            note: None,
+            location: Location {
+                chapter: None,
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at: row.timestamp.and_then(|ts| DateTime::from_timestamp(ts, 0)),
+            sources: vec![Source::Instapaper],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    Ok(books)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_parse_csv_groups_by_url() {
+        let csv = "URL,Title,Selection,Folder,Timestamp\n\
+                   https://example.com/a,Article A,First passage,Unread,1700000000\n\
+                   https://example.com/b,Article B,,Archive,1700000100\n\
+                   https://example.com/a,Article A,\"Second, with comma\",Unread,1700000200\n";
+
+        let path = std::env::temp_dir().join(format!("readingsync_instapaper_{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, csv).unwrap();
+        let books = parse_csv(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "Article A");
+        assert_eq!(book.url.as_deref(), Some("https://example.com/a"));
+        assert_eq!(book.sources, vec![Source::Instapaper]);
+        assert_eq!(book.highlights.len(), 2);
+        assert_eq!(book.highlights[1].text, "Second, with comma");
+        assert_eq!(book.highlights[0].created_at, Utc.timestamp_opt(1700000000, 0).single());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let result = parse_csv(Path::new("/nonexistent/instapaper.csv"));
+        assert!(matches!(result, Err(InstapaperError::FileNotFound(_))));
+    }
+}
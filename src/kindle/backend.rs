@@ -0,0 +1,190 @@
+//! The browser the notebook scraper drives
+//!
+//! `browser.rs` only needs a handful of operations from a browser: open a page, run
+//! JavaScript in it, find and click an element and read the URL. `BrowserBackend` and
+//! `BrowserPage` put those behind traits, so the same scraping logic (selectors, waits,
+//! pagination) runs on Chrome over CDP (`ChromeBackend`, the default) or on any browser
+//! with a WebDriver server, e.g. Firefox with geckodriver (`kindle::webdriver`, behind
+//! the `webdriver` feature).
+
+use crate::kindle::browser::BrowserConfig;
+use crate::kindle::chrome;
+use crate::error::KindleError;
+use headless_chrome::{Browser, LaunchOptions, Tab};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Which browser automation protocol to use
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Launch Chrome, Chromium, Edge or Brave and drive it over CDP
+    #[default]
+    Chrome,
+    /// Drive the browser of an already running WebDriver server, e.g. geckodriver
+    WebDriver { url: String },
+}
+
+/// WebDriver server used when none is given; geckodriver's default port
+pub const DEFAULT_WEBDRIVER_URL: &str = "http://localhost:4444";
+
+/// Response bodies collected while a page listens for them
+pub type CapturedResponses = Arc<Mutex<Vec<String>>>;
+
+/// A running browser
+pub trait BrowserBackend: Send + Sync {
+    /// Open a page to work in
+    fn new_page(&self) -> anyhow::Result<Box<dyn BrowserPage>>;
+
+    /// Whether the browser still answers
+    fn is_alive(&self) -> bool;
+
+    /// Most pages that can scrape at the same time
+    fn max_pages(&self) -> usize;
+}
+
+/// A tab or window of a `BrowserBackend`
+pub trait BrowserPage: Send + Sync {
+    /// Load a URL and wait for the page to load
+    fn navigate(&self, url: &str) -> anyhow::Result<()>;
+
+    /// The page's current URL
+    fn url(&self) -> String;
+
+    /// Evaluate a JavaScript expression and return its value, awaiting it if it is a
+    /// promise; undefined is `Value::Null`
+    fn evaluate(&self, js: &str) -> anyhow::Result<serde_json::Value>;
+
+    /// Whether an element matches the CSS selector
+    fn has_element(&self, selector: &str) -> bool;
+
+    /// Scroll the first element matching the CSS selector into view
+    fn scroll_into_view(&self, selector: &str) -> anyhow::Result<()>;
+
+    /// Click the first element matching the CSS selector like a user would
+    fn click(&self, selector: &str) -> anyhow::Result<()>;
+
+    /// Collect the bodies of responses whose URL passes `matches`, until
+    /// `stop_capturing`
+    ///
+    /// Returns None when the backend can't see the page's network traffic (WebDriver),
+    /// so the caller reads the page instead.
+    fn capture_responses(
+        &self,
+        _matches: Box<dyn Fn(&str) -> bool + Send + Sync>,
+    ) -> anyhow::Result<Option<CapturedResponses>> {
+        Ok(None)
+    }
+
+    /// Stop collecting responses
+    fn stop_capturing(&self) {}
+}
+
+/// Name of the response handler collecting captured responses
+const CAPTURE_HANDLER: &str = "readingsync-capture";
+
+/// Chrome driven over CDP by headless_chrome
+pub struct ChromeBackend {
+    browser: Browser,
+}
+
+impl ChromeBackend {
+    /// Launch the configured or first found Chrome-compatible browser
+    pub fn launch(config: &BrowserConfig) -> Result<Self, KindleError> {
+        let chrome_path = chrome::find_browser(config.chrome_path.as_deref())?;
+        let mut launch_options = LaunchOptions::default_builder();
+
+        launch_options
+            .path(Some(chrome_path.clone()))
+            .headless(config.headless)
+            .window_size(Some(config.window_size));
+
+        // Set user data directory for session persistence
+        if let Some(ref user_data_dir) = config.user_data_dir {
+            launch_options.user_data_dir(Some(PathBuf::from(user_data_dir)));
+        }
+
+        let launch_options = launch_options
+            .build()
+            .map_err(|e| KindleError::ParseError(format!("Failed to build launch options: {}", e)))?;
+
+        let browser = Browser::new(launch_options)
+            .map_err(|e| KindleError::ParseError(format!("Failed to launch browser {}: {}", chrome_path.display(), e)))?;
+
+        Ok(Self { browser })
+    }
+}
+
+impl BrowserBackend for ChromeBackend {
+    fn new_page(&self) -> anyhow::Result<Box<dyn BrowserPage>> {
+        Ok(Box::new(ChromePage(self.browser.new_tab()?)))
+    }
+
+    fn is_alive(&self) -> bool {
+        // Asking also counts as activity, which keeps an idle connection open
+        self.browser.get_version().is_ok()
+    }
+
+    fn max_pages(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// A Chrome tab
+struct ChromePage(Arc<Tab>);
+
+impl BrowserPage for ChromePage {
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        self.0.navigate_to(url)?;
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        self.0.get_url()
+    }
+
+    fn evaluate(&self, js: &str) -> anyhow::Result<serde_json::Value> {
+        Ok(self.0.evaluate(js, true)?.value.unwrap_or_default())
+    }
+
+    fn has_element(&self, selector: &str) -> bool {
+        self.0.find_element(selector).is_ok()
+    }
+
+    fn scroll_into_view(&self, selector: &str) -> anyhow::Result<()> {
+        self.0.find_element(selector)?.scroll_into_view()?;
+        Ok(())
+    }
+
+    fn click(&self, selector: &str) -> anyhow::Result<()> {
+        self.0.find_element(selector)?.click()?;
+        Ok(())
+    }
+
+    fn capture_responses(
+        &self,
+        matches: Box<dyn Fn(&str) -> bool + Send + Sync>,
+    ) -> anyhow::Result<Option<CapturedResponses>> {
+        let captured: CapturedResponses = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&captured);
+
+        self.0.register_response_handling(
+            CAPTURE_HANDLER,
+            Box::new(move |event, fetch_body| {
+                if !matches(&event.response.url) {
+                    return;
+                }
+                if let Ok(body) = fetch_body() {
+                    if !body.base_64_encoded {
+                        sink.lock().unwrap().push(body.body);
+                    }
+                }
+            }),
+        )?;
+
+        Ok(Some(captured))
+    }
+
+    fn stop_capturing(&self) {
+        let _ = self.0.deregister_response_handling(CAPTURE_HANDLER);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::KindleError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One book as saved by Bookcision
+#[derive(Debug, Deserialize)]
+struct BookcisionBook {
+    title: String,
+    #[serde(default)]
+    authors: Option<String>,
+    #[serde(default)]
+    asin: Option<String>,
+    #[serde(default)]
+    highlights: Vec<BookcisionHighlight>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BookcisionHighlight {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    note: Option<String>,
+    #[serde(default)]
+    is_note_only: bool,
+    #[serde(default)]
+    location: Option<BookcisionLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookcisionLocation {
+    value: Option<serde_json::Value>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// Import Bookcision JSON exports from a file or a directory of files
+///
+/// Bookcision saves Kindle highlights, so books are tagged with the Kindle source and
+/// merge with a fresh sync of the same book.
+pub fn parse_export(path: &Path) -> Result<Vec<Book>, KindleError> {
+    if !path.exists() {
+        return Err(KindleError::BookcisionNotFound(path.to_path_buf()));
+    }
+
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|e| KindleError::LocalReadError(path.to_path_buf(), e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("json")))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut books: Vec<Book> = Vec::new();
+
+    for file in files {
+        let content = fs::read_to_string(&file).map_err(|e| KindleError::LocalReadError(file.clone(), e))?;
+        let book = parse_book(&content).map_err(|e| KindleError::BookcisionParseError(file.clone(), e))?;
+
+        match books.iter_mut().find(|b| b.id == book.id) {
+            Some(existing) => existing.highlights.extend(book.highlights),
+            None => books.push(book),
+        }
+    }
+
+    Ok(books)
+}
+
+/// Convert the contents of one Bookcision JSON file
+pub fn parse_book(content: &str) -> Result<Book, serde_json::Error> {
+    let export: BookcisionBook = serde_json::from_str(content)?;
+
+    let author = export.authors.map(|a| a.trim().to_string()).filter(|a| !a.is_empty());
+    let mut book = Book::new(export.title.trim().to_string(), author);
+    book.sources = vec![Source::Kindle];
+    if let Some(asin) = export.asin.filter(|a| !a.is_empty()) {
+        book.source_ids.insert("asin".to_string(), asin);
+    }
+
+    for highlight in export.highlights {
+        let note = highlight.note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+
+        // Note-only entries have no highlighted text, so the note becomes the text
+        let (text, note) = if highlight.is_note_only || highlight.text.trim().is_empty() {
+            match note {
+                Some(note) => (note, None),
+                None => continue,
+            }
+        } else {
+            (highlight.text.trim().to_string(), note)
+        };
+
+        let position = highlight.location.as_ref().and_then(format_position);
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, &text, position.as_deref()),
+            text,
+            note,
+            location: Location {
+                chapter: None,
+                position,
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    Ok(book)
+}
+
+/// "Location 1234" or "Page 56", defaulting to a Kindle location
+fn format_position(location: &BookcisionLocation) -> Option<String> {
+    let value = match location.value.as_ref()? {
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) if !s.trim().is_empty() => s.trim().to_string(),
+        _ => return None,
+    };
+
+    match location.kind.as_deref().map(str::to_lowercase).as_deref() {
+        Some("page") => Some(format!("Page {}", value)),
+        _ => Some(format!("Location {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::clippings::parse_clippings_content;
+    use crate::merge::merge_books;
+
+    const EXPORT: &str = r#"{
+        "asin": "B00B7NPRY8",
+        "title": "Dune",
+        "authors": "Frank Herbert",
+        "highlights": [
+            {"text": "I must not fear.", "isNoteOnly": false, "location": {"url": "kindle://book?action=open&asin=B00B7NPRY8&location=100", "value": 100}, "note": "Litany"},
+            {"text": "Fear is the mind-killer.", "isNoteOnly": false, "location": {"value": 12, "type": "page"}, "note": null},
+            {"text": "", "isNoteOnly": true, "location": {"value": 200}, "note": "Remember this"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_book() {
+        let book = parse_book(EXPORT).unwrap();
+
+        assert_eq!(book.title, "Dune");
+        assert_eq!(book.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(book.sources, vec![Source::Kindle]);
+        assert_eq!(book.asin(), Some("B00B7NPRY8"));
+        assert_eq!(book.highlights.len(), 3);
+
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("Location 100"));
+        assert_eq!(book.highlights[0].note.as_deref(), Some("Litany"));
+        assert_eq!(book.highlights[1].location.position.as_deref(), Some("Page 12"));
+        assert_eq!(book.highlights[2].text, "Remember this");
+        assert_eq!(book.highlights[2].note, None);
+    }
+
+    #[test]
+    fn test_collapses_with_fresh_sync() {
+        let dir = std::env::temp_dir().join(format!("readingsync_bookcision_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Dune.json"), EXPORT).unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let bookcision = parse_export(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let kindle = parse_clippings_content(
+            "Dune (Frank Herbert)\n\
+             - Your Highlight on Location 100-101 | Added on Monday, January 1, 2024\n\
+             \n\
+             I must not  fear.\n\
+             ==========\n",
+        )
+        .unwrap();
+
+        let merged = merge_books(vec![kindle, bookcision]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sources, vec![Source::Kindle]);
+        assert_eq!(merged[0].highlights.len(), 3);
+    }
+
+    #[test]
+    fn test_missing_export() {
+        let result = parse_export(Path::new("/nonexistent/bookcision.json"));
+        assert!(matches!(result, Err(KindleError::BookcisionNotFound(_))));
+    }
+}
@@ -1,14 +1,63 @@
 use crate::error::KindleError;
-use crate::model::{generate_book_id, Book, Highlight, Location, Source};
-use headless_chrome::{Browser, LaunchOptions, Tab};
-use std::sync::Arc;
+use crate::kindle::progress::{LineProgress, ScrapeObserver};
+use crate::kindle::concurrent::{scrape_concurrently, Event, Job, RateLimiter, MAX_CONCURRENCY, MIN_BOOK_INTERVAL};
+use crate::kindle::retry::{BookScraper, FailedBook, RetryPolicy};
+use crate::kindle::backend::{Backend, BrowserBackend, BrowserPage, CapturedResponses, ChromeBackend};
+use crate::kindle::checkpoint::Checkpoint;
+use crate::kindle::clippings::normalize_title;
+use crate::kindle::debug_dump::{BookCapture, DebugDump};
+use crate::kindle::network::{self, RawAnnotation};
+use crate::kindle::parse::{parse_notebook_date, to_highlight};
+use crate::kindle::report::{BookReport, BookStatus, LoginStatus, ScrapeReport};
+use crate::kindle::sync_state::SyncState;
+use crate::kindle::wait::{SystemClock, Wait};
+use crate::merge;
+use crate::model::{
+    generate_book_id, Book, DocumentType, Highlight, Source,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Each region's Amazon store domain; `read.` plus the domain serves its notebook
+pub const REGION_DOMAINS: [(&str, &str); 12] = [
+    ("us", "amazon.com"),
+    ("uk", "amazon.co.uk"),
+    ("de", "amazon.de"),
+    ("fr", "amazon.fr"),
+    ("es", "amazon.es"),
+    ("it", "amazon.it"),
+    ("jp", "amazon.co.jp"),
+    ("ca", "amazon.ca"),
+    ("au", "amazon.com.au"),
+    ("in", "amazon.in"),
+    ("br", "amazon.com.br"),
+    ("mx", "amazon.com.mx"),
+];
+
+/// The region whose store `host` belongs to, e.g. "uk" for `read.amazon.co.uk`
+///
+/// Accepts cookie hosts with a leading dot. Matching is on whole labels, so
+/// `amazon.com.au` is Australia and not the US.
+pub fn region_for_host(host: &str) -> Option<&'static str> {
+    let host = host.trim_start_matches('.').to_lowercase();
+    REGION_DOMAINS
+        .iter()
+        .find(|(_, domain)| host == *domain || host.ends_with(&format!(".{}", domain)))
+        .map(|(code, _)| *code)
+}
 
 /// Amazon region configuration for browser-based scraping
 #[derive(Debug, Clone)]
 pub struct AmazonRegion {
     pub code: String,
+    /// Store domain, e.g. `amazon.co.uk`
+    pub domain: String,
     pub notebook_url: String,
     pub signin_url: String,
 }
@@ -59,12 +108,20 @@ impl AmazonRegion {
             _ => return Err(KindleError::InvalidRegion(code.to_string())),
         };
 
+        let domain = notebook_url.trim_start_matches("https://read.").trim_end_matches("/notebook");
         Ok(Self {
             code: code.to_lowercase(),
+            domain: domain.to_string(),
             notebook_url: notebook_url.to_string(),
             signin_url: signin_url.to_string(),
         })
     }
+
+    /// The region a page of Amazon's is on, e.g. the notebook after a redirect
+    pub fn from_url(url: &str) -> Option<Self> {
+        let host = url::Url::parse(url).ok()?.host_str()?.to_string();
+        Self::from_code(region_for_host(&host)?).ok()
+    }
 }
 
 /// Configuration for the browser scraper
@@ -73,10 +130,71 @@ pub struct BrowserConfig {
     pub headless: bool,
     /// Amazon region
     pub region: AmazonRegion,
+    /// Whether `region` is only a starting point: when Amazon redirects the notebook
+    /// to another region's, carry on there instead of failing with `WrongRegion`
+    pub detect_region: bool,
     /// Path to store user data (for session persistence)
     pub user_data_dir: Option<String>,
     /// Timeout for page loads in seconds
     pub timeout_secs: u64,
+    /// How long to wait for a book's highlights to appear after clicking it
+    pub per_book_timeout_secs: u64,
+    /// Pause between books, to avoid rate limiting
+    pub inter_book_delay_ms: u64,
+    /// Most pages of highlights read per book, as a safety limit
+    pub max_pages: usize,
+    /// Browser window width and height
+    pub window_size: (u32, u32),
+    /// Retries for books that fail to scrape
+    pub retry: RetryPolicy,
+    /// Whether someone can log in in the browser window; when false (always when
+    /// headless) a sign-in page fails with `LoginRequired` instead of waiting
+    pub interactive: bool,
+    /// Browser binary to launch instead of searching for one
+    pub chrome_path: Option<PathBuf>,
+    /// How highlights are read from the notebook page
+    pub engine: ScrapeEngine,
+    /// Tabs scraping books at once; capped at `concurrent::MAX_CONCURRENCY`
+    pub concurrency: usize,
+    /// Which part of the library to list: books, personal documents or both
+    pub content_type: ContentType,
+    /// The browser to drive: Chrome over CDP, or a WebDriver server
+    pub backend: Backend,
+    /// Save every book's raw annotation pages here (see `debug_dump`); without it only
+    /// books whose annotations couldn't be read are saved, to the data dir
+    pub debug_dump: Option<PathBuf>,
+}
+
+/// The notebook's content filter, listing books, personal documents or both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentType {
+    #[default]
+    Books,
+    /// Personal documents, e.g. sent with Send to Kindle
+    Docs,
+    All,
+}
+
+impl ContentType {
+    /// The filter settings to list, in order
+    fn document_types(self) -> &'static [DocumentType] {
+        match self {
+            ContentType::Books => &[DocumentType::Book],
+            ContentType::Docs => &[DocumentType::PersonalDocument],
+            ContentType::All => &[DocumentType::Book, DocumentType::PersonalDocument],
+        }
+    }
+}
+
+/// How highlights are read from the notebook page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrapeEngine {
+    /// Parse the annotation responses the page fetches (see `kindle::network`),
+    /// falling back to `Dom` for a book when none is seen
+    #[default]
+    Network,
+    /// Query the rendered page and click through its pagination
+    Dom,
 }
 
 impl Default for BrowserConfig {
@@ -84,49 +202,279 @@ impl Default for BrowserConfig {
         Self {
             headless: false, // Show browser by default for login
             region: AmazonRegion::from_code("us").unwrap(),
+            detect_region: false,
             user_data_dir: None,
             timeout_secs: 30,
+            per_book_timeout_secs: 10,
+            inter_book_delay_ms: 500,
+            max_pages: 100,
+            window_size: (1280, 900),
+            retry: RetryPolicy::default(),
+            interactive: true,
+            chrome_path: None,
+            engine: ScrapeEngine::default(),
+            concurrency: 1,
+            content_type: ContentType::default(),
+            backend: Backend::default(),
+            debug_dump: None,
+        }
+    }
+}
+
+/// A book in the notebook page's library list
+#[derive(Debug, Clone, Serialize)]
+pub struct NotebookEntry {
+    pub asin: String,
+    pub title: String,
+    pub author: Option<String>,
+    /// "Last annotated" date as shown in the list
+    pub last_annotated: Option<String>,
+    /// Cover thumbnail in the list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_url: Option<String>,
+    /// Listed under Books or under Personal Documents
+    pub document_type: DocumentType,
+    /// The title as listed, when it was cleaned up (file-name-like personal documents)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_title: Option<String>,
+}
+
+impl NotebookEntry {
+    /// Tag the entry with the filter it was listed under
+    ///
+    /// Personal documents are often titled after their file, so their titles get the
+    /// same clean-up as sideloaded books in My Clippings.txt.
+    fn classify(&mut self, document_type: DocumentType) {
+        self.document_type = document_type;
+        if document_type == DocumentType::PersonalDocument {
+            let title = normalize_title(&self.title);
+            if title != self.title {
+                self.original_title = Some(std::mem::replace(&mut self.title, title));
+            }
         }
     }
 }
 
+/// Optional state a scrape reads and updates
+#[derive(Default)]
+pub struct ScrapeOptions<'a> {
+    /// Skip books whose "last annotated" date is unchanged since the last sync
+    pub state: Option<&'a mut SyncState>,
+    /// Save each book as it is scraped, and skip books already saved by an interrupted run
+    pub checkpoint: Option<&'a mut Checkpoint>,
+    /// Only scrape some of the books in the library
+    pub selection: BookSelection,
+    /// Once set, no further books are started, e.g. on SIGTERM; the books done so far
+    /// are returned as usual
+    pub stop: Option<&'a AtomicBool>,
+}
+
+/// Which books of the notebook's library list to scrape
+///
+/// Books are first matched against `patterns`, then `skip` and `limit` slice the result.
+#[derive(Debug, Clone, Default)]
+pub struct BookSelection {
+    /// Case-insensitive title substrings or ASINs; empty selects every book
+    pub patterns: Vec<String>,
+    pub skip: usize,
+    pub limit: Option<usize>,
+}
+
+impl BookSelection {
+    fn matches(&self, entry: &NotebookEntry) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| pattern_matches(p, entry))
+    }
+
+    /// The selected entries, and the patterns that matched no book
+    fn apply(&self, entries: Vec<NotebookEntry>) -> (Vec<NotebookEntry>, Vec<String>) {
+        let unmatched = self
+            .patterns
+            .iter()
+            .filter(|p| !entries.iter().any(|e| pattern_matches(p, e)))
+            .cloned()
+            .collect();
+
+        let selected = entries
+            .into_iter()
+            .filter(|e| self.matches(e))
+            .skip(self.skip)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        (selected, unmatched)
+    }
+}
+
+fn pattern_matches(pattern: &str, entry: &NotebookEntry) -> bool {
+    entry.asin.eq_ignore_ascii_case(pattern) || entry.title.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+/// Result of a scrape
+#[derive(Debug, Default)]
+pub struct ScrapeOutcome {
+    pub books: Vec<Book>,
+    /// Books reused from the sync state because they were unchanged
+    pub skipped: usize,
+    /// Books taken from the checkpoint of an interrupted run
+    pub resumed: usize,
+    /// Books that still failed after every retry
+    pub failed: Vec<FailedBook>,
+    /// Selection patterns that matched no book in the library
+    pub unmatched: Vec<String>,
+    /// Per-book record of the run
+    pub report: Option<ScrapeReport>,
+}
+
 /// Scrape Kindle highlights using a headless browser
 pub struct KindleBrowserScraper {
-    browser: Browser,
+    browser: Box<dyn BrowserBackend>,
     config: BrowserConfig,
+    /// The region Amazon redirected to, when `config.detect_region` followed it
+    detected_region: Mutex<Option<AmazonRegion>>,
+    dump: DebugDump,
 }
 
-impl KindleBrowserScraper {
-    /// Create a new browser scraper
-    pub fn new(config: BrowserConfig) -> Result<Self, KindleError> {
-        let mut launch_options = LaunchOptions::default_builder();
+/// How long an interactive run waits for the user to log in
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Chrome profile holding the Amazon session when no `user_data_dir` is configured
+pub fn default_profile_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("readingsync")
+        .join("chrome_profile")
+}
+
+/// One look at a tab on its way to the notebook
+enum SessionCheck {
+    /// The notebook's library list has loaded
+    Ready,
+    /// Amazon wants someone to sign in or pass a challenge
+    Signin(SigninPage),
+    /// Neither yet, e.g. mid-redirect
+    Loading,
+}
+
+/// Whether a URL is part of Amazon's sign-in flow (`/ap/signin`, `/ap/mfa`, ...)
+fn is_signin_url(url: &str) -> bool {
+    url.contains("/ap/") || url.contains("signin")
+}
+
+/// A page Amazon shows instead of the notebook until someone acts on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SigninPage {
+    Login,
+    /// One-time password or other two-step verification
+    TwoFactor,
+    Captcha,
+    /// "Switch accounts" chooser
+    AccountSwitcher,
+}
+
+/// Reports which challenge markers are on the page, as a JSON list
+const SIGNIN_MARKERS_JS: &str = r#"
+    (function() {
+        const markers = [];
+        if (document.querySelector('#captchacharacters, form[action*="validateCaptcha"], img[src*="captcha"]')) markers.push('captcha');
+        if (document.querySelector('#auth-mfa-otpcode, input[name="otpCode"], input[name="code"]')) markers.push('otp');
+        if (document.querySelector('.cvf-account-switcher, [data-name="switch_account_request"], #ap-account-switcher-container')) markers.push('switcher');
+        return JSON.stringify(markers);
+    })()
+"#;
+
+impl SigninPage {
+    /// Classify a page from its URL and the markers `SIGNIN_MARKERS_JS` found on it
+    fn detect(url: &str, markers: &[String]) -> Option<Self> {
+        let has = |marker: &str| markers.iter().any(|m| m == marker);
+
+        if has("captcha") || url.contains("validateCaptcha") {
+            Some(Self::Captcha)
+        } else if has("switcher") || url.contains("/ap/accountswitcher") || url.contains("switch_account") {
+            Some(Self::AccountSwitcher)
+        } else if has("otp") || url.contains("/ap/mfa") || url.contains("/ap/cvf") {
+            Some(Self::TwoFactor)
+        } else if is_signin_url(url) {
+            Some(Self::Login)
+        } else {
+            None
+        }
+    }
 
-        launch_options
-            .headless(config.headless)
-            .window_size(Some((1280, 900)));
+    /// What the user needs to do in the browser window
+    fn instruction(self) -> &'static str {
+        match self {
+            Self::Login => "Please log in to your Amazon account in the browser window",
+            Self::TwoFactor => "Enter the verification code Amazon sent you in the browser",
+            Self::Captcha => "Solve the CAPTCHA Amazon is showing in the browser window",
+            Self::AccountSwitcher => "Choose your Amazon account in the browser window",
+        }
+    }
 
-        // Set user data directory for session persistence
-        if let Some(ref user_data_dir) = config.user_data_dir {
-            launch_options.user_data_dir(Some(std::path::PathBuf::from(user_data_dir)));
+    /// The error for a run that can't act on this page
+    fn error(self, had_session: bool) -> KindleError {
+        match self {
+            Self::Login if had_session => KindleError::SessionExpired,
+            Self::Login => KindleError::LoginRequired,
+            Self::TwoFactor => KindleError::TwoFactorRequired,
+            Self::Captcha => KindleError::CaptchaRequired,
+            Self::AccountSwitcher => KindleError::AccountChoiceRequired,
         }
+    }
+}
+
+/// Scrapes books one at a time in an open notebook tab
+struct TabScraper<'a> {
+    scraper: &'a KindleBrowserScraper,
+    tab: &'a dyn BrowserPage,
+}
+
+impl BookScraper for TabScraper<'_> {
+    fn scrape_book(&self, asin: &str, title: &str, author: Option<&str>) -> Result<Book, KindleError> {
+        self.scraper.scrape_book_highlights(self.tab, asin, title, author)
+    }
 
-        let launch_options = launch_options
-            .build()
-            .map_err(|e| KindleError::ParseError(format!("Failed to build launch options: {}", e)))?;
+    fn reset(&self) -> Result<(), KindleError> {
+        self.scraper.reload_notebook(self.tab)
+    }
+}
 
-        let browser = Browser::new(launch_options)
-            .map_err(|e| KindleError::ParseError(format!("Failed to launch browser: {}", e)))?;
+impl KindleBrowserScraper {
+    /// Create a new browser scraper
+    pub fn new(config: BrowserConfig) -> Result<Self, KindleError> {
+        let browser: Box<dyn BrowserBackend> = match &config.backend {
+            Backend::Chrome => Box::new(ChromeBackend::launch(&config)?),
+            #[cfg(feature = "webdriver")]
+            Backend::WebDriver { url } => Box::new(crate::kindle::webdriver::WebDriverBackend::connect(url, &config)?),
+            #[cfg(not(feature = "webdriver"))]
+            Backend::WebDriver { .. } => return Err(KindleError::BackendUnavailable("webdriver")),
+        };
 
-        Ok(Self { browser, config })
+        let dump = DebugDump::new(config.debug_dump.clone());
+        Ok(Self {
+            browser,
+            config,
+            detected_region: Mutex::new(None),
+            dump,
+        })
     }
 
     /// Create with default user data directory for session persistence
+    ///
+    /// The default directory is a Chrome profile. A WebDriver browser starts from a
+    /// throwaway profile unless `user_data_dir` names one of its own, so without it the
+    /// login is lost with the session: a run that can't log in interactively fails with
+    /// `SessionNotPersisted`, and a visible one warns that the login won't be kept.
     pub fn with_session_persistence(mut config: BrowserConfig) -> Result<Self, KindleError> {
         if config.user_data_dir.is_none() {
-            let data_dir = dirs::data_local_dir()
-                .unwrap_or_else(|| std::path::PathBuf::from("."))
-                .join("readingsync")
-                .join("chrome_profile");
+            if config.backend != Backend::Chrome {
+                if !config.interactive || config.headless {
+                    return Err(KindleError::SessionNotPersisted);
+                }
+                eprintln!("Note: the WebDriver browser uses a temporary profile, so this login won't be kept; pass --user-data-dir with a Firefox profile to keep it");
+                return Self::new(config);
+            }
+
+            let data_dir = default_profile_dir();
 
             // Create directory if it doesn't exist
             std::fs::create_dir_all(&data_dir)
@@ -138,115 +486,521 @@ impl KindleBrowserScraper {
         Self::new(config)
     }
 
+    /// Whether the browser still answers, e.g. between the syncs of `kindle --watch`
+    ///
+    /// Asking also counts as activity, which keeps an idle connection open.
+    pub fn is_alive(&self) -> bool {
+        self.browser.is_alive()
+    }
+
     /// Get a new tab
-    fn new_tab(&self) -> Result<Arc<Tab>, KindleError> {
+    fn new_tab(&self) -> Result<Box<dyn BrowserPage>, KindleError> {
         self.browser
-            .new_tab()
+            .new_page()
             .map_err(|e| KindleError::ParseError(format!("Failed to create tab: {}", e)))
     }
 
-    /// Wait for user to complete login
-    pub fn wait_for_login(&self, tab: &Tab) -> Result<(), KindleError> {
+    /// Whether someone can complete a login in the browser window
+    fn is_interactive(&self) -> bool {
+        self.config.interactive && !self.config.headless
+    }
+
+    /// Open the notebook and wait until it is usable, logging in first if needed
+    ///
+    /// Interactively this polls until the user has signed in in the browser window and
+    /// the notebook has loaded, for up to `LOGIN_TIMEOUT`. Otherwise a sign-in page
+    /// still showing after `timeout_secs` fails with `KindleError::LoginRequired`.
+    /// Returns whether the existing session was reused or a sign-in was completed.
+    pub fn wait_for_login(&self, tab: &dyn BrowserPage) -> Result<LoginStatus, KindleError> {
         eprintln!("Navigating to Amazon Kindle notebook...");
+        self.open_notebook(tab)?;
 
-        tab.navigate_to(&self.config.region.notebook_url)
-            .map_err(|e| KindleError::ParseError(format!("Failed to navigate: {}", e)))?;
+        let page_timeout = Duration::from_secs(self.config.timeout_secs);
+        let start = std::time::Instant::now();
+        let mut prompted: Option<SigninPage> = None;
+        let mut saw_signin = false;
+
+        loop {
+            let signin = match self.session_check(tab) {
+                SessionCheck::Ready => {
+                    if prompted.is_some() {
+                        eprintln!("Successfully logged in!");
+                    }
+                    self.check_region(tab)?;
+                    return Ok(if saw_signin { LoginStatus::Established } else { LoginStatus::Reused });
+                }
+                SessionCheck::Signin(page) => Some(page),
+                SessionCheck::Loading => None,
+            };
+            saw_signin |= signin.is_some();
+
+            if let Some(page) = signin {
+                if self.is_interactive() {
+                    // Prompt again when Amazon moves on, e.g. from the password to 2FA
+                    if prompted != Some(page) {
+                        print_banner(&[page.instruction(), "The sync continues by itself once the notebook loads"]);
+                        prompted = Some(page);
+                    }
+                } else if page != SigninPage::Login {
+                    // A challenge won't go away by itself, so don't wait it out
+                    return Err(page.error(self.had_session()));
+                }
+            }
+
+            // Someone logging in gets far longer than a page load
+            let deadline = if prompted.is_some() { LOGIN_TIMEOUT } else { page_timeout };
+            if start.elapsed() > deadline {
+                return Err(match signin {
+                    Some(page) => page.error(self.had_session()),
+                    None => KindleError::ParseError("Timeout waiting for notebook page".to_string()),
+                });
+            }
 
-        // Wait for page to load
-        thread::sleep(Duration::from_secs(2));
-
-        // Check if we need to log in
-        let url = tab.get_url();
-        if url.contains("signin") || url.contains("ap/signin") {
-            eprintln!("\n╔════════════════════════════════════════════════════════════╗");
-            eprintln!("║  Please log in to your Amazon account in the browser window ║");
-            eprintln!("║  Press Enter here once you've completed login...            ║");
-            eprintln!("╚════════════════════════════════════════════════════════════╝\n");
-
-            // Wait for user input
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input)
-                .map_err(|e| KindleError::ParseError(format!("Failed to read input: {}", e)))?;
+            thread::sleep(Duration::from_millis(500));
         }
+    }
 
-        // Wait for notebook page to load
-        self.wait_for_notebook_page(tab)?;
+    /// Open the notebook in a new tab and wait until it is usable, logging in if needed
+    ///
+    /// Used on its own by `kindle login` and `kindle status`; whether a sign-in page
+    /// waits for the user or fails follows `BrowserConfig.interactive` and `headless`.
+    pub fn login(&self) -> Result<LoginStatus, KindleError> {
+        let tab = self.new_tab()?;
+        self.wait_for_login(tab.as_ref())
+    }
 
-        eprintln!("Successfully logged in!");
+    /// Navigate the tab to the region's notebook page
+    fn open_notebook(&self, tab: &dyn BrowserPage) -> Result<(), KindleError> {
+        tab.navigate(&self.region().notebook_url)
+            .map_err(|e| KindleError::ParseError(format!("Failed to navigate: {}", e)))?;
         Ok(())
     }
 
-    /// Wait for the notebook page to be fully loaded
-    fn wait_for_notebook_page(&self, tab: &Tab) -> Result<(), KindleError> {
-        let timeout = Duration::from_secs(self.config.timeout_secs);
-        let start = std::time::Instant::now();
+    /// The region being scraped: the configured one, or the one Amazon redirected to
+    pub fn region(&self) -> AmazonRegion {
+        self.detected_region
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| self.config.region.clone())
+    }
 
-        loop {
-            if start.elapsed() > timeout {
-                return Err(KindleError::ParseError("Timeout waiting for notebook page".to_string()));
-            }
+    /// Catch Amazon sending the loaded notebook to another region's store, which it
+    /// does when the account is registered there
+    ///
+    /// With `detect_region` the scrape follows it; otherwise it's an error naming the
+    /// region to pass instead.
+    fn check_region(&self, tab: &dyn BrowserPage) -> Result<(), KindleError> {
+        let current = self.region();
+        let Some(landed) = AmazonRegion::from_url(&tab.url()).filter(|r| r.domain != current.domain) else {
+            return Ok(());
+        };
+        if !self.config.detect_region {
+            return Err(KindleError::WrongRegion {
+                expected: current.domain,
+                detected: landed.domain,
+                code: landed.code,
+            });
+        }
+        eprintln!("Your Amazon account is on {}; syncing its notebook", landed.domain);
+        *self.detected_region.lock().unwrap() = Some(landed);
+        Ok(())
+    }
 
-            let url = tab.get_url();
-            if url.contains("notebook") && !url.contains("signin") {
-                // Try to find the book list element
-                if tab.find_element(".kp-notebook-library-each-book").is_ok() {
-                    return Ok(());
-                }
-                // Also check for empty library message
-                if tab.find_element("#kp-notebook-library").is_ok() {
-                    return Ok(());
-                }
-            }
+    /// Where the tab stands on its way to the notebook
+    fn session_check(&self, tab: &dyn BrowserPage) -> SessionCheck {
+        match self.signin_page(tab) {
+            Some(page) => SessionCheck::Signin(page),
+            None if self.notebook_ready(tab) => SessionCheck::Ready,
+            None => SessionCheck::Loading,
+        }
+    }
 
-            thread::sleep(Duration::from_millis(500));
+    /// The sign-in or challenge page the tab is showing, if any
+    fn signin_page(&self, tab: &dyn BrowserPage) -> Option<SigninPage> {
+        let markers: Vec<String> = tab
+            .evaluate(SIGNIN_MARKERS_JS)
+            .ok()
+            .and_then(|v| v.as_str().and_then(|s| serde_json::from_str(s).ok()))
+            .unwrap_or_default();
+        SigninPage::detect(&tab.url(), &markers)
+    }
+
+    /// Whether the browser profile holds cookies from an earlier session, so a login
+    /// page means that session expired rather than that nobody has logged in yet
+    fn had_session(&self) -> bool {
+        self.config
+            .user_data_dir
+            .as_ref()
+            .is_some_and(|dir| {
+                // Chrome's cookie store, or Firefox's for a WebDriver profile
+                let dir = Path::new(dir);
+                dir.join("Default").join("Cookies").exists() || dir.join("cookies.sqlite").exists()
+            })
+    }
+
+    /// Whether the notebook page has loaded its library list
+    fn notebook_ready(&self, tab: &dyn BrowserPage) -> bool {
+        let url = tab.url();
+        url.contains("notebook")
+            && !is_signin_url(&url)
+            && (tab.has_element(".kp-notebook-library-each-book")
+                || tab.has_element("#kp-notebook-library"))
+    }
+
+    /// Wait for the notebook page to be fully loaded
+    ///
+    /// Landing on a sign-in or challenge page instead fails with its specific error.
+    fn wait_for_notebook_page(&self, tab: &dyn BrowserPage) -> Result<(), KindleError> {
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        if Wait::up_to(timeout).until(&SystemClock, || self.notebook_ready(tab).then_some(())).is_some() {
+            return Ok(());
         }
+
+        Err(match self.signin_page(tab) {
+            Some(page) => page.error(self.had_session()),
+            None => KindleError::ParseError("Timeout waiting for notebook page".to_string()),
+        })
     }
 
-    /// Scrape all books and highlights
+    /// Scrape all books and highlights, reporting progress line by line on stderr
     pub fn scrape_all(&self) -> Result<Vec<Book>, KindleError> {
+        self.scrape_all_with(&mut LineProgress)
+    }
+
+    /// Scrape all books and highlights, reporting progress to `observer`
+    pub fn scrape_all_with(&self, observer: &mut dyn ScrapeObserver) -> Result<Vec<Book>, KindleError> {
+        Ok(self.scrape_with(ScrapeOptions::default(), observer)?.books)
+    }
+
+    /// Log in and read the library list without opening any book
+    ///
+    /// Returns the selected books and the `--only` patterns that matched none.
+    pub fn list_books(&self, selection: &BookSelection) -> Result<(Vec<NotebookEntry>, Vec<String>), KindleError> {
+        let tab = self.new_tab()?;
+        self.wait_for_login(tab.as_ref())?;
+        Ok(selection.apply(self.get_book_list(tab.as_ref())?))
+    }
+
+    /// Scrape books, reusing and updating the state and checkpoint in `options`
+    ///
+    /// Only books in the selection are considered. Books saved in the checkpoint, then
+    /// books whose "last annotated" date matches the sync state, are taken from there
+    /// instead of being opened. Every book scraped is recorded in both.
+    pub fn scrape_with(
+        &self,
+        options: ScrapeOptions<'_>,
+        observer: &mut dyn ScrapeObserver,
+    ) -> Result<ScrapeOutcome, KindleError> {
+        let ScrapeOptions {
+            mut state,
+            mut checkpoint,
+            selection,
+            stop,
+        } = options;
+
         let tab = self.new_tab()?;
 
         // Ensure we're logged in
-        self.wait_for_login(&tab)?;
+        let mut report = ScrapeReport::new(self.wait_for_login(tab.as_ref())?);
 
         // Get list of books
-        eprintln!("Fetching book list...");
-        let book_asins = self.get_book_list(&tab)?;
-        eprintln!("Found {} books", book_asins.len());
+        observer.status("Fetching book list...");
+        let library = self.get_book_list(tab.as_ref())?;
+        if library.is_empty() {
+            observer.status("No Kindle books found in the notebook; nothing to sync");
+        }
+        let (entries, unmatched) = selection.apply(library);
+        let total = entries.len();
+        observer.book_list_fetched(total);
+
+        let mut books: Vec<Option<Book>> = vec![None; total];
+        let mut reused = Vec::new();
+        let mut outcome = ScrapeOutcome {
+            unmatched,
+            ..Default::default()
+        };
 
-        let mut books = Vec::new();
+        let previous_highlights: Vec<Option<usize>> = entries
+            .iter()
+            .map(|entry| {
+                state
+                    .as_deref()
+                    .and_then(|state| state.books.get(&entry.asin))
+                    .map(|previous| previous.book.highlights.len())
+            })
+            .collect();
+        let book_report = |i: usize, status, book: Option<&Book>, attempts, seconds, error| BookReport {
+            asin: entries[i].asin.clone(),
+            title: entries[i].title.clone(),
+            status,
+            highlights: book.map_or(0, |b| b.highlights.len()),
+            previous_highlights: previous_highlights[i],
+            attempts,
+            seconds,
+            truncated: book.is_some_and(|b| b.truncated),
+            error,
+        };
+        let mut book_reports: Vec<Option<BookReport>> = vec![None; total];
+
+        // Resumed and unchanged books need no browser; the rest become jobs
+        let mut jobs = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(book) = checkpoint.as_deref().and_then(|checkpoint| checkpoint.get(&entry.asin)) {
+                book_reports[i] = Some(book_report(i, BookStatus::Resumed, Some(book), 0, 0.0, None));
+                observer.book_resumed(i, total, &entry.title);
+                if let Some(state) = state.as_deref_mut() {
+                    state.record(&entry.asin, entry.last_annotated.as_deref(), book);
+                }
+                reused.push(book.clone());
+                outcome.resumed += 1;
+                continue;
+            }
 
-        for (i, (asin, title, author)) in book_asins.iter().enumerate() {
-            eprintln!("  [{}/{}] Scraping: {}", i + 1, book_asins.len(), title);
+            let previous = state
+                .as_deref()
+                .and_then(|state| state.unchanged(&entry.asin, entry.last_annotated.as_deref()));
+            if let Some(book) = previous {
+                book_reports[i] = Some(book_report(i, BookStatus::Skipped, Some(book), 0, 0.0, None));
+                observer.book_skipped(i, total, &entry.title);
+                reused.push(book.clone());
+                outcome.skipped += 1;
+                continue;
+            }
 
-            match self.scrape_book_highlights(&tab, asin, title, author.as_deref()) {
-                Ok(book) => {
-                    eprintln!("    → {} highlights", book.highlights.len());
-                    books.push(book);
-                }
+            jobs.push(Job {
+                index: i,
+                asin: entry.asin.clone(),
+                title: entry.title.clone(),
+                author: entry.author.clone(),
+            });
+        }
+
+        let mut tabs = vec![tab];
+        let wanted = self.config.concurrency.clamp(1, MAX_CONCURRENCY).min(self.browser.max_pages()).min(jobs.len());
+        while tabs.len() < wanted {
+            match self.new_tab().and_then(|tab| self.reload_notebook(tab.as_ref()).map(|_| tab)) {
+                Ok(tab) => tabs.push(tab),
                 Err(e) => {
-                    eprintln!("    → Error: {}", e);
+                    observer.status(&format!("Could not open another tab ({}); scraping with {}", e, tabs.len()));
+                    break;
                 }
             }
-
-            // Small delay between books to avoid rate limiting
-            thread::sleep(Duration::from_millis(500));
         }
+        let workers: Vec<TabScraper> = tabs.iter().map(|tab| TabScraper { scraper: self, tab: tab.as_ref() }).collect();
+
+        let mut started_at = vec![None; total];
+        scrape_concurrently(
+            &workers,
+            &jobs,
+            &self.config.retry,
+            &RateLimiter::new(MIN_BOOK_INTERVAL),
+            // Small delay between a tab's books to avoid rate limiting
+            Duration::from_millis(self.config.inter_book_delay_ms),
+            stop,
+            &mut |event| {
+                match event {
+                    Event::Started { index } => {
+                        started_at[index] = Some(Instant::now());
+                        observer.book_started(index, total, &entries[index].title);
+                    }
+                    Event::Retrying { index, retry, error, wait } => {
+                        observer.book_retrying(index, total, &entries[index].title, retry, &error, wait);
+                    }
+                    Event::Finished { index, attempts, result } => {
+                        let entry = &entries[index];
+                        let seconds = started_at[index].map_or(0.0, |start: Instant| start.elapsed().as_secs_f64());
+                        match *result {
+                            Ok(mut book) => {
+                                book.cover_url = entry.cover_url.clone();
+                                book.document_type = Some(entry.document_type);
+                                book.original_title = entry.original_title.clone();
+                                book_reports[index] = Some(book_report(index, BookStatus::Scraped, Some(&book), attempts, seconds, None));
+                                observer.book_finished(index, total, &book);
+                                if let Some(state) = state.as_deref_mut() {
+                                    state.record(&entry.asin, entry.last_annotated.as_deref(), &book);
+                                }
+                                if let Some(checkpoint) = checkpoint.as_deref_mut() {
+                                    checkpoint.record(&entry.asin, &book)?;
+                                }
+                                books[index] = Some(book);
+                            }
+                            Err(failed) => {
+                                book_reports[index] = Some(book_report(index, BookStatus::Failed, None, failed.attempts, seconds, Some(failed.error.clone())));
+                                observer.book_failed(index, total, &failed);
+                                outcome.failed.push(failed);
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
 
-        Ok(books)
+        // Tabs finish books out of order; keep the library list's order
+        let books: Vec<Book> = books.into_iter().flatten().collect();
+        outcome.failed.sort_by_key(|failed| entries.iter().position(|entry| entry.asin == failed.asin));
+        report.books = book_reports.into_iter().flatten().collect();
+
+        outcome.books = merge::merge_books(vec![books, reused]);
+        report.finished_at = Utc::now();
+        outcome.report = Some(report);
+
+        observer.finished(&outcome.books);
+        Ok(outcome)
+    }
+
+    /// Reload the notebook page, e.g. after a book left the DOM in a bad state
+    fn reload_notebook(&self, tab: &dyn BrowserPage) -> Result<(), KindleError> {
+        self.open_notebook(tab)?;
+        self.wait_for_notebook_page(tab)
     }
 
     /// Get list of books from the notebook page
-    fn get_book_list(&self, tab: &Tab) -> Result<Vec<(String, String, Option<String>)>, KindleError> {
+    ///
+    /// The library list loads more books as it is scrolled (and may drop ones scrolled
+    /// past), so it is scrolled to the bottom and read until no new books appear. An
+    /// empty library gives an empty list.
+    fn get_book_list(&self, tab: &dyn BrowserPage) -> Result<Vec<NotebookEntry>, KindleError> {
         // Navigate to notebook if not already there
-        let url = tab.get_url();
+        let url = tab.url();
         if !url.contains("notebook") {
-            tab.navigate_to(&self.config.region.notebook_url)
-                .map_err(|e| KindleError::ParseError(format!("Failed to navigate: {}", e)))?;
+            self.open_notebook(tab)?;
             self.wait_for_notebook_page(tab)?;
         }
 
-        // Execute JavaScript to extract book data
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        for &document_type in self.config.content_type.document_types() {
+            // Without a filter control the default view is taken to be the books
+            if !self.select_content_filter(tab, document_type)? && document_type != DocumentType::Book {
+                return Err(KindleError::ParseError(
+                    "Could not find the notebook's Personal Documents filter".to_string(),
+                ));
+            }
+
+            let listed = collect_library(
+                || {
+                    let visible = self.visible_books(tab)?;
+                    self.scroll_library(tab);
+                    Ok(visible)
+                },
+                &|| {
+                    // Move on as soon as the scroll has rendered more books
+                    let before = self.rendered_books(tab);
+                    Wait::up_to(LIBRARY_SETTLE).until(&SystemClock, || (self.rendered_books(tab) != before).then_some(()));
+                },
+            )?;
+
+            // An item listed under both filters keeps the first
+            for mut entry in listed {
+                if seen.insert(entry.asin.clone()) {
+                    entry.classify(document_type);
+                    entries.push(entry);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Switch the library list to books or personal documents
+    ///
+    /// Returns false when the page has no such filter. After a switch, waits for the
+    /// list to re-render.
+    fn select_content_filter(&self, tab: &dyn BrowserPage, document_type: DocumentType) -> Result<bool, KindleError> {
+        let labels = match document_type {
+            DocumentType::Book => r#"["books"]"#,
+            DocumentType::PersonalDocument => r#"["personal documents", "documents", "docs"]"#,
+        };
+        let js = format!(
+            r#"
+            (function(labels) {{
+                const matches = el => labels.includes(el.textContent.trim().toLowerCase());
+                // A <select>, or the Amazon dropdown that stands in for one
+                for (const select of document.querySelectorAll('select')) {{
+                    const option = Array.from(select.options).find(matches);
+                    if (option) {{
+                        if (select.value === option.value) return 'selected';
+                        select.value = option.value;
+                        select.dispatchEvent(new Event('change', {{bubbles: true}}));
+                        return 'changed';
+                    }}
+                }}
+                const item = Array.from(document.querySelectorAll('.a-dropdown-item a, [role="option"], [role="tab"]')).find(matches);
+                if (item) {{
+                    item.click();
+                    return 'changed';
+                }}
+                return 'missing';
+            }})({})
+        "#,
+            labels
+        );
+
+        let before = self.first_listed_book(tab);
+        let result = tab.evaluate(&js)
+            .map_err(|e| KindleError::ParseError(format!("Failed to switch the library filter: {}", e)))?
+            .as_str()
+            .map(String::from)
+            .unwrap_or_default();
+
+        if result == "changed" {
+            // The list is replaced, possibly by an empty one
+            let wait = Wait::up_to(LIBRARY_FILTER_TIMEOUT);
+            wait.until(&SystemClock, || (self.first_listed_book(tab) != before).then_some(()));
+            wait.until_stable(&SystemClock, || self.rendered_books(tab));
+        }
+        Ok(result != "missing")
+    }
+
+    /// ASIN of the first book in the library list, if any
+    fn first_listed_book(&self, tab: &dyn BrowserPage) -> Option<String> {
+        tab.evaluate("(function() { const el = document.querySelector('.kp-notebook-library-each-book'); return el ? el.id : null; })()")
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+    }
+
+    /// Make sure the book is in the rendered library list, switching the content
+    /// filter if it is listed under another one, e.g. after a reload
+    fn show_book(&self, tab: &dyn BrowserPage, asin: &str) -> Result<(), KindleError> {
+        let selector = format!("#{}", asin);
+        if tab.has_element(&selector) {
+            return Ok(());
+        }
+        for &document_type in self.config.content_type.document_types() {
+            if self.select_content_filter(tab, document_type)? && tab.has_element(&selector) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// How many books the library list has rendered
+    fn rendered_books(&self, tab: &dyn BrowserPage) -> Option<u64> {
+        tab.evaluate("document.querySelectorAll('.kp-notebook-library-each-book').length")
+            .ok()
+            .and_then(|v| v.as_u64())
+    }
+
+    /// Scroll the library list to its end so it loads the next books
+    fn scroll_library(&self, tab: &dyn BrowserPage) {
+        let js = r#"
+            (function() {
+                const books = document.querySelectorAll('.kp-notebook-library-each-book');
+                if (books.length > 0) books[books.length - 1].scrollIntoView({block: 'end'});
+                // The scrolling element is the library or one of its ancestors
+                for (let el = document.querySelector('#kp-notebook-library'); el; el = el.parentElement) {
+                    if (el.scrollHeight > el.clientHeight) el.scrollTop = el.scrollHeight;
+                }
+                return books.length;
+            })()
+        "#;
+        let _ = tab.evaluate(js);
+    }
+
+    /// The books currently rendered in the library list
+    fn visible_books(&self, tab: &dyn BrowserPage) -> Result<Vec<NotebookEntry>, KindleError> {
         let js = r#"
             (function() {
                 const books = [];
@@ -255,6 +1009,8 @@ impl KindleBrowserScraper {
                     const asin = el.id || '';
                     const titleEl = el.querySelector('h2');
                     const authorEl = el.querySelector('p.kp-notebook-searchable');
+                    const dateEl = el.querySelector('[id^="kp-notebook-annotated-date"]');
+                    const coverEl = el.querySelector('img');
 
                     const title = titleEl ? titleEl.textContent.trim() : '';
                     let author = authorEl ? authorEl.textContent.trim() : '';
@@ -264,20 +1020,24 @@ impl KindleBrowserScraper {
                         author = author.substring(3).trim();
                     }
 
+                    let lastAnnotated = dateEl ? (dateEl.value || dateEl.textContent || '').trim() : '';
+                    // Absolute URL; books without a cover show no image or a placeholder data URI
+                    const cover = coverEl && coverEl.src && coverEl.src.startsWith('http') ? coverEl.src : null;
+
                     if (asin && title) {
-                        books.push({asin: asin, title: title, author: author || null});
+                        books.push({asin: asin, title: title, author: author || null, lastAnnotated: lastAnnotated || null, cover: cover});
                     }
                 });
                 return JSON.stringify(books);
             })()
         "#;
 
-        let result = tab.evaluate(js, true)
+        let result = tab.evaluate(js)
             .map_err(|e| KindleError::ParseError(format!("Failed to execute JS: {}", e)))?;
 
         let json_str = result
-            .value
-            .and_then(|v| v.as_str().map(String::from))
+            .as_str()
+            .map(String::from)
             .ok_or_else(|| KindleError::ParseError("Failed to get book list".to_string()))?;
 
         let book_data: Vec<serde_json::Value> = serde_json::from_str(&json_str)
@@ -289,7 +1049,17 @@ impl KindleBrowserScraper {
                 let asin = v.get("asin")?.as_str()?.to_string();
                 let title = v.get("title")?.as_str()?.to_string();
                 let author = v.get("author").and_then(|a| a.as_str()).map(String::from);
-                Some((asin, title, author))
+                let last_annotated = v.get("lastAnnotated").and_then(|d| d.as_str()).map(String::from);
+                let cover_url = v.get("cover").and_then(|c| c.as_str()).map(String::from);
+                Some(NotebookEntry {
+                    asin,
+                    title,
+                    author,
+                    last_annotated,
+                    cover_url,
+                    document_type: DocumentType::Book,
+                    original_title: None,
+                })
             })
             .collect();
 
@@ -299,77 +1069,195 @@ impl KindleBrowserScraper {
     /// Scrape highlights for a specific book
     fn scrape_book_highlights(
         &self,
-        tab: &Tab,
+        tab: &dyn BrowserPage,
         asin: &str,
         title: &str,
         author: Option<&str>,
     ) -> Result<Book, KindleError> {
-        // Get the current first highlight text before clicking (to detect change)
-        let get_first_highlight_js = r#"
-            (function() {
-                const el = document.querySelector('#highlight');
-                return el ? el.textContent.trim().substring(0, 50) : '';
-            })()
-        "#;
+        // What the annotation pane shows before clicking, to tell when it has changed
+        let before = self.annotation_pane(tab).unwrap_or_default();
 
-        let old_highlight = tab.evaluate(get_first_highlight_js, true)
-            .ok()
-            .and_then(|r| r.value)
-            .and_then(|v| v.as_str().map(String::from))
-            .unwrap_or_default();
+        // Listen for the annotation responses before the click triggers them
+        let captured = match self.config.engine {
+            ScrapeEngine::Network => self.capture_annotations(tab, asin)?,
+            ScrapeEngine::Dom => None,
+        };
 
         // Click on the book in the sidebar using native click
+        self.show_book(tab, asin)?;
         let selector = format!("#{}", asin);
-        let element = tab.find_element(&selector)
-            .map_err(|e| KindleError::ParseError(format!("Could not find book element {}: {}", asin, e)))?;
+        if !tab.has_element(&selector) {
+            return Err(KindleError::ParseError(format!("Could not find book element {}", asin)));
+        }
 
-        // Scroll into view first
-        element.scroll_into_view()
+        // Scroll into view first, and let the list stop moving before clicking
+        tab.scroll_into_view(&selector)
             .map_err(|e| KindleError::ParseError(format!("Failed to scroll: {}", e)))?;
+        Wait::up_to(SCROLL_TIMEOUT).until_stable(&SystemClock, || self.element_top(tab, asin));
 
-        thread::sleep(Duration::from_millis(200));
-
-        // Click using headless_chrome native click
-        element.click()
+        // Click natively rather than from JavaScript
+        tab.click(&selector)
             .map_err(|e| KindleError::ParseError(format!("Failed to click: {}", e)))?;
 
-        // Wait for content to change (either different highlight or loading state)
-        let timeout = Duration::from_secs(10);
-        let start = std::time::Instant::now();
+        // Wait for the pane to show this book and finish loading; on timeout read
+        // whatever is there, as before
+        self.wait_for_annotations(tab, |pane| pane.shows_book(asin, &before));
 
-        loop {
-            if start.elapsed() > timeout {
-                break;
+        let id = generate_book_id(title, author);
+        let annotated_at = self.book_annotated_date(tab);
+
+        let mut capture = BookCapture::default();
+        let from_network = match captured {
+            Some(captured) => self.network_highlights(tab, asin, &id, &captured, &mut capture)?,
+            None => None,
+        };
+        let (mut all_highlights, truncated) = match from_network {
+            Some(scraped) => scraped,
+            None => self.dom_highlights(tab, &id, &mut capture)?,
+        };
+
+        // Most highlights carry no date of their own; the book's annotated date is the
+        // closest we have
+        for highlight in &mut all_highlights {
+            if highlight.created_at.is_none() {
+                highlight.created_at = annotated_at;
             }
+        }
 
-            let new_highlight = tab.evaluate(get_first_highlight_js, true)
-                .ok()
-                .and_then(|r| r.value)
-                .and_then(|v| v.as_str().map(String::from))
-                .unwrap_or_default();
-
-            // Content changed or cleared (loading)
-            if new_highlight != old_highlight || new_highlight.is_empty() {
-                // If empty, wait a bit more for content to load
-                if new_highlight.is_empty() {
-                    thread::sleep(Duration::from_millis(500));
-                }
+        // The notebook renders highlights in no particular order across pages
+        all_highlights.sort_by_cached_key(|h| h.location.reading_order());
+        self.dump.finish_book(asin, title, &capture, &all_highlights);
+
+        Ok(Book {
+            id,
+            title: title.to_string(),
+            author: author.map(String::from),
+            sources: vec![Source::Kindle],
+            highlights: all_highlights,
+            finished: None,
+            finished_at: None,
+            url: None,
+            isbn: None,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids: BTreeMap::from([("asin".to_string(), asin.to_string())]),
+            document_type: None,
+            progress: None,
+            last_opened_at: None,
+            language: None,
+            genre: None,
+        })
+    }
+
+    /// Collect the bodies of this book's annotation responses from now on
+    ///
+    /// None when the backend can't see network responses; the book is then read from
+    /// the page.
+    fn capture_annotations(&self, tab: &dyn BrowserPage, asin: &str) -> Result<Option<CapturedResponses>, KindleError> {
+        let asin = asin.to_string();
+        tab.capture_responses(Box::new(move |url| network::is_annotations_url(url, &asin)))
+            .map_err(|e| KindleError::ParseError(format!("Failed to listen for responses: {}", e)))
+    }
+
+    /// Highlights from the captured annotation responses, fetching further pages by token,
+    /// and whether any page said the publisher limits the highlights shown
+    ///
+    /// Returns None when no response arrived in time, so the caller can fall back to
+    /// reading the page.
+    fn network_highlights(
+        &self,
+        tab: &dyn BrowserPage,
+        asin: &str,
+        book_id: &str,
+        captured: &Mutex<Vec<String>>,
+        capture: &mut BookCapture,
+    ) -> Result<Option<(Vec<Highlight>, bool)>, KindleError> {
+        let timeout = Duration::from_secs(self.config.per_book_timeout_secs);
+        let first = Wait::up_to(timeout).until(&SystemClock, || captured.lock().unwrap().first().cloned());
+        tab.stop_capturing();
+
+        let Some(first) = first else {
+            return Ok(None);
+        };
+
+        let mut page = network::parse_annotations(&first);
+        capture.html(&first, page.has_container);
+        let mut highlights = Vec::new();
+        let mut limited = false;
+        let mut pages = 1;
+
+        loop {
+            highlights.extend(page.annotations.iter().map(|raw| to_highlight(book_id, raw)));
+            limited |= page.limited;
+
+            let Some(token) = page.next_token.as_deref() else {
+                break;
+            };
+            pages += 1;
+            if pages > self.config.max_pages {
                 break;
             }
 
-            thread::sleep(Duration::from_millis(100));
+            let body = self.fetch_annotations(tab, asin, token, page.content_limit_state.as_deref())?;
+            page = network::parse_annotations(&body);
+            capture.html(&body, page.has_container);
         }
 
-        // Extra delay to ensure DOM is fully updated
-        thread::sleep(Duration::from_secs(1));
+        Ok(Some((highlights, limited)))
+    }
+
+    /// Request a page of annotations from inside the notebook page, with its session
+    fn fetch_annotations(
+        &self,
+        tab: &dyn BrowserPage,
+        asin: &str,
+        token: &str,
+        content_limit_state: Option<&str>,
+    ) -> Result<String, KindleError> {
+        let params = serde_json::json!({
+            "asin": asin,
+            "token": token,
+            "contentLimitState": content_limit_state.unwrap_or_default(),
+        });
+        let js = format!(
+            r#"
+            (async function() {{
+                const params = new URLSearchParams({});
+                const response = await fetch('/notebook?' + params, {{credentials: 'include'}});
+                if (!response.ok) throw new Error('HTTP ' + response.status);
+                return await response.text();
+            }})()
+        "#,
+            params
+        );
+
+        tab.evaluate(&js)
+            .map_err(|e| KindleError::ParseError(format!("Failed to fetch annotations: {}", e)))?
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| KindleError::ParseError("Empty annotations response".to_string()))
+    }
 
-        // Collect all highlights with pagination
+    /// Highlights read from the rendered page, clicking through its pagination, and
+    /// whether the page said the publisher limits the highlights shown
+    fn dom_highlights(
+        &self,
+        tab: &dyn BrowserPage,
+        book_id: &str,
+        capture: &mut BookCapture,
+    ) -> Result<(Vec<Highlight>, bool), KindleError> {
         let mut all_highlights = Vec::new();
+        let mut limited = false;
         let mut page = 1;
 
         loop {
-            let (highlights, has_more) = self.extract_highlights_from_page(tab)?;
+            let (highlights, has_more, page_limited) = self.extract_highlights_from_page(tab, book_id, capture)?;
             all_highlights.extend(highlights);
+            limited |= page_limited;
 
             if !has_more {
                 break;
@@ -377,32 +1265,43 @@ impl KindleBrowserScraper {
 
             // Click "next page" and wait
             page += 1;
-            if page > 100 {
+            if page > self.config.max_pages {
                 // Safety limit
                 break;
             }
 
+            let before = self.annotation_pane(tab).unwrap_or_default();
             if !self.click_next_page(tab)? {
                 break;
             }
-
-            thread::sleep(Duration::from_secs(1));
+            self.wait_for_annotations(tab, |pane| pane.first != before.first);
         }
 
-        let id = generate_book_id(title, author);
-        Ok(Book {
-            id,
-            title: title.to_string(),
-            author: author.map(String::from),
-            sources: vec![Source::Kindle],
-            highlights: all_highlights,
-            finished: None,
-            finished_at: None,
-        })
+        Ok((all_highlights, limited))
+    }
+
+    /// The "last annotated" date in the header of the open book, if shown
+    fn book_annotated_date(&self, tab: &dyn BrowserPage) -> Option<DateTime<Utc>> {
+        let js = r#"
+            (function() {
+                const el = document.querySelector('#kp-notebook-annotated-date, .kp-notebook-annotated-date');
+                return el ? (el.value || el.textContent || '').trim() : '';
+            })()
+        "#;
+
+        tab.evaluate(js)
+            .ok()
+            .and_then(|v| v.as_str().and_then(parse_notebook_date))
     }
 
-    /// Extract highlights from the current page
-    fn extract_highlights_from_page(&self, tab: &Tab) -> Result<(Vec<Highlight>, bool), KindleError> {
+    /// Extract highlights from the current page, whether another page follows, and
+    /// whether the page says the publisher limits the highlights shown
+    fn extract_highlights_from_page(
+        &self,
+        tab: &dyn BrowserPage,
+        book_id: &str,
+        capture: &mut BookCapture,
+    ) -> Result<(Vec<Highlight>, bool, bool), KindleError> {
         let js = r#"
             (function() {
                 const highlights = [];
@@ -415,6 +1314,7 @@ impl KindleBrowserScraper {
                     const highlightEl = container.querySelector('#highlight');
                     const noteEl = container.querySelector('#note');
                     const locationEl = container.querySelector('#kp-annotation-location');
+                    const dateEl = container.querySelector('[id^="kp-annotation-date"], .kp-notebook-annotation-date');
 
                     if (highlightEl) {
                         const text = highlightEl.textContent.trim();
@@ -423,6 +1323,7 @@ impl KindleBrowserScraper {
 
                             const note = noteEl ? noteEl.textContent.trim() : null;
                             const location = locationEl ? locationEl.textContent.trim() : null;
+                            const date = dateEl ? (dateEl.value || dateEl.textContent || '').trim() : null;
 
                             // Try to get highlight color
                             let color = null;
@@ -437,6 +1338,7 @@ impl KindleBrowserScraper {
                                 text: text,
                                 note: note || null,
                                 location: location || null,
+                                date: date || null,
                                 color: color
                             });
                         }
@@ -447,54 +1349,87 @@ impl KindleBrowserScraper {
                 const nextPageEl = document.querySelector('.kp-notebook-annotations-next-page-start');
                 const hasMore = nextPageEl && nextPageEl.value && nextPageEl.value.length > 0;
 
-                return JSON.stringify({highlights: highlights, hasMore: hasMore});
+                const container = containers.length > 0 || !!document.querySelector('#kp-notebook-annotations');
+
+                return JSON.stringify({highlights: highlights, hasMore: hasMore, container: container});
             })()
         "#;
 
-        let result = tab.evaluate(js, true)
+        let result = tab.evaluate(js)
             .map_err(|e| KindleError::ParseError(format!("Failed to execute JS: {}", e)))?;
 
         let json_str = result
-            .value
-            .and_then(|v| v.as_str().map(String::from))
+            .as_str()
+            .map(String::from)
             .ok_or_else(|| KindleError::ParseError("Failed to get highlights".to_string()))?;
 
         let data: serde_json::Value = serde_json::from_str(&json_str)
             .map_err(|e| KindleError::ParseError(format!("Failed to parse highlights: {}", e)))?;
 
         let has_more = data.get("hasMore").and_then(|v| v.as_bool()).unwrap_or(false);
+        capture.json(&json_str, data.get("container").and_then(|v| v.as_bool()).unwrap_or(false));
 
         let highlights = data
             .get("highlights")
             .and_then(|v| v.as_array())
             .map(|arr| {
                 arr.iter()
-                    .filter_map(|v| {
-                        let text = v.get("text")?.as_str()?.to_string();
-                        let note = v.get("note").and_then(|n| n.as_str()).map(String::from);
-                        let position = v.get("location").and_then(|l| l.as_str()).map(String::from);
-
-                        Some(Highlight {
-                            id: uuid::Uuid::new_v4().to_string(),
-                            text,
-                            note,
-                            location: Location {
-                                chapter: None,
-                                position,
-                            },
-                            created_at: None,
-                            source: Source::Kindle,
-                        })
-                    })
+                    .filter_map(|v| RawAnnotation::deserialize(v).ok())
+                    .map(|raw| to_highlight(book_id, &raw))
                     .collect()
             })
             .unwrap_or_default();
 
-        Ok((highlights, has_more))
+        Ok((highlights, has_more, self.shows_limit_notice(tab)))
+    }
+
+    /// Whether the page shows a banner saying the publisher limits the highlights shown
+    fn shows_limit_notice(&self, tab: &dyn BrowserPage) -> bool {
+        let js = format!(
+            "Array.from(document.querySelectorAll({})).map(el => el.innerText).join('\\n')",
+            serde_json::to_string(network::NOTICE_SELECTOR).unwrap_or_default()
+        );
+
+        tab.evaluate(&js)
+            .ok()
+            .and_then(|v| v.as_str().map(network::mentions_annotation_limit))
+            .unwrap_or(false)
     }
 
     /// Click the "next page" button for pagination
-    fn click_next_page(&self, tab: &Tab) -> Result<bool, KindleError> {
+    /// The state of the annotation pane, or None if it couldn't be read
+    fn annotation_pane(&self, tab: &dyn BrowserPage) -> Option<AnnotationPane> {
+        tab.evaluate(ANNOTATION_PANE_JS)
+            .ok()
+            .and_then(|v| v.as_str().and_then(|s| serde_json::from_str(s).ok()))
+    }
+
+    /// Wait until the annotation pane is present, not loading and `changed`, then until
+    /// its highlight count holds across two polls
+    ///
+    /// Gives up after `per_book_timeout_secs`; the caller then reads what is there.
+    fn wait_for_annotations(&self, tab: &dyn BrowserPage, changed: impl Fn(&AnnotationPane) -> bool) {
+        let wait = Wait::up_to(Duration::from_secs(self.config.per_book_timeout_secs));
+        let ready = wait.until(&SystemClock, || {
+            self.annotation_pane(tab).filter(|pane| pane.container && !pane.loading && changed(pane))
+        });
+        if ready.is_some() {
+            wait.until_stable(&SystemClock, || {
+                self.annotation_pane(tab).filter(|pane| !pane.loading).map(|pane| pane.count)
+            });
+        }
+    }
+
+    /// Where the element with this id is in the viewport, to tell when scrolling stops
+    fn element_top(&self, tab: &dyn BrowserPage, id: &str) -> Option<i64> {
+        let js = format!(
+            "(function() {{ const el = document.getElementById({}); return el ? Math.round(el.getBoundingClientRect().top) : null; }})()",
+            serde_json::to_string(id).unwrap_or_default()
+        );
+        tab.evaluate(&js).ok().and_then(|v| v.as_i64())
+    }
+
+    fn click_next_page(&self, tab: &dyn BrowserPage) -> Result<bool, KindleError> {
         let js = r#"
             (function() {
                 // Find the "next page" link/button
@@ -519,9 +1454,287 @@ impl KindleBrowserScraper {
             })()
         "#;
 
-        let result = tab.evaluate(js, true)
+        let result = tab.evaluate(js)
             .map_err(|e| KindleError::ParseError(format!("Failed to click next: {}", e)))?;
 
-        Ok(result.value.and_then(|v| v.as_bool()).unwrap_or(false))
+        Ok(result.as_bool().unwrap_or(false))
+    }
+}
+
+/// Longest wait for the library list to stop moving before clicking a book
+const SCROLL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Summarises the annotation pane: the book it shows (hidden ASIN input), whether it is
+/// there at all, whether a spinner is visible, and its first highlight and highlight count
+const ANNOTATION_PANE_JS: &str = r#"
+    (function() {
+        const asin = document.querySelector('#kp-notebook-annotations-asin');
+        const first = document.querySelector('#highlight');
+        const spinners = document.querySelectorAll('#kp-notebook-spinner, .kp-notebook-spinner, #kp-notebook-annotations .a-spinner');
+        return JSON.stringify({
+            asin: asin && asin.value ? asin.value : null,
+            container: !!document.querySelector('#kp-notebook-annotations'),
+            loading: Array.from(spinners).some(el => el.offsetParent !== null),
+            first: first ? first.textContent.trim().substring(0, 50) : '',
+            count: document.querySelectorAll('#highlight').length,
+        });
+    })()
+"#;
+
+/// What the annotation pane shows, from `ANNOTATION_PANE_JS`
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+struct AnnotationPane {
+    asin: Option<String>,
+    container: bool,
+    loading: bool,
+    first: String,
+    count: usize,
+}
+
+impl AnnotationPane {
+    /// Whether the pane has switched to this book since `before` was read
+    ///
+    /// The hidden ASIN input says so directly; without it, a different first highlight
+    /// is the only sign.
+    fn shows_book(&self, asin: &str, before: &AnnotationPane) -> bool {
+        match &self.asin {
+            Some(shown) => shown == asin,
+            None => !self.first.is_empty() && self.first != before.first,
+        }
+    }
+}
+
+/// Longest wait for the library list to change after switching its content filter
+const LIBRARY_FILTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait after a library scroll for the next books to render
+const LIBRARY_SETTLE: Duration = Duration::from_millis(700);
+/// Rounds without new books before the library list is taken as complete
+const LIBRARY_STABLE_ROUNDS: usize = 3;
+/// The same for a list that is still empty, which may just not have rendered yet
+const LIBRARY_EMPTY_ROUNDS: usize = 10;
+/// Most scroll rounds, as a safety limit
+const LIBRARY_MAX_ROUNDS: usize = 500;
+
+/// Read a lazily loaded list batch by batch until it stops growing
+///
+/// `next_batch` returns the books visible now and scrolls on; `settle` waits for more
+/// to load. Books are kept in first-seen order, once per ASIN.
+fn collect_library(
+    mut next_batch: impl FnMut() -> Result<Vec<NotebookEntry>, KindleError>,
+    settle: &dyn Fn(),
+) -> Result<Vec<NotebookEntry>, KindleError> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    let mut unchanged = 0;
+
+    for _ in 0..LIBRARY_MAX_ROUNDS {
+        let before = entries.len();
+        for entry in next_batch()? {
+            if seen.insert(entry.asin.clone()) {
+                entries.push(entry);
+            }
+        }
+
+        if entries.len() == before {
+            unchanged += 1;
+            let needed = if entries.is_empty() { LIBRARY_EMPTY_ROUNDS } else { LIBRARY_STABLE_ROUNDS };
+            if unchanged >= needed {
+                break;
+            }
+        } else {
+            unchanged = 0;
+        }
+
+        settle();
+    }
+
+    Ok(entries)
+}
+
+/// Print lines in a box on stderr, to stand out from progress output
+fn print_banner(lines: &[&str]) {
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 4;
+    eprintln!("\n╔{}╗", "═".repeat(width));
+    for line in lines {
+        eprintln!("║  {}{}║", line, " ".repeat(width - 2 - line.chars().count()));
+    }
+    eprintln!("╚{}╝\n", "═".repeat(width));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(asin: &str, title: &str) -> NotebookEntry {
+        NotebookEntry {
+            asin: asin.to_string(),
+            title: title.to_string(),
+            author: None,
+            last_annotated: None,
+            cover_url: None,
+            document_type: DocumentType::Book,
+            original_title: None,
+        }
+    }
+
+    #[test]
+    fn test_book_selection() {
+        let library = vec![
+            entry("B001", "Walden"),
+            entry("B002", "Dune"),
+            entry("B003", "Dune Messiah"),
+            entry("B004", "Emma"),
+        ];
+        let titles = |selection: &BookSelection| {
+            let (entries, unmatched) = selection.apply(library.clone());
+            (entries.into_iter().map(|e| e.title).collect::<Vec<_>>(), unmatched)
+        };
+
+        let selection = BookSelection {
+            patterns: vec!["dune".to_string(), "b004".to_string(), "Ulysses".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            titles(&selection),
+            (vec!["Dune".into(), "Dune Messiah".into(), "Emma".into()], vec!["Ulysses".to_string()])
+        );
+
+        let selection = BookSelection {
+            skip: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(titles(&selection).0, vec!["Dune", "Dune Messiah"]);
+        assert_eq!(titles(&BookSelection::default()).0.len(), 4);
+    }
+
+    #[test]
+    fn test_classify_personal_documents() {
+        let mut doc = entry("B0DOC00001", "the_art_of_unix_programming_pdf");
+        doc.classify(DocumentType::PersonalDocument);
+        assert_eq!(doc.document_type, DocumentType::PersonalDocument);
+        assert_eq!(doc.title, "The Art of Unix Programming");
+        assert_eq!(doc.original_title.as_deref(), Some("the_art_of_unix_programming_pdf"));
+
+        let mut article = entry("B0DOC00002", "How to Do Great Work");
+        article.classify(DocumentType::PersonalDocument);
+        assert_eq!(article.title, "How to Do Great Work");
+        assert_eq!(article.original_title, None);
+
+        // Bought books keep their titles as listed
+        let mut book = entry("B00B7NPRY8", "walden_epub");
+        book.classify(DocumentType::Book);
+        assert_eq!(book.document_type, DocumentType::Book);
+        assert_eq!(book.title, "walden_epub");
+
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(json["document_type"], "personal_document");
+    }
+
+    #[test]
+    fn test_annotation_pane_shows_book() {
+        let pane = |asin: Option<&str>, first: &str| AnnotationPane {
+            asin: asin.map(String::from),
+            container: true,
+            first: first.to_string(),
+            ..Default::default()
+        };
+        let before = pane(Some("B00OLD0000"), "Simplify, simplify.");
+
+        assert!(pane(Some("B00B7NPRY8"), "I went to the woods.").shows_book("B00B7NPRY8", &before));
+        // Same text can't tell books apart, but the ASIN input can
+        assert!(pane(Some("B00B7NPRY8"), "Simplify, simplify.").shows_book("B00B7NPRY8", &before));
+        assert!(!pane(Some("B00OLD0000"), "Simplify, simplify.").shows_book("B00B7NPRY8", &before));
+
+        // Without the input, only a changed first highlight counts
+        assert!(pane(None, "I went to the woods.").shows_book("B00B7NPRY8", &before));
+        assert!(!pane(None, "Simplify, simplify.").shows_book("B00B7NPRY8", &before));
+        assert!(!pane(None, "").shows_book("B00B7NPRY8", &before));
+    }
+
+    #[test]
+    fn test_collect_library_until_stable() {
+        // The list renders 50 books at a time and forgets ones scrolled far past
+        let all: Vec<NotebookEntry> = (0..120).map(|i| entry(&format!("B{:03}", i), &format!("Book {}", i))).collect();
+        let mut shown: usize = 0;
+        let mut calls = 0;
+        let books = collect_library(
+            || {
+                calls += 1;
+                let start = shown;
+                shown = (shown + 50).min(all.len());
+                Ok(all[start..shown].to_vec())
+            },
+            &|| {},
+        )
+        .unwrap();
+
+        assert_eq!(books.len(), 120);
+        assert_eq!(books[0].asin, "B000");
+        assert_eq!(books[119].asin, "B119");
+        assert_eq!(calls, 3 + LIBRARY_STABLE_ROUNDS);
+
+        let mut calls = 0;
+        let empty = collect_library(
+            || {
+                calls += 1;
+                Ok(Vec::new())
+            },
+            &|| {},
+        )
+        .unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(calls, LIBRARY_EMPTY_ROUNDS);
+    }
+
+    #[test]
+    fn test_is_signin_url() {
+        assert!(is_signin_url("https://www.amazon.com/ap/signin?openid.return_to=https%3A%2F%2Fread.amazon.com"));
+        assert!(is_signin_url("https://www.amazon.co.uk/ap/mfa?arb=x"));
+        assert!(!is_signin_url("https://read.amazon.com/notebook"));
+        assert!(!is_signin_url("https://read.amazon.co.jp/notebook?asin=B00B7NPRY8"));
+    }
+
+    #[test]
+    fn test_region_for_host() {
+        assert_eq!(region_for_host("read.amazon.com"), Some("us"));
+        assert_eq!(region_for_host(".amazon.co.uk"), Some("uk"));
+        assert_eq!(region_for_host("www.amazon.com.au"), Some("au"));
+        assert_eq!(region_for_host("Read.Amazon.DE"), Some("de"));
+        assert_eq!(region_for_host("amazon.com.evil.example"), None);
+        assert_eq!(region_for_host("notamazon.com"), None);
+
+        let landed = AmazonRegion::from_url("https://read.amazon.co.uk/notebook?ref_=kcr_notebook_lib").unwrap();
+        assert_eq!((landed.code.as_str(), landed.domain.as_str()), ("uk", "amazon.co.uk"));
+        assert_eq!(landed.notebook_url, "https://read.amazon.co.uk/notebook");
+        assert!(AmazonRegion::from_url("about:blank").is_none());
+    }
+
+    #[test]
+    fn test_detect_signin_pages() {
+        let markers = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(SigninPage::detect("https://read.amazon.com/notebook", &[]), None);
+        assert_eq!(SigninPage::detect("https://www.amazon.com/ap/signin?x=1", &[]), Some(SigninPage::Login));
+        assert_eq!(SigninPage::detect("https://www.amazon.com/ap/mfa?arb=1", &[]), Some(SigninPage::TwoFactor));
+        assert_eq!(
+            SigninPage::detect("https://www.amazon.com/ap/signin", &markers(&["otp"])),
+            Some(SigninPage::TwoFactor)
+        );
+        assert_eq!(
+            SigninPage::detect("https://www.amazon.com/errors/validateCaptcha", &[]),
+            Some(SigninPage::Captcha)
+        );
+        assert_eq!(
+            SigninPage::detect("https://www.amazon.com/ap/cvf/request", &markers(&["captcha"])),
+            Some(SigninPage::Captcha)
+        );
+        assert_eq!(
+            SigninPage::detect("https://www.amazon.com/ap/signin", &markers(&["switcher"])),
+            Some(SigninPage::AccountSwitcher)
+        );
+        assert!(matches!(SigninPage::Login.error(true), KindleError::SessionExpired));
+        assert!(matches!(SigninPage::Login.error(false), KindleError::LoginRequired));
     }
 }
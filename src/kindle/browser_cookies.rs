@@ -0,0 +1,523 @@
+//! Amazon cookies read straight from an installed browser's cookie store
+//!
+//! Saves exporting a cookies.txt for `kindle-cookies`. Cookies are read into memory
+//! only; nothing is copied or written. Firefox keeps plain values in `cookies.sqlite`,
+//! Safari in `Cookies.binarycookies`. Chrome encrypts its values with a key from the OS
+//! keychain (macOS) or secret service (Linux), so decrypting them needs the
+//! `chrome-cookies` feature; Chrome on Windows (DPAPI) isn't supported.
+
+use crate::error::KindleError;
+use crate::kindle::browser::region_for_host;
+use rusqlite::{Connection, OpenFlags};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// A browser whose cookies can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieBrowser {
+    Chrome,
+    Firefox,
+    Safari,
+}
+
+impl CookieBrowser {
+    pub fn name(self) -> &'static str {
+        match self {
+            CookieBrowser::Chrome => "Chrome",
+            CookieBrowser::Firefox => "Firefox",
+            CookieBrowser::Safari => "Safari",
+        }
+    }
+}
+
+/// A cookie from a browser's store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredCookie {
+    /// Host or domain, e.g. `.amazon.com`
+    pub host: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// The browser's cookies for every Amazon store, e.g. amazon.com and amazon.co.uk
+///
+/// Takes the most recently used profile when there are several. Reading them all
+/// lets the caller tell which store the account is signed in to; an empty list means
+/// the browser was never used with Amazon.
+pub fn read_amazon_cookies(browser: CookieBrowser) -> Result<Vec<StoredCookie>, KindleError> {
+    let candidates = store_candidates(browser);
+    let store = newest_existing(&candidates)
+        .ok_or_else(|| KindleError::CookieStoreNotFound(browser.name(), candidates.clone()))?;
+
+    let cookies = match browser {
+        CookieBrowser::Firefox => read_firefox(&store, AMAZON_HOSTS)?,
+        CookieBrowser::Safari => {
+            let data = fs::read(&store).map_err(|e| unreadable(browser, &store, e))?;
+            parse_binary_cookies(&data)
+                .map_err(|e| KindleError::CookieLoadError(format!("{}: {}", store.display(), e)))?
+        }
+        CookieBrowser::Chrome => read_chrome(&store, AMAZON_HOSTS)?,
+    };
+
+    Ok(cookies.into_iter().filter(|c| region_for_host(&c.host).is_some()).collect())
+}
+
+/// Whether a cookie host is the domain or one of its subdomains
+pub(crate) fn matches_domain(host: &str, domain: &str) -> bool {
+    let host = host.trim_start_matches('.');
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Where each browser keeps its cookie stores, one per profile
+fn store_candidates(browser: CookieBrowser) -> Vec<PathBuf> {
+    let home = dirs::home_dir().unwrap_or_default();
+    match browser {
+        CookieBrowser::Firefox => {
+            let roots = [
+                home.join("Library/Application Support/Firefox/Profiles"),
+                home.join(".mozilla/firefox"),
+                home.join("snap/firefox/common/.mozilla/firefox"),
+                dirs::config_dir().unwrap_or_default().join("Mozilla/Firefox/Profiles"),
+            ];
+            let mut stores = Vec::new();
+            for root in roots {
+                if let Ok(profiles) = fs::read_dir(&root) {
+                    stores.extend(profiles.flatten().map(|profile| profile.path().join("cookies.sqlite")));
+                }
+            }
+            stores
+        }
+        CookieBrowser::Safari => vec![
+            home.join("Library/Containers/com.apple.Safari/Data/Library/Cookies/Cookies.binarycookies"),
+            home.join("Library/Cookies/Cookies.binarycookies"),
+        ],
+        CookieBrowser::Chrome => {
+            let roots = [
+                home.join("Library/Application Support/Google/Chrome"),
+                home.join("Library/Application Support/Chromium"),
+                home.join(".config/google-chrome"),
+                home.join(".config/chromium"),
+                dirs::data_local_dir().unwrap_or_default().join("Google/Chrome/User Data"),
+            ];
+            let mut stores = Vec::new();
+            for root in roots {
+                let Ok(profiles) = fs::read_dir(&root) else {
+                    continue;
+                };
+                for profile in profiles.flatten() {
+                    // Newer versions keep the store under Network/
+                    stores.push(profile.path().join("Network").join("Cookies"));
+                    stores.push(profile.path().join("Cookies"));
+                }
+            }
+            stores
+        }
+    }
+}
+
+/// The most recently modified of the stores that exist
+fn newest_existing(candidates: &[PathBuf]) -> Option<PathBuf> {
+    candidates
+        .iter()
+        .filter_map(|path| Some((fs::metadata(path).ok()?.modified().ok()?, path)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path.clone())
+}
+
+fn unreadable(browser: CookieBrowser, path: &Path, e: std::io::Error) -> KindleError {
+    let hint = if e.kind() == ErrorKind::PermissionDenied && browser == CookieBrowser::Safari {
+        "; give your terminal Full Disk Access in System Settings > Privacy & Security"
+    } else {
+        ""
+    };
+    KindleError::CookieStoreUnreadable(browser.name(), format!("{}: {}{}", path.display(), e, hint))
+}
+
+/// Run a query against a browser's SQLite cookie store
+///
+/// A running browser may hold a lock on the store; it is then opened as immutable,
+/// which reads the file as it is on disk (missing only changes not yet checkpointed)
+/// without taking a lock or creating journal files.
+fn query_store<T>(
+    browser: CookieBrowser,
+    path: &Path,
+    query: impl Fn(&Connection) -> rusqlite::Result<T>,
+) -> Result<T, KindleError> {
+    let locked = |e: &rusqlite::Error| {
+        matches!(e.sqlite_error_code(), Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked))
+    };
+
+    let first = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(|conn| query(&conn));
+    match first {
+        Err(e) if locked(&e) => {}
+        other => {
+            return other.map_err(|e| KindleError::CookieStoreUnreadable(browser.name(), format!("{}: {}", path.display(), e)))
+        }
+    }
+
+    let uri = Url::from_file_path(path)
+        .map(|url| format!("{}?immutable=1", url))
+        .map_err(|_| KindleError::CookieStoreUnreadable(browser.name(), format!("{}: not an absolute path", path.display())))?;
+    Connection::open_with_flags(uri, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI)
+        .and_then(|conn| query(&conn))
+        .map_err(|e| {
+            KindleError::CookieStoreUnreadable(
+                browser.name(),
+                format!("{} is locked ({}); quit {} and try again", path.display(), e, browser.name()),
+            )
+        })
+}
+
+/// SQL LIKE pattern for cookies of the domain and its subdomains
+/// SQL LIKE pattern narrowing a store to Amazon's hosts; `region_for_host` then
+/// drops look-alikes
+const AMAZON_HOSTS: &str = "%amazon.%";
+
+fn read_firefox(path: &Path, host_pattern: &str) -> Result<Vec<StoredCookie>, KindleError> {
+    query_store(CookieBrowser::Firefox, path, |conn| {
+        let mut stmt = conn.prepare("SELECT host, name, value FROM moz_cookies WHERE host LIKE ?1")?;
+        let rows = stmt.query_map([host_pattern], |row| {
+            Ok(StoredCookie {
+                host: row.get(0)?,
+                name: row.get(1)?,
+                value: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// A Chrome cookie as stored: a plain value, or an encrypted one
+struct ChromeRow {
+    host: String,
+    name: String,
+    value: String,
+    encrypted: Vec<u8>,
+}
+
+fn read_chrome(path: &Path, host_pattern: &str) -> Result<Vec<StoredCookie>, KindleError> {
+    let (rows, version) = query_store(CookieBrowser::Chrome, path, |conn| {
+        // Since version 24 encrypted values start with a hash of the host
+        let version: i64 = conn
+            .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| row.get::<_, String>(0))
+            .map(|v| v.parse().unwrap_or(0))
+            .unwrap_or(0);
+        let mut stmt = conn.prepare("SELECT host_key, name, value, encrypted_value FROM cookies WHERE host_key LIKE ?1")?;
+        let rows = stmt.query_map([host_pattern], |row| {
+            Ok(ChromeRow {
+                host: row.get(0)?,
+                name: row.get(1)?,
+                value: row.get(2)?,
+                encrypted: row.get(3)?,
+            })
+        })?;
+        Ok((rows.collect::<rusqlite::Result<Vec<_>>>()?, version))
+    })?;
+
+    let mut decryptor = None;
+    rows.into_iter()
+        .map(|row| {
+            let value = if row.encrypted.is_empty() {
+                row.value
+            } else {
+                if decryptor.is_none() {
+                    decryptor = Some(chrome_crypto::Decryptor::new()?);
+                }
+                let decryptor = decryptor.as_ref().expect("just created");
+                let mut plain = decryptor.decrypt(&row.encrypted)?;
+                if version >= 24 && plain.len() >= 32 {
+                    plain.drain(..32);
+                }
+                String::from_utf8(plain).map_err(|_| {
+                    KindleError::CookiesEncrypted("Chrome", format!("cookie {} did not decrypt to text", row.name))
+                })?
+            };
+            Ok(StoredCookie {
+                host: row.host,
+                name: row.name,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Decrypting Chrome's cookie values
+///
+/// Values carry a version prefix: `v10`/`v11` mark AES-128-CBC with a key derived
+/// (PBKDF2-SHA1, salt "saltysalt") from a password that macOS keeps in the keychain
+/// ("Chrome Safe Storage") and Linux in the secret service, or the fixed "peanuts" for
+/// `v10` on Linux without one.
+#[cfg(feature = "chrome-cookies")]
+mod chrome_crypto {
+    use crate::error::KindleError;
+    use std::process::Command;
+
+    pub struct Decryptor {
+        /// Key for `v10` values, and for `v11` ones where there is one
+        v10: [u8; 16],
+        v11: Option<[u8; 16]>,
+    }
+
+    fn derive_key(password: &[u8], iterations: usize) -> Result<[u8; 16], KindleError> {
+        let mut key = [0u8; 16];
+        openssl::pkcs5::pbkdf2_hmac(password, b"saltysalt", iterations, openssl::hash::MessageDigest::sha1(), &mut key)
+            .map_err(|e| KindleError::CookiesEncrypted("Chrome", e.to_string()))?;
+        Ok(key)
+    }
+
+    /// Output of a command that prints a password, if it succeeds
+    fn password_from(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+        let output = Command::new(program).args(args).output().ok()?;
+        let password = String::from_utf8(output.stdout).ok()?;
+        (output.status.success() && !password.trim().is_empty()).then(|| password.trim().as_bytes().to_vec())
+    }
+
+    impl Decryptor {
+        #[cfg(target_os = "macos")]
+        pub fn new() -> Result<Self, KindleError> {
+            let password = ["Chrome Safe Storage", "Chromium Safe Storage"]
+                .iter()
+                .find_map(|service| password_from("security", &["find-generic-password", "-w", "-s", service]))
+                .ok_or_else(|| {
+                    KindleError::CookiesEncrypted(
+                        "Chrome",
+                        "the keychain did not release \"Chrome Safe Storage\"; allow access when macOS asks".to_string(),
+                    )
+                })?;
+            let key = derive_key(&password, 1003)?;
+            Ok(Self { v10: key, v11: None })
+        }
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        pub fn new() -> Result<Self, KindleError> {
+            let v11 = ["chrome", "chromium"]
+                .iter()
+                .find_map(|application| password_from("secret-tool", &["lookup", "application", application]))
+                .map(|password| derive_key(&password, 1))
+                .transpose()?;
+            Ok(Self {
+                v10: derive_key(b"peanuts", 1)?,
+                v11,
+            })
+        }
+
+        #[cfg(windows)]
+        pub fn new() -> Result<Self, KindleError> {
+            Err(KindleError::CookiesEncrypted(
+                "Chrome",
+                "Chrome on Windows protects cookies with DPAPI, which isn't supported; use --cookies-from firefox or a cookies.txt".to_string(),
+            ))
+        }
+
+        pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>, KindleError> {
+            let (key, data) = match encrypted.split_at_checked(3) {
+                Some((b"v10", data)) => (Some(self.v10), data),
+                Some((b"v11", data)) => (self.v11, data),
+                _ => {
+                    return Err(KindleError::CookiesEncrypted("Chrome", "unknown cookie encryption version".to_string()));
+                }
+            };
+            let key = key.ok_or_else(|| {
+                KindleError::CookiesEncrypted(
+                    "Chrome",
+                    "the key is in the secret service, which `secret-tool lookup application chrome` couldn't read".to_string(),
+                )
+            })?;
+            openssl::symm::decrypt(openssl::symm::Cipher::aes_128_cbc(), &key, Some(&[b' '; 16]), data)
+                .map_err(|e| KindleError::CookiesEncrypted("Chrome", format!("decryption failed ({}); the key may have changed", e)))
+        }
+    }
+}
+
+#[cfg(not(feature = "chrome-cookies"))]
+mod chrome_crypto {
+    use crate::error::KindleError;
+
+    pub struct Decryptor;
+
+    impl Decryptor {
+        pub fn new() -> Result<Self, KindleError> {
+            Err(KindleError::CookiesEncrypted(
+                "Chrome",
+                "this build can't decrypt them; rebuild with `--features chrome-cookies`, or use --cookies-from firefox".to_string(),
+            ))
+        }
+
+        pub fn decrypt(&self, _encrypted: &[u8]) -> Result<Vec<u8>, KindleError> {
+            unreachable!("a Decryptor can't be created without the chrome-cookies feature")
+        }
+    }
+}
+
+/// Parse Safari's `Cookies.binarycookies`
+///
+/// The file is `cook`, a big-endian page count and page sizes, then the pages. Each
+/// page starts `00 00 01 00` and holds little-endian cookie offsets; each cookie has
+/// little-endian offsets (from the cookie's start) to NUL-terminated host, name, path
+/// and value strings.
+fn parse_binary_cookies(data: &[u8]) -> Result<Vec<StoredCookie>, String> {
+    let be_u32 = |at: usize| -> Result<usize, String> {
+        let bytes = data.get(at..at + 4).ok_or("truncated file")?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    if data.get(..4) != Some(b"cook") {
+        return Err("not a binarycookies file".to_string());
+    }
+
+    let pages = be_u32(4)?;
+    let mut page_start = 8 + 4 * pages;
+    let mut cookies = Vec::new();
+    for i in 0..pages {
+        let size = be_u32(8 + 4 * i)?;
+        let page = data.get(page_start..page_start + size).ok_or("truncated page")?;
+        cookies.extend(parse_cookie_page(page)?);
+        page_start += size;
+    }
+    Ok(cookies)
+}
+
+fn parse_cookie_page(page: &[u8]) -> Result<Vec<StoredCookie>, String> {
+    let le_u32 = |bytes: &[u8], at: usize| -> Result<usize, String> {
+        let bytes = bytes.get(at..at + 4).ok_or("truncated cookie")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let string = |cookie: &[u8], at: usize| -> Result<String, String> {
+        let bytes = cookie.get(at..).ok_or("bad string offset")?;
+        let end = bytes.iter().position(|&b| b == 0).ok_or("unterminated string")?;
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    };
+
+    if page.get(..4) != Some(&[0, 0, 1, 0]) {
+        return Err("bad page header".to_string());
+    }
+    let count = le_u32(page, 4)?;
+    (0..count)
+        .map(|i| {
+            let offset = le_u32(page, 8 + 4 * i)?;
+            let size = le_u32(page, offset)?;
+            let cookie = page.get(offset..offset + size).ok_or("truncated cookie")?;
+            Ok(StoredCookie {
+                host: string(cookie, le_u32(cookie, 16)?)?,
+                name: string(cookie, le_u32(cookie, 20)?)?,
+                value: string(cookie, le_u32(cookie, 28)?)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One binarycookies cookie record
+    fn cookie_record(host: &str, name: &str, path: &str, value: &str) -> Vec<u8> {
+        let header = 56;
+        let mut strings = Vec::new();
+        let mut offsets = Vec::new();
+        for s in [host, name, path, value] {
+            offsets.push((header + strings.len()) as u32);
+            strings.extend_from_slice(s.as_bytes());
+            strings.push(0);
+        }
+        let mut record = Vec::new();
+        record.extend(((header + strings.len()) as u32).to_le_bytes());
+        record.extend([0u8; 4]);
+        record.extend(1u32.to_le_bytes()); // secure
+        record.extend([0u8; 4]);
+        for offset in offsets {
+            record.extend(offset.to_le_bytes());
+        }
+        record.extend([0u8; 8]); // end of header
+        record.extend([0u8; 16]); // expiry and creation dates
+        record.extend(strings);
+        record
+    }
+
+    #[test]
+    fn test_parse_binary_cookies() {
+        let records = [
+            cookie_record(".amazon.com", "session-id", "/", "123-456"),
+            cookie_record("www.example.org", "id", "/", "x"),
+        ];
+        let mut page = vec![0, 0, 1, 0];
+        page.extend((records.len() as u32).to_le_bytes());
+        let mut offset = 8 + 4 * records.len() + 4;
+        for record in &records {
+            page.extend((offset as u32).to_le_bytes());
+            offset += record.len();
+        }
+        page.extend([0u8; 4]);
+        for record in &records {
+            page.extend(record);
+        }
+
+        let mut file = b"cook".to_vec();
+        file.extend(1u32.to_be_bytes());
+        file.extend((page.len() as u32).to_be_bytes());
+        file.extend(page);
+
+        let cookies = parse_binary_cookies(&file).unwrap();
+        assert_eq!(
+            cookies[0],
+            StoredCookie {
+                host: ".amazon.com".to_string(),
+                name: "session-id".to_string(),
+                value: "123-456".to_string(),
+            }
+        );
+        assert_eq!(cookies[1].host, "www.example.org");
+
+        assert!(parse_binary_cookies(b"cook\0\0\0\x01").is_err());
+        assert!(parse_binary_cookies(b"SQLite format 3").is_err());
+    }
+
+    #[test]
+    fn test_matches_domain() {
+        assert!(matches_domain(".amazon.com", "amazon.com"));
+        assert!(matches_domain("read.amazon.com", "amazon.com"));
+        assert!(!matches_domain(".amazon.com.au", "amazon.com"));
+        assert!(!matches_domain("notamazon.com", "amazon.com"));
+    }
+
+    #[cfg(all(feature = "chrome-cookies", unix, not(target_os = "macos")))]
+    #[test]
+    fn test_decrypt_chrome_v10() {
+        use openssl::symm::{encrypt, Cipher};
+
+        let decryptor = chrome_crypto::Decryptor::new().unwrap();
+        let mut key = [0u8; 16];
+        openssl::pkcs5::pbkdf2_hmac(b"peanuts", b"saltysalt", 1, openssl::hash::MessageDigest::sha1(), &mut key).unwrap();
+        let mut encrypted = b"v10".to_vec();
+        encrypted.extend(encrypt(Cipher::aes_128_cbc(), &key, Some(&[b' '; 16]), b"123-456").unwrap());
+
+        assert_eq!(decryptor.decrypt(&encrypted).unwrap(), b"123-456");
+        assert!(decryptor.decrypt(b"v99garbage").is_err());
+    }
+
+    #[test]
+    fn test_read_firefox_store() {
+        let dir = std::env::temp_dir().join(format!("readingsync_firefox_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cookies.sqlite");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY, name TEXT, value TEXT, host TEXT, path TEXT);
+                 INSERT INTO moz_cookies (name, value, host, path) VALUES
+                     ('session-id', '123-456', '.amazon.com', '/'),
+                     ('at-main', 'Atza|token', 'www.amazon.com', '/'),
+                     ('session-id', 'uk', '.amazon.co.uk', '/'),
+                     ('sid', 'other', '.example.org', '/');",
+            )
+            .unwrap();
+        }
+
+        let cookies = read_firefox(&path, AMAZON_HOSTS).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let hosts: Vec<&str> = cookies.iter().map(|c| c.host.as_str()).collect();
+        assert_eq!(hosts, vec![".amazon.com", "www.amazon.com", ".amazon.co.uk"]);
+    }
+}
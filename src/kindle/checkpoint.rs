@@ -0,0 +1,235 @@
+use crate::error::KindleError;
+use crate::model::Book;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the checkpoint file layout, bumped on incompatible changes
+const FORMAT: u32 = 1;
+
+/// Books scraped so far by a browser sync, saved after each one
+///
+/// The file is JSON Lines: a header with the format version and run id, then one line
+/// per scraped book. An interrupted sync can be resumed from it, skipping the books
+/// it already holds.
+pub struct Checkpoint {
+    path: PathBuf,
+    file: File,
+    run_id: String,
+    books: HashMap<String, Book>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: u32,
+    run_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    asin: String,
+    book: Book,
+}
+
+/// Get the default checkpoint path
+pub fn default_checkpoint_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("readingsync")
+        .join("kindle_checkpoint.jsonl")
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint, replacing any left by an earlier run
+    pub fn create(path: &Path) -> Result<Self, KindleError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| checkpoint_error(path, e))?;
+        }
+
+        Self::write(path, uuid::Uuid::new_v4().to_string(), HashMap::new())
+    }
+
+    /// Write a checkpoint file holding `books`, through a temporary file so an
+    /// existing checkpoint is only replaced once the new one is complete
+    fn write(path: &Path, run_id: String, books: HashMap<String, Book>) -> Result<Self, KindleError> {
+        let temp = path.with_extension("jsonl.tmp");
+        let mut checkpoint = Self {
+            path: temp.clone(),
+            file: File::create(&temp).map_err(|e| checkpoint_error(path, e))?,
+            run_id,
+            books: HashMap::new(),
+        };
+
+        let header = Header {
+            format: FORMAT,
+            run_id: checkpoint.run_id.clone(),
+        };
+        let header = serde_json::to_string(&header).map_err(|e| checkpoint_error(path, e))?;
+        writeln!(checkpoint.file, "{}", header).map_err(|e| checkpoint_error(path, e))?;
+        for (asin, book) in books {
+            checkpoint.record(&asin, &book)?;
+        }
+
+        fs::rename(&temp, path).map_err(|e| checkpoint_error(path, e))?;
+        checkpoint.path = path.to_path_buf();
+        Ok(checkpoint)
+    }
+
+    /// Reopen the checkpoint of an interrupted run to continue it
+    ///
+    /// A last line cut off by a crash is dropped; anything else unreadable, or a file
+    /// from an incompatible version, is an error rather than silently ignored.
+    pub fn resume(path: &Path) -> Result<Self, KindleError> {
+        let file = File::open(path).map_err(|e| checkpoint_error(path, e))?;
+        let lines: Vec<String> = BufReader::new(file)
+            .lines()
+            .collect::<Result<_, _>>()
+            .map_err(|e| checkpoint_error(path, e))?;
+
+        let header: Header = lines
+            .first()
+            .and_then(|line| serde_json::from_str(line).ok())
+            .ok_or_else(|| checkpoint_error(path, "not a readingsync checkpoint; delete it to start over"))?;
+
+        if header.format != FORMAT {
+            return Err(checkpoint_error(
+                path,
+                format!(
+                    "written by an incompatible version (format {}, expected {}); delete it to start over",
+                    header.format, FORMAT
+                ),
+            ));
+        }
+
+        let mut books = HashMap::new();
+        let entries = &lines[1..];
+        for (i, line) in entries.iter().enumerate() {
+            match serde_json::from_str::<Entry>(line) {
+                Ok(entry) => {
+                    books.insert(entry.asin, entry.book);
+                }
+                // Interrupted while writing the last book
+                Err(_) if i + 1 == entries.len() => {}
+                Err(e) => return Err(checkpoint_error(path, format!("line {}: {}", i + 2, e))),
+            }
+        }
+
+        // Rewrite without any truncated line so appends start on a fresh line
+        Self::write(path, header.run_id, books)
+    }
+
+    /// Number of books saved in an existing checkpoint, or None if there is none
+    pub fn pending(path: &Path) -> Option<usize> {
+        let file = File::open(path).ok()?;
+        let complete = BufReader::new(file)
+            .lines()
+            .skip(1)
+            .map_while(Result::ok)
+            .filter(|line| serde_json::from_str::<Entry>(line).is_ok())
+            .count();
+        Some(complete)
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// The book saved for an ASIN, if this run already scraped it
+    pub fn get(&self, asin: &str) -> Option<&Book> {
+        self.books.get(asin)
+    }
+
+    pub fn len(&self) -> usize {
+        self.books.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty()
+    }
+
+    /// Append a scraped book, flushing it to disk straight away
+    pub fn record(&mut self, asin: &str, book: &Book) -> Result<(), KindleError> {
+        let entry = Entry {
+            asin: asin.to_string(),
+            book: book.clone(),
+        };
+        let line = serde_json::to_string(&entry).map_err(|e| checkpoint_error(&self.path, e))?;
+        writeln!(self.file, "{}", line).map_err(|e| checkpoint_error(&self.path, e))?;
+        self.file.flush().map_err(|e| checkpoint_error(&self.path, e))?;
+
+        self.books.insert(asin.to_string(), book.clone());
+        Ok(())
+    }
+
+    /// Delete the checkpoint once the sync has completed
+    pub fn finish(self) -> Result<(), KindleError> {
+        drop(self.file);
+        fs::remove_file(&self.path).map_err(|e| checkpoint_error(&self.path, e))
+    }
+}
+
+fn checkpoint_error(path: &Path, error: impl std::fmt::Display) -> KindleError {
+    KindleError::CheckpointError(path.to_path_buf(), error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("readingsync_checkpoint_{}", uuid::Uuid::new_v4()))
+            .join("checkpoint.jsonl")
+    }
+
+    #[test]
+    fn test_resume_after_interruption() {
+        let path = temp_path();
+
+        let mut checkpoint = Checkpoint::create(&path).unwrap();
+        let run_id = checkpoint.run_id().to_string();
+        checkpoint.record("B001", &Book::new("Walden".to_string(), None)).unwrap();
+        checkpoint.record("B002", &Book::new("Dune".to_string(), None)).unwrap();
+        drop(checkpoint);
+
+        // Simulate a crash half-way through writing the third book
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"asin\":\"B003\",\"book\":{{\"id\"").unwrap();
+        drop(file);
+
+        assert_eq!(Checkpoint::pending(&path), Some(2));
+
+        let mut resumed = Checkpoint::resume(&path).unwrap();
+        assert_eq!(resumed.run_id(), run_id);
+        assert_eq!(resumed.len(), 2);
+        assert_eq!(resumed.get("B002").unwrap().title, "Dune");
+        assert!(resumed.get("B003").is_none());
+
+        resumed.record("B003", &Book::new("Emma".to_string(), None)).unwrap();
+        drop(resumed);
+        assert_eq!(Checkpoint::resume(&path).unwrap().len(), 3);
+
+        Checkpoint::resume(&path).unwrap().finish().unwrap();
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_resume_rejects_other_formats() {
+        let path = temp_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        fs::write(&path, "{\"format\":99,\"run_id\":\"x\"}\n").unwrap();
+        let error = Checkpoint::resume(&path).err().unwrap();
+        assert!(error.to_string().contains("incompatible version (format 99"));
+
+        fs::write(&path, "[1, 2, 3]\n").unwrap();
+        let error = Checkpoint::resume(&path).err().unwrap();
+        assert!(error.to_string().contains("not a readingsync checkpoint"));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::KindleError;
+use std::path::{Path, PathBuf};
+
+/// Executable names looked up on PATH, most specific first
+const PATH_NAMES: &[&str] = &[
+    "google-chrome-stable",
+    "google-chrome",
+    "chromium",
+    "chromium-browser",
+    "microsoft-edge-stable",
+    "microsoft-edge",
+    "brave-browser",
+    "brave",
+    "chrome",
+];
+
+/// Install locations outside PATH, e.g. macOS app bundles and Homebrew
+#[cfg(target_os = "macos")]
+const INSTALL_PATHS: &[&str] = &[
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+    "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+    "/Applications/Brave Browser.app/Contents/MacOS/Brave Browser",
+    "/opt/homebrew/bin/chromium",
+    "/usr/local/bin/chromium",
+];
+
+#[cfg(windows)]
+const INSTALL_PATHS: &[&str] = &[
+    r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+    r"C:\Program Files\BraveSoftware\Brave-Browser\Application\brave.exe",
+];
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const INSTALL_PATHS: &[&str] = &[
+    "/usr/bin/google-chrome",
+    "/usr/bin/chromium",
+    "/usr/bin/chromium-browser",
+    "/snap/bin/chromium",
+    "/usr/bin/microsoft-edge",
+    "/usr/bin/brave-browser",
+    "/opt/google/chrome/chrome",
+];
+
+/// Find a Chrome-compatible browser to drive
+///
+/// A configured path is used as is (and must exist). Otherwise the `CHROME`
+/// environment variable, then Chrome, Chromium, Edge and Brave on PATH and in their
+/// usual install locations are tried in turn. `BrowserNotFound` lists every path tried.
+pub fn find_browser(configured: Option<&Path>) -> Result<PathBuf, KindleError> {
+    if let Some(path) = configured {
+        return first_existing(vec![path.to_path_buf()]);
+    }
+
+    let mut candidates: Vec<PathBuf> = std::env::var_os("CHROME").map(PathBuf::from).into_iter().collect();
+    if let Some(path_var) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&path_var) {
+            candidates.extend(PATH_NAMES.iter().map(|name| dir.join(executable_name(name))));
+        }
+    }
+    candidates.extend(INSTALL_PATHS.iter().map(PathBuf::from));
+
+    first_existing(candidates)
+}
+
+fn first_existing(candidates: Vec<PathBuf>) -> Result<PathBuf, KindleError> {
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(KindleError::BrowserNotFound(candidates)),
+    }
+}
+
+fn executable_name(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_first_existing_reports_every_candidate() {
+        let dir = std::env::temp_dir().join(format!("readingsync_chrome_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let chromium = dir.join("chromium");
+        fs::write(&chromium, "").unwrap();
+
+        let missing = dir.join("google-chrome");
+        assert_eq!(first_existing(vec![missing.clone(), chromium.clone()]).unwrap(), chromium);
+
+        let error = find_browser(Some(&missing)).unwrap_err();
+        let _ = fs::remove_dir_all(&dir);
+
+        match error {
+            KindleError::BrowserNotFound(tried) => assert_eq!(tried, vec![missing]),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
@@ -1,10 +1,16 @@
 use crate::error::KindleError;
-use crate::model::{generate_book_id, Book, Highlight, Location, Source};
-use chrono::{DateTime, TimeZone, Utc};
+use crate::model::{
+    generate_book_id, generate_highlight_id, parse_position_range, Book, Highlight, HighlightKind, HighlightStyle, Location,
+    Source,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs;
-use std::path::Path;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 /// Parse Kindle's My Clippings.txt file
 ///
@@ -16,110 +22,657 @@ use std::path::Path;
 /// The actual highlighted text goes here...
 /// ==========
 /// ```
-pub fn parse_clippings(path: &Path) -> Result<Vec<Book>, KindleError> {
+///
+/// Dates are device-local time and are interpreted in `timezone`.
+pub fn parse_clippings(path: &Path, timezone: Tz) -> Result<Vec<Book>, KindleError> {
+    if !path.exists() {
+        return Err(KindleError::ClippingsFileNotFound(path.to_path_buf()));
+    }
+
+    Ok(parse_clippings_report(path, timezone)?.books)
+}
+
+/// Books parsed from a clippings file, with notes on entries that didn't parse cleanly
+#[derive(Debug, Default)]
+pub struct ClippingsReport {
+    pub books: Vec<Book>,
+    pub warnings: Vec<ClippingsWarning>,
+    /// Entries dropped as verbatim copies of another entry in the same file
+    pub duplicates: usize,
+}
+
+/// An entry that was skipped or only partly parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClippingsWarning {
+    /// Byte offset of the entry in the file
+    pub offset: usize,
+    /// Line number of the entry in the file, starting at 1
+    pub line: usize,
+    /// First line of the entry, usually the book title
+    pub first_line: String,
+    pub reason: String,
+    /// Whether the entry was dropped (otherwise it was kept with what could be parsed)
+    pub skipped: bool,
+}
+
+impl std::fmt::Display for ClippingsWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = if self.skipped { "skipped" } else { "partly parsed" };
+        write!(
+            f,
+            "line {} (byte {}, {:?}): {}, {}",
+            self.line, self.offset, self.first_line, self.reason, action
+        )
+    }
+}
+
+/// Parse a clippings file leniently, reporting entries that didn't parse cleanly
+pub fn parse_clippings_report(path: &Path, timezone: Tz) -> Result<ClippingsReport, KindleError> {
     if !path.exists() {
         return Err(KindleError::ClippingsFileNotFound(path.to_path_buf()));
     }
 
-    let content = fs::read_to_string(path).map_err(KindleError::ClippingsReadError)?;
+    let file = fs::File::open(path).map_err(KindleError::ClippingsReadError)?;
+    let mut reader = BufReader::new(file);
+
+    // UTF-16 files come from old devices and are small, so they are decoded in one go
+    let head = reader.fill_buf().map_err(KindleError::ClippingsReadError)?;
+    if utf16_data(head).is_some() {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(KindleError::ClippingsReadError)?;
+        let content = decode_clippings(&bytes).map_err(KindleError::ClippingsReadError)?;
+        return Ok(parse_clippings_content_report(&content, timezone));
+    }
+
+    collect_report(ClippingsReader::new(reader, timezone))
+}
+
+/// Separator between entries in My Clippings.txt
+const SEPARATOR: &str = "==========";
+
+/// Streaming reader over the entries of a UTF-8 clippings file
+///
+/// Reads a line at a time, so memory stays proportional to one entry however large the
+/// file is. Yields each clipping that parsed; malformed entries are skipped or kept
+/// partially and recorded in [`ClippingsReader::warnings`]. A leading byte order mark
+/// is ignored and CRLF/CR line endings are accepted.
+pub struct ClippingsReader<R> {
+    reader: R,
+    timezone: Tz,
+    warnings: Vec<ClippingsWarning>,
+    /// Lines read but not yet consumed, with their byte offset and line number
+    pending: VecDeque<(String, usize, usize)>,
+    offset: usize,
+    line: usize,
+    done: bool,
+}
+
+/// Raw text of one entry and where it starts
+struct RawEntry {
+    text: String,
+    first_line: String,
+    offset: usize,
+    line: usize,
+}
+
+impl<R: BufRead> ClippingsReader<R> {
+    pub fn new(reader: R, timezone: Tz) -> Self {
+        Self {
+            reader,
+            timezone,
+            warnings: Vec::new(),
+            pending: VecDeque::new(),
+            offset: 0,
+            line: 0,
+            done: false,
+        }
+    }
+
+    /// Entries skipped or partly parsed so far
+    pub fn warnings(&self) -> &[ClippingsWarning] {
+        &self.warnings
+    }
+
+    pub fn into_warnings(self) -> Vec<ClippingsWarning> {
+        self.warnings
+    }
+
+    /// Next line with its byte offset and line number, or None at the end of the file
+    fn next_line(&mut self) -> Result<Option<(String, usize, usize)>, KindleError> {
+        if let Some(line) = self.pending.pop_front() {
+            return Ok(Some(line));
+        }
+
+        let mut buf = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut buf).map_err(KindleError::ClippingsReadError)?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let mut start = self.offset;
+        self.offset += read;
+
+        let mut bytes = buf.as_slice();
+        if start == 0 {
+            if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+                bytes = rest;
+                start += 3;
+            }
+        }
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+        let text = std::str::from_utf8(bytes).map_err(|_| {
+            KindleError::ClippingsReadError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("clippings file is not valid UTF-8 (line {})", self.line + 1),
+            ))
+        })?;
+
+        // Old Macs end lines with a bare CR
+        for piece in text.split('\r') {
+            self.line += 1;
+            self.pending.push_back((piece.to_string(), start, self.line));
+            start += piece.len() + 1;
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    /// Lines up to the next separator, or None once only blank lines remain
+    fn next_entry(&mut self) -> Result<Option<RawEntry>, KindleError> {
+        let mut entry: Option<RawEntry> = None;
+
+        while let Some((line, offset, number)) = self.next_line()? {
+            if line.trim_start().starts_with(SEPARATOR) {
+                if entry.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            match entry {
+                Some(ref mut entry) => {
+                    entry.text.push('\n');
+                    entry.text.push_str(&line);
+                }
+                None if line.trim().is_empty() => {}
+                None => {
+                    entry = Some(RawEntry {
+                        first_line: line.trim().to_string(),
+                        offset: offset + (line.len() - line.trim_start().len()),
+                        line: number,
+                        text: line,
+                    })
+                }
+            }
+        }
+
+        Ok(entry)
+    }
+
+    fn warn(&mut self, entry: &RawEntry, reason: String, skipped: bool) {
+        self.warnings.push(ClippingsWarning {
+            offset: entry.offset,
+            line: entry.line,
+            first_line: entry.first_line.clone(),
+            reason,
+            skipped,
+        });
+    }
+}
+
+impl<R: BufRead> Iterator for ClippingsReader<R> {
+    type Item = Result<Clipping, KindleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let entry = match self.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+
+            match parse_clipping_entry(&entry.text, self.timezone) {
+                Ok((clipping, problems)) => {
+                    for reason in problems {
+                        self.warn(&entry, reason, false);
+                    }
+                    return Some(Ok(clipping));
+                }
+                Err(reason) => self.warn(&entry, reason, true),
+            }
+        }
+
+        self.done = true;
+        None
+    }
+}
+
+/// Decode a clippings file as UTF-8 or UTF-16, dropping any byte order mark
+///
+/// Some older devices and tools save the file as UTF-16; that is detected from its BOM,
+/// or without one from the zero bytes ASCII text has in every other position.
+pub fn decode_clippings(bytes: &[u8]) -> Result<String, std::io::Error> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8(rest.to_vec()).map_err(|_| invalid("clippings file is not valid UTF-8"));
+    }
+
+    let Some((data, big_endian)) = utf16_data(bytes) else {
+        return String::from_utf8(bytes.to_vec()).map_err(|_| invalid("clippings file is not valid UTF-8"));
+    };
+
+    if data.len() % 2 != 0 {
+        return Err(invalid("clippings file has a truncated UTF-16 character"));
+    }
+
+    let units = data.chunks_exact(2).map(|pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    });
+
+    let content: String = char::decode_utf16(units)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid("clippings file is not valid UTF-16"))?;
+
+    Ok(content.trim_start_matches('\u{feff}').to_string())
+}
+
+/// UTF-16 data after any BOM and whether it is big-endian, if the bytes are UTF-16
+fn utf16_data(bytes: &[u8]) -> Option<(&[u8], bool)> {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => Some((rest, false)),
+        [0xFE, 0xFF, rest @ ..] => Some((rest, true)),
+        [_, 0, ..] if looks_like_utf16(bytes, false) => Some((bytes, false)),
+        [0, _, ..] if looks_like_utf16(bytes, true) => Some((bytes, true)),
+        _ => None,
+    }
+}
+
+/// Whether BOM-less bytes look like UTF-16: the high byte of most leading units is zero
+fn looks_like_utf16(bytes: &[u8], big_endian: bool) -> bool {
+    let sample: Vec<&[u8]> = bytes.chunks_exact(2).take(64).collect();
+    let high = if big_endian { 0 } else { 1 };
+    let zeros = sample.iter().filter(|pair| pair[high] == 0).count();
+
+    !sample.is_empty() && zeros * 4 >= sample.len() * 3
+}
+
+/// Expand clippings paths into the files to parse
+///
+/// Files are kept as given; a directory contributes every `*.txt` directly inside it,
+/// so a folder of backed-up snapshots can be imported at once.
+pub fn clippings_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, KindleError> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if !path.exists() {
+            return Err(KindleError::ClippingsFileNotFound(path.clone()));
+        }
+
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(KindleError::ClippingsReadError)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.is_file()
+                        && p.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("txt"))
+                })
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
 
-    parse_clippings_content(&content)
+    Ok(files)
 }
 
-/// Parse the content of a clippings file
+/// Parse the content of a clippings file, reading dates as UTC
 pub fn parse_clippings_content(content: &str) -> Result<Vec<Book>, KindleError> {
-    let entries = content.split("==========").filter(|s| !s.trim().is_empty());
+    parse_clippings_content_in(content, chrono_tz::UTC)
+}
 
-    let mut books_map: HashMap<String, Book> = HashMap::new();
+/// Parse the content of a clippings file, reading dates in the given timezone
+///
+/// A leading byte order mark is ignored and CRLF/CR line endings are normalized.
+pub fn parse_clippings_content_in(content: &str, timezone: Tz) -> Result<Vec<Book>, KindleError> {
+    Ok(parse_clippings_content_report(content, timezone).books)
+}
+
+/// Parse the content of a clippings file, reporting entries that didn't parse cleanly
+///
+/// Malformed entries (truncated, missing their metadata line, or run together with the
+/// next one by a missing separator) are skipped or kept partially, each with a warning.
+pub fn parse_clippings_content_report(content: &str, timezone: Tz) -> ClippingsReport {
+    collect_report(ClippingsReader::new(content.as_bytes(), timezone)).expect("in-memory content is valid UTF-8")
+}
+
+/// Group the clippings of a reader into books
+fn collect_report<R: BufRead>(mut reader: ClippingsReader<R>) -> Result<ClippingsReport, KindleError> {
+    let mut books_map: HashMap<String, (Book, Vec<Clipping>)> = HashMap::new();
+    let mut duplicates = 0;
 
-    for entry in entries {
-        if let Some(clipping) = parse_clipping_entry(entry) {
-            let book_id = generate_book_id(&clipping.book_title, clipping.author.as_deref());
+    for clipping in &mut reader {
+        let clipping = clipping?;
+        let book_id = generate_book_id(&clipping.book_title, clipping.author.as_deref());
 
-            let book = books_map.entry(book_id.clone()).or_insert_with(|| Book {
-                id: book_id,
+        let (_, clippings) = books_map.entry(book_id.clone()).or_insert_with(|| {
+            let book = Book {
+                id: book_id.clone(),
                 title: clipping.book_title.clone(),
                 author: clipping.author.clone(),
                 sources: vec![Source::Kindle],
                 highlights: Vec::new(),
                 finished: None,
                 finished_at: None,
-            });
+                url: None,
+                isbn: None,
+                collections: Vec::new(),
+                unavailable_highlights: 0,
+                truncated: false,
+                original_title: clipping.original_title.clone(),
+                aliases: Vec::new(),
+                cover_url: None,
+                source_ids: BTreeMap::new(),
+                document_type: None,
+                progress: None,
+                last_opened_at: None,
+                language: None,
+                genre: None,
+            };
+            (book, Vec::new())
+        });
+
+        clippings.push(clipping);
+    }
+
+    let books = books_map
+        .into_values()
+        .map(|(mut book, clippings)| {
+            let (clippings, dropped) = dedupe_clippings(clippings);
+            duplicates += dropped;
+
+            let (placeholders, clippings): (Vec<Clipping>, Vec<Clipping>) =
+                clippings.into_iter().partition(|c| is_clipping_limit_placeholder(&c.content));
+
+            book.unavailable_highlights = placeholders.len();
+            book.highlights = build_highlights(&book.id, clippings);
+            book.sort_highlights_by_position();
+            book
+        })
+        .collect();
+
+    Ok(ClippingsReport {
+        books,
+        warnings: reader.into_warnings(),
+        duplicates,
+    })
+}
+
+/// Drop verbatim copies of a clipping, keeping the earliest "Added on" date
+///
+/// Copying books between devices or restoring one appends the same entries again.
+/// Returns the remaining clippings in file order and how many were dropped.
+fn dedupe_clippings(clippings: Vec<Clipping>) -> (Vec<Clipping>, usize) {
+    let mut seen: HashMap<(ClippingType, String, Option<String>, Option<String>), usize> = HashMap::new();
+    let mut kept: Vec<Clipping> = Vec::new();
+    let mut dropped = 0;
+
+    for clipping in clippings {
+        let key = (
+            clipping.clipping_type,
+            crate::merge::normalize_text(&clipping.content),
+            clipping.location.clone(),
+            clipping.page.clone(),
+        );
+
+        match seen.get(&key) {
+            Some(&i) => {
+                dropped += 1;
+                let existing = &mut kept[i];
+                if let Some(added_on) = clipping.added_on {
+                    if existing.added_on.is_none_or(|current| added_on < current) {
+                        existing.added_on = Some(added_on);
+                    }
+                }
+            }
+            None => {
+                seen.insert(key, kept.len());
+                kept.push(clipping);
+            }
+        }
+    }
+
+    (kept, dropped)
+}
 
-            // Only add highlights, skip bookmarks
-            if clipping.clipping_type == ClippingType::Highlight
-                || clipping.clipping_type == ClippingType::Note
-            {
-                let highlight = Highlight {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    text: clipping.content,
-                    note: if clipping.clipping_type == ClippingType::Note {
-                        None // Notes have the text as the main content
-                    } else {
-                        None
-                    },
-                    location: Location {
-                        chapter: None,
-                        position: clipping.location,
-                    },
-                    created_at: clipping.added_on,
-                    source: Source::Kindle,
+/// Turn one book's clippings into highlights, attaching notes to what they annotate
+///
+/// Kindle writes a note as its own entry located at the end of the highlighted range,
+/// so each note goes to the highlight whose range contains its location, preferring one
+/// that ends exactly there. Notes with no such highlight are kept as standalone notes.
+/// Bookmarks are kept with empty text.
+fn build_highlights(book_id: &str, clippings: Vec<Clipping>) -> Vec<Highlight> {
+    let (notes, clippings): (Vec<Clipping>, Vec<Clipping>) =
+        clippings.into_iter().partition(|c| c.clipping_type == ClippingType::Note);
+
+    let mut highlights: Vec<Highlight> = clippings
+        .into_iter()
+        .map(|c| match c.clipping_type {
+            ClippingType::Bookmark => {
+                let bookmark = Clipping {
+                    content: String::new(),
+                    ..c
                 };
-                book.highlights.push(highlight);
+                make_highlight(book_id, bookmark, HighlightKind::Bookmark)
+            }
+            _ => make_highlight(book_id, c, HighlightKind::Highlight),
+        })
+        .collect();
+
+    for note in notes {
+        let target = highlights
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.kind == HighlightKind::Highlight)
+            .filter_map(|(i, h)| note_distance(&note, h).map(|distance| (i, distance)))
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(i, _)| i);
+
+        match target {
+            Some(i) => {
+                let highlight = &mut highlights[i];
+                highlight.note = Some(match highlight.note.take() {
+                    Some(existing) => format!("{}\n\n{}", existing, note.content),
+                    None => note.content,
+                });
             }
+            None => highlights.push(make_highlight(book_id, note, HighlightKind::Note)),
         }
     }
 
-    Ok(books_map.into_values().collect())
+    highlights
+}
+
+/// How far before the end of a highlight's range a note sits, if it falls within it
+///
+/// Locations are compared when both have one, otherwise pages.
+fn note_distance(note: &Clipping, highlight: &Highlight) -> Option<u64> {
+    let (note_position, range) = [
+        (note.location.as_deref(), highlight.location.position.as_deref()),
+        (note.page.as_deref(), highlight.location.page.as_deref()),
+    ]
+    .into_iter()
+    .find_map(|pair| match pair {
+        (Some(note_position), Some(range)) => Some((note_position, range)),
+        _ => None,
+    })?;
+
+    let (_, position) = parse_position_range(note_position)?;
+    let (start, end) = parse_position_range(range)?;
+
+    if (start..=end).contains(&position) {
+        Some(end - position)
+    } else {
+        None
+    }
+}
+
+fn make_highlight(book_id: &str, clipping: Clipping, kind: HighlightKind) -> Highlight {
+    // A standalone note keeps its body as the note, not as highlighted text
+    let (text, note) = match kind {
+        HighlightKind::Note => (String::new(), Some(clipping.content.clone())),
+        _ => (clipping.content.clone(), None),
+    };
+
+    Highlight {
+        id: generate_highlight_id(
+            book_id,
+            &clipping.content,
+            clipping.location.as_deref().or(clipping.page.as_deref()),
+        ),
+        text,
+        note,
+        location: Location {
+            chapter: None,
+            position: clipping.location,
+            page: clipping.page,
+            raw: None,
+        },
+        created_at: clipping.added_on,
+        sources: vec![Source::Kindle],
+        color: None,
+        kind,
+        style: HighlightStyle::Highlight,
+        context: None,
+        deleted_upstream: false,
+    }
+}
+
+/// Whether a clipping's text is the placeholder Kindle writes once a publisher's
+/// clipping limit is reached, e.g. "<You have reached the clipping limit for this item>"
+///
+/// The wording varies by firmware and language, so any short text wrapped in angle
+/// brackets that mentions a clipping limit matches.
+fn is_clipping_limit_placeholder(text: &str) -> bool {
+    let text = text.trim();
+    let Some(inner) = text.strip_prefix('<').and_then(|t| t.strip_suffix('>')) else {
+        return false;
+    };
+
+    let inner = inner.to_lowercase();
+    [
+        "clipping limit",
+        "clipping-limit",
+        "clippinglimit",
+        "limite de",
+        "límite de",
+        "limite di",
+        "limite máximo",
+        "上限",
+    ]
+    .iter()
+    .any(|phrase| inner.contains(phrase))
 }
 
-#[derive(Debug, PartialEq)]
-enum ClippingType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClippingType {
     Highlight,
     Note,
     Bookmark,
 }
 
+/// One entry of a clippings file
+#[derive(Debug)]
+pub struct Clipping {
+    pub book_title: String,
+    /// Raw title, when the title was cleaned up
+    pub original_title: Option<String>,
+    pub author: Option<String>,
+    pub clipping_type: ClippingType,
+    pub location: Option<String>,
+    pub page: Option<String>,
+    pub added_on: Option<DateTime<Utc>>,
+    pub content: String,
+}
+
+/// Fields of a metadata line
 #[derive(Debug)]
-struct Clipping {
-    book_title: String,
-    author: Option<String>,
+struct Metadata {
     clipping_type: ClippingType,
     location: Option<String>,
+    page: Option<String>,
     added_on: Option<DateTime<Utc>>,
-    content: String,
 }
 
 /// Parse a single clipping entry
-fn parse_clipping_entry(entry: &str) -> Option<Clipping> {
+///
+/// Returns the reason when the entry can't be used, otherwise the clipping along with
+/// any problems with the parts that were kept.
+fn parse_clipping_entry(entry: &str, timezone: Tz) -> Result<(Clipping, Vec<String>), String> {
     let lines: Vec<&str> = entry.trim().lines().collect();
 
     if lines.len() < 2 {
-        return None;
+        return Err("truncated entry without a metadata line".to_string());
     }
 
     // First line: Book Title (Author Name)
-    let (book_title, author) = parse_title_author(lines[0]);
+    let (raw_title, author) = parse_title_author(lines[0]);
+    let book_title = normalize_title(&raw_title);
+    let original_title = (book_title != raw_title).then_some(raw_title);
 
     // Second line: - Your Highlight on Location 123-145 | Added on Monday, January 1, 2024
-    let (clipping_type, location, added_on) = parse_metadata(lines[1])?;
+    let metadata = parse_metadata(lines[1], timezone)
+        .ok_or_else(|| format!("unrecognized metadata line {:?}", lines[1].trim()))?;
 
     // Rest is the content (skip empty lines at the start)
-    let content_lines: Vec<&str> = lines[2..].iter().skip_while(|l| l.is_empty()).copied().collect();
+    let mut content_lines: Vec<&str> = lines[2..].iter().skip_while(|l| l.is_empty()).copied().collect();
+    let mut problems = Vec::new();
+
+    // A missing separator runs the next entry into this one; keep only this entry's text
+    if let Some(next) = content_lines.iter().position(|line| is_metadata_line(line)) {
+        problems.push("entry runs into the next one (missing separator)".to_string());
+        content_lines.truncate(next.saturating_sub(1));
+    }
+
     let content = content_lines.join("\n").trim().to_string();
 
-    if content.is_empty() && clipping_type != ClippingType::Bookmark {
-        return None;
+    if content.is_empty() && metadata.clipping_type != ClippingType::Bookmark {
+        return Err("entry has no text".to_string());
+    }
+
+    if metadata.added_on.is_none() {
+        problems.push("missing or unreadable date".to_string());
     }
 
-    Some(Clipping {
+    let clipping = Clipping {
         book_title,
+        original_title,
         author,
-        clipping_type,
-        location,
-        added_on,
+        clipping_type: metadata.clipping_type,
+        location: metadata.location,
+        page: metadata.page,
+        added_on: metadata.added_on,
         content,
-    })
+    };
+
+    Ok((clipping, problems))
+}
+
+/// Whether a line looks like a clipping metadata line ("- Your Highlight on ...")
+fn is_metadata_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("- ") && detect_language(line).is_some() && (extract_location(line).is_some() || extract_page(line).is_some())
 }
 
 /// Parse the title and author from the first line
@@ -127,7 +680,8 @@ fn parse_title_author(line: &str) -> (String, Option<String>) {
     let line = line.trim();
 
     // Match pattern: "Title (Author)"
-    let re = Regex::new(r"^(.+?)\s*\(([^)]+)\)\s*$").unwrap();
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^(.+?)\s*\(([^)]+)\)\s*$").unwrap());
 
     if let Some(caps) = re.captures(line) {
         let title = caps.get(1).map(|m| m.as_str().trim().to_string()).unwrap();
@@ -139,97 +693,554 @@ fn parse_title_author(line: &str) -> (String, Option<String>) {
     }
 }
 
-/// Parse the metadata line (type, location, date)
-fn parse_metadata(line: &str) -> Option<(ClippingType, Option<String>, Option<DateTime<Utc>>)> {
+/// Clean up the file-name-like titles sideloaded documents get
+///
+/// A title counts as sideloaded when it has underscores, a file extension such as
+/// "_epub" or ".pdf", or bracketed download-site tags like "(z-lib)". Those get the
+/// extension and bracketed parts dropped, separators turned into spaces and are
+/// title-cased, so "walden_or_life-in-the-woods_epub (z-lib)" becomes "Walden or Life in
+/// the Woods". Other titles are returned unchanged.
+pub fn normalize_title(raw: &str) -> String {
+    let raw = raw.trim();
+
+    static BRACKETS: OnceLock<Regex> = OnceLock::new();
+    static EXTENSION: OnceLock<Regex> = OnceLock::new();
+    static JUNK: OnceLock<Regex> = OnceLock::new();
+    let brackets = BRACKETS.get_or_init(|| Regex::new(r"\s*(?:\([^()]*\)|\[[^\[\]]*\]|\{[^{}]*\})").unwrap());
+    let extension = EXTENSION.get_or_init(|| Regex::new(r"(?i)[._-]+(?:pdf|epub|mobi|azw3?|kfx|txt)$").unwrap());
+    let junk = JUNK.get_or_init(|| Regex::new(r"(?i)z-?lib|libgen|anna'?s archive|oceanofpdf|\.(?:com|org|net)\b").unwrap());
+
+    let without_brackets = brackets.replace_all(raw, "").trim().to_string();
+    let has_junk = brackets.find_iter(raw).any(|m| junk.is_match(m.as_str()));
+
+    if !raw.contains('_') && !extension.is_match(&without_brackets) && !has_junk {
+        return raw.to_string();
+    }
+
+    let mut title = without_brackets;
+    while extension.is_match(&title) {
+        title = extension.replace(&title, "").to_string();
+    }
+
+    let words: Vec<String> = title
+        .split(['_', '-', ' '])
+        .filter(|w| !w.is_empty())
+        .enumerate()
+        .map(|(i, word)| title_case_word(word, i == 0))
+        .collect();
+
+    if words.is_empty() {
+        return raw.to_string();
+    }
+
+    words.join(" ")
+}
+
+fn title_case_word(word: &str, first: bool) -> String {
+    const SMALL_WORDS: [&str; 12] = ["a", "an", "and", "as", "at", "by", "for", "in", "of", "on", "or", "the"];
+
+    let lower = word.to_lowercase();
+    if !first && SMALL_WORDS.contains(&lower.as_str()) {
+        return lower;
+    }
+
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => lower,
+    }
+}
+
+/// Rename books whose title matches a configured alias
+///
+/// Aliases map a raw or cleaned-up title (ignoring case) to the title to use instead.
+/// Renamed books get new IDs, so they merge with the same book from other sources;
+/// books that end up with the same ID are combined.
+pub fn apply_title_aliases(books: Vec<Book>, aliases: &BTreeMap<String, String>) -> Vec<Book> {
+    if aliases.is_empty() {
+        return books;
+    }
+
+    let lookup: HashMap<String, &String> = aliases.iter().map(|(k, v)| (k.trim().to_lowercase(), v)).collect();
+
+    let renamed = books.into_iter().map(|mut book| {
+        let alias = [Some(&book.title), book.original_title.as_ref()]
+            .into_iter()
+            .flatten()
+            .find_map(|title| lookup.get(&title.trim().to_lowercase()).copied());
+
+        let Some(alias) = alias.filter(|alias| **alias != book.title) else {
+            return book;
+        };
+
+        let original = book.original_title.take().unwrap_or_else(|| book.title.clone());
+        book.original_title = Some(original);
+        book.title = alias.clone();
+        book.id = generate_book_id(&book.title, book.author.as_deref());
+
+        for highlight in &mut book.highlights {
+            let position = highlight.location.position.as_deref().or(highlight.location.page.as_deref());
+            highlight.id = generate_highlight_id(&book.id, highlight.body(), position);
+        }
+
+        book
+    });
+
+    crate::merge::merge_books(renamed.map(|book| vec![book]).collect())
+}
+
+/// Parse the metadata line (type, location, page, date)
+///
+/// Newer firmware writes both a page and a location ("- Your Highlight on page 45 |
+/// Location 678-690 | Added on ..."); either may be missing.
+fn parse_metadata(line: &str, timezone: Tz) -> Option<Metadata> {
     let line = line.trim();
 
-    // Determine clipping type
-    let clipping_type = if line.contains("Highlight") {
-        ClippingType::Highlight
-    } else if line.contains("Note") {
-        ClippingType::Note
-    } else if line.contains("Bookmark") {
-        ClippingType::Bookmark
-    } else {
-        return None;
-    };
+    // Determine clipping type (and the Kindle's interface language)
+    let (_, clipping_type) = detect_language(line)?;
+
+    Some(Metadata {
+        clipping_type,
+        location: extract_location(line),
+        page: extract_page(line),
+        added_on: extract_date(line, timezone),
+    })
+}
+
+/// Keywords a Kindle writes into the metadata line in one interface language
+struct Language {
+    highlight: &'static str,
+    note: &'static str,
+    bookmark: &'static str,
+    /// Pattern capturing the location number or range
+    location: &'static str,
+    /// Pattern capturing the page number or range
+    page: &'static str,
+    /// Text preceding the date
+    added_on: &'static str,
+    /// Lowercase month names; empty for languages with numeric dates (年月日)
+    months: [&'static str; 12],
+}
+
+const NUMERIC_MONTHS: [&str; 12] = [""; 12];
+
+/// Supported interface languages; English is tried first
+const LANGUAGES: &[Language] = &[
+    Language {
+        highlight: "Highlight",
+        note: "Note",
+        bookmark: "Bookmark",
+        location: r"(?i)(?:Location|Loc\.)\s*(\d+(?:-\d+)?)",
+        page: r"(?i)\bpage\s*(\d+(?:-\d+)?)",
+        added_on: "Added on",
+        months: [
+            "january", "february", "march", "april", "may", "june",
+            "july", "august", "september", "october", "november", "december",
+        ],
+    },
+    // Deutsch: "- Ihre Markierung bei Position 12-14 | Hinzugefügt am Montag, 1. Januar 2024 13:05:09"
+    Language {
+        highlight: "Markierung",
+        note: "Notiz",
+        bookmark: "Lesezeichen",
+        location: r"(?i)Position\s*(\d+(?:-\d+)?)",
+        page: r"(?i)Seite\s*(\d+(?:-\d+)?)",
+        added_on: "Hinzugefügt am",
+        months: [
+            "januar", "februar", "märz", "april", "mai", "juni",
+            "juli", "august", "september", "oktober", "november", "dezember",
+        ],
+    },
+    // Français: "- Votre surlignement à l'emplacement 12-14 | Ajouté le lundi 1 janvier 2024 13:05:09"
+    Language {
+        highlight: "surlignement",
+        note: "note",
+        bookmark: "signet",
+        location: r"(?i)emplacement\s*(\d+(?:-\d+)?)",
+        page: r"(?i)\bpage\s*(\d+(?:-\d+)?)",
+        added_on: "Ajouté le",
+        months: [
+            "janvier", "février", "mars", "avril", "mai", "juin",
+            "juillet", "août", "septembre", "octobre", "novembre", "décembre",
+        ],
+    },
+    // Español: "- Tu subrayado en la posición 12-14 | Añadido el lunes, 1 de enero de 2024 13:05:09"
+    Language {
+        highlight: "subrayado",
+        note: "nota",
+        bookmark: "marcador",
+        location: r"(?i)posición\s*(\d+(?:-\d+)?)",
+        page: r"(?i)página\s*(\d+(?:-\d+)?)",
+        added_on: "Añadido el",
+        months: [
+            "enero", "febrero", "marzo", "abril", "mayo", "junio",
+            "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+        ],
+    },
+    // Italiano: "- La tua evidenziazione alla posizione 12-14 | Aggiunto in data lunedì 1 gennaio 2024 13:05:09"
+    Language {
+        highlight: "evidenziazione",
+        note: "nota",
+        bookmark: "segnalibro",
+        location: r"(?i)posizione\s*(\d+(?:-\d+)?)",
+        page: r"(?i)pagina\s*(\d+(?:-\d+)?)",
+        added_on: "Aggiunto",
+        months: [
+            "gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno",
+            "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre",
+        ],
+    },
+    // Português: "- Seu destaque na posição 12-14 | Adicionado: segunda-feira, 1 de janeiro de 2024 13:05:09"
+    Language {
+        highlight: "destaque",
+        note: "nota",
+        bookmark: "marcador",
+        location: r"(?i)posição\s*(\d+(?:-\d+)?)",
+        page: r"(?i)página\s*(\d+(?:-\d+)?)",
+        added_on: "Adicionado",
+        months: [
+            "janeiro", "fevereiro", "março", "abril", "maio", "junho",
+            "julho", "agosto", "setembro", "outubro", "novembro", "dezembro",
+        ],
+    },
+    // 日本語: "- 位置No. 12-14のハイライト |作成日: 2024年1月1日月曜日 13:05:09"
+    Language {
+        highlight: "ハイライト",
+        note: "メモ",
+        bookmark: "ブックマーク",
+        location: r"位置No\.\s*(\d+(?:-\d+)?)",
+        page: r"(\d+(?:-\d+)?)\s*ページ",
+        added_on: "作成日",
+        months: NUMERIC_MONTHS,
+    },
+    // 中文: "- 您在位置 #12-14的标注 | 添加于 2024年1月1日星期一 下午1:05:09"
+    Language {
+        highlight: "标注",
+        note: "笔记",
+        bookmark: "书签",
+        location: r"位置\s*#?\s*(\d+(?:-\d+)?)",
+        page: r"第\s*(\d+(?:-\d+)?)\s*页",
+        added_on: "添加于",
+        months: NUMERIC_MONTHS,
+    },
+];
+
+impl Language {
+    fn clipping_type(&self, line: &str) -> Option<ClippingType> {
+        if line.contains(self.highlight) {
+            Some(ClippingType::Highlight)
+        } else if line.contains(self.note) {
+            Some(ClippingType::Note)
+        } else if line.contains(self.bookmark) {
+            Some(ClippingType::Bookmark)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the line uses this language's location or page wording
+    fn recognizes_position(&self, line: &str) -> bool {
+        position_regex(self.location).is_match(line) || position_regex(self.page).is_match(line)
+    }
+}
 
-    // Extract location
-    let location = extract_location(line);
+/// Work out the language of a metadata line and the clipping type it describes
+///
+/// Detection is per line, so a file from a device whose language was changed part-way
+/// through still parses. Several languages share type words (e.g. "nota"), in which
+/// case the one whose date wording, then location wording, also matches wins.
+fn detect_language(line: &str) -> Option<(&'static Language, ClippingType)> {
+    let mut candidates = LANGUAGES
+        .iter()
+        .filter_map(|language| language.clipping_type(line).map(|t| (language, t)))
+        .collect::<Vec<_>>();
 
-    // Extract date
-    let added_on = extract_date(line);
+    let best = candidates
+        .iter()
+        .position(|(language, _)| line.contains(language.added_on))
+        .or_else(|| candidates.iter().position(|(language, _)| language.recognizes_position(line)))
+        .unwrap_or(0);
 
-    Some((clipping_type, location, added_on))
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(candidates.swap_remove(best))
+    }
 }
 
-/// Extract location from metadata line
+/// Extract location from metadata line, e.g. "123-145" from "Location 123-145"
 fn extract_location(line: &str) -> Option<String> {
-    // Match patterns like "Location 123-145" or "Location 123" or "page 45"
-    let re = Regex::new(r"(?i)(?:Location|Loc\.|page)\s*(\d+(?:-\d+)?)").unwrap();
+    let (language, _) = detect_language(line)?;
+    capture_number(language.location, line)
+}
+
+/// Extract the page from metadata line, e.g. "45" from "on page 45"
+fn extract_page(line: &str) -> Option<String> {
+    let (language, _) = detect_language(line)?;
+    capture_number(language.page, line)
+}
 
-    re.captures(line)
+/// The compiled location or page pattern of a language
+///
+/// Every language's patterns are compiled together the first time one is needed.
+fn position_regex(pattern: &str) -> &'static Regex {
+    static REGEXES: OnceLock<HashMap<&'static str, Regex>> = OnceLock::new();
+    let regexes = REGEXES.get_or_init(|| {
+        LANGUAGES
+            .iter()
+            .flat_map(|language| [language.location, language.page])
+            .map(|pattern| (pattern, Regex::new(pattern).unwrap()))
+            .collect()
+    });
+    &regexes[pattern]
+}
+
+fn capture_number(pattern: &'static str, line: &str) -> Option<String> {
+    position_regex(pattern)
+        .captures(line)
         .and_then(|caps| caps.get(1))
         .map(|m| m.as_str().to_string())
 }
 
-/// Extract date from metadata line
-fn extract_date(line: &str) -> Option<DateTime<Utc>> {
-    // Match pattern: "Added on Day, Month DD, YYYY HH:MM:SS AM/PM"
-    // or "Added on Day, DD Month YYYY HH:MM:SS" (international format)
-
-    // Try common formats
-    let date_patterns = [
-        // "Added on Monday, January 1, 2024 12:00:00 PM"
-        r"Added on \w+,\s*(\w+)\s+(\d+),\s*(\d{4})",
-        // "Added on Monday, 1 January 2024"
-        r"Added on \w+,\s*(\d+)\s+(\w+)\s+(\d{4})",
-    ];
-
-    for pattern in date_patterns {
-        let re = Regex::new(pattern).ok()?;
-        if let Some(caps) = re.captures(line) {
-            // Try to parse the date
-            // This is simplified - full implementation would handle all cases
-            if let Some(year) = caps.get(3) {
-                if let Ok(year_num) = year.as_str().parse::<i32>() {
-                    // Return a rough date (just the year for now)
-                    if let Some(dt) = Utc.with_ymd_and_hms(year_num, 1, 1, 0, 0, 0).single() {
-                        return Some(dt);
-                    }
-                }
-            }
-        }
+/// Extract the "Added on" date from a metadata line
+///
+/// Handles the US ("Monday, January 1, 2024 1:05:09 PM") and day-first
+/// ("Montag, 1. Januar 2024 13:05:09", "lunes, 1 de enero de 2024 13:05:09") orderings
+/// with localized month names, and numeric CJK dates ("2024年1月1日 下午1:05:09"), with or
+/// without a time. Kindles write device-local time, which is interpreted in `timezone`.
+fn extract_date(line: &str, timezone: Tz) -> Option<DateTime<Utc>> {
+    let (language, _) = detect_language(line)?;
+    let start = line.find(language.added_on)? + language.added_on.len();
+    let value = line[start..].to_lowercase();
+
+    let (date, rest) = if language.months == NUMERIC_MONTHS {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new(r"(\d{4})\s*年\s*(\d{1,2})\s*月\s*(\d{1,2})\s*日").unwrap());
+        let caps = re.captures(&value)?;
+        let date = NaiveDate::from_ymd_opt(caps[1].parse().ok()?, caps[2].parse().ok()?, caps[3].parse().ok()?)?;
+        (date, &value[caps.get(0)?.end()..])
+    } else {
+        let month_number = |name: &str| language.months.iter().position(|m| *m == name).map(|i| i as u32 + 1);
+
+        static DAY_FIRST: OnceLock<Regex> = OnceLock::new();
+        static MONTH_FIRST: OnceLock<Regex> = OnceLock::new();
+        let day_first = DAY_FIRST.get_or_init(|| Regex::new(r"(\d{1,2})(?:\.|er)?\s+(?:de\s+)?(\p{L}+)\s+(?:de\s+)?(\d{4})").unwrap());
+        let month_first = MONTH_FIRST.get_or_init(|| Regex::new(r"(\p{L}+)\s+(\d{1,2}),?\s+(\d{4})").unwrap());
+
+        let found = day_first
+            .captures(&value)
+            .and_then(|caps| Some((caps[1].parse().ok()?, month_number(&caps[2])?, caps[3].parse().ok()?, caps.get(0)?.end())))
+            .or_else(|| {
+                month_first.captures(&value).and_then(|caps| {
+                    Some((caps[2].parse().ok()?, month_number(&caps[1])?, caps[3].parse().ok()?, caps.get(0)?.end()))
+                })
+            });
+        let (day, month, year, end) = found?;
+        (NaiveDate::from_ymd_opt(year, month, day)?, &value[end..])
+    };
+
+    let naive = date.and_time(extract_time(rest).unwrap_or(NaiveTime::MIN));
+
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Parse the time following a date, in 24-hour or 12-hour form
+///
+/// 12-hour times are marked by AM/PM, 上午/下午 or 午前/午後.
+fn extract_time(value: &str) -> Option<NaiveTime> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(\d{1,2}):(\d{2})(?::(\d{2}))?").unwrap());
+    let caps = re.captures(value)?;
+
+    let mut hour: u32 = caps[1].parse().ok()?;
+    let minute = caps[2].parse().ok()?;
+    let second = caps.get(3).map_or(Some(0), |m| m.as_str().parse().ok())?;
+
+    static PM: OnceLock<Regex> = OnceLock::new();
+    static AM: OnceLock<Regex> = OnceLock::new();
+    let is_pm = PM.get_or_init(|| Regex::new(r"\bpm\b|下午|午後").unwrap()).is_match(value);
+    let is_am = AM.get_or_init(|| Regex::new(r"\bam\b|上午|午前").unwrap()).is_match(value);
+    if is_pm && hour < 12 {
+        hour += 12;
+    } else if is_am && hour == 12 {
+        hour = 0;
     }
 
-    None
+    NaiveTime::from_hms_opt(hour, minute, second)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merge::merge_books;
 
-    #[test]
-    fn test_parse_title_author() {
-        let (title, author) = parse_title_author("The Great Gatsby (F. Scott Fitzgerald)");
-        assert_eq!(title, "The Great Gatsby");
-        assert_eq!(author, Some("F. Scott Fitzgerald".to_string()));
-
-        let (title, author) = parse_title_author("Some Book Without Author");
-        assert_eq!(title, "Some Book Without Author");
-        assert_eq!(author, None);
-    }
+    const GERMAN: &str = "Der Process (Franz Kafka)
+- Ihre Markierung bei Position 120-122 | Hinzugefügt am Montag, 1. Januar 2024 13:05:09
 
-    #[test]
-    fn test_parse_clippings_content() {
-        let content = r#"
-The Great Gatsby (F. Scott Fitzgerald)
-- Your Highlight on Location 123-145 | Added on Monday, January 1, 2024
+Jemand mußte Josef K. verleumdet haben.
+==========
+Der Process (Franz Kafka)
+- Ihre Notiz auf Seite 4 | bei Position 310 | Hinzugefügt am Dienstag, 12. März 2024 08:00:00
 
-In my younger and more vulnerable years my father gave me some advice.
+Der berühmte erste Satz
 ==========
-The Great Gatsby (F. Scott Fitzgerald)
-- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+Der Process (Franz Kafka)
+- Ihr Lesezeichen bei Position 300 | Hinzugefügt am Dienstag, 12. März 2024 08:01:00
+
+
+==========
+";
+
+    const FRENCH: &str = "L'Étranger (Albert Camus)
+- Votre surlignement sur la page 9 | emplacement 120-122 | Ajouté le lundi 1 janvier 2024 13:05:09
+
+Aujourd'hui, maman est morte.
+==========
+L'Étranger (Albert Camus)
+- Votre note à l'emplacement 310 | Ajouté le mardi 12 mars 2024 08:00:00
+
+Ou peut-être hier
+==========
+";
+
+    const SPANISH: &str = "Cien años de soledad (Gabriel García Márquez)
+- Tu subrayado en la posición 120-122 | Añadido el lunes, 1 de enero de 2024 13:05:09
+
+Muchos años después, frente al pelotón de fusilamiento...
+==========
+Cien años de soledad (Gabriel García Márquez)
+- Tu nota en la posición 310 | Añadido el martes, 12 de marzo de 2024 8:00:00
+
+Macondo
+==========
+";
+
+    const ITALIAN: &str = "Il nome della rosa (Umberto Eco)
+- La tua evidenziazione alla posizione 120-122 | Aggiunto in data lunedì 1 gennaio 2024 13:05:09
+
+Era una bella mattina di fine novembre.
+==========
+Il nome della rosa (Umberto Eco)
+- La tua nota alla posizione 310 | Aggiunto in data martedì 12 marzo 2024 08:00:00
+
+Adso
+==========
+";
+
+    const PORTUGUESE: &str = "Dom Casmurro (Machado de Assis)
+- Seu destaque na posição 120-122 | Adicionado: segunda-feira, 1 de janeiro de 2024 13:05:09
+
+Uma noite destas, vindo da cidade para o Engenho Novo...
+==========
+Dom Casmurro (Machado de Assis)
+- Sua nota na posição 310 | Adicionado: terça-feira, 12 de março de 2024 08:00:00
+
+Capitu
+==========
+";
+
+    const JAPANESE: &str = "吾輩は猫である (夏目漱石)
+- 位置No. 120-122のハイライト |作成日: 2024年1月1日月曜日 13:05:09
+
+吾輩は猫である。名前はまだ無い。
+==========
+吾輩は猫である (夏目漱石)
+- 12ページ|位置No. 310のメモ |作成日: 2024年3月12日火曜日 8:00:00
+
+有名な書き出し
+==========
+";
+
+    const CHINESE: &str = "呐喊 (鲁迅)
+- 您在位置 #120-122的标注 | 添加于 2024年1月1日星期一 下午1:05:09
+
+其实地上本没有路，走的人多了，也便成了路。
+==========
+呐喊 (鲁迅)
+- 您在第 12 页（位置 #310）的笔记 | 添加于 2024年3月12日星期二 上午8:00:00
+
+故乡
+==========
+";
+
+    /// Parse a single-book fixture, with highlights in date order
+    fn parse_fixture(content: &str) -> Book {
+        let mut books = parse_clippings_content(content).unwrap();
+        assert_eq!(books.len(), 1);
+        let mut book = books.remove(0);
+        book.highlights.sort_by_key(|h| h.created_at);
+        book
+    }
+
+    #[test]
+    fn test_parse_localized_clippings() {
+        let first = Utc.with_ymd_and_hms(2024, 1, 1, 13, 5, 9).single();
+        let second = Utc.with_ymd_and_hms(2024, 3, 12, 8, 0, 0).single();
+
+        for content in [GERMAN, FRENCH, SPANISH, ITALIAN, PORTUGUESE, JAPANESE, CHINESE] {
+            let mut book = parse_fixture(content);
+            assert!(book.author.is_some(), "{}", book.title);
+
+            // The note is past the highlight so stands alone
+            book.highlights.retain(|h| h.kind != HighlightKind::Bookmark);
+            assert_eq!(book.highlights.len(), 2, "{}", book.title);
+            assert_eq!(book.highlights[0].created_at, first, "{}", book.title);
+            assert_eq!(book.highlights[0].location.position.as_deref(), Some("120-122"), "{}", book.title);
+            assert_eq!(book.highlights[1].created_at, second, "{}", book.title);
+            assert_eq!(book.highlights[1].location.position.as_deref(), Some("310"), "{}", book.title);
+            assert_eq!(book.highlights[1].kind, HighlightKind::Note, "{}", book.title);
+        }
+    }
+
+    #[test]
+    fn test_detect_language_per_entry() {
+        // A device switched from English to German part-way through
+        let content = format!(
+            "Der Process (Franz Kafka)\n- Your Highlight on Location 1-2 | Added on Monday, January 1, 2024 1:05:09 PM\n\nJemand\n==========\n{}",
+            GERMAN
+        );
+        let book = parse_fixture(&content);
+
+        // Two highlights, the German note and the German bookmark
+        assert_eq!(book.highlights.len(), 4);
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("1-2"));
+    }
+
+    #[test]
+    fn test_detect_language_shared_keywords() {
+        // "nota" is Spanish, Italian and Portuguese; the date wording decides
+        let (language, clipping_type) = detect_language("- Sua nota na posição 122 | Adicionado: terça-feira").unwrap();
+        assert_eq!(language.added_on, "Adicionado");
+        assert_eq!(clipping_type, ClippingType::Note);
+
+        let (language, _) = detect_language("- La tua nota alla posizione 122").unwrap();
+        assert_eq!(language.added_on, "Aggiunto");
+    }
+
+    #[test]
+    fn test_parse_title_author() {
+        let (title, author) = parse_title_author("The Great Gatsby (F. Scott Fitzgerald)");
+        assert_eq!(title, "The Great Gatsby");
+        assert_eq!(author, Some("F. Scott Fitzgerald".to_string()));
+
+        let (title, author) = parse_title_author("Some Book Without Author");
+        assert_eq!(title, "Some Book Without Author");
+        assert_eq!(author, None);
+    }
+
+    #[test]
+    fn test_parse_clippings_content() {
+        let content = r#"
+The Great Gatsby (F. Scott Fitzgerald)
+- Your Highlight on Location 123-145 | Added on Monday, January 1, 2024
+
+In my younger and more vulnerable years my father gave me some advice.
+==========
+The Great Gatsby (F. Scott Fitzgerald)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
 
 So we beat on, boats against the current.
 ==========
@@ -241,6 +1252,529 @@ So we beat on, boats against the current.
         assert_eq!(books[0].highlights.len(), 2);
     }
 
+    #[test]
+    fn test_highlight_ids_are_stable_across_parses() {
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 900-910 | Added on Tuesday, January 2, 2024
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+- Your Note on Location 210 | Added on Tuesday, January 2, 2024
+
+The motto of the book
+==========
+"#;
+
+        let ids = |books: Vec<Book>| books[0].highlights.iter().map(|h| h.id.clone()).collect::<Vec<_>>();
+        let first = ids(parse_clippings_content(content).unwrap());
+        let second = ids(parse_clippings_content(content).unwrap());
+
+        assert_eq!(first, second);
+        // The same passage highlighted at two locations stays two highlights
+        assert_eq!(first.len(), 2);
+        assert_ne!(first[0], first[1]);
+        let book_id = generate_book_id("Walden", Some("Henry David Thoreau"));
+        assert!(first.contains(&generate_highlight_id(&book_id, "Simplify, simplify.", Some("200-210"))));
+    }
+
+    #[test]
+    fn test_note_attaches_to_highlight() {
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024 10:00:00 AM
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 300-312 | Added on Monday, January 1, 2024 10:05:00 AM
+
+I went to the woods because I wished to live deliberately.
+==========
+Walden (Henry David Thoreau)
+- Your Note on Location 210 | Added on Monday, January 1, 2024 10:00:30 AM
+
+The whole book in two words
+==========
+"#;
+
+        let books = parse_clippings_content(content).unwrap();
+        let highlights = &books[0].highlights;
+
+        assert_eq!(highlights.len(), 2);
+        let simplify = highlights.iter().find(|h| h.text == "Simplify, simplify.").unwrap();
+        assert_eq!(simplify.note.as_deref(), Some("The whole book in two words"));
+        assert_eq!(simplify.kind, HighlightKind::Highlight);
+        assert!(highlights.iter().all(|h| h.text != "The whole book in two words"));
+    }
+
+    #[test]
+    fn test_orphan_note_stands_alone() {
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+- Your Note on Location 450 | Added on Monday, January 1, 2024
+
+Reread this chapter
+==========
+"#;
+
+        let books = parse_clippings_content(content).unwrap();
+        let highlights = &books[0].highlights;
+
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].note, None);
+
+        let note = highlights.iter().find(|h| h.kind == HighlightKind::Note).unwrap();
+        assert_eq!(note.text, "");
+        assert_eq!(note.note.as_deref(), Some("Reread this chapter"));
+        assert_eq!(note.location.position.as_deref(), Some("450"));
+    }
+
+    #[test]
+    fn test_bookmarks_are_kept() {
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Bookmark on Location 300 | Added on Monday, January 1, 2024 10:00:00 AM
+
+
+==========
+Walden (Henry David Thoreau)
+- Your Bookmark on page 20 | Location 450 | Added on Tuesday, January 2, 2024 10:00:00 AM
+
+
+==========
+"#;
+
+        let books = parse_clippings_content(content).unwrap();
+        let mut bookmarks = books[0].highlights.clone();
+        bookmarks.sort_by_key(|h| h.created_at);
+
+        assert_eq!(bookmarks.len(), 2);
+        assert!(bookmarks.iter().all(|h| h.kind == HighlightKind::Bookmark && h.text.is_empty()));
+        assert_eq!(bookmarks[0].location.position.as_deref(), Some("300"));
+        assert_eq!(bookmarks[1].location.page.as_deref(), Some("20"));
+        assert_eq!(bookmarks[1].created_at, Utc.with_ymd_and_hms(2024, 1, 2, 10, 0, 0).single());
+        assert_ne!(bookmarks[0].id, bookmarks[1].id);
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("walden_or_life-in-the-woods_epub (z-lib)"), "Walden or Life in the Woods");
+        assert_eq!(normalize_title("the-great-gatsby.pdf"), "The Great Gatsby");
+        assert_eq!(normalize_title("Middlemarch [Anna's Archive]"), "Middlemarch");
+        assert_eq!(normalize_title("Moby_Dick.mobi"), "Moby Dick");
+
+        // Real titles are left alone
+        assert_eq!(normalize_title("Self-Reliance"), "Self-Reliance");
+        assert_eq!(normalize_title("Dune (Dune Chronicles, Book 1)"), "Dune (Dune Chronicles, Book 1)");
+        assert_eq!(normalize_title("The Art of PDF"), "The Art of PDF");
+    }
+
+    #[test]
+    fn test_sideloaded_title_merges_with_apple_books() {
+        let content = r#"
+walden_epub (z-lib) (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+"#;
+
+        let clippings = parse_clippings_content(content).unwrap();
+        assert_eq!(clippings[0].title, "Walden");
+        assert_eq!(clippings[0].original_title.as_deref(), Some("walden_epub (z-lib)"));
+
+        let mut apple = Book::new("Walden".to_string(), Some("Henry David Thoreau".to_string()));
+        apple.sources = vec![Source::AppleBooks];
+
+        let merged = merge_books(vec![vec![apple], clippings]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sources, vec![Source::AppleBooks, Source::Kindle]);
+        assert_eq!(merged[0].highlights.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_title_aliases() {
+        let content = r#"
+ThoreauW_1854_final (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+"#;
+
+        let books = parse_clippings_content(content).unwrap();
+        let aliases = BTreeMap::from([("thoreauw_1854_final".to_string(), "Walden".to_string())]);
+        let books = apply_title_aliases(books, &aliases);
+
+        let expected_id = generate_book_id("Walden", Some("Henry David Thoreau"));
+        assert_eq!(books[0].title, "Walden");
+        assert_eq!(books[0].id, expected_id);
+        assert_eq!(books[0].original_title.as_deref(), Some("ThoreauW_1854_final"));
+        assert_eq!(
+            books[0].highlights[0].id,
+            generate_highlight_id(&expected_id, "Simplify, simplify.", Some("200-210"))
+        );
+    }
+
+    #[test]
+    fn test_is_clipping_limit_placeholder() {
+        assert!(is_clipping_limit_placeholder("<You have reached the clipping limit for this item>"));
+        assert!(is_clipping_limit_placeholder(" <You have reached the clipping limit for this item> "));
+        assert!(is_clipping_limit_placeholder("<Sie haben das Clipping-Limit für diesen Artikel erreicht>"));
+        assert!(is_clipping_limit_placeholder("<Vous avez atteint la limite de coupures pour cet article>"));
+        assert!(is_clipping_limit_placeholder("<Ha alcanzado el límite de recortes para este artículo>"));
+        assert!(is_clipping_limit_placeholder("<このアイテムのクリップ上限に達しました>"));
+
+        assert!(!is_clipping_limit_placeholder("You have reached the clipping limit for this item"));
+        assert!(!is_clipping_limit_placeholder("<b>Bold</b> text"));
+        assert!(!is_clipping_limit_placeholder("Simplify, simplify."));
+    }
+
+    #[test]
+    fn test_clipping_limit_placeholders_are_counted() {
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 300-310 | Added on Monday, January 1, 2024
+
+ <You have reached the clipping limit for this item>
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 400-410 | Added on Monday, January 1, 2024
+
+ <You have reached the clipping limit for this item>
+==========
+"#;
+
+        let books = parse_clippings_content(content).unwrap();
+
+        assert_eq!(books[0].highlights.len(), 1);
+        assert_eq!(books[0].unavailable_highlights, 2);
+    }
+
+    const MANGLED: &str = "Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+Simplify, simplify.
+==========
+Walden (Henry David Thoreau)
+==========
+Walden (Henry David Thoreau)
+- Something the Kindle never wrote
+
+Garbage
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 300-310 | Added on Monday, January 1, 2024
+
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 400-410 | Added on sometime last week
+
+Undated text
+==========
+Walden (Henry David Thoreau)
+- Your Highlight on Location 500-510 | Added on Monday, January 1, 2024
+
+Run-on text
+Walden (Henry David Thoreau)
+- Your Highlight on Location 600-610 | Added on Monday, January 1, 2024
+
+Lost text
+==========
+";
+
+    #[test]
+    fn test_parse_report_warns_on_mangled_entries() {
+        let report = parse_clippings_content_report(MANGLED, chrono_tz::UTC);
+
+        let texts: Vec<&str> = report.books[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, vec!["Simplify, simplify.", "Undated text", "Run-on text"]);
+
+        let summary: Vec<(bool, &str)> = report
+            .warnings
+            .iter()
+            .map(|w| (w.skipped, w.reason.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, "truncated entry without a metadata line"),
+                (true, "unrecognized metadata line \"- Something the Kindle never wrote\""),
+                (true, "entry has no text"),
+                (false, "missing or unreadable date"),
+                (false, "entry runs into the next one (missing separator)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_report_offsets_point_at_entries() {
+        let report = parse_clippings_content_report(MANGLED, chrono_tz::UTC);
+
+        for warning in &report.warnings {
+            assert!(MANGLED[warning.offset..].starts_with(&warning.first_line));
+            assert_eq!(warning.first_line, "Walden (Henry David Thoreau)");
+        }
+        assert_eq!(report.warnings[0].offset, MANGLED.find("==========\n").unwrap() + 11);
+    }
+
+    #[test]
+    fn test_identical_entries_are_deduped() {
+        let entry = |added_on: &str| {
+            format!(
+                "Walden (Henry David Thoreau)\n- Your Highlight on Location 200-210 | Added on {}\n\nSimplify,  simplify.\n==========\n",
+                added_on
+            )
+        };
+        let content = [
+            entry("Wednesday, March 6, 2024 9:00:00 AM"),
+            entry("Monday, January 1, 2024 1:05:09 PM"),
+            entry("Friday, February 2, 2024 8:00:00 AM"),
+        ]
+        .concat();
+
+        let report = parse_clippings_content_report(&content, chrono_tz::UTC);
+        let highlights = &report.books[0].highlights;
+
+        assert_eq!(report.duplicates, 2);
+        assert_eq!(highlights.len(), 1);
+        assert_eq!(highlights[0].created_at, Utc.with_ymd_and_hms(2024, 1, 1, 13, 5, 9).single());
+    }
+
+    #[test]
+    fn test_reader_reports_line_numbers() {
+        let mut reader = ClippingsReader::new(MANGLED.as_bytes(), chrono_tz::UTC);
+        assert_eq!(reader.by_ref().count(), 3);
+
+        let lines: Vec<usize> = reader.warnings().iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![6, 8, 13, 17, 22]);
+        assert!(reader.warnings()[0].to_string().starts_with("line 6 (byte "));
+    }
+
+    #[test]
+    fn test_reader_rejects_invalid_utf8_with_line() {
+        let mut bytes = WALDEN.as_bytes().to_vec();
+        bytes.extend(b"Walden (Henry David Thoreau)\n\xff\n");
+
+        let error = ClippingsReader::new(bytes.as_slice(), chrono_tz::UTC)
+            .find_map(Result::err)
+            .unwrap();
+        assert!(error.to_string().contains("line 12"));
+    }
+
+    /// Generates a clippings file entry by entry without holding it in memory
+    struct SyntheticClippings {
+        next: usize,
+        total: usize,
+        buf: Vec<u8>,
+    }
+
+    impl Read for SyntheticClippings {
+        fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+            if self.buf.is_empty() && self.next < self.total {
+                let i = self.next;
+                self.next += 1;
+                self.buf = format!(
+                    "Book {} (Author {})\r\n- Your Highlight on Location {}-{} | Added on Monday, January 1, 2024 1:05:09 PM\r\n\r\nHighlight number {}\r\n==========\r\n",
+                    i % 100,
+                    i % 100,
+                    i,
+                    i + 5,
+                    i
+                )
+                .into_bytes();
+            }
+
+            let n = out.len().min(self.buf.len());
+            out[..n].copy_from_slice(&self.buf[..n]);
+            self.buf.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_reader_streams_large_files() {
+        let total = 100_000;
+        let source = SyntheticClippings {
+            next: 0,
+            total,
+            buf: Vec::new(),
+        };
+
+        let started = std::time::Instant::now();
+        let report = collect_report(ClippingsReader::new(BufReader::new(source), chrono_tz::UTC)).unwrap();
+
+        assert!(report.warnings.is_empty());
+        assert_eq!(report.books.len(), 100);
+        assert_eq!(report.books.iter().map(|b| b.highlights.len()).sum::<usize>(), total);
+        // Linear work finishes in seconds even in debug builds; quadratic work would not
+        assert!(started.elapsed() < std::time::Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_report_clean_file_has_no_warnings() {
+        let report = parse_clippings_content_report(WALDEN, chrono_tz::UTC);
+        assert!(report.warnings.is_empty());
+        assert_eq!(report.books.len(), 1);
+    }
+
+    const WALDEN: &str = "Walden (Henry David Thoreau)\n- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024\n\nSimplify, simplify.\n==========\nWalden (Henry David Thoreau)\n- Your Note on Location 210 | Added on Monday, January 1, 2024\n\nThe whole book\n==========\n";
+
+    fn utf16_bytes(content: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let units = bom.then_some(0xFEFF).into_iter().chain(content.encode_utf16());
+        for unit in units {
+            if big_endian {
+                bytes.extend(unit.to_be_bytes());
+            } else {
+                bytes.extend(unit.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_clippings_encodings() {
+        let crlf = WALDEN.replace('\n', "\r\n");
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend(crlf.as_bytes());
+
+        let encodings: Vec<(&str, Vec<u8>)> = vec![
+            ("utf-8 bom + crlf", utf8_bom),
+            ("cr", WALDEN.replace('\n', "\r").into_bytes()),
+            ("utf-16le bom", utf16_bytes(&crlf, false, true)),
+            ("utf-16be bom", utf16_bytes(WALDEN, true, true)),
+            ("utf-16le", utf16_bytes(WALDEN, false, false)),
+            ("utf-16be", utf16_bytes(&crlf, true, false)),
+        ];
+
+        let expected = parse_clippings_content(WALDEN).unwrap();
+
+        for (name, bytes) in encodings {
+            let content = decode_clippings(&bytes).unwrap();
+            let books = parse_clippings_content(&content).unwrap();
+
+            assert_eq!(books.len(), 1, "{}", name);
+            assert_eq!(books[0].id, expected[0].id, "{}", name);
+            assert_eq!(books[0].title, "Walden", "{}", name);
+
+            let ids = |book: &Book| book.highlights.iter().map(|h| (h.id.clone(), h.note.clone())).collect::<Vec<_>>();
+            assert_eq!(ids(&books[0]), ids(&expected[0]), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_clippings_utf16_file() {
+        let path = std::env::temp_dir().join(format!("readingsync_clippings_{}.txt", uuid::Uuid::new_v4()));
+        fs::write(&path, utf16_bytes(&WALDEN.replace('\n', "\r\n"), false, true)).unwrap();
+
+        let books = parse_clippings(&path, chrono_tz::UTC).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(books[0].id, generate_book_id("Walden", Some("Henry David Thoreau")));
+        assert_eq!(books[0].highlights[0].note.as_deref(), Some("The whole book"));
+    }
+
+    #[test]
+    fn test_clippings_directory_snapshots_collapse() {
+        let dir = std::env::temp_dir().join(format!("readingsync_clippings_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = "Walden (Henry David Thoreau)\n- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024\n\nSimplify, simplify.\n==========\n";
+        let second = format!(
+            "{}Walden (Henry David Thoreau)\n- Your Highlight on Location 300-310 | Added on Monday, January 8, 2024\n\nI went to the woods.\n==========\n",
+            first
+        );
+        fs::write(dir.join("2023 Paperwhite.txt"), first).unwrap();
+        fs::write(dir.join("2024 Oasis.txt"), second).unwrap();
+        fs::write(dir.join("notes.md"), "ignored").unwrap();
+
+        let files = clippings_files(std::slice::from_ref(&dir)).unwrap();
+        let books: Vec<Vec<Book>> = files.iter().map(|f| parse_clippings(f, chrono_tz::UTC).unwrap()).collect();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(files.len(), 2);
+        let merged = merge_books(books);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_date_us_format() {
+        let utc = chrono_tz::UTC;
+
+        assert_eq!(
+            extract_date("- Your Highlight on Location 1-2 | Added on Monday, January 1, 2024 12:34:56 PM", utc),
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 34, 56).single()
+        );
+        assert_eq!(
+            extract_date("- Your Highlight on Location 1-2 | Added on Friday, March 8, 2024 9:05:03 PM", utc),
+            Utc.with_ymd_and_hms(2024, 3, 8, 21, 5, 3).single()
+        );
+        // Midnight and noon
+        assert_eq!(
+            extract_date("- Your Note on Location 5 | Added on Tuesday, July 16, 2024 12:00:00 AM", utc),
+            Utc.with_ymd_and_hms(2024, 7, 16, 0, 0, 0).single()
+        );
+        assert_eq!(
+            extract_date("- Your Note on Location 5 | Added on Tuesday, July 16, 2024 12:00:00 PM", utc),
+            Utc.with_ymd_and_hms(2024, 7, 16, 12, 0, 0).single()
+        );
+        // Date only
+        assert_eq!(
+            extract_date("- Your Highlight on Location 1-2 | Added on Monday, January 1, 2024", utc),
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn test_extract_date_international_format() {
+        let utc = chrono_tz::UTC;
+
+        assert_eq!(
+            extract_date("- Your Highlight on page 4 | Added on Sunday, 3 March 2024 18:07:45", utc),
+            Utc.with_ymd_and_hms(2024, 3, 3, 18, 7, 45).single()
+        );
+        assert_eq!(
+            extract_date("- Your Highlight on page 4 | Added on Sunday, 31 December 2023 00:00:00", utc),
+            Utc.with_ymd_and_hms(2023, 12, 31, 0, 0, 0).single()
+        );
+        assert_eq!(extract_date("- Your Bookmark on page 4", utc), None);
+    }
+
+    #[test]
+    fn test_extract_date_in_timezone() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+
+        // CET in winter, CEST in summer
+        assert_eq!(
+            extract_date("- Your Highlight on Location 1-2 | Added on Monday, January 1, 2024 12:30:00 AM", berlin),
+            Utc.with_ymd_and_hms(2023, 12, 31, 23, 30, 0).single()
+        );
+        assert_eq!(
+            extract_date("- Your Highlight on Location 1-2 | Added on Monday, 1 July 2024 12:00:00", berlin),
+            Utc.with_ymd_and_hms(2024, 7, 1, 10, 0, 0).single()
+        );
+    }
+
     #[test]
     fn test_extract_location() {
         assert_eq!(
@@ -251,9 +1785,15 @@ So we beat on, boats against the current.
             extract_location("- Your Highlight on Location 123"),
             Some("123".to_string())
         );
-        assert_eq!(
-            extract_location("- Your Highlight on page 45"),
-            Some("45".to_string())
-        );
+        assert_eq!(extract_location("- Your Highlight on page 45"), None);
+    }
+
+    #[test]
+    fn test_extract_page_and_location() {
+        let line = "- Your Highlight on page 45 | Location 678-690 | Added on Monday, January 1, 2024";
+
+        assert_eq!(extract_page(line), Some("45".to_string()));
+        assert_eq!(extract_location(line), Some("678-690".to_string()));
+        assert_eq!(extract_page("- Your Highlight on Location 123-145"), None);
     }
 }
@@ -0,0 +1,302 @@
+use crate::error::KindleError;
+use crate::kindle::retry::{scrape_with_retries, BookScraper, FailedBook, RetryPolicy};
+use crate::model::Book;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Most tabs scraping at once
+///
+/// Every tab makes the same notebook requests a person clicking through books would,
+/// just several times as fast; beyond a handful Amazon starts throttling or answering
+/// with captchas, which costs more time than the extra tabs save.
+pub const MAX_CONCURRENCY: usize = 4;
+
+/// Least time between two books starting, across all tabs
+pub const MIN_BOOK_INTERVAL: Duration = Duration::from_millis(750);
+
+/// Spaces out book starts shared by every tab
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Wait for the next free slot
+    ///
+    /// The slot is reserved before sleeping, so tabs waiting together get consecutive
+    /// slots instead of all waking at once.
+    pub fn wait(&self) {
+        thread::sleep(self.reserve().saturating_duration_since(Instant::now()));
+    }
+
+    /// `wait` for async tasks, sleeping on the tokio timer instead of the thread
+    pub async fn wait_async(&self) {
+        tokio::time::sleep_until(self.reserve().into()).await;
+    }
+
+    fn reserve(&self) -> Instant {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + self.interval);
+        slot
+    }
+}
+
+/// A book to scrape; `index` is its place in the book list
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub index: usize,
+    pub asin: String,
+    pub title: String,
+    pub author: Option<String>,
+}
+
+/// Progress from the workers, delivered in the calling thread
+#[derive(Debug)]
+pub enum Event {
+    Started {
+        index: usize,
+    },
+    Retrying {
+        index: usize,
+        retry: u32,
+        error: KindleError,
+        wait: Duration,
+    },
+    Finished {
+        index: usize,
+        /// Attempts taken, including retries
+        attempts: u32,
+        result: Box<Result<Book, FailedBook>>,
+    },
+}
+
+/// Scrape `jobs` with one thread per worker, e.g. one per browser tab
+///
+/// Workers take the next job from a shared queue, so a slow book holds up only its own
+/// tab. Each book waits for `limiter` before starting and its worker pauses `delay`
+/// after it. Events are passed to `on_event` on the calling thread as they arrive, so
+/// the caller can report progress and checkpoint without locking; finished books may
+/// arrive out of order and are matched up by `index`. A failing book only ends its own
+/// job. If `on_event` fails, no further books are started and the error is returned
+/// once the running ones finish. Setting `stop`, e.g. from a signal handler, likewise
+/// lets the running books finish and starts no more.
+pub fn scrape_concurrently<S: BookScraper + Sync>(
+    workers: &[S],
+    jobs: &[Job],
+    policy: &RetryPolicy,
+    limiter: &RateLimiter,
+    delay: Duration,
+    stop: Option<&AtomicBool>,
+    on_event: &mut dyn FnMut(Event) -> Result<(), KindleError>,
+) -> Result<(), KindleError> {
+    let next_job = AtomicUsize::new(0);
+    let cancelled = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for worker in workers {
+            let sender = sender.clone();
+            let (next_job, cancelled) = (&next_job, &cancelled);
+            scope.spawn(move || {
+                while !cancelled.load(Ordering::Relaxed) && !stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                    let Some(job) = jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    limiter.wait();
+                    let _ = sender.send(Event::Started { index: job.index });
+
+                    let mut attempts = 1;
+                    let result = scrape_with_retries(
+                        worker,
+                        policy,
+                        &job.asin,
+                        &job.title,
+                        job.author.as_deref(),
+                        &mut |retry, error, wait| {
+                            attempts = retry + 1;
+                            let _ = sender.send(Event::Retrying { index: job.index, retry, error, wait });
+                        },
+                        &thread::sleep,
+                    );
+                    let attempts = result.as_ref().map_or_else(|failed| failed.attempts, |_| attempts);
+                    let _ = sender.send(Event::Finished { index: job.index, attempts, result: Box::new(result) });
+
+                    thread::sleep(delay);
+                }
+            });
+        }
+        // Only the workers' senders are left, so the loop ends when they are done
+        drop(sender);
+
+        let mut first_error = None;
+        for event in receiver {
+            if first_error.is_some() {
+                continue;
+            }
+            if let Err(e) = on_event(event) {
+                cancelled.store(true, Ordering::Relaxed);
+                first_error = Some(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Takes a title-dependent time per book and fails books titled "broken"
+    struct Mock {
+        running: &'static AtomicUsize,
+        most_running: &'static AtomicUsize,
+    }
+
+    impl BookScraper for Mock {
+        fn scrape_book(&self, _asin: &str, title: &str, author: Option<&str>) -> Result<Book, KindleError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_running.fetch_max(running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5 * (title.len() as u64 % 4)));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if title == "broken" {
+                Err(KindleError::ParseError("no highlights panel".to_string()))
+            } else {
+                Ok(Book::new(title.to_string(), author.map(String::from)))
+            }
+        }
+
+        fn reset(&self) -> Result<(), KindleError> {
+            Ok(())
+        }
+    }
+
+    fn jobs(titles: &[&str]) -> Vec<Job> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(index, title)| Job {
+                index,
+                asin: format!("B{:03}", index),
+                title: title.to_string(),
+                author: None,
+            })
+            .collect()
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            retries: 1,
+            initial_backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_scrapes_concurrently_in_list_order() {
+        static RUNNING: AtomicUsize = AtomicUsize::new(0);
+        static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+        let workers: Vec<Mock> = (0..3)
+            .map(|_| Mock {
+                running: &RUNNING,
+                most_running: &MOST_RUNNING,
+            })
+            .collect();
+        let titles = ["Walden", "broken", "Emma", "Middlemarch", "Dune", "Ulysses", "It", "Beloved"];
+        let jobs = jobs(&titles);
+
+        let mut results: Vec<Option<Result<Book, FailedBook>>> = (0..jobs.len()).map(|_| None).collect();
+        let mut started = 0;
+        let mut retried = Vec::new();
+        scrape_concurrently(&workers, &jobs, &policy(), &RateLimiter::new(Duration::ZERO), Duration::ZERO, None, &mut |event| {
+            match event {
+                Event::Started { .. } => started += 1,
+                Event::Retrying { index, .. } => retried.push(index),
+                Event::Finished { index, result, .. } => results[index] = Some(*result),
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(started, titles.len());
+        assert_eq!(retried, vec![1]);
+        assert!(MOST_RUNNING.load(Ordering::SeqCst) > 1);
+        assert!(MOST_RUNNING.load(Ordering::SeqCst) <= 3);
+
+        for (title, result) in titles.iter().zip(results) {
+            match result.unwrap() {
+                Ok(book) => assert_eq!(book.title, *title),
+                Err(failed) => {
+                    assert_eq!(*title, "broken");
+                    assert_eq!(failed.attempts, 2);
+                    assert!(failed.error.contains("no highlights panel"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stops_starting_books_after_callback_error() {
+        static RUNNING: AtomicUsize = AtomicUsize::new(0);
+        static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+        let workers = [Mock {
+            running: &RUNNING,
+            most_running: &MOST_RUNNING,
+        }];
+        let jobs = jobs(&["Walden", "Emma", "Dune"]);
+
+        let mut finished = 0;
+        let result = scrape_concurrently(&workers, &jobs, &policy(), &RateLimiter::new(Duration::ZERO), Duration::ZERO, None, &mut |event| {
+            if let Event::Finished { .. } = event {
+                finished += 1;
+                return Err(KindleError::ParseError("disk full".to_string()));
+            }
+            Ok(())
+        });
+
+        assert!(result.unwrap_err().to_string().contains("disk full"));
+        assert_eq!(finished, 1);
+    }
+
+    #[test]
+    fn test_stop_flag_starts_no_books() {
+        static RUNNING: AtomicUsize = AtomicUsize::new(0);
+        static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+        let workers = [Mock {
+            running: &RUNNING,
+            most_running: &MOST_RUNNING,
+        }];
+        let stop = AtomicBool::new(true);
+
+        let mut events = 0;
+        scrape_concurrently(&workers, &jobs(&["Walden", "Emma"]), &policy(), &RateLimiter::new(Duration::ZERO), Duration::ZERO, Some(&stop), &mut |_| {
+            events += 1;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(events, 0);
+    }
+
+    #[test]
+    fn test_rate_limiter_spaces_starts() {
+        let limiter = RateLimiter::new(Duration::from_millis(20));
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| limiter.wait());
+            }
+        });
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,206 @@
+//! Raw notebook pages saved next to what was parsed from them
+//!
+//! When Amazon changes the notebook markup the scrapers don't fail, they just find no
+//! highlights. A dump holds each book's pages exactly as read (annotation fragments, or
+//! the JSON the DOM engine's script returned) and a `parsed.json` with the result, so
+//! the breakage can be reproduced against the parsers or turned into a fixture.
+//!
+//! Nothing is redacted: pages carry highlight text, notes, book titles and whatever
+//! account details Amazon includes, so a dump is personal data and should be reviewed
+//! before it's shared.
+
+use crate::model::Highlight;
+use chrono::Local;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The raw pages read for one book, in order
+#[derive(Debug, Default)]
+pub struct BookCapture {
+    /// File extension ("html" or "json") and body of each page
+    pages: Vec<(&'static str, String)>,
+    /// Some page held the annotations list or annotation rows
+    found_container: bool,
+}
+
+impl BookCapture {
+    /// Record an annotations fragment and whether it had the annotation markup
+    pub fn html(&mut self, body: &str, has_container: bool) {
+        self.pages.push(("html", body.to_string()));
+        self.found_container |= has_container;
+    }
+
+    /// Record what a page script returned and whether it found the annotation markup
+    pub fn json(&mut self, body: &str, has_container: bool) {
+        self.pages.push(("json", body.to_string()));
+        self.found_container |= has_container;
+    }
+
+    /// Whether the pages had annotations in them but nothing was parsed from them,
+    /// which is how a markup change shows
+    pub fn is_suspicious(&self, highlights: &[Highlight]) -> bool {
+        self.found_container && highlights.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+struct Parsed<'a> {
+    asin: &'a str,
+    title: &'a str,
+    pages: usize,
+    found_container: bool,
+    highlights: &'a [Highlight],
+}
+
+/// Where books' raw pages are saved
+///
+/// With a directory (`--debug-dump`) every book is saved there. Without one only
+/// suspicious books are (see `BookCapture::is_suspicious`), under
+/// `default_dump_dir()`, with a warning saying where.
+#[derive(Debug, Clone)]
+pub struct DebugDump {
+    dir: Option<PathBuf>,
+    fallback: PathBuf,
+}
+
+/// Where books with unreadable annotations are saved when no `--debug-dump` is given:
+/// a directory per run under the data dir
+pub fn default_dump_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("readingsync")
+        .join("debug")
+        .join(Local::now().format("%Y%m%d-%H%M%S").to_string())
+}
+
+impl DebugDump {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            fallback: default_dump_dir(),
+        }
+    }
+
+    /// Save a book's pages and highlights if it's due, returning the directory written
+    ///
+    /// Failing to write only warns: a dump must never fail the sync it's diagnosing.
+    pub fn finish_book(&self, asin: &str, title: &str, capture: &BookCapture, highlights: &[Highlight]) -> Option<PathBuf> {
+        let suspicious = capture.is_suspicious(highlights);
+        let root = match &self.dir {
+            Some(dir) => dir,
+            None if suspicious => &self.fallback,
+            None => return None,
+        };
+
+        match write_book(root, asin, title, capture, highlights) {
+            Ok(dir) => {
+                if suspicious {
+                    eprintln!(
+                        "Warning: \"{}\" has annotations on the page but none could be read; the notebook markup may have changed. Raw pages saved to {} (they may contain personal data)",
+                        title,
+                        dir.display()
+                    );
+                }
+                Some(dir)
+            }
+            Err(e) => {
+                eprintln!("Warning: could not save the raw pages of \"{}\" to {}: {}", title, root.display(), e);
+                None
+            }
+        }
+    }
+}
+
+fn write_book(root: &Path, asin: &str, title: &str, capture: &BookCapture, highlights: &[Highlight]) -> io::Result<PathBuf> {
+    let name: String = asin
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let dir = root.join(name);
+    // A retried book replaces the pages of its earlier attempt
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    for (i, (extension, body)) in capture.pages.iter().enumerate() {
+        fs::write(dir.join(format!("page-{:02}.{}", i + 1, extension)), body)?;
+    }
+    let parsed = Parsed {
+        asin,
+        title,
+        pages: capture.pages.len(),
+        found_container: capture.found_container,
+        highlights,
+    };
+    fs::write(dir.join("parsed.json"), serde_json::to_string_pretty(&parsed)?)?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::network;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("readingsync_{}_{}", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_dumps_only_suspicious_books_by_default() {
+        let fallback = temp_dir("dump_fallback");
+        let dump = DebugDump {
+            dir: None,
+            fallback: fallback.clone(),
+        };
+
+        // Rows Amazon renamed the highlight span in
+        let changed = r#"<div class="a-row a-spacing-base"><span id="highlight-text">Simplify, simplify.</span></div>"#;
+        let page = network::parse_annotations(changed);
+        let mut capture = BookCapture::default();
+        capture.html(changed, page.has_container);
+        assert!(page.annotations.is_empty());
+
+        let written = dump.finish_book("B001", "Walden", &capture, &[]).unwrap();
+        assert_eq!(written, fallback.join("B001"));
+        assert_eq!(fs::read_to_string(written.join("page-01.html")).unwrap(), changed);
+        let parsed: serde_json::Value = serde_json::from_str(&fs::read_to_string(written.join("parsed.json")).unwrap()).unwrap();
+        assert_eq!(parsed["found_container"], true);
+        assert_eq!(parsed["highlights"].as_array().unwrap().len(), 0);
+
+        // No annotation markup at all, e.g. a book without highlights
+        let mut empty = BookCapture::default();
+        empty.html("<div></div>", false);
+        assert_eq!(dump.finish_book("B002", "Cape Cod", &empty, &[]), None);
+        assert!(!fallback.join("B002").exists());
+
+        let _ = fs::remove_dir_all(&fallback);
+    }
+
+    #[test]
+    fn test_dumps_every_book_into_given_dir() {
+        let dir = temp_dir("dump");
+        let dump = DebugDump {
+            dir: Some(dir.clone()),
+            fallback: temp_dir("dump_unused"),
+        };
+
+        let html = r#"<div class="a-row a-spacing-base"><span id="highlight">I went to the woods.</span><span id="kp-annotation-location">12</span></div>"#;
+        let page = network::parse_annotations(html);
+        let highlights: Vec<Highlight> = page.annotations.iter().map(|raw| crate::kindle::parse::to_highlight("walden", raw)).collect();
+        let mut capture = BookCapture::default();
+        capture.html(html, page.has_container);
+        capture.json(r#"{"highlights":[],"hasMore":false}"#, true);
+
+        let written = dump.finish_book("B0/01", "Walden", &capture, &highlights).unwrap();
+        let files = ["page-01.html", "page-02.json", "parsed.json"].map(|name| written.join(name).exists());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(files, [true; 3]);
+        assert_eq!(written, dir.join("B0_01"));
+        assert!(!capture.is_suspicious(&highlights));
+        assert_eq!(highlights.len(), 1);
+    }
+}
@@ -0,0 +1,553 @@
+//! Import from the Kindle desktop app's local annotation sidecars
+//!
+//! The Kindle for Mac/PC app keeps a `<ASIN>_EBOK.sdr` folder per book in
+//! `My Kindle Content`, holding KRDS sidecars (`.yjr`, `.azw3r`) with annotations.
+//! KRDS is an undocumented binary format, so parsing is deliberately conservative:
+//! anything unexpected is skipped and counted rather than guessed at.
+//!
+//! The sidecars store positions, not the highlighted passage, so only notes can be
+//! imported. Highlights are counted in [`LocalImport::highlights_without_text`].
+
+use crate::error::KindleError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::DateTime;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const KRDS_SIGNATURE: &[u8] = b"\x00\x00\x00\x00\x00\x1a\xb1\x26";
+
+/// Sidecar extensions in KRDS format
+const KRDS_EXTENSIONS: [&str; 2] = ["yjr", "azw3r"];
+
+/// Books read from the local cache, with counts of what couldn't be imported
+#[derive(Debug, Default)]
+pub struct LocalImport {
+    pub books: Vec<Book>,
+    /// Sidecar files that couldn't be decoded
+    pub unreadable_files: usize,
+    /// Annotation records with an unknown type or missing fields
+    pub skipped_records: usize,
+    /// Highlights found without their text (the app doesn't store it locally)
+    pub highlights_without_text: usize,
+}
+
+/// Title and author from the app's content catalog
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    pub title: String,
+    pub author: Option<String>,
+}
+
+/// A decoded KRDS value
+#[derive(Debug, Clone, PartialEq)]
+pub enum KrdsValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Char(char),
+    Object(String, Vec<KrdsValue>),
+}
+
+/// Candidate `My Kindle Content` folders for the Kindle desktop app
+pub fn default_content_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    if let Some(home) = dirs::home_dir() {
+        // macOS (App Store and classic builds)
+        for container in ["com.amazon.Kindle", "Kindle"] {
+            dirs.push(
+                home.join("Library/Containers")
+                    .join(container)
+                    .join("Data/Library/Application Support/Kindle/My Kindle Content"),
+            );
+        }
+        // Windows
+        dirs.push(home.join("Documents").join("My Kindle Content"));
+    }
+
+    if let Some(local) = dirs::data_local_dir() {
+        dirs.push(local.join("Amazon").join("Kindle").join("My Kindle Content"));
+    }
+
+    dirs
+}
+
+/// Candidate locations of the app's content catalog
+pub fn default_catalog_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Some(home) = dirs::home_dir() {
+        for container in ["com.amazon.Kindle", "Kindle"] {
+            paths.push(
+                home.join("Library/Containers")
+                    .join(container)
+                    .join("Data/Library/Application Support/Kindle/Cache/KindleSyncMetadataCache.xml"),
+            );
+        }
+    }
+
+    if let Some(local) = dirs::data_local_dir() {
+        paths.push(local.join("Amazon/Kindle/Cache/KindleSyncMetadataCache.xml"));
+    }
+
+    paths
+}
+
+/// Import notes from the Kindle desktop app's content folder
+///
+/// Uses the first existing default folder/catalog when not given explicitly. Books the
+/// catalog doesn't know are titled by ASIN.
+pub fn extract(content_dir: Option<PathBuf>, catalog: Option<PathBuf>) -> Result<LocalImport, KindleError> {
+    let content_dir = match content_dir {
+        Some(dir) => dir,
+        None => default_content_dirs()
+            .into_iter()
+            .find(|d| d.is_dir())
+            .ok_or(KindleError::LocalContentNotFound)?,
+    };
+
+    if !content_dir.is_dir() {
+        return Err(KindleError::LocalReadError(
+            content_dir.clone(),
+            std::io::Error::new(std::io::ErrorKind::NotFound, "directory not found"),
+        ));
+    }
+
+    let catalog_path = catalog.or_else(|| default_catalog_paths().into_iter().find(|p| p.exists()));
+    let catalog = match catalog_path {
+        Some(path) => {
+            let xml = fs::read_to_string(&path).map_err(|e| KindleError::LocalReadError(path.clone(), e))?;
+            parse_catalog(&xml)
+        }
+        None => HashMap::new(),
+    };
+
+    let mut sidecars = Vec::new();
+    collect_sidecars(&content_dir, &mut sidecars)?;
+    sidecars.sort();
+
+    let mut import = LocalImport::default();
+
+    for path in sidecars {
+        let data = fs::read(&path).map_err(|e| KindleError::LocalReadError(path.clone(), e))?;
+
+        let Some(values) = parse_krds(&data) else {
+            import.unreadable_files += 1;
+            continue;
+        };
+
+        let asin = asin_from_path(&path);
+        let entry = catalog.get(&asin).cloned().unwrap_or(CatalogEntry {
+            title: asin.clone(),
+            author: None,
+        });
+
+        let mut book = Book::new(entry.title, entry.author);
+        book.sources = vec![Source::Kindle];
+        book.source_ids.insert("asin".to_string(), asin);
+
+        collect_annotations(&values, &mut book, &mut import);
+
+        if book.highlights.is_empty() {
+            continue;
+        }
+
+        match import.books.iter_mut().find(|b| b.id == book.id) {
+            Some(existing) => existing.highlights.extend(book.highlights),
+            None => import.books.push(book),
+        }
+    }
+
+    Ok(import)
+}
+
+fn collect_sidecars(dir: &Path, sidecars: &mut Vec<PathBuf>) -> Result<(), KindleError> {
+    let entries = fs::read_dir(dir).map_err(|e| KindleError::LocalReadError(dir.to_path_buf(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| KindleError::LocalReadError(dir.to_path_buf(), e))?.path();
+
+        if path.is_dir() {
+            collect_sidecars(&path, sidecars)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| KRDS_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            sidecars.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// ASIN from a sidecar path, e.g. `B00ABC1234_EBOK.sdr/B00ABC1234_EBOK.yjr` -> `B00ABC1234`
+fn asin_from_path(path: &Path) -> String {
+    let stem = path
+        .parent()
+        .filter(|p| p.extension().is_some_and(|e| e == "sdr"))
+        .and_then(|p| p.file_stem())
+        .or_else(|| path.file_stem())
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    stem.split('_').next().unwrap_or_default().to_string()
+}
+
+/// Parse the app's `KindleSyncMetadataCache.xml` into ASIN -> title/author
+pub fn parse_catalog(xml: &str) -> HashMap<String, CatalogEntry> {
+    static ENTRY: OnceLock<Regex> = OnceLock::new();
+    static ASIN: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static AUTHOR: OnceLock<Regex> = OnceLock::new();
+    let entry_re = ENTRY.get_or_init(|| Regex::new(r"(?s)<meta_data>(.*?)</meta_data>").unwrap());
+    let asin_re = ASIN.get_or_init(|| Regex::new(r"(?s)<ASIN>(.*?)</ASIN>").unwrap());
+    let title_re = TITLE.get_or_init(|| Regex::new(r"(?s)<title(?:\s[^>]*)?>(.*?)</title>").unwrap());
+    let author_re = AUTHOR.get_or_init(|| Regex::new(r"(?s)<author(?:\s[^>]*)?>(.*?)</author>").unwrap());
+
+    let mut catalog = HashMap::new();
+
+    for caps in entry_re.captures_iter(xml) {
+        let block = &caps[1];
+
+        let (Some(asin), Some(title)) = (asin_re.captures(block), title_re.captures(block)) else {
+            continue;
+        };
+
+        let authors: Vec<String> = author_re
+            .captures_iter(block)
+            .map(|c| unescape_xml(c[1].trim()))
+            .filter(|a| !a.is_empty())
+            .collect();
+
+        catalog.insert(
+            asin[1].trim().to_string(),
+            CatalogEntry {
+                title: unescape_xml(title[1].trim()),
+                author: if authors.is_empty() { None } else { Some(authors.join(", ")) },
+            },
+        );
+    }
+
+    catalog
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Decode a KRDS file into its top-level values; None if it isn't valid KRDS
+///
+/// Layout: an 8-byte signature, an 8-byte version (1), then typed values. Each value
+/// starts with a type byte; objects are a name followed by values up to an end marker.
+pub fn parse_krds(data: &[u8]) -> Option<Vec<KrdsValue>> {
+    let rest = data.strip_prefix(KRDS_SIGNATURE)?;
+    let mut reader = KrdsReader { data: rest, pos: 0 };
+
+    if reader.take_u64()? != 1 {
+        return None;
+    }
+
+    let mut values = Vec::new();
+    while reader.pos < reader.data.len() {
+        values.push(reader.value()?);
+    }
+
+    Some(values)
+}
+
+struct KrdsReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+const TYPE_BOOLEAN: u8 = 0;
+const TYPE_INT: u8 = 1;
+const TYPE_LONG: u8 = 2;
+const TYPE_UTF: u8 = 3;
+const TYPE_DOUBLE: u8 = 4;
+const TYPE_SHORT: u8 = 5;
+const TYPE_FLOAT: u8 = 6;
+const TYPE_BYTE: u8 = 7;
+const TYPE_CHAR: u8 = 8;
+const TYPE_FIELD_BEGIN: u8 = 0xfe;
+const TYPE_FIELD_END: u8 = 0xff;
+
+impl KrdsReader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn take_u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn take_utf(&mut self) -> Option<String> {
+        // A leading 1 marks an empty string
+        if self.take(1)?[0] == 1 {
+            return Some(String::new());
+        }
+        let len = u16::from_be_bytes(self.take(2)?.try_into().ok()?) as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn value(&mut self) -> Option<KrdsValue> {
+        let value = match self.take(1)?[0] {
+            TYPE_BOOLEAN => KrdsValue::Bool(self.take(1)?[0] == 1),
+            TYPE_INT => KrdsValue::Int(i32::from_be_bytes(self.take(4)?.try_into().ok()?) as i64),
+            TYPE_LONG => KrdsValue::Int(i64::from_be_bytes(self.take(8)?.try_into().ok()?)),
+            TYPE_UTF => KrdsValue::Str(self.take_utf()?),
+            TYPE_DOUBLE => KrdsValue::Float(f64::from_be_bytes(self.take(8)?.try_into().ok()?)),
+            TYPE_SHORT => KrdsValue::Int(i16::from_be_bytes(self.take(2)?.try_into().ok()?) as i64),
+            TYPE_FLOAT => KrdsValue::Float(f32::from_be_bytes(self.take(4)?.try_into().ok()?) as f64),
+            TYPE_BYTE => KrdsValue::Int(self.take(1)?[0] as i8 as i64),
+            TYPE_CHAR => KrdsValue::Char(self.take(1)?[0] as char),
+            TYPE_FIELD_BEGIN => {
+                let name = self.take_utf()?;
+                let mut children = Vec::new();
+                loop {
+                    if *self.data.get(self.pos)? == TYPE_FIELD_END {
+                        self.pos += 1;
+                        break;
+                    }
+                    children.push(self.value()?);
+                }
+                KrdsValue::Object(name, children)
+            }
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+/// Walk decoded values for `annotation.personal.*` objects
+fn collect_annotations(values: &[KrdsValue], book: &mut Book, import: &mut LocalImport) {
+    for value in values {
+        let KrdsValue::Object(name, children) = value else {
+            continue;
+        };
+
+        match name.strip_prefix("annotation.personal.") {
+            Some(kind) => add_annotation(kind, children, book, import),
+            None => collect_annotations(children, book, import),
+        }
+    }
+}
+
+/// Convert one annotation record
+///
+/// Records hold, in order: start position, end position, creation time, modification
+/// time, template, and for notes the note text. Only the leading fields are relied on.
+fn add_annotation(kind: &str, children: &[KrdsValue], book: &mut Book, import: &mut LocalImport) {
+    let mut strings = Vec::new();
+    let mut ints = Vec::new();
+    flatten(children, &mut strings, &mut ints);
+
+    let Some(start) = strings.first().filter(|s| !s.is_empty()).cloned() else {
+        import.skipped_records += 1;
+        return;
+    };
+
+    match kind {
+        "bookmark" => {}
+        "highlight" | "underline" => import.highlights_without_text += 1,
+        "note" => {
+            // The note is the last string that isn't one of the positions
+            let Some(text) = strings.iter().skip(2).rev().find(|s| !s.trim().is_empty()) else {
+                import.skipped_records += 1;
+                return;
+            };
+            let text = text.trim().to_string();
+
+            book.highlights.push(Highlight {
+                id: generate_highlight_id(&book.id, &text, Some(&start)),
+                text,
+                note: None,
+                location: Location {
+                    chapter: None,
+                    position: Some(start),
+                    page: None,
+                    raw: None,
+                },
+                created_at: ints.first().copied().and_then(DateTime::from_timestamp_millis),
+                sources: vec![Source::Kindle],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+        _ => import.skipped_records += 1,
+    }
+}
+
+fn flatten(values: &[KrdsValue], strings: &mut Vec<String>, ints: &mut Vec<i64>) {
+    for value in values {
+        match value {
+            KrdsValue::Str(s) => strings.push(s.clone()),
+            KrdsValue::Int(n) => ints.push(*n),
+            KrdsValue::Object(_, children) => flatten(children, strings, ints),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    /// Minimal KRDS writer for building fixtures
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn new() -> Self {
+            let mut data = KRDS_SIGNATURE.to_vec();
+            data.extend_from_slice(&1u64.to_be_bytes());
+            Writer(data)
+        }
+
+        fn utf(&mut self, s: &str) -> &mut Self {
+            self.0.push(TYPE_UTF);
+            self.raw_utf(s);
+            self
+        }
+
+        fn raw_utf(&mut self, s: &str) {
+            if s.is_empty() {
+                self.0.push(1);
+            } else {
+                self.0.push(0);
+                self.0.extend_from_slice(&(s.len() as u16).to_be_bytes());
+                self.0.extend_from_slice(s.as_bytes());
+            }
+        }
+
+        fn int(&mut self, n: i32) -> &mut Self {
+            self.0.push(TYPE_INT);
+            self.0.extend_from_slice(&n.to_be_bytes());
+            self
+        }
+
+        fn long(&mut self, n: i64) -> &mut Self {
+            self.0.push(TYPE_LONG);
+            self.0.extend_from_slice(&n.to_be_bytes());
+            self
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.0.push(TYPE_FIELD_BEGIN);
+            self.raw_utf(name);
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.0.push(TYPE_FIELD_END);
+            self
+        }
+    }
+
+    fn sample_sidecar() -> Vec<u8> {
+        let mut w = Writer::new();
+        w.int(1)
+            .begin("annotation.cache.object")
+            .int(3)
+            .int(2)
+            .begin("saved.avl.interval.tree")
+            .int(1)
+            .begin("annotation.personal.note")
+            .utf("AbCd:1200")
+            .utf("AbCd:1200")
+            .long(1700000000000)
+            .long(1700000000000)
+            .utf("")
+            .utf("Remember this")
+            .end()
+            .end()
+            .int(1)
+            .begin("saved.avl.interval.tree")
+            .int(1)
+            .begin("annotation.personal.highlight")
+            .utf("AbCd:1000")
+            .utf("AbCd:1100")
+            .long(1700000000000)
+            .long(1700000000000)
+            .utf("")
+            .end()
+            .end()
+            .int(99)
+            .begin("saved.avl.interval.tree")
+            .int(1)
+            .begin("annotation.personal.handwritten_note")
+            .utf("AbCd:10")
+            .end()
+            .end()
+            .end();
+        w.0
+    }
+
+    #[test]
+    fn test_parse_krds_rejects_other_files() {
+        assert!(parse_krds(b"not a sidecar").is_none());
+
+        let mut truncated = sample_sidecar();
+        truncated.truncate(truncated.len() - 5);
+        assert!(parse_krds(&truncated).is_none());
+    }
+
+    #[test]
+    fn test_parse_catalog() {
+        let xml = r#"<response><add_update_list>
+            <meta_data><ASIN>B00ABC1234</ASIN><title pronunciation="x">Pride &amp; Prejudice</title>
+            <authors><author pronunciation="y">Jane Austen</author></authors></meta_data>
+        </add_update_list></response>"#;
+
+        let catalog = parse_catalog(xml);
+        let entry = &catalog["B00ABC1234"];
+        assert_eq!(entry.title, "Pride & Prejudice");
+        assert_eq!(entry.author.as_deref(), Some("Jane Austen"));
+    }
+
+    #[test]
+    fn test_extract_notes_and_counts() {
+        let dir = std::env::temp_dir().join(format!("readingsync_kindle_local_{}", uuid::Uuid::new_v4()));
+        let sdr = dir.join("B00ABC1234_EBOK.sdr");
+        fs::create_dir_all(&sdr).unwrap();
+        fs::write(sdr.join("B00ABC1234_EBOK.yjr"), sample_sidecar()).unwrap();
+        fs::write(sdr.join("B00ABC1234_EBOK.azw3r"), b"garbage").unwrap();
+
+        let catalog = dir.join("KindleSyncMetadataCache.xml");
+        fs::write(
+            &catalog,
+            "<meta_data><ASIN>B00ABC1234</ASIN><title>Emma</title><authors><author>Jane Austen</author></authors></meta_data>",
+        )
+        .unwrap();
+
+        let import = extract(Some(dir.clone()), Some(catalog)).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(import.unreadable_files, 1);
+        assert_eq!(import.highlights_without_text, 1);
+        assert_eq!(import.skipped_records, 1);
+        assert_eq!(import.books.len(), 1);
+
+        let book = &import.books[0];
+        assert_eq!(book.title, "Emma");
+        assert_eq!(book.highlights.len(), 1);
+        assert_eq!(book.highlights[0].text, "Remember this");
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("AbCd:1200"));
+        assert_eq!(book.highlights[0].created_at, Utc.timestamp_opt(1700000000, 0).single());
+    }
+}
@@ -1,7 +1,30 @@
+pub mod backend;
+pub mod bookcision;
 pub mod browser;
+pub mod browser_cookies;
+pub mod checkpoint;
+pub mod chrome;
+pub mod concurrent;
 pub mod clippings;
+pub mod debug_dump;
+pub mod local;
+pub mod network;
+pub mod notebook_export;
+pub mod parse;
+pub mod progress;
+pub mod report;
+pub mod retry;
 pub mod scraper;
+pub mod sync_state;
+pub mod wait;
+#[cfg(feature = "webdriver")]
+pub mod webdriver;
 
-pub use browser::{AmazonRegion, BrowserConfig, KindleBrowserScraper};
+pub use backend::Backend;
+pub use browser::{
+    AmazonRegion, BookSelection, BrowserConfig, ContentType, KindleBrowserScraper, NotebookEntry, ScrapeEngine, ScrapeOptions,
+    ScrapeOutcome,
+};
 pub use clippings::parse_clippings;
+pub use progress::ScrapeObserver;
 pub use scraper::scrape_highlights;
@@ -0,0 +1,247 @@
+//! Read notebook annotations from the responses the notebook page fetches
+//!
+//! Opening a book on the notebook page requests
+//! `/notebook?asin=<ASIN>&contentLimitState=&token=`, and each further page of
+//! annotations is the same request with the `token` from the previous response. The
+//! response is an HTML fragment with one `.a-row.a-spacing-base` per annotation and
+//! hidden inputs holding the next token. Parsing it directly doesn't depend on how the
+//! page happens to lay out and restyle those fragments.
+
+use crate::kindle::parse;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// An annotation as found on the page, before it becomes a `Highlight`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RawAnnotation {
+    pub text: String,
+    pub note: Option<String>,
+    /// Location text, e.g. "Location: 1,234" or "Page 57"
+    pub location: Option<String>,
+    pub date: Option<String>,
+    pub color: Option<String>,
+}
+
+/// One page of annotations for a book
+#[derive(Debug, Default, PartialEq)]
+pub struct AnnotationPage {
+    pub annotations: Vec<RawAnnotation>,
+    /// Token requesting the next page; None on the last page
+    pub next_token: Option<String>,
+    /// Passed back with the next request, e.g. when a publisher limits clippings
+    pub content_limit_state: Option<String>,
+    /// The response carries a banner saying not every highlight is shown
+    pub limited: bool,
+    /// The response has the annotations list or annotation rows, whether or not any
+    /// annotation could be read from them
+    pub has_container: bool,
+}
+
+/// The annotations list and its rows
+const CONTAINER_SELECTOR: &str = "#kp-notebook-annotations, .a-row.a-spacing-base";
+
+/// Elements that hold notices rather than annotations
+pub const NOTICE_SELECTOR: &str = ".a-alert-content, [id*=\"limit\"], [class*=\"limit-message\"]";
+
+/// Whether a notice says the publisher limits the highlights shown, e.g. "You have
+/// reached the limit of annotations" or "Some highlights have been hidden"
+pub fn mentions_annotation_limit(notice: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(
+            r"(?i)(reached|exceeded) the (clipping |export |annotation |highlight )?limit|limit of (annotations|highlights|clippings)|(highlights|annotations) (have been|are|were) (hidden|truncated)|publisher (limits|has limited|restricts)",
+        )
+        .unwrap()
+    });
+    re.is_match(notice)
+}
+
+/// Whether a response URL is the annotations request for this book
+pub fn is_annotations_url(url: &str, asin: &str) -> bool {
+    url.contains("/notebook?") && url.split(['?', '&']).any(|param| param == format!("asin={}", asin))
+}
+
+/// Parse an annotations response
+pub fn parse_annotations(html: &str) -> AnnotationPage {
+    let fragment = Html::parse_fragment(html);
+
+    let row = Selector::parse(".a-row.a-spacing-base").unwrap();
+    let highlight = Selector::parse("#highlight").unwrap();
+    let note = Selector::parse("#note").unwrap();
+    let header = Selector::parse("#annotationHighlightHeader, #annotationNoteHeader").unwrap();
+    let location = Selector::parse("#kp-annotation-location").unwrap();
+    let date = Selector::parse("[id^=\"kp-annotation-date\"], .kp-notebook-annotation-date").unwrap();
+    let colored = Selector::parse("[class*=\"kp-notebook-highlight-\"]").unwrap();
+    let next_token = Selector::parse(".kp-notebook-annotations-next-page-start").unwrap();
+    let limit_state = Selector::parse(".kp-notebook-content-limit-state").unwrap();
+
+    let notice = Selector::parse(NOTICE_SELECTOR).unwrap();
+    let container = Selector::parse(CONTAINER_SELECTOR).unwrap();
+    let input_value = |selector: &Selector| {
+        fragment
+            .select(selector)
+            .next()
+            .and_then(|el| el.value().attr("value"))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+    };
+
+    let mut page = AnnotationPage {
+        next_token: input_value(&next_token),
+        content_limit_state: input_value(&limit_state),
+        limited: fragment.select(&notice).any(|el| mentions_annotation_limit(&element_text(el))),
+        has_container: fragment.select(&container).next().is_some(),
+        ..Default::default()
+    };
+
+    for element in fragment.select(&row) {
+        // Only the innermost rows are annotations; outer ones wrap the whole list
+        if element.select(&row).next().is_some() {
+            continue;
+        }
+        let Some(text) = element.select(&highlight).next().map(element_text).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        if page.annotations.iter().any(|a| a.text == text) {
+            continue;
+        }
+
+        // The header reads "Yellow highlight | Page: 57" and tells pages from locations;
+        // the hidden input only has the number
+        let location = element
+            .select(&header)
+            .next()
+            .map(element_text)
+            .and_then(|h| h.split_once('|').map(|(_, l)| l.trim().to_string()))
+            .or_else(|| element.select(&location).next().and_then(|el| el.value().attr("value").map(String::from)))
+            .filter(|l| !l.is_empty());
+
+        let color = element
+            .select(&colored)
+            .find_map(|el| parse::highlight_color(el.value().attr("class").unwrap_or_default()));
+
+        page.annotations.push(RawAnnotation {
+            text,
+            note: element.select(&note).next().and_then(|el| parse::clean_note(&element_text(el))),
+            location,
+            date: element
+                .select(&date)
+                .next()
+                .map(|el| el.value().attr("value").map(String::from).unwrap_or_else(|| element_text(el)))
+                .filter(|d| !d.is_empty()),
+            color,
+        });
+    }
+
+    page
+}
+
+/// Text content with whitespace (including non-breaking spaces) collapsed
+fn element_text(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<String>()
+        .split(|c: char| c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST_PAGE: &str = r#"
+        <div class="a-row a-spacing-base" id="kp-notebook-annotations">
+          <input type="hidden" class="kp-notebook-annotations-next-page-start" value="0x2A9F:1234">
+          <input type="hidden" class="kp-notebook-content-limit-state" value="">
+          <div id="QID1" class="a-row a-spacing-base">
+            <div class="a-column a-span10 kp-notebook-row-separator">
+              <input type="hidden" id="kp-annotation-location" value="1234">
+              <span id="annotationHighlightHeader" class="kp-notebook-metadata">Yellow highlight | Location:&nbsp;1,234</span>
+              <div id="highlight-QID1" class="kp-notebook-highlight kp-notebook-selectable kp-notebook-highlight-yellow">
+                <span id="highlight" class="a-size-base-plus">Simplify,   simplify.</span>
+              </div>
+              <div id="note-QID1" class="kp-notebook-note">
+                <span id="note-label">Note:</span><span id="note">The whole book</span>
+              </div>
+            </div>
+          </div>
+          <div id="QID2" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="57">
+            <span id="annotationHighlightHeader">Blue highlight | Page:&nbsp;57</span>
+            <div class="kp-notebook-highlight kp-notebook-highlight-blue"><span id="highlight">I went to the woods.</span></div>
+            <div class="kp-notebook-note aok-hidden"><span id="note"></span></div>
+          </div>
+          <div id="QID3" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="88">
+            <div class="kp-notebook-highlight kp-notebook-highlight-pink"><span id="highlight">No header here.</span></div>
+          </div>
+        </div>
+    "#;
+
+    #[test]
+    fn test_parse_annotations() {
+        let page = parse_annotations(FIRST_PAGE);
+
+        assert_eq!(page.next_token.as_deref(), Some("0x2A9F:1234"));
+        assert_eq!(page.content_limit_state, None);
+        assert!(page.has_container);
+        assert_eq!(page.annotations.len(), 3);
+
+        assert_eq!(
+            page.annotations[0],
+            RawAnnotation {
+                text: "Simplify, simplify.".to_string(),
+                note: Some("The whole book".to_string()),
+                location: Some("Location: 1,234".to_string()),
+                date: None,
+                color: Some("yellow".to_string()),
+            }
+        );
+        assert_eq!(page.annotations[1].location.as_deref(), Some("Page: 57"));
+        assert_eq!(page.annotations[1].note, None);
+        assert_eq!(page.annotations[1].color.as_deref(), Some("blue"));
+        assert_eq!(page.annotations[2].location.as_deref(), Some("88"));
+    }
+
+    #[test]
+    fn test_parse_last_page() {
+        let html = r#"<input type="hidden" class="kp-notebook-annotations-next-page-start" value="">
+            <div class="a-row a-spacing-base"><span id="highlight">Last one.</span></div>"#;
+        let page = parse_annotations(html);
+
+        assert_eq!(page.next_token, None);
+        assert_eq!(page.annotations.len(), 1);
+    }
+
+    #[test]
+    fn test_detects_limit_banner() {
+        let html = r#"<div class="a-box a-alert a-alert-warning"><div class="a-alert-content">
+            You have reached the clipping limit for this item. Some highlights are hidden.</div></div>
+            <div class="a-row a-spacing-base"><span id="highlight">Only one.</span></div>"#;
+        assert!(parse_annotations(html).limited);
+        assert!(!parse_annotations(FIRST_PAGE).limited);
+
+        assert!(mentions_annotation_limit("You have reached the limit of annotations for this book"));
+        assert!(mentions_annotation_limit("The publisher limits how many highlights can be displayed"));
+        assert!(!mentions_annotation_limit("Your highlights are synced across devices"));
+    }
+
+    #[test]
+    fn test_is_annotations_url() {
+        assert!(is_annotations_url(
+            "https://read.amazon.com/notebook?asin=B00B7NPRY8&contentLimitState=&",
+            "B00B7NPRY8"
+        ));
+        assert!(is_annotations_url(
+            "https://read.amazon.co.uk/notebook?contentLimitState=&token=abc&asin=B00B7NPRY8",
+            "B00B7NPRY8"
+        ));
+        assert!(!is_annotations_url("https://read.amazon.com/notebook?asin=B00OTHER1", "B00B7NPRY8"));
+        assert!(!is_annotations_url("https://read.amazon.com/notebook", "B00B7NPRY8"));
+    }
+}
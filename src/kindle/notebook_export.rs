@@ -0,0 +1,309 @@
+use crate::error::KindleError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// A parsed "Highlight (yellow) - Page 12 · Location 100" heading
+#[derive(Debug, PartialEq)]
+struct NoteHeading {
+    kind: NoteKind,
+    color: Option<String>,
+    position: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum NoteKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+/// Parse an HTML file from the Kindle app's "Export notebook"
+pub fn parse_html(path: &Path) -> Result<Book, KindleError> {
+    if !path.exists() {
+        return Err(KindleError::NotebookExportNotFound(path.to_path_buf()));
+    }
+
+    let html = fs::read_to_string(path).map_err(|e| KindleError::LocalReadError(path.to_path_buf(), e))?;
+
+    parse_notebook_document(&html).ok_or_else(|| {
+        KindleError::ParseError(format!("{} is not a Kindle notebook export", path.display()))
+    })
+}
+
+/// Parse the contents of a notebook export
+///
+/// The current layout is a flat list of divs: `bookTitle`, `authors`, then
+/// `sectionHeading` for each chapter and `noteHeading`/`noteText` pairs. The older
+/// layout puts the same cells in table rows, either classed like the divs or as bare
+/// rows with a heading cell and a text cell. A note directly after a highlight is
+/// attached to it; other notes are kept with the note as the text.
+///
+/// Returns None when the document has no title or no annotations.
+pub fn parse_notebook_document(html: &str) -> Option<Book> {
+    let document = Html::parse_document(html);
+
+    let classed = Selector::parse(".bookTitle, .authors, .sectionHeading, .noteHeading, .noteText, tr").unwrap();
+    let cells = Selector::parse("td, th").unwrap();
+    let color_span = Selector::parse("span[class^=\"highlight_\"]").unwrap();
+
+    let mut title: Option<String> = None;
+    let mut author: Option<String> = None;
+    let mut chapter: Option<String> = None;
+    let mut heading: Option<NoteHeading> = None;
+    let mut entries: Vec<(NoteHeading, Option<String>, String)> = Vec::new();
+
+    for element in document.select(&classed) {
+        let class = element.value().attr("class").unwrap_or_default();
+        let text = element_text(element);
+
+        if element.value().name() == "tr" {
+            // Classed cells inside the row are visited on their own
+            if element.select(&classed).any(|e| e.value().attr("class").is_some()) {
+                continue;
+            }
+
+            let row: Vec<ElementRef> = element.select(&cells).collect();
+            match row.as_slice() {
+                [only] => chapter = Some(element_text(*only)).filter(|c| !c.is_empty()),
+                [head, body, ..] => {
+                    if let Some(mut parsed) = parse_heading(&element_text(*head)) {
+                        parsed.color = parsed.color.or_else(|| span_color(*head, &color_span));
+                        entries.push((parsed, chapter.clone(), element_text(*body)));
+                    }
+                }
+                [] => {}
+            }
+            continue;
+        }
+
+        if class.contains("bookTitle") {
+            title = Some(text);
+        } else if class.contains("authors") {
+            author = Some(text).filter(|a| !a.is_empty());
+        } else if class.contains("sectionHeading") {
+            chapter = Some(text).filter(|c| !c.is_empty());
+        } else if class.contains("noteHeading") {
+            heading = parse_heading(&text).map(|mut parsed| {
+                parsed.color = parsed.color.or_else(|| span_color(element, &color_span));
+                parsed
+            });
+        } else if class.contains("noteText") {
+            if let Some(parsed) = heading.take() {
+                entries.push((parsed, chapter.clone(), text));
+            }
+        }
+    }
+
+    let title = title.filter(|t| !t.is_empty())?;
+    let mut book = Book::new(title, author);
+    book.sources = vec![Source::Kindle];
+
+    let mut previous_was_highlight = false;
+
+    for (heading, chapter, text) in entries {
+        match heading.kind {
+            NoteKind::Bookmark => {
+                previous_was_highlight = false;
+                continue;
+            }
+            _ if text.is_empty() => continue,
+            NoteKind::Note if previous_was_highlight => {
+                if let Some(last) = book.highlights.last_mut() {
+                    last.note = Some(text);
+                }
+                previous_was_highlight = false;
+                continue;
+            }
+            kind => previous_was_highlight = kind == NoteKind::Highlight,
+        }
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, &text, heading.position.as_deref()),
+            text,
+            note: None,
+            location: Location {
+                chapter,
+                position: heading.position,
+                page: None,
+                raw: None,
+            },
+            created_at: None,
+            sources: vec![Source::Kindle],
+            color: heading.color,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    if book.highlights.is_empty() {
+        return None;
+    }
+
+    Some(book)
+}
+
+/// Parse a note heading, e.g. "Highlight (yellow) - Page 12 · Location 100"
+///
+/// The Kindle location is preferred as the position, falling back to the page.
+fn parse_heading(text: &str) -> Option<NoteHeading> {
+    static KIND: OnceLock<Regex> = OnceLock::new();
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    static PAGE: OnceLock<Regex> = OnceLock::new();
+    let kind_re = KIND.get_or_init(|| Regex::new(r"(?i)^\s*(highlight|note|bookmark)\s*(?:\(\s*(\w+)\s*\))?").unwrap());
+    let location_re = LOCATION.get_or_init(|| Regex::new(r"(?i)location\s+([\d,]+)").unwrap());
+    let page_re = PAGE.get_or_init(|| Regex::new(r"(?i)page\s+([\w,]+)").unwrap());
+
+    let caps = kind_re.captures(text)?;
+    let kind = match caps[1].to_lowercase().as_str() {
+        "highlight" => NoteKind::Highlight,
+        "note" => NoteKind::Note,
+        _ => NoteKind::Bookmark,
+    };
+
+    let position = location_re
+        .captures(text)
+        .map(|c| format!("Location {}", c[1].replace(',', "")))
+        .or_else(|| page_re.captures(text).map(|c| format!("Page {}", &c[1])));
+
+    Some(NoteHeading {
+        kind,
+        color: caps.get(2).map(|m| m.as_str().to_lowercase()),
+        position,
+    })
+}
+
+/// Color from a `<span class="highlight_yellow">` inside a heading
+fn span_color(element: ElementRef, selector: &Selector) -> Option<String> {
+    element
+        .select(selector)
+        .next()
+        .and_then(|span| span.value().attr("class"))
+        .and_then(|class| class.split_whitespace().find_map(|c| c.strip_prefix("highlight_")))
+        .map(str::to_lowercase)
+}
+
+/// Element text with whitespace collapsed
+fn element_text(element: ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIV_LAYOUT: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "XHTML1-s.dtd" >
+<html xmlns="http://www.w3.org/TR/1999/REC-html-in-xml" xml:lang="en" lang="en">
+<head><meta charset="UTF-8"><title>Notebook Export</title></head>
+<body>
+<div class="bodyContainer">
+<div class="notebookFor">Notebook Export</div>
+<div class="bookTitle">Dune
+</div>
+<div class="authors">
+Frank Herbert
+</div>
+<div class="citation">
+Herbert, Frank. Dune. Ace, 2005. Kindle edition.
+</div>
+<hr />
+<div class="sectionHeading">Book One: Dune</div>
+<div class="noteHeading">Highlight(<span class="highlight_yellow">yellow</span>) - Page 12 · Location 100</div>
+<div class="noteText">I must not fear.</div>
+<div class="noteHeading">Note - Page 12 · Location 100</div>
+<div class="noteText">The litany</div>
+<div class="noteHeading">Bookmark - Page 20 · Location 150</div>
+<div class="noteText"></div>
+<div class="sectionHeading">Book Two: Muad'Dib</div>
+<div class="noteHeading">Highlight(<span class="highlight_blue">blue</span>) - Location 1,234</div>
+<div class="noteText">The mystery of life isn't a problem to solve, but a reality to experience.</div>
+<div class="noteHeading">Bookmark - Location 1,300</div>
+<div class="noteText"></div>
+<div class="noteHeading">Note - Location 1,400</div>
+<div class="noteText">Compare with the Orange Catholic Bible</div>
+</div>
+</body>
+</html>"#;
+
+    const TABLE_LAYOUT: &str = r#"<html>
+<head><title>Walden</title></head>
+<body>
+<h1 class="bookTitle">Walden</h1>
+<h2 class="authors">Henry David Thoreau</h2>
+<table>
+<tr><td colspan="2">Economy</td></tr>
+<tr><td>Highlight (pink) - Page 12</td><td>Simplify, simplify.</td></tr>
+<tr><td>Note - Page 12</td><td>Motto</td></tr>
+<tr><td colspan="2">Where I Lived</td></tr>
+<tr><td>Highlight (yellow) - Page 90</td><td>I went to the woods because I wished to live deliberately.</td></tr>
+</table>
+</body>
+</html>"#;
+
+    #[test]
+    fn test_parse_div_layout() {
+        let book = parse_notebook_document(DIV_LAYOUT).unwrap();
+
+        assert_eq!(book.title, "Dune");
+        assert_eq!(book.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(book.sources, vec![Source::Kindle]);
+        assert_eq!(book.highlights.len(), 3);
+
+        let first = &book.highlights[0];
+        assert_eq!(first.text, "I must not fear.");
+        assert_eq!(first.note.as_deref(), Some("The litany"));
+        assert_eq!(first.color.as_deref(), Some("yellow"));
+        assert_eq!(first.location.chapter.as_deref(), Some("Book One: Dune"));
+        assert_eq!(first.location.position.as_deref(), Some("Location 100"));
+
+        let second = &book.highlights[1];
+        assert_eq!(second.color.as_deref(), Some("blue"));
+        assert_eq!(second.location.chapter.as_deref(), Some("Book Two: Muad'Dib"));
+        assert_eq!(second.location.position.as_deref(), Some("Location 1234"));
+
+        // A note without a highlight before it stands alone
+        let third = &book.highlights[2];
+        assert_eq!(third.text, "Compare with the Orange Catholic Bible");
+        assert_eq!(third.color, None);
+    }
+
+    #[test]
+    fn test_parse_table_layout() {
+        let book = parse_notebook_document(TABLE_LAYOUT).unwrap();
+
+        assert_eq!(book.title, "Walden");
+        assert_eq!(book.author.as_deref(), Some("Henry David Thoreau"));
+        assert_eq!(book.highlights.len(), 2);
+
+        let first = &book.highlights[0];
+        assert_eq!(first.text, "Simplify, simplify.");
+        assert_eq!(first.note.as_deref(), Some("Motto"));
+        assert_eq!(first.color.as_deref(), Some("pink"));
+        assert_eq!(first.location.chapter.as_deref(), Some("Economy"));
+        assert_eq!(first.location.position.as_deref(), Some("Page 12"));
+
+        assert_eq!(book.highlights[1].location.chapter.as_deref(), Some("Where I Lived"));
+    }
+
+    #[test]
+    fn test_parse_heading() {
+        let heading = parse_heading("Highlight (orange) - Page 45 · Location 1,678").unwrap();
+        assert_eq!(heading.kind, NoteKind::Highlight);
+        assert_eq!(heading.color.as_deref(), Some("orange"));
+        assert_eq!(heading.position.as_deref(), Some("Location 1678"));
+
+        assert_eq!(parse_heading("Note - Page xii").unwrap().position.as_deref(), Some("Page xii"));
+        assert!(parse_heading("Contents").is_none());
+    }
+
+    #[test]
+    fn test_not_a_notebook() {
+        assert!(parse_notebook_document("<html><body><p>Hello</p></body></html>").is_none());
+    }
+}
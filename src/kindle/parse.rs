@@ -0,0 +1,301 @@
+//! Turning notebook annotations into highlights
+//!
+//! The browser scraper (either engine) and the cookie scraper read the same notebook
+//! markup, so what they read goes through the same steps here: color classes, notes,
+//! the page/location text and dates. Location and page labels are recognized in the
+//! notebook's English, German, French, Spanish and Italian versions.
+
+use crate::kindle::network::RawAnnotation;
+use crate::model::{generate_highlight_id, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Build a highlight from an annotation read by any engine
+pub fn to_highlight(book_id: &str, raw: &RawAnnotation) -> Highlight {
+    let (page, position) = raw.location.as_deref().map(parse_location_text).unwrap_or_default();
+    let text = raw.text.trim().to_string();
+
+    Highlight {
+        id: generate_highlight_id(book_id, &text, position.as_deref()),
+        text,
+        note: raw.note.as_deref().and_then(clean_note),
+        location: Location {
+            chapter: None,
+            position,
+            page,
+            raw: None,
+        },
+        created_at: raw.date.as_deref().and_then(parse_notebook_date),
+        sources: vec![Source::Kindle],
+        color: raw.color.as_deref().and_then(highlight_color),
+        kind: HighlightKind::Highlight,
+        style: HighlightStyle::Highlight,
+        context: None,
+        deleted_upstream: false,
+    }
+}
+
+/// The highlight color named by a `kp-notebook-highlight-<color>` class
+///
+/// Takes a whole class attribute or a color already pulled out of one, so
+/// "kp-notebook-highlight kp-notebook-highlight-yellow" and "Yellow" both give "yellow".
+pub fn highlight_color(class: &str) -> Option<String> {
+    static COLOR: OnceLock<Regex> = OnceLock::new();
+    let color_re = COLOR.get_or_init(|| Regex::new(r"kp-notebook-highlight-(\w+)").unwrap());
+    match color_re.captures(class) {
+        Some(caps) => Some(caps[1].to_lowercase()),
+        None if !class.contains("kp-notebook") => {
+            Some(class.trim().to_lowercase()).filter(|c| !c.is_empty() && c.chars().all(char::is_alphanumeric))
+        }
+        None => None,
+    }
+}
+
+/// A note's text, or None for the empty placeholder the notebook keeps under every
+/// highlight
+pub fn clean_note(text: &str) -> Option<String> {
+    let text = text.split(|c: char| c.is_whitespace()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ");
+    Some(text).filter(|t| !t.is_empty())
+}
+
+/// Split the notebook's location text into a printed page and a position
+///
+/// The element holds "Location 1,234", "Page 57" or both (in German "Position: 1.234"
+/// and "Seite: 57"); as in the notebook export, the Kindle location is the position
+/// and the page only falls back to it. Thousands separators are dropped so positions
+/// sort numerically, e.g. "Location 1234-1240".
+pub fn parse_location_text(text: &str) -> (Option<String>, Option<String>) {
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    static PAGE: OnceLock<Regex> = OnceLock::new();
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let location_re = LOCATION.get_or_init(|| {
+        Regex::new(
+            r"(?i)\b(?:loc(?:ation|\.)?|position|posici[oó]n|posizione|emplacement)\s*:?\s*([\d,.]+(?:\s*-\s*[\d,.]+)?)",
+        )
+        .unwrap()
+    });
+    let page_re =
+        PAGE.get_or_init(|| Regex::new(r"(?i)\b(?:page|seite|p[aá]gina)\s*:?\s*(\w[\w,.]*(?:\s*-\s*\w[\w,.]*)?)").unwrap());
+    let number_re = NUMBER.get_or_init(|| Regex::new(r"^[\d,.]+(?:\s*-\s*[\d,.]+)?$").unwrap());
+    let clean = |s: &str| s.trim_end_matches('.').replace([',', '.', ' '], "");
+
+    let page = page_re.captures(text).map(|c| clean(&c[1]));
+    let location = location_re.captures(text).map(|c| clean(&c[1])).or_else(|| {
+        // A bare number is a location, as on devices without page numbers
+        let text = text.trim();
+        number_re.is_match(text).then(|| clean(text))
+    });
+
+    match (page, location) {
+        (page, Some(location)) => (page, Some(format!("Location {}", location))),
+        (Some(page), None) => (None, Some(format!("Page {}", page))),
+        (None, None) => (None, Some(text.trim()).filter(|t| !t.is_empty()).map(String::from)),
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// Parse a date from the notebook page into UTC
+///
+/// Handles the US ("Monday January 1, 2024", "Jan 1, 2024") and UK ("Monday, 1 January
+/// 2024") orderings and numeric US dates, with an optional "Last annotated on" / "Added
+/// on" prefix and optional 12- or 24-hour time. The page shows no timezone, so the
+/// value is taken as UTC.
+pub fn parse_notebook_date(value: &str) -> Option<DateTime<Utc>> {
+    let is_month = |token: &str| {
+        token.len() >= 3 && MONTHS.iter().any(|m| m.starts_with(&token.to_lowercase()))
+    };
+
+    // Drop prefix words and the weekday: the date starts at a month name or a number
+    let cleaned = value.replace(',', " ");
+    let tokens: Vec<&str> = cleaned
+        .split_whitespace()
+        .map(|t| t.trim_end_matches('.'))
+        .skip_while(|t| !is_month(t) && !t.starts_with(|c: char| c.is_ascii_digit()))
+        .collect();
+    let date = tokens.join(" ");
+
+    const DATES: [&str; 5] = ["%B %d %Y", "%d %B %Y", "%b %d %Y", "%d %b %Y", "%m/%d/%Y"];
+    const TIMES: [&str; 4] = ["%I:%M:%S %p", "%I:%M %p", "%H:%M:%S", "%H:%M"];
+
+    DATES
+        .iter()
+        .flat_map(|d| TIMES.iter().map(move |t| format!("{} {}", d, t)))
+        .find_map(|format| NaiveDateTime::parse_from_str(&date, &format).ok())
+        .or_else(|| {
+            DATES
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(&date, format).ok())
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::network::parse_annotations;
+    use chrono::TimeZone;
+
+    /// A notebook page of amazon.com, as both scrapers fetch it
+    const US_PAGE: &str = r#"
+        <div class="a-row a-spacing-base" id="kp-notebook-annotations">
+          <input type="hidden" class="kp-notebook-annotations-next-page-start" value="">
+          <div id="QID1" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="1234">
+            <span id="annotationHighlightHeader" class="kp-notebook-metadata">Yellow highlight | Location:&nbsp;1,234</span>
+            <div class="kp-notebook-highlight kp-notebook-selectable kp-notebook-highlight-yellow">
+              <span id="highlight" class="a-size-base-plus">Simplify, simplify.</span>
+            </div>
+            <div class="kp-notebook-note"><span id="note-label">Note:</span><span id="note">The whole book</span></div>
+          </div>
+          <div id="QID2" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="57">
+            <span id="annotationHighlightHeader" class="kp-notebook-metadata">Orange highlight | Page:&nbsp;57</span>
+            <div class="kp-notebook-highlight kp-notebook-highlight-orange"><span id="highlight">I went to the woods.</span></div>
+            <div class="kp-notebook-note aok-hidden"><span id="note-label">Note:</span><span id="note">
+            </span></div>
+          </div>
+        </div>
+    "#;
+
+    /// The same page on amazon.de
+    const DE_PAGE: &str = r#"
+        <div class="a-row a-spacing-base" id="kp-notebook-annotations">
+          <input type="hidden" class="kp-notebook-annotations-next-page-start" value="">
+          <div id="QID1" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="1234">
+            <span id="annotationHighlightHeader" class="kp-notebook-metadata">Gelbe Markierung | Position:&nbsp;1.234</span>
+            <div class="kp-notebook-highlight kp-notebook-highlight-yellow"><span id="highlight">Vereinfache, vereinfache.</span></div>
+            <div class="kp-notebook-note"><span id="note-label">Notiz:</span><span id="note">Das ganze Buch</span></div>
+          </div>
+          <div id="QID2" class="a-row a-spacing-base">
+            <input type="hidden" id="kp-annotation-location" value="57">
+            <span id="annotationHighlightHeader" class="kp-notebook-metadata">Blaue Markierung | Seite:&nbsp;57</span>
+            <div class="kp-notebook-highlight kp-notebook-highlight-blue"><span id="highlight">Ich ging in die Wälder.</span></div>
+            <div class="kp-notebook-note aok-hidden"><span id="note-label">Notiz:</span><span id="note">&nbsp;</span></div>
+          </div>
+        </div>
+    "#;
+
+    fn highlights(html: &str) -> Vec<Highlight> {
+        parse_annotations(html).annotations.iter().map(|raw| to_highlight("book", raw)).collect()
+    }
+
+    #[test]
+    fn test_us_notebook_page() {
+        let highlights = highlights(US_PAGE);
+
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].color.as_deref(), Some("yellow"));
+        assert_eq!(highlights[0].note.as_deref(), Some("The whole book"));
+        assert_eq!(highlights[0].location.position.as_deref(), Some("Location 1234"));
+        assert_eq!(highlights[1].color.as_deref(), Some("orange"));
+        assert_eq!(highlights[1].note, None);
+        assert_eq!(highlights[1].location.position.as_deref(), Some("Page 57"));
+    }
+
+    #[test]
+    fn test_highlight_ids_are_stable() {
+        let first = highlights(US_PAGE);
+        let second = highlights(US_PAGE);
+
+        let ids: Vec<&str> = first.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, second.iter().map(|h| h.id.as_str()).collect::<Vec<_>>());
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[0], generate_highlight_id("book", &first[0].text, Some("Location 1234")));
+        // Another book's highlight of the same passage is a different highlight
+        let elsewhere = parse_annotations(US_PAGE).annotations.iter().map(|raw| to_highlight("other", raw)).next();
+        assert_ne!(elsewhere.map(|h| h.id).as_deref(), Some(ids[0]));
+    }
+
+    #[test]
+    fn test_german_notebook_page() {
+        let highlights = highlights(DE_PAGE);
+
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].color.as_deref(), Some("yellow"));
+        assert_eq!(highlights[0].note.as_deref(), Some("Das ganze Buch"));
+        assert_eq!(highlights[0].location.position.as_deref(), Some("Location 1234"));
+        assert_eq!(highlights[1].text, "Ich ging in die Wälder.");
+        assert_eq!(highlights[1].color.as_deref(), Some("blue"));
+        assert_eq!(highlights[1].note, None);
+        assert_eq!(highlights[1].location.position.as_deref(), Some("Page 57"));
+    }
+
+    #[test]
+    fn test_parse_location_text() {
+        let parsed = |page: Option<&str>, position: Option<&str>| (page.map(String::from), position.map(String::from));
+
+        assert_eq!(parse_location_text("Location 1,234"), parsed(None, Some("Location 1234")));
+        assert_eq!(parse_location_text("Location: 57"), parsed(None, Some("Location 57")));
+        assert_eq!(parse_location_text("Location 1,234-1,240"), parsed(None, Some("Location 1234-1240")));
+        assert_eq!(parse_location_text("Page 57"), parsed(None, Some("Page 57")));
+        assert_eq!(parse_location_text("Page xii"), parsed(None, Some("Page xii")));
+        assert_eq!(parse_location_text("Page 12 | Location 1,100"), parsed(Some("12"), Some("Location 1100")));
+        assert_eq!(parse_location_text(" 2,045 "), parsed(None, Some("Location 2045")));
+        assert_eq!(parse_location_text("Chapter 3"), parsed(None, Some("Chapter 3")));
+        assert_eq!(parse_location_text("  "), parsed(None, None));
+    }
+
+    #[test]
+    fn test_parse_localized_location_text() {
+        let parsed = |page: Option<&str>, position: Option<&str>| (page.map(String::from), position.map(String::from));
+
+        assert_eq!(parse_location_text("Position: 1.234"), parsed(None, Some("Location 1234")));
+        assert_eq!(parse_location_text("Seite: 57"), parsed(None, Some("Page 57")));
+        assert_eq!(parse_location_text("Seite 12 | Position 1.100"), parsed(Some("12"), Some("Location 1100")));
+        assert_eq!(parse_location_text("Emplacement : 880"), parsed(None, Some("Location 880")));
+        assert_eq!(parse_location_text("Página: 9"), parsed(None, Some("Page 9")));
+        assert_eq!(parse_location_text("Posizione 4.021"), parsed(None, Some("Location 4021")));
+    }
+
+    #[test]
+    fn test_highlight_color() {
+        assert_eq!(highlight_color("kp-notebook-highlight kp-notebook-highlight-yellow").as_deref(), Some("yellow"));
+        assert_eq!(highlight_color("kp-notebook-highlight-Orange").as_deref(), Some("orange"));
+        assert_eq!(highlight_color("Blue").as_deref(), Some("blue"));
+        assert_eq!(highlight_color("kp-notebook-highlight"), None);
+        assert_eq!(highlight_color(""), None);
+    }
+
+    #[test]
+    fn test_clean_note() {
+        assert_eq!(clean_note("  Read  this\u{a0}again \n").as_deref(), Some("Read this again"));
+        assert_eq!(clean_note(" \u{a0}\n "), None);
+    }
+
+    #[test]
+    fn test_parse_notebook_date_formats() {
+        let new_year = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        for value in [
+            "Monday January 1, 2024",
+            "Monday, January 1, 2024",
+            "Last annotated on Monday January 1, 2024",
+            "Jan 1, 2024",
+            "Monday, 1 January 2024",
+            "1 Jan. 2024",
+            "01/01/2024",
+        ] {
+            assert_eq!(parse_notebook_date(value), Some(new_year), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_notebook_date_with_time() {
+        assert_eq!(
+            parse_notebook_date("Added on Friday, March 8, 2024 1:05:09 PM"),
+            Some(Utc.with_ymd_and_hms(2024, 3, 8, 13, 5, 9).unwrap())
+        );
+        assert_eq!(
+            parse_notebook_date("Friday, 8 March 2024 13:05"),
+            Some(Utc.with_ymd_and_hms(2024, 3, 8, 13, 5, 0).unwrap())
+        );
+        assert_eq!(parse_notebook_date(""), None);
+        assert_eq!(parse_notebook_date("Not a date"), None);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::KindleError;
+use crate::kindle::retry::FailedBook;
+use crate::model::Book;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Receives progress events while the browser scraper works through the library
+///
+/// All methods default to doing nothing, so an embedding UI only implements the
+/// events it shows. `index` counts from 0.
+pub trait ScrapeObserver {
+    /// A general status message, e.g. "Fetching book list..."
+    fn status(&mut self, _message: &str) {}
+
+    /// The book list was read and `total` books will be scraped
+    fn book_list_fetched(&mut self, _total: usize) {}
+
+    fn book_started(&mut self, _index: usize, _total: usize, _title: &str) {}
+
+    /// An incremental sync reused the book from the last run
+    fn book_skipped(&mut self, _index: usize, _total: usize, _title: &str) {}
+
+    /// A resumed sync took the book from the interrupted run's checkpoint
+    fn book_resumed(&mut self, _index: usize, _total: usize, _title: &str) {}
+
+    fn book_finished(&mut self, _index: usize, _total: usize, _book: &Book) {}
+
+    /// Scraping a book failed and will be retried after `wait`
+    fn book_retrying(
+        &mut self,
+        _index: usize,
+        _total: usize,
+        _title: &str,
+        _retry: u32,
+        _error: &KindleError,
+        _wait: Duration,
+    ) {
+    }
+
+    /// Scraping a book failed on every attempt; the scrape carries on with the next one
+    fn book_failed(&mut self, _index: usize, _total: usize, _failed: &FailedBook) {}
+
+    /// Every book has been attempted
+    fn finished(&mut self, _books: &[Book]) {}
+}
+
+/// Observer that ignores every event
+pub struct NoProgress;
+
+impl ScrapeObserver for NoProgress {}
+
+/// Line-by-line progress on stderr, for logs and non-interactive terminals
+pub struct LineProgress;
+
+impl ScrapeObserver for LineProgress {
+    fn status(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
+
+    fn book_list_fetched(&mut self, total: usize) {
+        eprintln!("Found {} books", total);
+    }
+
+    fn book_started(&mut self, index: usize, total: usize, title: &str) {
+        eprintln!("  [{}/{}] Scraping: {}", index + 1, total, title);
+    }
+
+    fn book_skipped(&mut self, index: usize, total: usize, title: &str) {
+        eprintln!("  [{}/{}] Unchanged: {}", index + 1, total, title);
+    }
+
+    fn book_resumed(&mut self, index: usize, total: usize, title: &str) {
+        eprintln!("  [{}/{}] Already scraped: {}", index + 1, total, title);
+    }
+
+    fn book_finished(&mut self, _index: usize, _total: usize, book: &Book) {
+        eprintln!("    → {} highlights", book.highlights.len());
+    }
+
+    fn book_retrying(
+        &mut self,
+        _index: usize,
+        _total: usize,
+        _title: &str,
+        retry: u32,
+        error: &KindleError,
+        wait: Duration,
+    ) {
+        eprintln!("    → Error: {}; retry {} in {}", error, retry, format_duration(wait));
+    }
+
+    fn book_failed(&mut self, _index: usize, _total: usize, failed: &FailedBook) {
+        eprintln!("    → Error: {} (gave up after {} attempts)", failed.error, failed.attempts);
+    }
+}
+
+/// Width of the bar itself, in characters
+const BAR_WIDTH: usize = 24;
+
+/// A single progress line on stderr, redrawn in place
+///
+/// Shows books done/total, the running highlight count, an ETA and the current title.
+/// Errors are printed above the bar so they stay visible.
+pub struct ProgressBar {
+    total: usize,
+    done: usize,
+    highlights: usize,
+    title: String,
+    started: Instant,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        Self {
+            total: 0,
+            done: 0,
+            highlights: 0,
+            title: String::new(),
+            started: Instant::now(),
+        }
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let per_book = self.started.elapsed() / self.done as u32;
+        Some(per_book * (self.total - self.done) as u32)
+    }
+
+    fn draw(&self) {
+        let line = render_bar(self.done, self.total, self.highlights, self.eta(), &self.title);
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+
+    fn clear(&self) {
+        eprint!("\r\x1b[2K");
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScrapeObserver for ProgressBar {
+    fn status(&mut self, message: &str) {
+        self.clear();
+        eprintln!("{}", message);
+    }
+
+    fn book_list_fetched(&mut self, total: usize) {
+        self.total = total;
+        self.started = Instant::now();
+        self.draw();
+    }
+
+    fn book_started(&mut self, _index: usize, _total: usize, title: &str) {
+        self.title = title.to_string();
+        self.draw();
+    }
+
+    fn book_skipped(&mut self, _index: usize, _total: usize, _title: &str) {
+        // Skipped books take no time, so leave them out of the ETA
+        self.total -= 1;
+        self.draw();
+    }
+
+    fn book_resumed(&mut self, index: usize, total: usize, title: &str) {
+        self.book_skipped(index, total, title);
+    }
+
+    fn book_finished(&mut self, _index: usize, _total: usize, book: &Book) {
+        self.done += 1;
+        self.highlights += book.highlights.len();
+        self.draw();
+    }
+
+    fn book_retrying(
+        &mut self,
+        _index: usize,
+        _total: usize,
+        title: &str,
+        retry: u32,
+        error: &KindleError,
+        wait: Duration,
+    ) {
+        self.clear();
+        eprintln!("Error scraping {}: {}; retry {} in {}", title, error, retry, format_duration(wait));
+        self.draw();
+    }
+
+    fn book_failed(&mut self, _index: usize, _total: usize, failed: &FailedBook) {
+        self.done += 1;
+        self.clear();
+        eprintln!("Gave up on {}: {}", failed.title, failed.error);
+        self.draw();
+    }
+
+    fn finished(&mut self, _books: &[Book]) {
+        self.clear();
+    }
+}
+
+/// The progress display for the CLI: a bar on an interactive terminal, otherwise
+/// (or with `--verbose`) the line-by-line output
+pub fn default_observer(verbose: bool) -> Box<dyn ScrapeObserver> {
+    if !verbose && std::io::stderr().is_terminal() {
+        Box::new(ProgressBar::new())
+    } else {
+        Box::new(LineProgress)
+    }
+}
+
+/// Render one progress line, e.g.
+/// `[######------------------] 12/48 · 301 highlights · ETA 4m 10s · Walden`
+fn render_bar(done: usize, total: usize, highlights: usize, eta: Option<Duration>, title: &str) -> String {
+    let filled = (BAR_WIDTH * done).checked_div(total).unwrap_or(0).min(BAR_WIDTH);
+    let width = total.to_string().len();
+
+    let mut line = format!(
+        "[{}{}] {:>width$}/{} · {} highlights",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        done,
+        total,
+        highlights,
+        width = width
+    );
+    if let Some(eta) = eta {
+        line.push_str(&format!(" · ETA {}", format_duration(eta)));
+    }
+    if !title.is_empty() {
+        line.push_str(&format!(" · {}", truncate(title, 40)));
+    }
+    line
+}
+
+/// Format a duration as "1h 5m", "4m 10s" or "12s"
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+/// Shorten text to at most `max` characters, ending in "…" when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bar() {
+        assert_eq!(
+            render_bar(12, 48, 301, Some(Duration::from_secs(250)), "Walden"),
+            "[######------------------] 12/48 · 301 highlights · ETA 4m 10s · Walden"
+        );
+        assert_eq!(
+            render_bar(0, 0, 0, None, ""),
+            "[------------------------] 0/0 · 0 highlights"
+        );
+    }
+
+    #[test]
+    fn test_format_duration_and_truncate() {
+        assert_eq!(format_duration(Duration::from_secs(12)), "12s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h 5m");
+        assert_eq!(truncate("Walden", 10), "Walden");
+        assert_eq!(truncate("The Brothers Karamazov", 10), "The Broth…");
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::KindleError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a browser sync did, book by book
+///
+/// Written as JSON next to the library with `kindle --report`, so a sync can be
+/// checked or alerted on without diffing the library, e.g. for a book that suddenly
+/// returns far fewer highlights than last time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrapeReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub login: LoginStatus,
+    pub books: Vec<BookReport>,
+}
+
+/// How the run got past Amazon's sign-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginStatus {
+    /// The browser profile's session was still valid
+    Reused,
+    /// A sign-in page was shown and completed during the run
+    Established,
+}
+
+/// What happened to one book of the library list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookReport {
+    pub asin: String,
+    pub title: String,
+    pub status: BookStatus,
+    /// Highlights the run ended up with; 0 for failed books
+    pub highlights: usize,
+    /// Highlights the book had at the last sync, if it was synced before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_highlights: Option<usize>,
+    /// Scrape attempts, including retries; 0 for books not opened
+    pub attempts: u32,
+    /// Wall time spent on the book
+    pub seconds: f64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookStatus {
+    Scraped,
+    /// Unchanged since the last sync and reused from the sync state
+    Skipped,
+    /// Taken from an interrupted run's checkpoint
+    Resumed,
+    Failed,
+}
+
+impl ScrapeReport {
+    pub fn new(login: LoginStatus) -> Self {
+        let now = Utc::now();
+        Self {
+            started_at: now,
+            finished_at: now,
+            login,
+            books: Vec::new(),
+        }
+    }
+
+    /// Write the report as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> Result<(), KindleError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| KindleError::ReportError(path.to_path_buf(), e.to_string()))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| KindleError::ReportError(path.to_path_buf(), e.to_string()))?;
+
+        fs::write(path, content).map_err(|e| KindleError::ReportError(path.to_path_buf(), e.to_string()))
+    }
+}
+
+/// Report path for a library file: `library.json` gets `library.report.json`
+pub fn report_path(output: &Path) -> PathBuf {
+    output.with_extension("report.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_round_trip() {
+        let dir = std::env::temp_dir().join(format!("readingsync_report_{}", uuid::Uuid::new_v4()));
+        let path = report_path(&dir.join("library.json"));
+        assert_eq!(path.file_name().unwrap(), "library.report.json");
+
+        let mut report = ScrapeReport::new(LoginStatus::Reused);
+        report.books.push(BookReport {
+            asin: "B00B7NPRY8".to_string(),
+            title: "Walden".to_string(),
+            status: BookStatus::Scraped,
+            highlights: 3,
+            previous_highlights: Some(80),
+            attempts: 2,
+            seconds: 4.5,
+            truncated: true,
+            error: None,
+        });
+        report.save(&path).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(content.contains("\"login\": \"reused\""));
+        assert!(content.contains("\"status\": \"scraped\""));
+        assert!(!content.contains("\"error\""));
+        assert_eq!(serde_json::from_str::<ScrapeReport>(&content).unwrap(), report);
+    }
+}
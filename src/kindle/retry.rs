@@ -0,0 +1,248 @@
+use crate::error::KindleError;
+use crate::model::Book;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often and how patiently to retry a book that failed to scrape
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each one after
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            initial_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (counting from 1)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff * 2u32.saturating_pow(retry.saturating_sub(1))
+    }
+}
+
+/// Scrapes one book at a time, so retries can be driven (and tested) without a browser
+pub trait BookScraper {
+    fn scrape_book(&self, asin: &str, title: &str, author: Option<&str>) -> Result<Book, KindleError>;
+
+    /// Get back to a known-good page before retrying, e.g. by reloading the notebook
+    fn reset(&self) -> Result<(), KindleError>;
+}
+
+/// A book that still failed after every retry
+#[derive(Debug, Clone)]
+pub struct FailedBook {
+    pub asin: String,
+    pub title: String,
+    pub attempts: u32,
+    /// The last error
+    pub error: String,
+}
+
+/// Scrape a book, retrying with exponential backoff
+///
+/// Before each retry `on_retry` is told the retry number, the error and the wait, then
+/// `sleep` waits and the scraper is reset. A failed reset counts as a failed attempt.
+/// An error that isn't `KindleError::is_retryable` fails the book straight away.
+pub fn scrape_with_retries(
+    scraper: &dyn BookScraper,
+    policy: &RetryPolicy,
+    asin: &str,
+    title: &str,
+    author: Option<&str>,
+    on_retry: &mut dyn FnMut(u32, KindleError, Duration),
+    sleep: &dyn Fn(Duration),
+) -> Result<Book, FailedBook> {
+    let mut retry = 0;
+
+    loop {
+        let result = if retry == 0 {
+            scraper.scrape_book(asin, title, author)
+        } else {
+            scraper.reset().and_then(|_| scraper.scrape_book(asin, title, author))
+        };
+
+        match result {
+            Ok(book) => return Ok(book),
+            Err(e) if retry < policy.retries && e.is_retryable() => {
+                retry += 1;
+                let wait = policy.backoff(retry);
+                on_retry(retry, e, wait);
+                sleep(wait);
+            }
+            Err(e) => {
+                return Err(FailedBook {
+                    asin: asin.to_string(),
+                    title: title.to_string(),
+                    attempts: retry + 1,
+                    error: e.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// `scrape_with_retries` for async scrapers: `attempt` scrapes the book afresh each
+/// time and waits sleep on the tokio timer
+pub async fn scrape_with_retries_async<F, Fut>(
+    policy: &RetryPolicy,
+    asin: &str,
+    title: &str,
+    mut attempt: F,
+    on_retry: &mut dyn FnMut(u32, KindleError, Duration),
+) -> Result<Book, FailedBook>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Book, KindleError>>,
+{
+    let mut retry = 0;
+
+    loop {
+        match attempt().await {
+            Ok(book) => return Ok(book),
+            Err(e) if retry < policy.retries && e.is_retryable() => {
+                retry += 1;
+                let wait = policy.backoff(retry);
+                on_retry(retry, e, wait);
+                tokio::time::sleep(wait).await;
+            }
+            Err(e) => {
+                return Err(FailedBook {
+                    asin: asin.to_string(),
+                    title: title.to_string(),
+                    attempts: retry + 1,
+                    error: e.to_string(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    /// Fails the first `failures` scrapes, then succeeds
+    struct Flaky {
+        failures: u32,
+        scrapes: Cell<u32>,
+        resets: Cell<u32>,
+    }
+
+    impl Flaky {
+        fn new(failures: u32) -> Self {
+            Self {
+                failures,
+                scrapes: Cell::new(0),
+                resets: Cell::new(0),
+            }
+        }
+    }
+
+    impl BookScraper for Flaky {
+        fn scrape_book(&self, _asin: &str, title: &str, author: Option<&str>) -> Result<Book, KindleError> {
+            self.scrapes.set(self.scrapes.get() + 1);
+            if self.scrapes.get() <= self.failures {
+                Err(KindleError::ParseError(format!("attempt {} failed", self.scrapes.get())))
+            } else {
+                Ok(Book::new(title.to_string(), author.map(String::from)))
+            }
+        }
+
+        fn reset(&self) -> Result<(), KindleError> {
+            self.resets.set(self.resets.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn run(scraper: &Flaky, policy: &RetryPolicy) -> (Result<Book, FailedBook>, Vec<Duration>, Vec<u32>) {
+        let waits = RefCell::new(Vec::new());
+        let mut retries = Vec::new();
+        let result = scrape_with_retries(
+            scraper,
+            policy,
+            "B000",
+            "Walden",
+            None,
+            &mut |retry, _, _| retries.push(retry),
+            &|wait| waits.borrow_mut().push(wait),
+        );
+        (result, waits.into_inner(), retries)
+    }
+
+    #[test]
+    fn test_retries_with_exponential_backoff() {
+        let policy = RetryPolicy {
+            retries: 3,
+            initial_backoff: Duration::from_secs(1),
+        };
+        let scraper = Flaky::new(2);
+
+        let (result, waits, retries) = run(&scraper, &policy);
+
+        assert_eq!(result.unwrap().title, "Walden");
+        assert_eq!(waits, vec![Duration::from_secs(1), Duration::from_secs(2)]);
+        assert_eq!(retries, vec![1, 2]);
+        assert_eq!(scraper.scrapes.get(), 3);
+        assert_eq!(scraper.resets.get(), 2);
+    }
+
+    #[test]
+    fn test_gives_up_after_retries() {
+        let scraper = Flaky::new(10);
+
+        let (result, waits, _) = run(&scraper, &RetryPolicy::default());
+
+        let failed = result.unwrap_err();
+        assert_eq!(failed.attempts, 3);
+        assert_eq!(failed.asin, "B000");
+        assert!(failed.error.contains("attempt 3 failed"));
+        assert_eq!(waits, vec![Duration::from_secs(2), Duration::from_secs(4)]);
+    }
+
+    #[test]
+    fn test_no_retries() {
+        let policy = RetryPolicy {
+            retries: 0,
+            ..RetryPolicy::default()
+        };
+        let scraper = Flaky::new(1);
+
+        let (result, waits, _) = run(&scraper, &policy);
+
+        assert_eq!(result.unwrap_err().attempts, 1);
+        assert!(waits.is_empty());
+        assert_eq!(scraper.resets.get(), 0);
+    }
+
+    #[test]
+    fn test_only_transient_errors_are_retried() {
+        struct Status(u16, Cell<u32>);
+        impl BookScraper for Status {
+            fn scrape_book(&self, _asin: &str, _title: &str, _author: Option<&str>) -> Result<Book, KindleError> {
+                self.1.set(self.1.get() + 1);
+                Err(KindleError::HttpStatus(self.0, "https://read.amazon.com/notebook".to_string()))
+            }
+
+            fn reset(&self) -> Result<(), KindleError> {
+                Ok(())
+            }
+        }
+
+        for (status, attempts) in [(404, 1), (429, 3), (503, 3)] {
+            let scraper = Status(status, Cell::new(0));
+            let result = scrape_with_retries(&scraper, &RetryPolicy::default(), "B000", "Walden", None, &mut |_, _, _| {}, &|_| {});
+            assert_eq!(result.unwrap_err().attempts, attempts, "HTTP {}", status);
+            assert_eq!(scraper.1.get(), attempts);
+        }
+        assert!(!KindleError::NotAuthenticated.is_retryable());
+    }
+}
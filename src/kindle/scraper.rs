@@ -1,12 +1,28 @@
-use crate::error::KindleError;
-use crate::model::{generate_book_id, Book, Highlight, Location, Source};
-use reqwest::blocking::Client;
+use crate::config::HttpConfig;
+use crate::error::{KindleError, NetworkError};
+use crate::http;
+use crate::kindle::browser::{region_for_host, ScrapeOutcome};
+use crate::kindle::browser_cookies::{self, CookieBrowser, StoredCookie};
+use crate::kindle::concurrent::RateLimiter;
+use crate::kindle::debug_dump::{BookCapture, DebugDump};
+use crate::kindle::progress::{NoProgress, ScrapeObserver};
+use crate::kindle::report::{BookReport, BookStatus, LoginStatus, ScrapeReport};
+use crate::kindle::retry::{scrape_with_retries_async, FailedBook, RetryPolicy};
+use crate::kindle::network;
+use crate::kindle::parse;
+use crate::model::{generate_book_id, Book, Highlight, Source};
+use chrono::Utc;
+use futures_util::stream::{self, StreamExt};
+use reqwest::Client;
 use reqwest::cookie::Jar;
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 use scraper::{Html, Selector};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
 /// Amazon region configuration for cookie-based scraping (legacy)
@@ -43,70 +59,309 @@ impl LegacyAmazonRegion {
     }
 }
 
+/// Where the cookie scraper gets its Amazon session from
+#[derive(Debug, Clone)]
+pub enum CookieSource {
+    /// A Netscape-format cookies.txt
+    File(PathBuf),
+    /// The cookie store of a browser installed on this machine
+    Browser(CookieBrowser),
+}
+
+/// Pacing and safety limits for the cookie scraper
+#[derive(Debug, Clone)]
+pub struct LegacyScrapeConfig {
+    /// Most pages of highlights read per book
+    pub max_pages: usize,
+    /// Least time between two requests to Amazon, across all books in flight
+    pub request_delay: Duration,
+    /// Books fetched at the same time; a book's own pages are always fetched in order
+    pub concurrency: usize,
+    /// Retries for a book whose requests fail with a transient error (429, 5xx, timeouts)
+    pub retry: RetryPolicy,
+    /// Proxy, extra root certificates and user agent
+    pub http: HttpConfig,
+    /// Save every book's raw pages here (see `debug_dump`); without it only books
+    /// whose annotations couldn't be read are saved, to the data dir
+    pub debug_dump: Option<PathBuf>,
+}
+
+impl Default for LegacyScrapeConfig {
+    fn default() -> Self {
+        Self {
+            max_pages: 100,
+            request_delay: Duration::from_millis(500),
+            concurrency: 3,
+            retry: RetryPolicy::default(),
+            http: HttpConfig::default(),
+            debug_dump: None,
+        }
+    }
+}
+
 /// Scrape highlights from Amazon's Kindle Notebook (legacy cookie-based method)
 pub fn scrape_highlights(
     cookies_path: &Path,
     region: &LegacyAmazonRegion,
 ) -> Result<Vec<Book>, KindleError> {
-    if !cookies_path.exists() {
-        return Err(KindleError::CookieFileNotFound(cookies_path.to_path_buf()));
-    }
+    let source = CookieSource::File(cookies_path.to_path_buf());
+    Ok(scrape_notebook(&source, Some(region), &LegacyScrapeConfig::default(), &mut NoProgress)?.books)
+}
 
-    // Load cookies
-    let jar = load_cookies(cookies_path, &region.domain)?;
+/// `scrape_highlights` for async callers
+pub async fn scrape_highlights_async(
+    cookies_path: &Path,
+    region: &LegacyAmazonRegion,
+) -> Result<Vec<Book>, KindleError> {
+    let source = CookieSource::File(cookies_path.to_path_buf());
+    Ok(scrape_notebook_async(&source, Some(region), &LegacyScrapeConfig::default(), &mut NoProgress)
+        .await?
+        .books)
+}
+
+/// Scrape the notebook with the session in `source`, reporting progress to `observer`
+///
+/// Runs `scrape_notebook_async` on a runtime of its own, so it must not be called
+/// from async code.
+pub fn scrape_notebook(
+    source: &CookieSource,
+    region: Option<&LegacyAmazonRegion>,
+    config: &LegacyScrapeConfig,
+    observer: &mut dyn ScrapeObserver,
+) -> Result<ScrapeOutcome, KindleError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(KindleError::RuntimeError)?;
+    runtime.block_on(scrape_notebook_async(source, region, config, observer))
+}
+
+/// Scrape the notebook with the session in `source`, reporting progress to `observer`
+///
+/// Up to `config.concurrency` books are fetched at once, all sharing the politeness
+/// delay. A book that still fails after its retries is recorded in
+/// `ScrapeOutcome.failed` and the scrape carries on with the others; only failing to
+/// read the book list (e.g. `NotAuthenticated`) ends it. Books come back in library
+/// order whatever order they finish in.
+///
+/// Without a `region` the one the cookies are signed in to is used (see
+/// `region_from_cookies`). A given region whose store has no sign-in cookie while
+/// another's does fails with `WrongRegion`.
+///
+/// The future borrows the observer across awaits and is not `Send`; await it
+/// directly (e.g. in `block_on`) rather than spawning it.
+pub async fn scrape_notebook_async(
+    source: &CookieSource,
+    region: Option<&LegacyAmazonRegion>,
+    config: &LegacyScrapeConfig,
+    observer: &mut dyn ScrapeObserver,
+) -> Result<ScrapeOutcome, KindleError> {
+    let cookies = match source {
+        CookieSource::File(path) => {
+            if !path.exists() {
+                return Err(KindleError::CookieFileNotFound(path.clone()));
+            }
+            read_cookie_file(path)?
+        }
+        CookieSource::Browser(browser) => {
+            let cookies = browser_cookies::read_amazon_cookies(*browser)?;
+            if cookies.is_empty() {
+                let domain = region.map_or("Amazon", |r| r.domain.as_str());
+                return Err(KindleError::NoBrowserCookies(browser.name(), domain.to_string()));
+            }
+            cookies
+        }
+    };
+
+    let detect = region.is_none();
+    let region = session_region(&cookies, region)?;
+    if detect {
+        observer.status(&format!("Amazon region {} ({}), from the cookies", region.code, region.domain));
+    }
+    if let CookieSource::Browser(browser) = source {
+        if !cookies.iter().any(|c| browser_cookies::matches_domain(&c.host, &region.domain)) {
+            return Err(KindleError::NoBrowserCookies(browser.name(), region.domain.clone()));
+        }
+    }
+    let jar = cookie_jar(&cookies, &region.domain)?;
 
     // Create HTTP client with cookies
-    let client = Client::builder()
+    let client = http::client_builder(&config.http)?
         .cookie_provider(Arc::new(jar))
-        .default_headers(default_headers())
-        .build()?;
+        .default_headers(default_headers(&config.http)?)
+        .build()
+        .map_err(|e| NetworkError::Client(e.to_string()))?;
+    let scraper = HttpScraper {
+        client,
+        region: &region,
+        config,
+        limiter: RateLimiter::new(config.request_delay),
+        dump: DebugDump::new(config.debug_dump.clone()),
+    };
 
     // Fetch book list
-    let books_data = fetch_book_list(&client, region)?;
+    observer.status("Fetching book list...");
+    let books_data = fetch_book_list(&scraper).await?;
+    let total = books_data.len();
+    observer.book_list_fetched(total);
+
+    // Events come from whichever book is at an await point, one at a time
+    let observer = RefCell::new(observer);
+    let mut results: Vec<(usize, BookReport, Result<Book, FailedBook>)> = stream::iter(books_data.into_iter().enumerate())
+        .map(|(index, book_data)| scrape_book(&scraper, index, total, book_data, &observer))
+        .buffer_unordered(config.concurrency.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let mut report = ScrapeReport::new(LoginStatus::Reused);
+    let mut outcome = ScrapeOutcome::default();
+    for (_, book_report, result) in results {
+        match result {
+            Ok(book) => outcome.books.push(book),
+            Err(failed) => outcome.failed.push(failed),
+        }
+        report.books.push(book_report);
+    }
 
-    // Fetch highlights for each book
-    let mut books = Vec::new();
-    for book_data in books_data {
-        let highlights = fetch_book_highlights(&client, region, &book_data.asin)?;
-
-        let id = generate_book_id(&book_data.title, book_data.author.as_deref());
-        let book = Book {
-            id,
-            title: book_data.title,
-            author: book_data.author,
-            sources: vec![Source::Kindle],
-            highlights,
-            finished: None,
-            finished_at: None,
-        };
-        books.push(book);
+    report.finished_at = Utc::now();
+    outcome.report = Some(report);
+    observer.into_inner().finished(&outcome.books);
+    Ok(outcome)
+}
+
+/// Scrape one book with retries, returning its place in the book list and its report
+async fn scrape_book(
+    scraper: &HttpScraper<'_>,
+    index: usize,
+    total: usize,
+    book_data: BookData,
+    observer: &RefCell<&mut dyn ScrapeObserver>,
+) -> (usize, BookReport, Result<Book, FailedBook>) {
+    observer.borrow_mut().book_started(index, total, &book_data.title);
+    let started = Instant::now();
+    let mut attempts = 1;
+    let result = scrape_with_retries_async(
+        &scraper.config.retry,
+        &book_data.asin,
+        &book_data.title,
+        || fetch_book(scraper, &book_data),
+        &mut |retry, error, wait| {
+            attempts = retry + 1;
+            observer
+                .borrow_mut()
+                .book_retrying(index, total, &book_data.title, retry, &error, wait);
+        },
+    )
+    .await;
+
+    let mut book_report = BookReport {
+        asin: book_data.asin.clone(),
+        title: book_data.title.clone(),
+        status: BookStatus::Scraped,
+        highlights: 0,
+        previous_highlights: None,
+        attempts,
+        seconds: started.elapsed().as_secs_f64(),
+        truncated: false,
+        error: None,
+    };
+    match &result {
+        Ok(book) => {
+            book_report.highlights = book.highlights.len();
+            book_report.truncated = book.truncated;
+            observer.borrow_mut().book_finished(index, total, book);
+        }
+        Err(failed) => {
+            book_report.status = BookStatus::Failed;
+            book_report.attempts = failed.attempts;
+            book_report.error = Some(failed.error.clone());
+            observer.borrow_mut().book_failed(index, total, failed);
+        }
     }
+    (index, book_report, result)
+}
 
-    Ok(books)
+/// Fetch every page of a book's highlights
+async fn fetch_book(scraper: &HttpScraper<'_>, book_data: &BookData) -> Result<Book, KindleError> {
+    let id = generate_book_id(&book_data.title, book_data.author.as_deref());
+    let mut capture = BookCapture::default();
+    let (highlights, truncated) = fetch_book_highlights(scraper, &book_data.asin, &id, &mut capture).await?;
+    scraper.dump.finish_book(&book_data.asin, &book_data.title, &capture, &highlights);
+
+    Ok(Book {
+        id,
+        title: book_data.title.clone(),
+        author: book_data.author.clone(),
+        sources: vec![Source::Kindle],
+        highlights,
+        finished: None,
+        finished_at: None,
+        url: None,
+        isbn: None,
+        collections: Vec::new(),
+        unavailable_highlights: 0,
+        truncated,
+        original_title: None,
+        aliases: Vec::new(),
+        cover_url: None,
+        source_ids: BTreeMap::from([("asin".to_string(), book_data.asin.clone())]),
+        document_type: None,
+        progress: None,
+        last_opened_at: None,
+        language: None,
+        genre: None,
+    })
 }
 
+/// Fetches notebook pages with the session cookies, spacing requests out
+struct HttpScraper<'a> {
+    client: Client,
+    region: &'a LegacyAmazonRegion,
+    config: &'a LegacyScrapeConfig,
+    limiter: RateLimiter,
+    dump: DebugDump,
+}
+
+impl HttpScraper<'_> {
+    /// GET a page after the politeness delay; an error status fails with `HttpStatus`
+    async fn get(&self, url: &str) -> Result<String, KindleError> {
+        self.limiter.wait_async().await;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| http::request_error(&e, url, &self.config.http))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(KindleError::HttpStatus(status.as_u16(), url.to_string()));
+        }
+        Ok(response.text().await?)
+    }
+}
+
+/// User agent sent unless `[http] user_agent` is set; the notebook expects a desktop browser
+const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
 /// Default headers for requests
-fn default_headers() -> HeaderMap {
+fn default_headers(config: &HttpConfig) -> Result<HeaderMap, NetworkError> {
+    let user_agent = config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
     let mut headers = HeaderMap::new();
     headers.insert(
         USER_AGENT,
-        HeaderValue::from_static(
-            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
-        ),
+        HeaderValue::from_str(user_agent).map_err(|_| NetworkError::InvalidUserAgent(user_agent.to_string()))?,
     );
-    headers
+    Ok(headers)
 }
 
-/// Load cookies from Netscape format file
-fn load_cookies(path: &Path, domain: &str) -> Result<Jar, KindleError> {
+/// The cookies in a Netscape-format cookies.txt
+fn read_cookie_file(path: &Path) -> Result<Vec<StoredCookie>, KindleError> {
     let content = fs::read_to_string(path)
         .map_err(|e| KindleError::CookieLoadError(format!("Failed to read cookie file: {}", e)))?;
 
-    let jar = Jar::default();
-    let base_url = format!("https://{}", domain)
-        .parse::<Url>()
-        .map_err(|e| KindleError::CookieLoadError(format!("Invalid URL: {}", e)))?;
-
+    let mut cookies = Vec::new();
     for line in content.lines() {
         let line = line.trim();
 
@@ -118,16 +373,89 @@ fn load_cookies(path: &Path, domain: &str) -> Result<Jar, KindleError> {
         // Netscape format: domain  flag  path  secure  expiration  name  value
         let parts: Vec<&str> = line.split('\t').collect();
         if parts.len() >= 7 {
-            let cookie_domain = parts[0].trim_start_matches('.');
-            let name = parts[5];
-            let value = parts[6];
-
-            // Only add cookies for the target domain
-            if cookie_domain.contains(domain) || domain.contains(cookie_domain) {
-                let cookie = format!("{}={}", name, value);
-                jar.add_cookie_str(&cookie, &base_url);
+            cookies.push(StoredCookie {
+                host: parts[0].to_string(),
+                name: parts[5].to_string(),
+                value: parts[6].to_string(),
+            });
+        }
+    }
+
+    Ok(cookies)
+}
+
+/// Whether a cookie holds an Amazon sign-in, e.g. `at-main` on amazon.com or
+/// `at-acbuk` on amazon.co.uk
+fn is_signin_cookie(name: &str) -> bool {
+    name.starts_with("at-") || name.starts_with("sess-at-")
+}
+
+/// The region of the store `cookies` are signed in to, if any
+fn signed_in_region(cookies: &[StoredCookie]) -> Option<&'static str> {
+    cookies
+        .iter()
+        .filter(|c| is_signin_cookie(&c.name))
+        .find_map(|c| region_for_host(&c.host))
+}
+
+/// The region whose Amazon store `cookies` belong to
+///
+/// The store holding a sign-in cookie wins; without one, the store with the most
+/// cookies (its notebook then reports `NotAuthenticated`). None when no cookie is for
+/// an Amazon store.
+pub fn region_from_cookies(cookies: &[StoredCookie]) -> Option<&'static str> {
+    signed_in_region(cookies).or_else(|| {
+        let mut counts: Vec<(&'static str, usize)> = Vec::new();
+        for code in cookies.iter().filter_map(|c| region_for_host(&c.host)) {
+            match counts.iter_mut().find(|(c, _)| *c == code) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((code, 1)),
             }
         }
+        // A stable sort, so ties go to the store seen first
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counts.first().map(|(code, _)| *code)
+    })
+}
+
+/// The region to scrape: `region` when given, else the one `cookies` belong to
+fn session_region(
+    cookies: &[StoredCookie],
+    region: Option<&LegacyAmazonRegion>,
+) -> Result<LegacyAmazonRegion, KindleError> {
+    let Some(region) = region else {
+        let code = region_from_cookies(cookies).ok_or(KindleError::RegionUndetected)?;
+        return LegacyAmazonRegion::from_code(code);
+    };
+
+    let signed_in_here = cookies
+        .iter()
+        .any(|c| is_signin_cookie(&c.name) && browser_cookies::matches_domain(&c.host, &region.domain));
+    if !signed_in_here {
+        if let Some(other) = signed_in_region(cookies) {
+            let other = LegacyAmazonRegion::from_code(other)?;
+            if other.domain != region.domain {
+                return Err(KindleError::WrongRegion {
+                    expected: region.domain.clone(),
+                    detected: other.domain,
+                    code: other.code,
+                });
+            }
+        }
+    }
+    Ok(region.clone())
+}
+
+/// A cookie jar holding the cookies for `domain`, scoped to it so they also reach
+/// read.<domain>
+fn cookie_jar(cookies: &[StoredCookie], domain: &str) -> Result<Jar, KindleError> {
+    let jar = Jar::default();
+    let base_url = format!("https://{}", domain)
+        .parse::<Url>()
+        .map_err(|e| KindleError::CookieLoadError(format!("Invalid URL: {}", e)))?;
+
+    for cookie in cookies.iter().filter(|c| browser_cookies::matches_domain(&c.host, domain)) {
+        jar.add_cookie_str(&format!("{}={}; Domain={}; Path=/", cookie.name, cookie.value, domain), &base_url);
     }
 
     Ok(jar)
@@ -141,14 +469,14 @@ struct BookData {
 }
 
 /// Fetch the list of books from the notebook page
-fn fetch_book_list(client: &Client, region: &LegacyAmazonRegion) -> Result<Vec<BookData>, KindleError> {
-    let response = client.get(&region.notebook_url).send()?;
-
-    if !response.status().is_success() {
-        return Err(KindleError::NotAuthenticated);
-    }
-
-    let html = response.text()?;
+async fn fetch_book_list(scraper: &HttpScraper<'_>) -> Result<Vec<BookData>, KindleError> {
+    let html = match scraper.get(&scraper.region.notebook_url).await {
+        Ok(html) => html,
+        Err(KindleError::HttpStatus(status, _)) if (400..500).contains(&status) && status != 429 => {
+            return Err(KindleError::NotAuthenticated)
+        }
+        Err(e) => return Err(e),
+    };
 
     // Check for login redirect
     if html.contains("ap_email") || html.contains("signIn") {
@@ -215,19 +543,27 @@ fn parse_book_list(html: &str) -> Result<Vec<BookData>, KindleError> {
     Ok(books)
 }
 
-/// Fetch highlights for a specific book
-fn fetch_book_highlights(
-    client: &Client,
-    region: &LegacyAmazonRegion,
+/// Fetch highlights for a specific book, following pagination tokens, and whether a
+/// page said the publisher limits the highlights shown
+///
+/// Pages are the same fragments the browser's network engine reads, so they go through
+/// `network::parse_annotations` and `parse::to_highlight` too. Stops after `max_pages`
+/// pages, or if a page hands back a token already followed.
+async fn fetch_book_highlights(
+    scraper: &HttpScraper<'_>,
     asin: &str,
-) -> Result<Vec<Highlight>, KindleError> {
+    book_id: &str,
+    capture: &mut BookCapture,
+) -> Result<(Vec<Highlight>, bool), KindleError> {
     let mut highlights = Vec::new();
+    let mut limited = false;
     let mut pagination_token: Option<String> = None;
     let mut content_limit_state: Option<String> = None;
+    let mut seen_tokens = HashSet::new();
 
-    loop {
+    for _ in 0..scraper.config.max_pages {
         // Build URL with pagination params
-        let mut url = format!("{}?asin={}", region.notebook_url, asin);
+        let mut url = format!("{}?asin={}", scraper.region.notebook_url, asin);
         if let Some(ref token) = pagination_token {
             url.push_str(&format!("&token={}", token));
         }
@@ -235,130 +571,281 @@ fn fetch_book_highlights(
             url.push_str(&format!("&contentLimitState={}", state));
         }
 
-        let response = client.get(&url).send()?;
-        let html = response.text()?;
+        let html = scraper.get(&url).await?;
 
-        let (page_highlights, next_token, next_state) = parse_highlights_page(&html)?;
-        highlights.extend(page_highlights);
+        let page = network::parse_annotations(&html);
+        capture.html(&html, page.has_container);
+        highlights.extend(page.annotations.iter().map(|raw| parse::to_highlight(book_id, raw)));
+        limited |= page.limited;
 
         // Check for next page
-        if next_token.is_some() {
-            pagination_token = next_token;
-            content_limit_state = next_state;
-        } else {
-            break;
+        match page.next_token {
+            Some(token) if seen_tokens.insert(token.clone()) => {
+                pagination_token = Some(token);
+                content_limit_state = page.content_limit_state;
+            }
+            _ => break,
         }
     }
 
-    Ok(highlights)
+    Ok((highlights, limited))
 }
 
-/// Parse highlights from a single page
-fn parse_highlights_page(
-    html: &str,
-) -> Result<(Vec<Highlight>, Option<String>, Option<String>), KindleError> {
-    let document = Html::parse_document(html);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Selectors for highlights
-    let highlight_container_selector = Selector::parse(".a-row.a-spacing-base")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+    #[test]
+    fn test_legacy_amazon_region() {
+        let us = LegacyAmazonRegion::from_code("us").unwrap();
+        assert_eq!(us.domain, "amazon.com");
 
-    let highlight_text_selector = Selector::parse("#highlight")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+        let uk = LegacyAmazonRegion::from_code("UK").unwrap();
+        assert_eq!(uk.domain, "amazon.co.uk");
 
-    let note_selector = Selector::parse("#note")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+        let invalid = LegacyAmazonRegion::from_code("xyz");
+        assert!(invalid.is_err());
+    }
 
-    let location_selector = Selector::parse("#kp-annotation-location")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+    #[test]
+    fn test_load_cookies_for_notebook() {
+        use reqwest::cookie::CookieStore;
+
+        let path = std::env::temp_dir().join(format!("readingsync_cookies_{}.txt", uuid::Uuid::new_v4()));
+        fs::write(
+            &path,
+            "# Netscape HTTP Cookie File\n\
+             .amazon.com\tTRUE\t/\tTRUE\t1999999999\tsession-id\t123-456\n\
+             .amazon.co.uk\tTRUE\t/\tTRUE\t1999999999\tsession-id\tuk-only\n\
+             www.amazon.com\tFALSE\t/\tTRUE\t1999999999\tat-main\tAtza|token\n",
+        )
+        .unwrap();
+
+        let jar = cookie_jar(&read_cookie_file(&path).unwrap(), "amazon.com").unwrap();
+        let _ = fs::remove_file(&path);
+
+        let notebook = Url::parse("https://read.amazon.com/notebook").unwrap();
+        let sent = jar.cookies(&notebook).unwrap();
+        let mut sent: Vec<&str> = sent.to_str().unwrap().split("; ").collect();
+        sent.sort();
+        assert_eq!(sent, vec!["at-main=Atza|token", "session-id=123-456"]);
+    }
 
-    // Pagination selectors
-    let next_page_selector = Selector::parse(".kp-notebook-annotations-next-page-start")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+    fn cookie(host: &str, name: &str) -> StoredCookie {
+        StoredCookie {
+            host: host.to_string(),
+            name: name.to_string(),
+            value: "x".to_string(),
+        }
+    }
 
-    let content_limit_selector = Selector::parse(".kp-notebook-content-limit-state")
-        .map_err(|e| KindleError::ParseError(format!("Invalid selector: {:?}", e)))?;
+    #[test]
+    fn test_region_from_cookies() {
+        // Visiting amazon.com leaves cookies, but the sign-in is on amazon.co.uk
+        let cookies = vec![
+            cookie(".amazon.com", "session-id"),
+            cookie(".amazon.com", "ubid-main"),
+            cookie("www.amazon.com", "i18n-prefs"),
+            cookie(".amazon.co.uk", "session-id"),
+            cookie(".amazon.co.uk", "at-acbuk"),
+        ];
+        assert_eq!(region_from_cookies(&cookies), Some("uk"));
+
+        let signed_out = vec![cookie(".amazon.com.au", "session-id"), cookie(".amazon.com.au", "ubid-acbau"), cookie(".amazon.com", "session-id")];
+        assert_eq!(region_from_cookies(&signed_out), Some("au"));
+
+        assert_eq!(region_from_cookies(&[cookie("read.amazon.de", "sess-at-acbde")]), Some("de"));
+        assert_eq!(region_from_cookies(&[cookie(".notamazon.com", "at-main")]), None);
+        assert_eq!(region_from_cookies(&[]), None);
+    }
 
-    let mut highlights = Vec::new();
-    let mut seen_texts: std::collections::HashSet<String> = std::collections::HashSet::new();
+    #[test]
+    fn test_session_region() {
+        let cookies = vec![cookie(".amazon.com", "session-id"), cookie(".amazon.de", "at-acbde")];
+        let us = LegacyAmazonRegion::from_code("us").unwrap();
+        let de = LegacyAmazonRegion::from_code("de").unwrap();
+
+        assert_eq!(session_region(&cookies, None).unwrap().domain, "amazon.de");
+        assert_eq!(session_region(&cookies, Some(&de)).unwrap().code, "de");
+        match session_region(&cookies, Some(&us)) {
+            Err(e @ KindleError::WrongRegion { .. }) => assert_eq!(
+                e.to_string(),
+                "Your Amazon account appears to be registered on amazon.de, not amazon.com — retry with --region de"
+            ),
+            other => panic!("expected WrongRegion, got {:?}", other),
+        }
+        // Signed in nowhere: the given region is tried and reports the missing login
+        assert_eq!(session_region(&cookies[..1], Some(&de)).unwrap().code, "de");
+        assert!(matches!(session_region(&[], None), Err(KindleError::RegionUndetected)));
+    }
 
-    for container in document.select(&highlight_container_selector) {
-        // Get highlight text
-        let text = match container.select(&highlight_text_selector).next() {
-            Some(elem) => elem.text().collect::<String>().trim().to_string(),
-            None => continue,
-        };
+    /// Serves `respond(path)` as (status, HTML) on localhost, one thread per connection
+    fn serve(respond: impl Fn(&str) -> (u16, String) + Send + Sync + 'static) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let respond = Arc::new(respond);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let respond = Arc::clone(&respond);
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut header = String::new();
+                    while reader.read_line(&mut header).unwrap() > 2 {
+                        header.clear();
+                    }
+                    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                    let (status, body) = respond(path);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {} Canned\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
 
-        if text.is_empty() {
-            continue;
-        }
+    fn library_page(books: &[(&str, &str)]) -> String {
+        let entries: String = books
+            .iter()
+            .map(|(asin, title)| {
+                format!(
+                    r#"<div id="{}" class="kp-notebook-library-each-book"><h2 class="kp-notebook-searchable">{}</h2><p class="kp-notebook-searchable">By: Henry David Thoreau</p></div>"#,
+                    asin, title
+                )
+            })
+            .collect();
+        format!("<html><body>{}</body></html>", entries)
+    }
 
-        // Deduplicate by text
-        if seen_texts.contains(&text) {
-            continue;
+    fn highlights_page(text: &str, next_token: Option<&str>) -> String {
+        format!(
+            r#"<html><body><div class="a-row a-spacing-base"><span id="highlight">{}</span><span id="kp-annotation-location">{}</span></div><input type="hidden" class="kp-notebook-annotations-next-page-start" value="{}"></body></html>"#,
+            text,
+            text.len(),
+            next_token.unwrap_or_default()
+        )
+    }
+
+    fn test_config() -> LegacyScrapeConfig {
+        LegacyScrapeConfig {
+            request_delay: Duration::ZERO,
+            retry: RetryPolicy {
+                retries: 2,
+                initial_backoff: Duration::from_millis(1),
+            },
+            ..LegacyScrapeConfig::default()
         }
-        seen_texts.insert(text.clone());
+    }
 
-        // Get note if present
-        let note = container
-            .select(&note_selector)
-            .next()
-            .map(|e| e.text().collect::<String>().trim().to_string())
-            .filter(|s| !s.is_empty());
+    fn cookies_file() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("readingsync_cookies_{}.txt", uuid::Uuid::new_v4()));
+        fs::write(&path, ".amazon.com\tTRUE\t/\tTRUE\t1999999999\tsession-id\t123-456\n").unwrap();
+        path
+    }
 
-        // Get location
-        let position = container
-            .select(&location_selector)
-            .next()
-            .map(|e| e.text().collect::<String>().trim().to_string())
-            .filter(|s| !s.is_empty());
+    fn local_region(base: &str) -> LegacyAmazonRegion {
+        LegacyAmazonRegion {
+            code: "us".to_string(),
+            domain: "amazon.com".to_string(),
+            notebook_url: format!("{}/notebook", base),
+        }
+    }
 
-        let highlight = Highlight {
-            id: uuid::Uuid::new_v4().to_string(),
-            text,
-            note,
-            location: Location {
-                chapter: None,
-                position,
-            },
-            created_at: None,
-            source: Source::Kindle,
-        };
+    #[test]
+    fn test_scrape_notebook_fetches_books_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base = serve({
+            let (in_flight, most_in_flight, requests) =
+                (Arc::clone(&in_flight), Arc::clone(&most_in_flight), Arc::clone(&requests));
+            move |path| {
+                requests.lock().unwrap().push(path.to_string());
+                let attempts = requests.lock().unwrap().iter().filter(|p| p.as_str() == path).count();
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                most_in_flight.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(50));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                match path {
+                    "/notebook" => (
+                        200,
+                        library_page(&[("B001", "Walden"), ("B002", "Civil Disobedience"), ("B003", "Cape Cod"), ("B004", "Missing")]),
+                    ),
+                    // Two chained pages
+                    "/notebook?asin=B001" => (200, highlights_page("Simplify, simplify.", Some("t2"))),
+                    "/notebook?asin=B001&token=t2" => (200, highlights_page("I went to the woods.", None)),
+                    // Throttled once, then served
+                    "/notebook?asin=B002" if attempts == 1 => (429, String::new()),
+                    "/notebook?asin=B002" => (200, highlights_page("Unjust laws exist.", None)),
+                    // Hands back the same token forever
+                    p if p.starts_with("/notebook?asin=B003") => (200, highlights_page("The sea is a wilderness.", Some("again"))),
+                    _ => (404, String::new()),
+                }
+            }
+        });
 
-        highlights.push(highlight);
+        let path = cookies_file();
+        let outcome = scrape_notebook(&CookieSource::File(path.clone()), Some(&local_region(&base)), &test_config(), &mut NoProgress).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let titles: Vec<&str> = outcome.books.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(titles, vec!["Walden", "Civil Disobedience", "Cape Cod"]);
+        assert_eq!(outcome.books[0].highlights.len(), 2);
+        assert_eq!(outcome.books[0].source_ids["asin"], "B001");
+        assert_eq!(outcome.books[2].highlights.len(), 2);
+
+        // A 404 isn't retried
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!(outcome.failed[0].asin, "B004");
+        assert_eq!(outcome.failed[0].attempts, 1);
+
+        let report = outcome.report.unwrap();
+        let attempts: Vec<u32> = report.books.iter().map(|b| b.attempts).collect();
+        assert_eq!(attempts, vec![1, 2, 1, 1]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.iter().filter(|p| p.starts_with("/notebook?asin=B003")).count(), 2);
+        let most = most_in_flight.load(Ordering::SeqCst);
+        assert!((2..=3).contains(&most), "{} requests in flight", most);
     }
 
-    // Get pagination tokens
-    let next_token = document
-        .select(&next_page_selector)
-        .next()
-        .and_then(|e| e.value().attr("value"))
-        .map(String::from)
-        .filter(|s| !s.is_empty());
-
-    let content_limit_state = document
-        .select(&content_limit_selector)
-        .next()
-        .and_then(|e| e.value().attr("value"))
-        .map(String::from)
-        .filter(|s| !s.is_empty());
-
-    Ok((highlights, next_token, content_limit_state))
-}
+    #[test]
+    fn test_scrape_highlights_async() {
+        let base = serve(|path| match path {
+            "/notebook" => (200, library_page(&[("B001", "Walden")])),
+            _ => (200, highlights_page("Simplify, simplify.", None)),
+        });
+        let path = cookies_file();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let books = runtime.block_on(scrape_highlights_async(&path, &local_region(&base))).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].highlights[0].text, "Simplify, simplify.");
+    }
 
     #[test]
-    fn test_legacy_amazon_region() {
-        let us = LegacyAmazonRegion::from_code("us").unwrap();
-        assert_eq!(us.domain, "amazon.com");
+    fn test_signed_out_is_not_authenticated() {
+        let base = serve(|_| (200, r#"<form name="signIn"><input id="ap_email"></form>"#.to_string()));
+        let path = cookies_file();
 
-        let uk = LegacyAmazonRegion::from_code("UK").unwrap();
-        assert_eq!(uk.domain, "amazon.co.uk");
+        let result = scrape_notebook(&CookieSource::File(path.clone()), Some(&local_region(&base)), &test_config(), &mut NoProgress);
+        let _ = fs::remove_file(&path);
 
-        let invalid = LegacyAmazonRegion::from_code("xyz");
-        assert!(invalid.is_err());
+        assert!(matches!(result, Err(KindleError::NotAuthenticated)));
     }
 }
@@ -0,0 +1,118 @@
+use crate::error::KindleError;
+use crate::model::Book;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the last browser sync saw of each book, keyed by ASIN
+///
+/// Lets an incremental sync skip books whose "last annotated" date on the notebook
+/// page hasn't changed, reusing the highlights scraped last time.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub books: BTreeMap<String, BookState>,
+}
+
+/// One book as of the last sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookState {
+    /// "Last annotated" date as shown on the notebook page
+    pub last_annotated: String,
+    pub book: Book,
+}
+
+/// Get the default sync state path
+pub fn default_state_path() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("readingsync")
+        .join("kindle_sync_state.json")
+}
+
+impl SyncState {
+    /// Load the state from a file, starting empty if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, KindleError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| KindleError::LocalReadError(path.to_path_buf(), e))?;
+
+        serde_json::from_str(&content).map_err(|e| KindleError::SyncStateError(path.to_path_buf(), e.to_string()))
+    }
+
+    /// Save the state to a file
+    pub fn save(&self, path: &Path) -> Result<(), KindleError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| KindleError::SyncStateError(path.to_path_buf(), e.to_string()))?;
+        }
+
+        let content =
+            serde_json::to_string(self).map_err(|e| KindleError::SyncStateError(path.to_path_buf(), e.to_string()))?;
+
+        fs::write(path, content).map_err(|e| KindleError::SyncStateError(path.to_path_buf(), e.to_string()))
+    }
+
+    /// The book from the last sync, if its last-annotated date is unchanged
+    ///
+    /// Books without a date on the page are never considered unchanged.
+    pub fn unchanged(&self, asin: &str, last_annotated: Option<&str>) -> Option<&Book> {
+        let state = self.books.get(asin)?;
+        (Some(state.last_annotated.as_str()) == last_annotated).then_some(&state.book)
+    }
+
+    /// Remember a freshly scraped book
+    pub fn record(&mut self, asin: &str, last_annotated: Option<&str>, book: &Book) {
+        match last_annotated {
+            Some(last_annotated) => {
+                self.books.insert(
+                    asin.to_string(),
+                    BookState {
+                        last_annotated: last_annotated.to_string(),
+                        book: book.clone(),
+                    },
+                );
+            }
+            None => {
+                self.books.remove(asin);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unchanged_compares_last_annotated() {
+        let book = Book::new("Walden".to_string(), None);
+        let mut state = SyncState::default();
+        state.record("B000", Some("Monday January 1, 2024"), &book);
+
+        assert!(state.unchanged("B000", Some("Monday January 1, 2024")).is_some());
+        assert!(state.unchanged("B000", Some("Friday March 8, 2024")).is_none());
+        assert!(state.unchanged("B000", None).is_none());
+        assert!(state.unchanged("B999", Some("Monday January 1, 2024")).is_none());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("readingsync_sync_state_{}", uuid::Uuid::new_v4()))
+            .join("state.json");
+
+        assert!(SyncState::load(&path).unwrap().books.is_empty());
+
+        let mut state = SyncState::default();
+        state.record("B000", Some("Monday January 1, 2024"), &Book::new("Walden".to_string(), None));
+        state.save(&path).unwrap();
+
+        let loaded = SyncState::load(&path).unwrap();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!(loaded.books["B000"].book.title, "Walden");
+    }
+}
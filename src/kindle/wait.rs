@@ -0,0 +1,191 @@
+//! Waiting on page conditions instead of sleeping a fixed time
+//!
+//! A flat sleep after a click is either longer than the page needs or, on a slow
+//! connection, too short, and the scrape then reads a half-rendered book. The browser
+//! scraper instead polls a condition (an element is present, a spinner is gone, a count
+//! has stopped changing) until it holds or a timeout passes. The clock is a trait so the
+//! polling can be tested without real waits.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default time between two polls
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time source for waits
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// How often to poll and for how long
+#[derive(Debug, Clone, Copy)]
+pub struct Wait {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Wait {
+    /// Poll every `POLL_INTERVAL` for up to `timeout`
+    pub fn up_to(timeout: Duration) -> Self {
+        Self {
+            interval: POLL_INTERVAL,
+            timeout,
+        }
+    }
+
+    /// Poll `check` until it returns a value, or None once the timeout has passed
+    ///
+    /// `check` runs at least once, so an already-met condition never waits.
+    pub fn until<T>(&self, clock: &dyn Clock, mut check: impl FnMut() -> Option<T>) -> Option<T> {
+        let start = clock.now();
+        loop {
+            if let Some(value) = check() {
+                return Some(value);
+            }
+            if clock.now().duration_since(start) >= self.timeout {
+                return None;
+            }
+            clock.sleep(self.interval);
+        }
+    }
+
+    /// Poll `read` until it returns the same value twice in a row
+    ///
+    /// None readings (e.g. still loading) never count as stable. Returns the last
+    /// reading once the timeout has passed, so a page that keeps changing is still read.
+    pub fn until_stable<T: PartialEq>(&self, clock: &dyn Clock, mut read: impl FnMut() -> Option<T>) -> Option<T> {
+        let mut last: Option<T> = None;
+        let stable = self.until(clock, || {
+            let current = read();
+            if current.is_some() && current == last {
+                return current;
+            }
+            last = current;
+            None
+        });
+        stable.or(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    /// Starts at a fixed instant and only moves when slept on
+    struct FakeClock {
+        now: Cell<Instant>,
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                now: Cell::new(Instant::now()),
+                sleeps: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+            self.sleeps.borrow_mut().push(duration);
+        }
+    }
+
+    fn wait() -> Wait {
+        Wait {
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_until_returns_once_condition_holds() {
+        let clock = FakeClock::new();
+        let mut polls = 0;
+
+        let result = wait().until(&clock, || {
+            polls += 1;
+            (polls == 4).then_some("loaded")
+        });
+
+        assert_eq!(result, Some("loaded"));
+        assert_eq!(clock.sleeps.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_until_met_condition_does_not_sleep() {
+        let clock = FakeClock::new();
+        assert_eq!(wait().until(&clock, || Some(1)), Some(1));
+        assert!(clock.sleeps.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_until_times_out() {
+        let clock = FakeClock::new();
+        let mut polls = 0;
+
+        let result: Option<()> = wait().until(&clock, || {
+            polls += 1;
+            None
+        });
+
+        assert_eq!(result, None);
+        // Polled at 0ms, 100ms, ..., 1000ms
+        assert_eq!(polls, 11);
+        assert_eq!(clock.sleeps.borrow().iter().sum::<Duration>(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_until_stable_waits_for_repeat_reading() {
+        let clock = FakeClock::new();
+        // Still loading, then highlights trickle in and settle at 12
+        let mut readings = vec![None, Some(3), Some(9), Some(12), Some(12), Some(15)].into_iter();
+
+        let result = wait().until_stable(&clock, || readings.next().flatten());
+
+        assert_eq!(result, Some(12));
+        assert_eq!(clock.sleeps.borrow().len(), 4);
+    }
+
+    #[test]
+    fn test_until_stable_gives_last_reading_on_timeout() {
+        let clock = FakeClock::new();
+        let mut count = 0;
+
+        let result = wait().until_stable(&clock, || {
+            count += 1;
+            Some(count)
+        });
+
+        assert_eq!(result, Some(11));
+    }
+
+    #[test]
+    fn test_until_stable_ignores_repeated_none() {
+        let clock = FakeClock::new();
+        let result: Option<u32> = wait().until_stable(&clock, || None);
+        assert_eq!(result, None);
+        assert_eq!(clock.sleeps.borrow().len(), 10);
+    }
+}
@@ -0,0 +1,259 @@
+//! A `BrowserBackend` for any W3C WebDriver server
+//!
+//! Meant for machines where only Firefox is available: start `geckodriver` (it listens
+//! on http://localhost:4444) and sync with `kindle --backend webdriver`. The protocol is
+//! plain JSON over HTTP, so this is a small blocking client on top of reqwest rather
+//! than an async WebDriver crate.
+//!
+//! Differences from the Chrome backend:
+//! - WebDriver can't see the page's network traffic, so highlights are always read
+//!   from the rendered page (`ScrapeEngine::Network` falls back to the DOM)
+//! - A session drives one window, so books are scraped one at a time
+//! - The driver starts the browser with a fresh temporary profile, which is thrown
+//!   away with the session. A login is only kept when `user_data_dir` names a Firefox
+//!   profile, which is passed to Firefox as `-profile`, so the driver has to run on
+//!   the same machine. Without one, runs that can't log in interactively fail up front
+//!   with `KindleError::SessionNotPersisted`.
+
+use crate::error::KindleError;
+use crate::kindle::backend::{BrowserBackend, BrowserPage};
+use crate::kindle::browser::BrowserConfig;
+use anyhow::{anyhow, Context};
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Key under which WebDriver returns an element reference
+const ELEMENT_KEY: &str = "element-6066-11e4-a931-00ec8ec1f3de";
+
+/// Longest wait for one command; navigation blocks until the page has loaded, and
+/// starting a session launches the browser
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A connection to a WebDriver session
+struct Session {
+    client: Client,
+    /// `<server>/session/<id>`
+    base: String,
+}
+
+impl Session {
+    fn command(&self, method: Method, path: &str, body: Option<Value>) -> anyhow::Result<Value> {
+        send(&self.client, method, &format!("{}{}", self.base, path), body)
+    }
+
+    /// Reference to the first element matching a CSS selector
+    fn find_element(&self, selector: &str) -> anyhow::Result<Value> {
+        let found = self.command(
+            Method::POST,
+            "/element",
+            Some(json!({"using": "css selector", "value": selector})),
+        )?;
+        found
+            .get(ELEMENT_KEY)
+            .cloned()
+            .ok_or_else(|| anyhow!("No element reference for {}", selector))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Ends the session, which closes the browser
+        let _ = self.command(Method::DELETE, "", None);
+    }
+}
+
+/// Send a command and return its `value`, turning WebDriver errors into messages
+fn send(client: &Client, method: Method, url: &str, body: Option<Value>) -> anyhow::Result<Value> {
+    let mut request = client.request(method, url);
+    if let Some(body) = body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+    }
+    let response = request.send().with_context(|| format!("WebDriver server at {} did not answer", url))?;
+    let status = response.status();
+    let text = response.text()?;
+    parse_response(status.is_success(), &text)
+}
+
+/// The `value` of a WebDriver response, or its error
+fn parse_response(success: bool, body: &str) -> anyhow::Result<Value> {
+    let parsed: Value = serde_json::from_str(body).with_context(|| format!("Unexpected WebDriver response: {}", body))?;
+    let value = parsed.get("value").cloned().unwrap_or(Value::Null);
+    if success {
+        return Ok(value);
+    }
+    let error = value.get("error").and_then(|e| e.as_str()).unwrap_or("unknown error");
+    let message = value.get("message").and_then(|m| m.as_str()).unwrap_or_default();
+    Err(anyhow!("{}: {}", error, message))
+}
+
+/// Capabilities asking for the configured window and profile
+///
+/// Vendor options are given for both Firefox and Chrome; each driver ignores the
+/// other's.
+fn capabilities(config: &BrowserConfig) -> Value {
+    let mut firefox_args = Vec::new();
+    let mut chrome_args = Vec::new();
+    if config.headless {
+        firefox_args.push("-headless".to_string());
+        chrome_args.push("--headless=new".to_string());
+    }
+    if let Some(dir) = &config.user_data_dir {
+        firefox_args.extend(["-profile".to_string(), dir.clone()]);
+        chrome_args.push(format!("--user-data-dir={}", dir));
+    }
+
+    json!({
+        "capabilities": {
+            "alwaysMatch": {
+                "moz:firefoxOptions": {"args": firefox_args},
+                "goog:chromeOptions": {"args": chrome_args},
+            }
+        }
+    })
+}
+
+/// A browser started by a WebDriver server
+pub struct WebDriverBackend {
+    session: Arc<Session>,
+}
+
+impl WebDriverBackend {
+    /// Start a session on the WebDriver server at `url`
+    pub fn connect(url: &str, config: &BrowserConfig) -> Result<Self, KindleError> {
+        let client = Client::builder()
+            .timeout(COMMAND_TIMEOUT)
+            .build()
+            .map_err(KindleError::HttpError)?;
+        let server = url.trim_end_matches('/');
+
+        let created = send(&client, Method::POST, &format!("{}/session", server), Some(capabilities(config)))
+            .map_err(|e| KindleError::ParseError(format!("Failed to start a WebDriver session at {}: {:#}", server, e)))?;
+        let id = created
+            .get("sessionId")
+            .and_then(|id| id.as_str())
+            .ok_or_else(|| KindleError::ParseError(format!("WebDriver server at {} returned no session id", server)))?;
+
+        let session = Session {
+            client,
+            base: format!("{}/session/{}", server, id),
+        };
+        let (width, height) = config.window_size;
+        // Not every driver can resize, e.g. headless ones with a fixed screen
+        let _ = session.command(Method::POST, "/window/rect", Some(json!({"width": width, "height": height})));
+
+        Ok(Self {
+            session: Arc::new(session),
+        })
+    }
+}
+
+impl BrowserBackend for WebDriverBackend {
+    /// The session's window; every page shares it
+    fn new_page(&self) -> anyhow::Result<Box<dyn BrowserPage>> {
+        Ok(Box::new(WebDriverPage {
+            session: Arc::clone(&self.session),
+        }))
+    }
+
+    fn is_alive(&self) -> bool {
+        self.session.command(Method::GET, "/url", None).is_ok()
+    }
+
+    fn max_pages(&self) -> usize {
+        1
+    }
+}
+
+struct WebDriverPage {
+    session: Arc<Session>,
+}
+
+impl BrowserPage for WebDriverPage {
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        self.session.command(Method::POST, "/url", Some(json!({"url": url})))?;
+        Ok(())
+    }
+
+    fn url(&self) -> String {
+        self.session
+            .command(Method::GET, "/url", None)
+            .ok()
+            .and_then(|url| url.as_str().map(String::from))
+            .unwrap_or_default()
+    }
+
+    fn evaluate(&self, js: &str) -> anyhow::Result<Value> {
+        // Scripts are function bodies; the driver awaits a returned promise
+        self.session.command(
+            Method::POST,
+            "/execute/sync",
+            Some(json!({"script": "return eval(arguments[0]);", "args": [js]})),
+        )
+    }
+
+    fn has_element(&self, selector: &str) -> bool {
+        self.session.find_element(selector).is_ok()
+    }
+
+    fn scroll_into_view(&self, selector: &str) -> anyhow::Result<()> {
+        let element = self.session.find_element(selector)?;
+        self.session.command(
+            Method::POST,
+            "/execute/sync",
+            Some(json!({
+                "script": "arguments[0].scrollIntoView({block: 'center'});",
+                "args": [{ELEMENT_KEY: element}],
+            })),
+        )?;
+        Ok(())
+    }
+
+    fn click(&self, selector: &str) -> anyhow::Result<()> {
+        let element = self.session.find_element(selector)?;
+        let id = element.as_str().ok_or_else(|| anyhow!("Bad element reference for {}", selector))?;
+        self.session.command(Method::POST, &format!("/element/{}/click", id), Some(json!({})))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let value = parse_response(true, r#"{"value": "https://read.amazon.com/notebook"}"#).unwrap();
+        assert_eq!(value, json!("https://read.amazon.com/notebook"));
+
+        let error = parse_response(
+            false,
+            r#"{"value": {"error": "no such element", "message": "Unable to locate element: #B00X", "stacktrace": ""}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "no such element: Unable to locate element: #B00X");
+
+        assert!(parse_response(false, "Bad Gateway").is_err());
+    }
+
+    #[test]
+    fn test_capabilities_pass_profile_and_headless() {
+        let config = BrowserConfig {
+            headless: true,
+            user_data_dir: Some("/home/me/firefox-kindle".to_string()),
+            ..Default::default()
+        };
+        let requested = capabilities(&config);
+        let always = &requested["capabilities"]["alwaysMatch"];
+
+        assert_eq!(always["moz:firefoxOptions"]["args"], json!(["-headless", "-profile", "/home/me/firefox-kindle"]));
+        assert_eq!(always["goog:chromeOptions"]["args"], json!(["--headless=new", "--user-data-dir=/home/me/firefox-kindle"]));
+
+        let windowed = capabilities(&BrowserConfig::default());
+        assert_eq!(windowed["capabilities"]["alwaysMatch"]["moz:firefoxOptions"]["args"], json!([]));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::KoboError;
+use crate::model::{generate_book_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Default location of the Kobo database when the device is mounted on macOS
+const DEVICE_DB_PATH: &str = "/Volumes/KOBOeReader/.kobo/KoboReader.sqlite";
+
+/// Extract highlights and notes from a Kobo's KoboReader.sqlite
+///
+/// When no path is given, the database is looked up on a mounted Kobo device.
+pub fn extract_full(db_path: Option<PathBuf>) -> Result<Vec<Book>, KoboError> {
+    let db_path = match db_path {
+        Some(path) => path,
+        None => {
+            let default = PathBuf::from(DEVICE_DB_PATH);
+            if !default.exists() {
+                return Err(KoboError::DeviceNotFound);
+            }
+            default
+        }
+    };
+
+    if !db_path.exists() {
+        return Err(KoboError::DatabaseNotFound(db_path));
+    }
+
+    extract_from_db(&db_path)
+}
+
+/// Read Bookmark rows joined to their book and chapter content entries
+fn extract_from_db(db_path: &Path) -> Result<Vec<Book>, KoboError> {
+    // Open read-only so the device database is never modified
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+            b.BookmarkID,
+            b.VolumeID,
+            book.Title,
+            book.Attribution,
+            chapter.Title,
+            b.Text,
+            b.Annotation,
+            b.DateCreated
+        FROM Bookmark b
+        JOIN content book ON book.ContentID = b.VolumeID
+        LEFT JOIN content chapter ON chapter.ContentID = b.ContentID
+        WHERE b.Type IN ('highlight', 'note')
+          AND (b.Hidden IS NULL OR b.Hidden = 'false')
+          AND book.Title IS NOT NULL
+        ORDER BY b.VolumeID, b.ContentID, b.StartContainerPath
+        "#,
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<String>>(7)?,
+        ))
+    })?;
+
+    let mut books_by_volume: HashMap<String, Book> = HashMap::new();
+
+    for row_result in rows {
+        let (id, volume_id, title, author, chapter, text, annotation, date_created) = row_result?;
+
+        let text = text.map(|t| t.trim().to_string()).unwrap_or_default();
+        let note = annotation
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty());
+
+        if text.is_empty() && note.is_none() {
+            continue;
+        }
+
+        let book = books_by_volume.entry(volume_id).or_insert_with(|| {
+            let author = author.filter(|a| !a.trim().is_empty());
+            Book {
+                id: generate_book_id(&title, author.as_deref()),
+                title,
+                author,
+                sources: vec![Source::Kobo],
+                highlights: Vec::new(),
+                finished: None,
+                finished_at: None,
+                url: None,
+                isbn: None,
+                collections: Vec::new(),
+                unavailable_highlights: 0,
+                truncated: false,
+                original_title: None,
+                aliases: Vec::new(),
+                cover_url: None,
+                source_ids: BTreeMap::new(),
+                document_type: None,
+                progress: None,
+                last_opened_at: None,
+                language: None,
+                genre: None,
+            }
+        });
+
+        book.highlights.push(Highlight {
+            id,
+            text,
+            note,
+            location: Location {
+                chapter: chapter.filter(|c| !c.trim().is_empty()),
+                position: None,
+                page: None,
+                raw: None,
+            },
+            created_at: date_created.as_deref().and_then(parse_kobo_date),
+            sources: vec![Source::Kobo],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    Ok(books_by_volume.into_values().collect())
+}
+
+/// Parse Kobo's DateCreated strings, e.g. "2024-01-01T12:34:56.000" or "2024-01-01T12:34:56Z"
+fn parse_kobo_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim().trim_end_matches('Z');
+
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn make_db(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE content (ContentID TEXT, Title TEXT, Attribution TEXT, ContentType INTEGER);
+            CREATE TABLE Bookmark (
+                BookmarkID TEXT, VolumeID TEXT, ContentID TEXT, StartContainerPath TEXT,
+                Text TEXT, Annotation TEXT, DateCreated TEXT, Type TEXT, Hidden TEXT
+            );
+            INSERT INTO content VALUES ('vol1', 'Dune', 'Frank Herbert', 6);
+            INSERT INTO content VALUES ('vol1!ch1', 'Book One: Dune', NULL, 9);
+            INSERT INTO Bookmark VALUES ('b1', 'vol1', 'vol1!ch1', 'span#kobo.1.1',
+                'Fear is the mind-killer.', NULL, '2024-01-01T12:34:56.000', 'highlight', 'false');
+            INSERT INTO Bookmark VALUES ('b2', 'vol1', 'vol1!ch1', 'span#kobo.2.1',
+                'I must not fear.', 'Litany', '2024-01-02T08:00:00Z', 'note', 'false');
+            INSERT INTO Bookmark VALUES ('b3', 'vol1', 'vol1!ch1', 'span#kobo.3.1',
+                NULL, NULL, '2024-01-03T08:00:00', 'dogear', 'false');
+            INSERT INTO Bookmark VALUES ('b4', 'vol1', 'vol1!ch1', 'span#kobo.4.1',
+                'Hidden text', NULL, '2024-01-03T08:00:00', 'highlight', 'true');
+            "#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_extract_from_db() {
+        let path = std::env::temp_dir().join(format!("readingsync_kobo_{}.sqlite", uuid::Uuid::new_v4()));
+        make_db(&path);
+
+        let books = extract_full(Some(path.clone())).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "Dune");
+        assert_eq!(book.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(book.sources, vec![Source::Kobo]);
+        assert_eq!(book.highlights.len(), 2);
+
+        let first = &book.highlights[0];
+        assert_eq!(first.id, "b1");
+        assert_eq!(first.location.chapter.as_deref(), Some("Book One: Dune"));
+        assert_eq!(first.created_at, Utc.with_ymd_and_hms(2024, 1, 1, 12, 34, 56).single());
+
+        assert_eq!(book.highlights[1].note.as_deref(), Some("Litany"));
+    }
+
+    #[test]
+    fn test_missing_database() {
+        let result = extract_full(Some(PathBuf::from("/nonexistent/KoboReader.sqlite")));
+        assert!(matches!(result, Err(KoboError::DatabaseNotFound(_))));
+    }
+}
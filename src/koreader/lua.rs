@@ -0,0 +1,329 @@
+//! Minimal parser for the Lua table literals KOReader writes to its sidecar files
+//!
+//! Only the subset produced by KOReader's serializer is supported: a `return` of a
+//! table containing strings, numbers, booleans, nil and nested tables.
+
+/// A parsed Lua value
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    /// Key/value pairs in source order; array entries get implicit integer keys
+    Table(Vec<(LuaValue, LuaValue)>),
+}
+
+impl LuaValue {
+    /// Look up a string key in a table
+    pub fn get(&self, key: &str) -> Option<&LuaValue> {
+        match self {
+            LuaValue::Table(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, LuaValue::Str(s) if s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Values of a table in source order
+    pub fn values(&self) -> impl Iterator<Item = &LuaValue> {
+        let entries = match self {
+            LuaValue::Table(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries.iter().map(|(_, v)| v)
+    }
+
+    /// Key/value pairs of a table in source order
+    pub fn entries(&self) -> &[(LuaValue, LuaValue)] {
+        match self {
+            LuaValue::Table(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LuaValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            LuaValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// String or number rendered as text
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            LuaValue::Str(s) => Some(s.clone()),
+            LuaValue::Number(n) if n.fract() == 0.0 => Some(format!("{}", *n as i64)),
+            LuaValue::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a KOReader metadata file (`return { ... }`)
+pub fn parse(input: &str) -> Result<LuaValue, String> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+
+    parser.skip_whitespace();
+    if parser.peek_word() == "return" {
+        parser.pos += "return".len();
+    }
+
+    let value = parser.parse_value()?;
+
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(format!("unexpected trailing input at offset {}", parser.pos));
+    }
+
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_word(&self) -> String {
+        self.chars[self.pos..]
+            .iter()
+            .take_while(|c| c.is_alphanumeric() || **c == '_')
+            .collect()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            other => Err(format!("expected '{}' at offset {}, found {:?}", expected, self.pos, other)),
+        }
+    }
+
+    /// Skip whitespace and `--` comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+
+            if self.chars[self.pos..].starts_with(&['-', '-']) {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<LuaValue, String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => self.parse_table(),
+            Some('"') | Some('\'') => self.parse_string().map(LuaValue::Str),
+            Some(c) if c == '-' || c == '.' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => {
+                let word = self.peek_word();
+                let value = match word.as_str() {
+                    "true" => LuaValue::Bool(true),
+                    "false" => LuaValue::Bool(false),
+                    "nil" => LuaValue::Nil,
+                    _ => return Err(format!("unexpected token '{}' at offset {}", word, self.pos)),
+                };
+                self.pos += word.chars().count();
+                Ok(value)
+            }
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn parse_table(&mut self) -> Result<LuaValue, String> {
+        self.expect('{')?;
+
+        let mut entries = Vec::new();
+        let mut next_index = 1.0;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(LuaValue::Table(entries));
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    let key = self.parse_value()?;
+                    self.expect(']')?;
+                    self.expect('=')?;
+                    entries.push((key, self.parse_value()?));
+                }
+                Some(c) if c.is_alphabetic() || c == '_' => {
+                    let word = self.peek_word();
+                    let save = self.pos;
+                    self.pos += word.chars().count();
+                    self.skip_whitespace();
+
+                    if self.peek() == Some('=') {
+                        self.pos += 1;
+                        entries.push((LuaValue::Str(word), self.parse_value()?));
+                    } else {
+                        // Bare `true`/`false`/`nil` array entry
+                        self.pos = save;
+                        entries.push((LuaValue::Number(next_index), self.parse_value()?));
+                        next_index += 1.0;
+                    }
+                }
+                Some(_) => {
+                    entries.push((LuaValue::Number(next_index), self.parse_value()?));
+                    next_index += 1.0;
+                }
+                None => return Err("unterminated table".to_string()),
+            }
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') | Some(';') => self.pos += 1,
+                Some('}') => {}
+                other => return Err(format!("expected ',' or '}}' at offset {}, found {:?}", self.pos, other)),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let quote = self.peek().ok_or("unexpected end of input")?;
+        self.pos += 1;
+
+        // Collected as bytes since decimal escapes encode raw bytes of UTF-8 sequences
+        let mut out: Vec<u8> = Vec::new();
+        let push = |out: &mut Vec<u8>, c: char| {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        };
+
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+
+            if c == quote {
+                return Ok(String::from_utf8_lossy(&out).into_owned());
+            }
+            if c != '\\' {
+                push(&mut out, c);
+                continue;
+            }
+
+            let escaped = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+
+            match escaped {
+                'n' | '\n' => out.push(b'\n'),
+                't' => out.push(b'\t'),
+                'r' => out.push(b'\r'),
+                'a' => out.push(0x07),
+                'b' => out.push(0x08),
+                'f' => out.push(0x0c),
+                'v' => out.push(0x0b),
+                '\r' => {
+                    out.push(b'\n');
+                    if self.peek() == Some('\n') {
+                        self.pos += 1;
+                    }
+                }
+                d if d.is_ascii_digit() => {
+                    // Decimal byte escape, up to three digits
+                    let mut code = d.to_digit(10).unwrap_or_default();
+                    for _ in 0..2 {
+                        match self.peek().and_then(|c| c.to_digit(10)) {
+                            Some(digit) => {
+                                code = code * 10 + digit;
+                                self.pos += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                    out.push(code.min(255) as u8);
+                }
+                other => push(&mut out, other),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<LuaValue, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+        {
+            self.pos += 1;
+        }
+
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        literal
+            .parse::<f64>()
+            .map(LuaValue::Number)
+            .map_err(|_| format!("invalid number '{}' at offset {}", literal, start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_tables() {
+        let input = r#"-- ./book.sdr/metadata.epub.lua
+return {
+    ["doc_props"] = {
+        ["title"] = "Dune",
+        ["authors"] = "Frank Herbert",
+    },
+    ["percent_finished"] = 0.25,
+    ["list"] = { "a", 'b', true, nil, -3 },
+    plain = "value",
+}
+"#;
+
+        let value = parse(input).unwrap();
+        let props = value.get("doc_props").unwrap();
+        assert_eq!(props.get("title").and_then(LuaValue::as_str), Some("Dune"));
+        assert_eq!(value.get("percent_finished").and_then(LuaValue::as_number), Some(0.25));
+        assert_eq!(value.get("list").unwrap().values().count(), 5);
+        assert_eq!(value.get("plain").and_then(LuaValue::as_str), Some("value"));
+    }
+
+    #[test]
+    fn test_parse_string_escapes() {
+        let value = parse(r#"{ ["text"] = "Line one\
+Line two \"quoted\" caf\195\169" }"#)
+        .unwrap();
+
+        let text = value.get("text").and_then(LuaValue::as_str);
+        assert_eq!(text, Some("Line one\nLine two \"quoted\" café"));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse("return { [\"a\"] = }").is_err());
+    }
+}
@@ -0,0 +1,305 @@
+pub mod lua;
+
+use crate::error::KoReaderError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use lua::LuaValue;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Import highlights from KOReader `metadata.*.lua` sidecar files
+///
+/// Walks `root` (a mounted device or any directory) for `.sdr` folders. Sidecars that
+/// can't be parsed are reported and skipped so one bad file doesn't abort the import.
+pub fn extract_full(root: &Path) -> Result<Vec<Book>, KoReaderError> {
+    if !root.is_dir() {
+        return Err(KoReaderError::DirectoryNotFound(root.to_path_buf()));
+    }
+
+    let mut sidecars = Vec::new();
+    collect_sidecars(root, &mut sidecars)?;
+    sidecars.sort();
+
+    let mut books: Vec<Book> = Vec::new();
+
+    for path in sidecars {
+        let content = fs::read_to_string(&path).map_err(|e| KoReaderError::ReadError(path.clone(), e))?;
+
+        let metadata = match lua::parse(&content) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                eprintln!("  Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        if let Some(book) = book_from_metadata(&metadata, &fallback_title(&path)) {
+            match books.iter_mut().find(|b| b.id == book.id) {
+                Some(existing) => existing.highlights.extend(book.highlights),
+                None => books.push(book),
+            }
+        }
+    }
+
+    Ok(books)
+}
+
+/// Recursively collect `metadata.<ext>.lua` files inside `.sdr` folders
+fn collect_sidecars(dir: &Path, sidecars: &mut Vec<PathBuf>) -> Result<(), KoReaderError> {
+    let entries = fs::read_dir(dir).map_err(|e| KoReaderError::ReadError(dir.to_path_buf(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| KoReaderError::ReadError(dir.to_path_buf(), e))?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+        if path.is_dir() {
+            // Skip hidden system folders like .Trashes or .kobo
+            if !name.starts_with('.') {
+                collect_sidecars(&path, sidecars)?;
+            }
+        } else if name.starts_with("metadata.")
+            && name.ends_with(".lua")
+            && dir.extension().is_some_and(|e| e == "sdr")
+        {
+            sidecars.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Title derived from the `.sdr` folder, which is named after the book file
+fn fallback_title(sidecar: &Path) -> String {
+    sidecar
+        .parent()
+        .and_then(|dir| dir.file_stem())
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+/// Build a book from a parsed sidecar; None when it holds no highlights
+///
+/// Newer KOReader versions keep highlights and notes in `annotations`. Older versions
+/// use `highlight` (keyed by page) with notes stored on the matching `bookmarks` entry.
+pub fn book_from_metadata(metadata: &LuaValue, fallback_title: &str) -> Option<Book> {
+    let props = metadata.get("doc_props");
+
+    let title = props
+        .and_then(|p| p.get("title"))
+        .and_then(LuaValue::as_str)
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or(fallback_title)
+        .to_string();
+
+    // Multiple authors are newline separated
+    let author = props
+        .and_then(|p| p.get("authors"))
+        .and_then(LuaValue::as_str)
+        .map(|a| a.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(", "))
+        .filter(|a| !a.is_empty());
+
+    let mut book = Book::new(title, author);
+    book.sources = vec![Source::KoReader];
+
+    match metadata.get("annotations") {
+        Some(annotations) => {
+            for entry in annotations.values() {
+                // Entries without a drawn range are page bookmarks
+                if entry.get("pos0").is_none() && entry.get("drawer").is_none() {
+                    continue;
+                }
+
+                let page = entry.get("pageno").or_else(|| entry.get("page"));
+                push_highlight(&mut book, entry, page, entry.get("note").and_then(LuaValue::as_str));
+            }
+        }
+        None => {
+            let bookmarks = metadata.get("bookmarks");
+
+            for (page, entries) in metadata.get("highlight").map(LuaValue::entries).unwrap_or_default() {
+                for entry in entries.values() {
+                    let note = bookmarks.and_then(|b| legacy_note(b, entry));
+                    push_highlight(&mut book, entry, Some(page), note);
+                }
+            }
+        }
+    }
+
+    if book.highlights.is_empty() {
+        None
+    } else {
+        Some(book)
+    }
+}
+
+fn push_highlight(book: &mut Book, entry: &LuaValue, page: Option<&LuaValue>, note: Option<&str>) {
+    let Some(text) = entry.get("text").and_then(LuaValue::as_str).map(str::trim) else {
+        return;
+    };
+    if text.is_empty() {
+        return;
+    }
+
+    // Reflowable documents store XPointers in `page`; only numeric pages are positions
+    let position = page
+        .and_then(LuaValue::as_number)
+        .map(|n| format!("Page {}", n as i64));
+
+    book.highlights.push(Highlight {
+        id: generate_highlight_id(&book.id, text, position.as_deref()),
+        text: text.to_string(),
+        note: note.map(str::trim).filter(|n| !n.is_empty()).map(String::from),
+        location: Location {
+            chapter: entry
+                .get("chapter")
+                .and_then(LuaValue::as_str)
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(String::from),
+            position,
+            page: None,
+            raw: None,
+        },
+        created_at: entry.get("datetime").and_then(LuaValue::as_str).and_then(parse_datetime),
+        sources: vec![Source::KoReader],
+        color: None,
+        kind: HighlightKind::Highlight,
+        style: HighlightStyle::Highlight,
+        context: None,
+        deleted_upstream: false,
+    });
+}
+
+/// Note for a legacy highlight, from the bookmark whose `notes` holds the highlighted text
+///
+/// Bookmark `text` defaults to "Page N <text> @ <datetime>"; anything else is a user note.
+fn legacy_note<'a>(bookmarks: &'a LuaValue, highlight: &LuaValue) -> Option<&'a str> {
+    static DEFAULT_TEXT: OnceLock<Regex> = OnceLock::new();
+    let default_text =
+        DEFAULT_TEXT.get_or_init(|| Regex::new(r"^Page \S+ [\s\S]* @ \d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}$").unwrap());
+    let text = highlight.get("text").and_then(LuaValue::as_str)?;
+
+    bookmarks
+        .values()
+        .find(|b| b.get("notes").and_then(LuaValue::as_str) == Some(text))
+        .and_then(|b| b.get("text").and_then(LuaValue::as_str))
+        .filter(|note| *note != text && !default_text.is_match(note))
+}
+
+/// Parse KOReader datetimes, e.g. "2024-01-05 10:20:30"
+fn parse_datetime(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const ANNOTATIONS_FORMAT: &str = r#"-- we can read Lua syntax here!
+return {
+    ["annotations"] = {
+        [1] = {
+            ["chapter"] = "Chapter 1",
+            ["datetime"] = "2024-01-05 10:20:30",
+            ["drawer"] = "lighten",
+            ["note"] = "Worth remembering",
+            ["page"] = "/body/DocFragment[3]/body/p[4]/text().0",
+            ["pageno"] = 12,
+            ["pos0"] = "/body/DocFragment[3]/body/p[4]/text().0",
+            ["pos1"] = "/body/DocFragment[3]/body/p[4]/text().52",
+            ["text"] = "Fear is the mind-killer.",
+        },
+        [2] = {
+            ["datetime"] = "2024-01-06 09:00:00",
+            ["page"] = "/body/DocFragment[5]/body/p[1]/text().0",
+            ["text"] = "in Chapter 2",
+        },
+    },
+    ["doc_props"] = {
+        ["authors"] = "Frank Herbert",
+        ["title"] = "Dune",
+    },
+}
+"#;
+
+    const LEGACY_FORMAT: &str = r#"return {
+    ["bookmarks"] = {
+        [1] = {
+            ["highlighted"] = true,
+            ["notes"] = "The spice must flow.",
+            ["text"] = "Famous line",
+        },
+    },
+    ["highlight"] = {
+        [42] = {
+            [1] = {
+                ["chapter"] = "Book Two",
+                ["datetime"] = "2019-03-02 08:15:00",
+                ["text"] = "The spice must flow.",
+            },
+        },
+    },
+    ["doc_props"] = {
+        ["title"] = "",
+    },
+}
+"#;
+
+    #[test]
+    fn test_annotations_format() {
+        let metadata = lua::parse(ANNOTATIONS_FORMAT).unwrap();
+        let book = book_from_metadata(&metadata, "dune").unwrap();
+
+        assert_eq!(book.title, "Dune");
+        assert_eq!(book.author.as_deref(), Some("Frank Herbert"));
+        assert_eq!(book.sources, vec![Source::KoReader]);
+
+        // The page bookmark is skipped
+        assert_eq!(book.highlights.len(), 1);
+
+        let highlight = &book.highlights[0];
+        assert_eq!(highlight.text, "Fear is the mind-killer.");
+        assert_eq!(highlight.note.as_deref(), Some("Worth remembering"));
+        assert_eq!(highlight.location.chapter.as_deref(), Some("Chapter 1"));
+        assert_eq!(highlight.location.position.as_deref(), Some("Page 12"));
+        assert_eq!(highlight.created_at, Utc.with_ymd_and_hms(2024, 1, 5, 10, 20, 30).single());
+    }
+
+    #[test]
+    fn test_legacy_format_uses_fallback_title_and_bookmark_notes() {
+        let metadata = lua::parse(LEGACY_FORMAT).unwrap();
+        let book = book_from_metadata(&metadata, "Dune Messiah").unwrap();
+
+        assert_eq!(book.title, "Dune Messiah");
+        assert_eq!(book.author, None);
+        assert_eq!(book.highlights[0].note.as_deref(), Some("Famous line"));
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("Page 42"));
+    }
+
+    #[test]
+    fn test_extract_walks_sdr_folders() {
+        let root = std::env::temp_dir().join(format!("readingsync_koreader_{}", uuid::Uuid::new_v4()));
+        let sdr = root.join("books").join("Dune.sdr");
+        fs::create_dir_all(&sdr).unwrap();
+        fs::write(sdr.join("metadata.epub.lua"), ANNOTATIONS_FORMAT).unwrap();
+        fs::write(sdr.join("metadata.epub.lua.old"), ANNOTATIONS_FORMAT).unwrap();
+        fs::write(root.join("books").join("Dune.epub"), "").unwrap();
+
+        let broken = root.join("Broken.sdr");
+        fs::create_dir_all(&broken).unwrap();
+        fs::write(broken.join("metadata.pdf.lua"), "return { [").unwrap();
+
+        let books = extract_full(&root).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "Dune");
+        assert_eq!(books[0].highlights.len(), 1);
+    }
+}
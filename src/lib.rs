@@ -1,10 +1,28 @@
+pub mod annotated_json;
 pub mod apple_books;
+pub mod calibre;
 pub mod config;
+pub mod covers;
 pub mod error;
+pub mod export;
+pub mod filter;
+pub mod goodreads;
+pub mod http;
+pub mod hypothesis;
+pub mod import_cmd;
+pub mod instapaper;
 pub mod kindle;
+pub mod kobo;
+pub mod koreader;
+pub mod libby;
 pub mod merge;
 pub mod model;
+pub mod moon_reader;
+pub mod play_books;
+pub mod pocket;
+pub mod readwise;
 
 pub use config::Config;
 pub use error::{Error, Result};
+pub use filter::FilterSpec;
 pub use model::{Book, Highlight, Library, Location, Source};
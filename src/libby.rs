@@ -0,0 +1,292 @@
+use crate::error::LibbyError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::DateTime;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Progress at or above which a title counts as finished
+const FINISHED_PERCENT: f64 = 0.99;
+
+/// A reading journey export for one title
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Journey {
+    reading_journey: JourneyTitle,
+    #[serde(default)]
+    circulation: Vec<Activity>,
+    #[serde(default)]
+    highlights: Vec<LibbyHighlight>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JourneyTitle {
+    title: TitleRef,
+    author: Option<String>,
+    percent: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleRef {
+    text: String,
+}
+
+/// A timeline export covering every loan
+#[derive(Debug, Deserialize)]
+struct Timeline {
+    timeline: Vec<TimelineEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineEntry {
+    title: TitleRef,
+    author: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Activity {
+    activity: String,
+    timestamp: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LibbyHighlight {
+    timestamp: Option<i64>,
+    chapter: Option<String>,
+    percent: Option<f64>,
+    #[serde(default)]
+    quote: String,
+    note: Option<String>,
+}
+
+/// Row of the timeline CSV export
+#[derive(Debug, Deserialize)]
+struct TimelineRow {
+    title: String,
+    #[serde(default)]
+    author: String,
+}
+
+/// Any of the JSON shapes Libby exports
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Journey(Journey),
+    Journeys(Vec<Journey>),
+    Timeline(Timeline),
+}
+
+/// Import a Libby export: a reading journey JSON (one title or a list), or the
+/// timeline as JSON or CSV
+///
+/// Titles without highlights, including every timeline entry, are only kept when
+/// `include_all` is set. Bookmarks carry no text and are skipped.
+pub fn parse_export(path: &Path, include_all: bool) -> Result<Vec<Book>, LibbyError> {
+    if !path.exists() {
+        return Err(LibbyError::FileNotFound(path.to_path_buf()));
+    }
+
+    let is_csv = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+
+    let books = if is_csv {
+        parse_timeline_csv(path)?
+    } else {
+        let content = fs::read_to_string(path)?;
+        match serde_json::from_str(&content)? {
+            Export::Journey(journey) => vec![journey_to_book(journey)],
+            Export::Journeys(journeys) => journeys.into_iter().map(journey_to_book).collect(),
+            Export::Timeline(timeline) => timeline
+                .timeline
+                .into_iter()
+                .map(|entry| new_book(&entry.title.text, entry.author.as_deref()))
+                .collect(),
+        }
+    };
+
+    Ok(dedupe(books)
+        .into_iter()
+        .filter(|b| include_all || !b.highlights.is_empty())
+        .collect())
+}
+
+fn parse_timeline_csv(path: &Path) -> Result<Vec<Book>, LibbyError> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut books = Vec::new();
+
+    for row in reader.deserialize() {
+        let row: TimelineRow = row?;
+        if !row.title.trim().is_empty() {
+            books.push(new_book(&row.title, Some(&row.author)));
+        }
+    }
+
+    Ok(books)
+}
+
+fn new_book(title: &str, author: Option<&str>) -> Book {
+    let author = author.map(str::trim).filter(|a| !a.is_empty());
+    let mut book = Book::new(title.trim().to_string(), author.map(String::from));
+    book.sources = vec![Source::Libby];
+    book
+}
+
+fn journey_to_book(journey: Journey) -> Book {
+    let title = &journey.reading_journey;
+    let mut book = new_book(&title.title.text, title.author.as_deref());
+
+    if let Some(percent) = title.percent {
+        let finished = percent >= FINISHED_PERCENT;
+        book.finished = Some(finished);
+        if finished {
+            book.finished_at = journey
+                .circulation
+                .iter()
+                .filter(|a| a.activity.eq_ignore_ascii_case("returned"))
+                .filter_map(|a| a.timestamp.and_then(DateTime::from_timestamp_millis))
+                .max();
+        }
+    }
+
+    for highlight in journey.highlights {
+        let text = highlight.quote.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let position = highlight.percent.map(format_percent);
+        let note = highlight.note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, text, position.as_deref()),
+            text: text.to_string(),
+            note,
+            location: Location {
+                chapter: highlight.chapter.filter(|c| !c.trim().is_empty()),
+                position,
+                page: None,
+                raw: None,
+            },
+            created_at: highlight.timestamp.and_then(DateTime::from_timestamp_millis),
+            sources: vec![Source::Libby],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    book
+}
+
+/// Progress fraction as a percentage, e.g. 0.4213 -> "42.1%"
+fn format_percent(percent: f64) -> String {
+    let formatted = format!("{:.1}", percent * 100.0);
+    format!("{}%", formatted.trim_end_matches(".0"))
+}
+
+/// Collapse repeated loans of the same title into one book
+fn dedupe(books: Vec<Book>) -> Vec<Book> {
+    let mut result: Vec<Book> = Vec::new();
+
+    for book in books {
+        match result.iter_mut().find(|b| b.id == book.id) {
+            Some(existing) => {
+                existing.highlights.extend(book.highlights);
+                if book.finished == Some(true) {
+                    existing.finished = Some(true);
+                    existing.finished_at = existing.finished_at.max(book.finished_at);
+                } else if existing.finished.is_none() {
+                    existing.finished = book.finished;
+                }
+            }
+            None => result.push(book),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const JOURNEY: &str = r##"{
+        "version": 1,
+        "readingJourney": {
+            "cover": {"format": "image/jpeg", "url": "https://example.com/cover.jpg"},
+            "title": {"text": "Piranesi", "url": "https://share.libbyapp.com/title/123", "titleId": "123", "format": "ebook"},
+            "author": "Susanna Clarke",
+            "publisher": "Bloomsbury",
+            "isbn": "9781635575637",
+            "percent": 1
+        },
+        "circulation": [
+            {"activity": "Borrowed", "details": "21 days", "library": {"text": "City Library"}, "timestamp": 1700000000000},
+            {"activity": "Returned", "details": "", "library": {"text": "City Library"}, "timestamp": 1701000000000}
+        ],
+        "highlights": [
+            {"timestamp": 1700100000000, "chapter": "Part 1", "percent": 0.0423, "color": "#FFB", "quote": "The Beauty of the House is immeasurable.", "note": "Opening"},
+            {"timestamp": 1700200000000, "chapter": "", "percent": 0.5, "color": "#FFB", "quote": "  ", "note": ""}
+        ],
+        "bookmarks": [
+            {"timestamp": 1700300000000, "chapter": "Part 2", "percent": 0.3}
+        ]
+    }"##;
+
+    fn write_temp(ext: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("readingsync_libby_{}.{}", uuid::Uuid::new_v4(), ext));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_journey() {
+        let path = write_temp("json", JOURNEY);
+        let books = parse_export(&path, false).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "Piranesi");
+        assert_eq!(book.author.as_deref(), Some("Susanna Clarke"));
+        assert_eq!(book.sources, vec![Source::Libby]);
+        assert_eq!(book.finished, Some(true));
+        assert_eq!(book.finished_at, Utc.timestamp_millis_opt(1701000000000).single());
+
+        assert_eq!(book.highlights.len(), 1);
+        let highlight = &book.highlights[0];
+        assert_eq!(highlight.note.as_deref(), Some("Opening"));
+        assert_eq!(highlight.location.chapter.as_deref(), Some("Part 1"));
+        assert_eq!(highlight.location.position.as_deref(), Some("4.2%"));
+        assert_eq!(highlight.created_at, Utc.timestamp_millis_opt(1700100000000).single());
+    }
+
+    #[test]
+    fn test_timeline_requires_include_all() {
+        let path = write_temp(
+            "csv",
+            "cover,title,author,publisher,isbn,timestamp,activity,details,library\n\
+             https://example.com/c.jpg,Piranesi,Susanna Clarke,Bloomsbury,9781635575637,2024-03-01 18:04,Borrowed,21 days,City Library\n\
+             https://example.com/c.jpg,Piranesi,Susanna Clarke,Bloomsbury,9781635575637,2024-03-20 09:12,Returned,,City Library\n",
+        );
+
+        let without = parse_export(&path, false).unwrap();
+        let with = parse_export(&path, true).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(without.is_empty());
+        assert_eq!(with.len(), 1);
+        assert!(with[0].highlights.is_empty());
+    }
+
+    #[test]
+    fn test_format_percent() {
+        assert_eq!(format_percent(0.5), "50%");
+        assert_eq!(format_percent(0.4213), "42.1%");
+    }
+}
@@ -1,12 +1,17 @@
 use readingsync::{
-    apple_books, kindle,
-    model::{Library, Source},
-    Config, Error,
+    annotated_json, apple_books, calibre, config::HttpConfig, covers,
+    error::{ConfigError, ExportError, KindleError, PocketError},
+    export, goodreads, hypothesis, import_cmd, instapaper, kindle, kobo, koreader, libby, merge, moon_reader, play_books, pocket, readwise,
+    model::{HighlightKind, Library, Source},
+    Config, Error, FilterSpec,
 };
-use chrono::Utc;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 /// Sync reading highlights from Kindle and Apple Books
 #[derive(Parser, Debug)]
@@ -16,152 +21,2162 @@ struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Output path for the library JSON file
+    /// Output path (library JSON or the chosen export format)
     #[arg(short, long, global = true)]
     output: Option<PathBuf>,
 
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json, global = true)]
+    format: OutputFormat,
+
+    /// Timezone used to interpret dates, e.g. "Europe/Berlin" [default: UTC]
+    #[arg(long, global = true)]
+    timezone: Option<chrono_tz::Tz>,
+
     /// Pretty-print JSON output
     #[arg(long, global = true)]
     pretty: bool,
 
-    /// Verbose logging
-    #[arg(short, long, global = true)]
-    verbose: bool,
-}
+    /// Keep the previous exported_at when nothing changed, so unchanged data
+    /// produces byte-identical JSON output
+    #[arg(long, global = true)]
+    canonical: bool,
+
+    /// Don't merge the existing output library into this run's results (with filters,
+    /// the default library)
+    #[arg(long, global = true)]
+    no_merge_existing: bool,
+
+    /// Download cover images into this directory and record their paths instead of URLs
+    #[arg(long, value_name = "DIR", global = true)]
+    download_covers: Option<PathBuf>,
+
+    /// Additional library JSON files to merge in (repeatable)
+    #[arg(long = "merge", value_name = "FILE", global = true)]
+    merge_files: Vec<PathBuf>,
+
+    /// Keep books apart whose titles differ only slightly (by a subtitle, an edition note
+    /// or punctuation) instead of merging them
+    #[arg(long, global = true)]
+    no_fuzzy_merge: bool,
+
+    /// Only merge highlights with identical text, keeping near-identical ones from different
+    /// sources (e.g. differing in quotes or a trailing period) apart
+    #[arg(long, global = true)]
+    strict_dedupe: bool,
+
+    /// Collapse extended or shortened re-highlights of the same passage
+    /// (always done for clippings)
+    #[arg(long, global = true)]
+    dedupe_overlaps: bool,
+
+    /// Include bookmarks in rendered formats (JSON always keeps them)
+    #[arg(long, global = true)]
+    include_bookmarks: bool,
+
+    /// Include highlights no longer present upstream in rendered formats (JSON always keeps them),
+    /// and read annotations deleted in Apple Books
+    #[arg(long, global = true)]
+    include_deleted: bool,
+
+    /// Show the passage around each highlight in rendered formats, where the source keeps
+    /// one (Apple Books)
+    #[arg(long, global = true)]
+    include_context: bool,
+
+    /// Leave the passage around each highlight out of JSON output, which keeps it by default
+    #[arg(long, global = true)]
+    no_context: bool,
+
+    /// Verbose logging
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(flatten)]
+    filters: FilterArgs,
+
+    #[command(flatten)]
+    quotes: QuoteArgs,
+
+    #[command(flatten)]
+    network: NetworkArgs,
+}
+
+/// Flags for narrowing down the library before it is written
+#[derive(clap::Args, Debug)]
+struct FilterArgs {
+    /// Only include books whose title contains this text, or with this book ID or ASIN
+    #[arg(long, global = true)]
+    book: Option<String>,
+
+    /// Only include books whose author contains this text
+    #[arg(long, global = true)]
+    author: Option<String>,
+
+    /// Only include books and highlights from this source: kindle, apple_books, kobo, google_play_books, pocket, instapaper, koreader, moon_reader, calibre, hypothesis, readwise, libby, goodreads, annotated_json
+    #[arg(long, global = true)]
+    source: Option<Source>,
+
+    /// Only include books in this collection, e.g. "Want to Read"
+    #[arg(long, global = true)]
+    collection: Option<String>,
+
+    /// Only include books in this language, e.g. "en" (also matches "en-GB")
+    #[arg(long, global = true)]
+    language: Option<String>,
+
+    /// Only include highlights made on or after this date (YYYY-MM-DD or RFC3339)
+    #[arg(long, global = true)]
+    since: Option<String>,
+
+    /// Only include highlights made on or before this date (YYYY-MM-DD or RFC3339)
+    #[arg(long, global = true)]
+    until: Option<String>,
+
+    /// Only include books with at least this many highlights
+    #[arg(long, global = true)]
+    min_highlights: Option<usize>,
+
+    /// Only include books read at least this far, in percent (finished books count as 100)
+    #[arg(long, global = true, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    min_progress: Option<u8>,
+
+    /// Only include highlights that have a note attached
+    #[arg(long, global = true, conflicts_with = "highlights_only")]
+    notes_only: bool,
+
+    /// Drop notes and note text from the output (e.g. for sharing publicly)
+    #[arg(long, global = true)]
+    highlights_only: bool,
+}
+
+/// Network settings overriding the `[http]` config
+#[derive(clap::Args, Debug)]
+struct NetworkArgs {
+    /// Proxy for HTTP requests, e.g. http://proxy.corp:3128 (default: http.proxy, then HTTPS_PROXY / HTTP_PROXY)
+    #[arg(long, value_name = "URL", global = true)]
+    proxy: Option<String>,
+
+    /// Extra PEM or DER root certificate to trust, e.g. a TLS-intercepting proxy's (repeatable)
+    #[arg(long = "ca-cert", value_name = "PATH", global = true)]
+    ca_certs: Vec<PathBuf>,
+
+    /// User-Agent header for HTTP requests (default: http.user_agent)
+    #[arg(long, value_name = "UA", global = true)]
+    user_agent: Option<String>,
+}
+
+impl NetworkArgs {
+    fn apply(&self, http: &mut HttpConfig) {
+        if let Some(proxy) = &self.proxy {
+            http.proxy = Some(proxy.clone());
+        }
+        http.ca_certs.extend(self.ca_certs.iter().cloned());
+        if let Some(user_agent) = &self.user_agent {
+            http.user_agent = Some(user_agent.clone());
+        }
+    }
+}
+
+/// Options for `--format quotes`
+#[derive(clap::Args, Debug)]
+struct QuoteArgs {
+    /// Minimum quote length in characters
+    #[arg(long, global = true)]
+    min_chars: Option<usize>,
+
+    /// Maximum quote length in characters
+    #[arg(long, global = true)]
+    max_chars: Option<usize>,
+
+    /// Seed for a reproducible quote shuffle
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// Skip quotes that don't end with terminal punctuation
+    #[arg(long, global = true)]
+    complete_sentences: bool,
+}
+
+impl FilterArgs {
+    /// Build a filter spec, interpreting bare dates in the given timezone
+    fn to_spec(&self, tz: chrono_tz::Tz) -> Result<FilterSpec, Error> {
+        Ok(FilterSpec {
+            book: self.book.clone(),
+            author: self.author.clone(),
+            source: self.source.clone(),
+            collection: self.collection.clone(),
+            language: self.language.clone(),
+            since: self
+                .since
+                .as_deref()
+                .map(|s| parse_date_bound(s, tz, NaiveTime::MIN))
+                .transpose()?,
+            until: self
+                .until
+                .as_deref()
+                .map(|s| parse_date_bound(s, tz, end_of_day()))
+                .transpose()?,
+            min_highlights: self.min_highlights,
+            min_progress: self.min_progress.map(|percent| f32::from(percent) / 100.0),
+            notes_only: self.notes_only,
+            highlights_only: self.highlights_only,
+        })
+    }
+}
+
+/// Output formats for the exported library
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Unified JSON library file
+    Json,
+    /// EPUB book with one chapter per book
+    Epub,
+    /// Directory with typed books.csv and highlights.csv
+    Analytics,
+    /// TiddlyWiki JSON import file, one tiddler per highlight
+    Tiddlywiki,
+    /// Markdown reading journal grouped by date highlighted
+    Journal,
+    /// Markdown reading log of finished books by year
+    Log,
+    /// Directory of .textbundle packages, one per book (Bear)
+    Textbundle,
+    /// Kindle "My Clippings.txt" format
+    Clippings,
+    /// GraphML graph of books and authors
+    Graphml,
+    /// Directory with a books.csv and Markdown pages for Notion import
+    Notion,
+    /// Shuffled JSON array of {text, author, title} quotes
+    Quotes,
+}
+
+impl OutputFormat {
+    /// File extension used for the default output path
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Analytics => "",
+            OutputFormat::Tiddlywiki => "json",
+            OutputFormat::Journal => "md",
+            OutputFormat::Log => "md",
+            OutputFormat::Textbundle => "",
+            OutputFormat::Clippings => "txt",
+            OutputFormat::Graphml => "graphml",
+            OutputFormat::Notion => "",
+            OutputFormat::Quotes => "json",
+        }
+    }
+}
+
+/// How `kindle` reads highlights from the notebook page
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Engine {
+    /// Parse the page's annotation responses, falling back to the DOM per book
+    Network,
+    /// Query the rendered page and click through its pagination
+    Dom,
+}
+
+impl From<Engine> for kindle::ScrapeEngine {
+    fn from(engine: Engine) -> Self {
+        match engine {
+            Engine::Network => kindle::ScrapeEngine::Network,
+            Engine::Dom => kindle::ScrapeEngine::Dom,
+        }
+    }
+}
+
+/// The browser `kindle` drives
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum BrowserBackend {
+    /// Launch Chrome, Chromium, Edge or Brave and drive it over CDP
+    Chrome,
+    /// Use a running WebDriver server such as geckodriver (Firefox); needs the webdriver feature
+    Webdriver,
+}
+
+/// An installed browser to take Amazon cookies from
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CookiesFrom {
+    /// Chrome or Chromium (needs the chrome-cookies feature to decrypt them)
+    Chrome,
+    Firefox,
+    /// Safari (macOS; the terminal needs Full Disk Access)
+    Safari,
+}
+
+impl From<CookiesFrom> for kindle::browser_cookies::CookieBrowser {
+    fn from(browser: CookiesFrom) -> Self {
+        match browser {
+            CookiesFrom::Chrome => Self::Chrome,
+            CookiesFrom::Firefox => Self::Firefox,
+            CookiesFrom::Safari => Self::Safari,
+        }
+    }
+}
+
+/// Which part of the Kindle library `kindle` lists
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ContentType {
+    /// Bought books (the notebook's default view)
+    Books,
+    /// Personal documents, e.g. sent with Send to Kindle
+    Docs,
+    /// Both
+    All,
+}
+
+impl From<ContentType> for kindle::ContentType {
+    fn from(content_type: ContentType) -> Self {
+        match content_type {
+            ContentType::Books => kindle::ContentType::Books,
+            ContentType::Docs => kindle::ContentType::Docs,
+            ContentType::All => kindle::ContentType::All,
+        }
+    }
+}
+
+/// The `--region` (and `kindle.region`) detecting the account's region instead
+const AUTO_REGION: &str = "auto";
+
+/// Flags for the browser-based Kindle sync
+#[derive(clap::Args, Debug)]
+struct KindleSyncArgs {
+    /// Amazon region: us, uk, de, fr, jp, etc., or auto to follow Amazon's redirect to
+    /// the account's own (default: kindle.region)
+    #[arg(long, global = true)]
+    region: Option<String>,
+
+    /// Run browser in headless mode (no visible window)
+    #[arg(long)]
+    headless: bool,
+
+    /// Re-scrape every book, not just those annotated since the last sync
+    #[arg(long)]
+    full: bool,
+
+    /// Continue an interrupted sync, keeping the books it already scraped
+    #[arg(long)]
+    resume: bool,
+
+    /// Times to retry a book that fails to scrape, with exponential backoff
+    #[arg(long, default_value_t = 2)]
+    retries: u32,
+
+    /// Chrome, Chromium, Edge or Brave binary to use (default: kindle.browser.chrome_path, then search)
+    #[arg(long, value_name = "PATH", global = true)]
+    chrome_path: Option<PathBuf>,
+
+    /// Timeout for page loads in seconds [default: 30]
+    #[arg(long, value_name = "SECS")]
+    timeout_secs: Option<u64>,
+
+    /// How long to wait for a book's highlights after opening it, in seconds [default: 10]
+    #[arg(long, value_name = "SECS")]
+    per_book_timeout_secs: Option<u64>,
+
+    /// Pause between books in milliseconds [default: 500]
+    #[arg(long, value_name = "MS")]
+    inter_book_delay_ms: Option<u64>,
+
+    /// Most pages of highlights to read per book [default: 100]
+    #[arg(long, value_name = "N")]
+    max_pages: Option<usize>,
+
+    /// Books to scrape at once, each in its own tab (1-4; more risks Amazon throttling) [default: 1]
+    #[arg(long, value_name = "N", value_parser = parse_concurrency)]
+    concurrency: Option<usize>,
+
+    /// Browser window size [default: 1280x900]
+    #[arg(long, value_name = "WxH", value_parser = parse_window_size)]
+    window_size: Option<(u32, u32)>,
+
+    /// Chrome profile directory holding the Amazon session (a Firefox profile with --backend webdriver)
+    #[arg(long, value_name = "DIR", global = true)]
+    user_data_dir: Option<PathBuf>,
+
+    /// Browser automation to use: Chrome over CDP, or a WebDriver server (e.g. geckodriver for Firefox)
+    #[arg(long, value_enum, default_value_t = BrowserBackend::Chrome, global = true)]
+    backend: BrowserBackend,
+
+    /// WebDriver server for --backend webdriver
+    #[arg(long, value_name = "URL", default_value = kindle::backend::DEFAULT_WEBDRIVER_URL, global = true)]
+    webdriver_url: String,
+
+    /// Fail with a login error instead of waiting for someone to log in (implied by --headless)
+    #[arg(long)]
+    non_interactive: bool,
+
+    /// Exit non-zero if any book still failed after its retries (output is still written)
+    #[arg(long)]
+    fail_on_error: bool,
+
+    /// Only scrape books whose title contains this text or with this ASIN (repeatable)
+    #[arg(long = "only", value_name = "TITLE|ASIN")]
+    only: Vec<String>,
+
+    /// Skip the first N books of the library list (after --only)
+    #[arg(long, default_value_t = 0)]
+    skip: usize,
+
+    /// Scrape at most N books (after --only and --skip)
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// How to read highlights: from the page's network responses, or from the DOM (for debugging)
+    #[arg(long, value_enum, default_value_t = Engine::Network)]
+    engine: Engine,
+
+    /// Which items to sync: books, personal documents (Send to Kindle) or all
+    #[arg(long, value_enum, default_value_t = ContentType::Books)]
+    content_type: ContentType,
+
+    /// Write a per-book JSON report of the run next to the output (<output>.report.json)
+    #[arg(long)]
+    report: bool,
+
+    /// Save every book's raw annotation pages and parsed highlights to DIR, to debug
+    /// markup changes (contains your highlights and notes)
+    #[arg(long, value_name = "DIR")]
+    debug_dump: Option<PathBuf>,
+
+    /// Log in and list the library without scraping highlights (a table, or JSON with --format json)
+    #[arg(long)]
+    list_only: bool,
+
+    /// Keep the browser open and sync again every --interval until stopped (SIGINT/SIGTERM)
+    #[arg(long, conflicts_with = "list_only")]
+    watch: bool,
+
+    /// Time between syncs with --watch, e.g. 30m, 6h or 1d
+    #[arg(long, value_name = "DURATION", default_value = "6h", value_parser = parse_interval, requires = "watch")]
+    interval: Duration,
+
+    #[command(subcommand)]
+    session: Option<KindleSession>,
+}
+
+/// Manage the stored Amazon session without syncing
+#[derive(Subcommand, Debug)]
+enum KindleSession {
+    /// Open the browser, wait for a successful login and keep the session
+    Login,
+
+    /// Check headlessly whether the stored session still reaches the notebook (exit 1 if not)
+    Status,
+
+    /// Delete the stored session (the browser profile directory)
+    Logout {
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+impl Default for KindleSyncArgs {
+    fn default() -> Self {
+        Self {
+            region: None,
+            headless: false,
+            full: false,
+            resume: false,
+            retries: 2,
+            chrome_path: None,
+            timeout_secs: None,
+            per_book_timeout_secs: None,
+            inter_book_delay_ms: None,
+            max_pages: None,
+            concurrency: None,
+            window_size: None,
+            user_data_dir: None,
+            backend: BrowserBackend::Chrome,
+            webdriver_url: kindle::backend::DEFAULT_WEBDRIVER_URL.to_string(),
+            non_interactive: false,
+            fail_on_error: false,
+            only: Vec::new(),
+            skip: 0,
+            limit: None,
+            engine: Engine::Network,
+            content_type: ContentType::Books,
+            report: false,
+            debug_dump: None,
+            list_only: false,
+            watch: false,
+            interval: Duration::from_secs(6 * 60 * 60),
+            session: None,
+        }
+    }
+}
+
+impl KindleSyncArgs {
+    /// Browser settings from these flags, then `[kindle.browser]`, then the defaults
+    fn browser_config(&self, kindle: &readingsync::config::KindleConfig) -> Result<kindle::BrowserConfig, Error> {
+        let settings = &kindle.browser;
+        let defaults = kindle::BrowserConfig::default();
+        // Auto starts from amazon.com, which redirects accounts registered elsewhere
+        let region = self.region.as_deref().unwrap_or(&kindle.region);
+        let detect_region = region.eq_ignore_ascii_case(AUTO_REGION);
+        Ok(kindle::BrowserConfig {
+            headless: self.headless || settings.headless.unwrap_or(defaults.headless),
+            region: kindle::AmazonRegion::from_code(if detect_region { "us" } else { region })?,
+            detect_region,
+            user_data_dir: self
+                .user_data_dir
+                .as_ref()
+                .or(settings.user_data_dir.as_ref())
+                .map(|dir| dir.to_string_lossy().to_string()),
+            timeout_secs: self.timeout_secs.or(settings.timeout_secs).unwrap_or(defaults.timeout_secs),
+            per_book_timeout_secs: self
+                .per_book_timeout_secs
+                .or(settings.per_book_timeout_secs)
+                .unwrap_or(defaults.per_book_timeout_secs),
+            inter_book_delay_ms: self
+                .inter_book_delay_ms
+                .or(settings.inter_book_delay_ms)
+                .unwrap_or(defaults.inter_book_delay_ms),
+            max_pages: self.max_pages.or(settings.max_pages).unwrap_or(defaults.max_pages),
+            window_size: self.window_size.or(settings.window_size).unwrap_or(defaults.window_size),
+            retry: kindle::retry::RetryPolicy {
+                retries: self.retries,
+                ..Default::default()
+            },
+            interactive: !self.non_interactive,
+            chrome_path: self.chrome_path.clone().or_else(|| settings.chrome_path.clone()),
+            engine: self.engine.into(),
+            concurrency: self.concurrency.or(settings.concurrency).unwrap_or(defaults.concurrency),
+            content_type: self.content_type.into(),
+            backend: match self.backend {
+                BrowserBackend::Chrome => kindle::Backend::Chrome,
+                BrowserBackend::Webdriver => kindle::Backend::WebDriver {
+                    url: self.webdriver_url.clone(),
+                },
+            },
+            debug_dump: self.debug_dump.clone(),
+        })
+    }
+
+    fn selection(&self) -> kindle::BookSelection {
+        kindle::BookSelection {
+            patterns: self.only.clone(),
+            skip: self.skip,
+            limit: self.limit,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Sync highlights from Kindle via browser (recommended)
+    #[command(name = "kindle")]
+    KindleSync(KindleSyncArgs),
+
+    /// Sync highlights from the Kindle notebook over HTTP with exported browser cookies (no browser needed)
+    #[command(name = "kindle-cookies")]
+    KindleCookies {
+        /// Netscape-format cookies.txt exported from a browser logged in to Amazon (default: kindle.cookies_path)
+        #[arg(long, value_name = "PATH")]
+        cookies: Option<PathBuf>,
+
+        /// Read the Amazon cookies from this browser's cookie store instead of a file
+        #[arg(long, value_enum, value_name = "BROWSER", conflicts_with = "cookies")]
+        cookies_from: Option<CookiesFrom>,
+
+        /// Amazon region: us, uk, de, fr, jp, etc., or auto to tell it from the cookies
+        /// (default: kindle.region)
+        #[arg(long)]
+        region: Option<String>,
+
+        /// Times to retry a book whose requests fail with a 429, a server error or a timeout
+        #[arg(long, default_value_t = 2)]
+        retries: u32,
+
+        /// Most pages of highlights to read per book
+        #[arg(long, value_name = "N", default_value_t = 100)]
+        max_pages: usize,
+
+        /// Least time between two requests to Amazon, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        request_delay_ms: u64,
+
+        /// Books to fetch at once (1-4); requests still keep --request-delay-ms apart
+        #[arg(long, value_name = "N", default_value_t = 3, value_parser = parse_concurrency)]
+        concurrency: usize,
+
+        /// Exit non-zero if any book still failed after its retries (output is still written)
+        #[arg(long)]
+        fail_on_error: bool,
+
+        /// Write a per-book JSON report of the run next to the output (<output>.report.json)
+        #[arg(long)]
+        report: bool,
+
+        /// Save every book's raw notebook pages and parsed highlights to DIR, to debug
+        /// markup changes (contains your highlights and notes)
+        #[arg(long, value_name = "DIR")]
+        debug_dump: Option<PathBuf>,
+    },
+
+    /// Import notes from the Kindle desktop app's local cache (no web scraping)
+    #[command(name = "kindle-app")]
+    KindleApp {
+        /// Path to the app's 'My Kindle Content' folder (default: auto-detect)
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Path to KindleSyncMetadataCache.xml for titles (default: auto-detect)
+        #[arg(long)]
+        catalog: Option<PathBuf>,
+    },
+
+    /// Export from Apple Books only
+    #[command(name = "apple-books")]
+    AppleBooks {
+        /// Read from an unencrypted iPhone/iPad backup folder instead of this Mac
+        #[arg(long)]
+        ios_backup: Option<PathBuf>,
+
+        /// List every database file found in the searched locations, marking the ones
+        /// that would be read, and exit
+        #[arg(long, conflicts_with = "ios_backup")]
+        list_databases: bool,
+
+        /// Also export store samples, which are skipped by default
+        #[arg(long)]
+        include_samples: bool,
+
+        /// Read every annotation, not just those changed since the last export
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Import highlights from a Kobo's KoboReader.sqlite
+    #[command(name = "kobo")]
+    Kobo {
+        /// Path to KoboReader.sqlite (default: mounted device)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+
+    /// Import notes from a Google Play Books Takeout export
+    #[command(name = "play-books")]
+    PlayBooks {
+        /// Path to the Takeout `Play Books` folder
+        #[arg(long)]
+        path: PathBuf,
+    },
+
+    /// Import article highlights from Pocket (export file or API)
+    #[command(name = "pocket")]
+    Pocket {
+        /// Path to the Pocket export ZIP or annotations JSON (omit to use the API)
+        path: Option<PathBuf>,
+
+        /// Pocket API consumer key
+        #[arg(long, env = "POCKET_CONSUMER_KEY")]
+        consumer_key: Option<String>,
+
+        /// Pocket API access token
+        #[arg(long, env = "POCKET_ACCESS_TOKEN", hide_env_values = true)]
+        access_token: Option<String>,
+
+        /// Also include saved articles that have no highlights
+        #[arg(long)]
+        include_unannotated: bool,
+    },
+
+    /// Import article highlights from Instapaper's CSV export
+    #[command(name = "instapaper")]
+    Instapaper {
+        /// Path to the Instapaper highlights CSV
+        path: PathBuf,
+    },
+
+    /// Import highlights from KOReader sidecar (metadata.*.lua) files
+    #[command(name = "koreader")]
+    KoReader {
+        /// Mounted device or directory to search for .sdr folders
+        #[arg(long)]
+        path: PathBuf,
+    },
+
+    /// Import annotations exported from Moon+ Reader
+    #[command(name = "moonreader")]
+    MoonReader {
+        /// Annotation export file (.an, .po, .mrexpt) or a directory of them
+        path: PathBuf,
+    },
+
+    /// Import viewer highlights from a Calibre library
+    #[command(name = "calibre")]
+    Calibre {
+        /// Calibre library folder (default: ~/Calibre Library)
+        #[arg(long)]
+        library: Option<PathBuf>,
+    },
+
+    /// Import web annotations from Hypothesis
+    #[command(name = "hypothesis")]
+    Hypothesis {
+        /// Hypothesis API token
+        #[arg(long, env = "HYPOTHESIS_TOKEN", hide_env_values = true)]
+        token: String,
+
+        /// Account to export, e.g. acct:name@hypothes.is (default: the token's owner)
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// Import highlights from a Readwise CSV export
+    #[command(name = "import-readwise")]
+    ImportReadwise {
+        /// Path to the Readwise export CSV
+        path: PathBuf,
+    },
+
+    /// Import highlights from a Libby reading journey or timeline export
+    #[command(name = "libby")]
+    Libby {
+        /// Path to the Libby export (JSON or CSV)
+        path: PathBuf,
+
+        /// Also import loans without highlights, for their finished status
+        #[arg(long)]
+        include_all: bool,
+    },
+
+    /// Mark books finished and add ISBNs from a Goodreads library export
+    #[command(name = "goodreads")]
+    Goodreads {
+        /// Path to the Goodreads library export CSV
+        path: PathBuf,
+    },
+
+    /// Import Kindle highlights saved with Bookcision
+    #[command(name = "bookcision")]
+    Bookcision {
+        /// Path to a Bookcision JSON file, or a directory of them
+        path: PathBuf,
+    },
+
+    /// Import an HTML notebook exported from the Kindle app
+    #[command(name = "notebook-export")]
+    NotebookExport {
+        /// Notebook export HTML files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Import annotations from a JSON export using a field mapping
+    #[command(name = "import-json")]
+    ImportJson {
+        /// Path to the annotation export JSON
+        path: PathBuf,
+
+        /// Built-in mapping: polar
+        #[arg(long, conflicts_with = "mapping", required_unless_present = "mapping")]
+        preset: Option<String>,
+
+        /// JSON file with a custom mapping of JSON pointers
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+    },
+
+    /// Import books printed as JSON by an external command
+    #[command(name = "import-cmd")]
+    ImportCmd {
+        /// Command and arguments, after `--`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Legacy: use My Clippings.txt file from Kindle device
+    #[command(name = "clippings")]
+    Clippings {
+        /// My Clippings.txt files, or directories of them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Fail on malformed entries instead of skipping them
+        #[arg(long)]
+        strict: bool,
+
+        /// Print each malformed entry that was skipped or partly parsed
+        #[arg(long)]
+        show_warnings: bool,
+    },
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Load config
+    let mut config = Config::load_default();
+    config.expand_paths();
+    args.network.apply(&mut config.http);
+
+    // Determine output path
+    let output_path = args.output.clone().unwrap_or_else(|| default_output_path(args.format));
+
+    if args.verbose {
+        eprintln!("Output path: {}", output_path.display());
+    }
+
+    // Parse filters up front so invalid values fail before any scraping
+    let tz = args.timezone.unwrap_or(chrono_tz::UTC);
+    let spec = args.filters.to_spec(tz)?;
+
+    // Filtered exports merge in the library but must not replace it with the subset
+    if args.format == OutputFormat::Json
+        && !args.no_merge_existing
+        && !spec.is_empty()
+        && output_path == default_output_path(OutputFormat::Json)
+    {
+        return Err(ExportError::FilteredOverLibrary(output_path).into());
+    }
+
+    // Books a Kindle sync gave up on, for --fail-on-error
+    let mut lost_books = 0;
+    let mut fail_on_lost_books = false;
+    // Saved once the output is written, so a failed write doesn't skip changes next time
+    let mut apple_books_state = None;
+
+    // Session commands and listing the Kindle library write no output file
+    if let Some(Commands::KindleSync(kindle_args)) = &args.command {
+        if let Some(session) = &kindle_args.session {
+            return run_kindle_session(session, kindle_args, &config);
+        }
+        if kindle_args.list_only {
+            let json = format_given(&matches) && args.format == OutputFormat::Json;
+            return run_kindle_list(kindle_args, &config, json);
+        }
+    }
+    if let Some(Commands::AppleBooks { list_databases: true, .. }) = &args.command {
+        run_apple_books_list(&config);
+        return Ok(());
+    }
+
+    // Handle commands
+    let books = match args.command.take() {
+        Some(Commands::KindleSync(kindle_args)) if kindle_args.watch => {
+            return run_kindle_watch(&kindle_args, &config, &args, &output_path, &spec);
+        }
+        Some(Commands::KindleSync(kindle_args)) => {
+            // A partial scrape relies on merging the existing library to keep other books
+            let partial = !kindle_args.only.is_empty() || kindle_args.skip > 0 || kindle_args.limit.is_some();
+            if partial && (args.format != OutputFormat::Json || args.no_merge_existing) {
+                eprintln!("Note: only the selected books will be in the output; other books are not merged in");
+            }
+            let report_path = kindle_args.report.then(|| kindle::report::report_path(&output_path));
+            let (books, lost) = run_kindle_browser_sync(&kindle_args, &config, report_path.as_deref(), args.verbose)?;
+            lost_books = lost;
+            fail_on_lost_books = kindle_args.fail_on_error;
+            books
+        }
+        Some(Commands::KindleCookies {
+            cookies,
+            cookies_from,
+            region,
+            retries,
+            max_pages,
+            request_delay_ms,
+            concurrency,
+            fail_on_error,
+            report,
+            debug_dump,
+        }) => {
+            let source = match cookies_from {
+                Some(browser) => kindle::scraper::CookieSource::Browser(browser.into()),
+                None => kindle::scraper::CookieSource::File(
+                    cookies
+                        .or_else(|| config.kindle.cookies_path.clone())
+                        .ok_or(KindleError::NoCookies)?,
+                ),
+            };
+            let scrape_config = kindle::scraper::LegacyScrapeConfig {
+                max_pages,
+                request_delay: Duration::from_millis(request_delay_ms),
+                concurrency,
+                retry: kindle::retry::RetryPolicy {
+                    retries,
+                    ..Default::default()
+                },
+                http: config.http.clone(),
+                debug_dump,
+            };
+            let report_path = report.then(|| kindle::report::report_path(&output_path));
+            let (books, lost) = run_kindle_cookies_sync(
+                &source,
+                region,
+                &scrape_config,
+                &config,
+                report_path.as_deref(),
+                args.verbose,
+            )?;
+            lost_books = lost;
+            fail_on_lost_books = fail_on_error;
+            books
+        }
+        Some(Commands::KindleApp { path, catalog }) => {
+            run_kindle_app_import(path, catalog, args.verbose)?
+        }
+        Some(Commands::AppleBooks {
+            ios_backup,
+            include_samples,
+            full,
+            ..
+        }) => {
+            let options = apple_books::AppleBooksOptions {
+                library_db: config.apple_books.library_db.clone(),
+                annotation_db: config.apple_books.annotation_db.clone(),
+                search: apple_books_search(&config),
+                ios_backup,
+                include_deleted: args.include_deleted,
+                include_samples,
+                ..Default::default()
+            };
+            // Incremental passes build on the previous export, so it has to be complete
+            let state_path = (args.format == OutputFormat::Json && !args.no_merge_existing && spec.is_empty())
+                .then(|| apple_books::incremental::state_path(&output_path));
+            let (books, state) =
+                run_apple_books_export(options, state_path.as_deref(), &output_path, full, args.verbose)?;
+            apple_books_state = state_path.zip(state);
+            books
+        }
+        Some(Commands::Clippings { paths, strict, show_warnings }) => run_clippings_import(
+            &paths,
+            tz,
+            &config.kindle.title_aliases,
+            strict,
+            show_warnings,
+            args.verbose,
+        )?,
+        Some(Commands::Kobo { path }) => {
+            run_kobo_import(path, args.verbose)?
+        }
+        Some(Commands::PlayBooks { path }) => {
+            run_play_books_import(&path, args.verbose)?
+        }
+        Some(Commands::Pocket { path, consumer_key, access_token, include_unannotated }) => {
+            run_pocket_import(path, consumer_key, access_token, include_unannotated, &config.http, args.verbose)?
+        }
+        Some(Commands::Instapaper { path }) => {
+            run_instapaper_import(&path, args.verbose)?
+        }
+        Some(Commands::KoReader { path }) => {
+            run_koreader_import(&path, args.verbose)?
+        }
+        Some(Commands::MoonReader { path }) => {
+            run_moon_reader_import(&path, args.verbose)?
+        }
+        Some(Commands::Calibre { library }) => {
+            run_calibre_import(library, args.verbose)?
+        }
+        Some(Commands::Hypothesis { token, user }) => {
+            run_hypothesis_import(&token, user.as_deref(), &config.http, args.verbose)?
+        }
+        Some(Commands::ImportReadwise { path }) => {
+            run_readwise_import(&path, args.verbose)?
+        }
+        Some(Commands::Libby { path, include_all }) => {
+            run_libby_import(&path, include_all, args.verbose)?
+        }
+        Some(Commands::Goodreads { path }) => {
+            run_goodreads_import(&path, args.verbose)?
+        }
+        Some(Commands::Bookcision { path }) => {
+            run_bookcision_import(&path, args.verbose)?
+        }
+        Some(Commands::NotebookExport { paths }) => {
+            run_notebook_export_import(&paths, args.verbose)?
+        }
+        Some(Commands::ImportJson { path, preset, mapping }) => {
+            run_json_import(&path, preset.as_deref(), mapping.as_deref(), args.verbose)?
+        }
+        Some(Commands::ImportCmd { command }) => {
+            run_command_import(&command, args.verbose)?
+        }
+        None => {
+            // Default: run Kindle browser sync
+            eprintln!("No command specified. Running Kindle sync...");
+            eprintln!("(Use --help to see all options)\n");
+            run_kindle_browser_sync(&KindleSyncArgs::default(), &config, None, args.verbose)?.0
+        }
+    };
+
+    write_library(books, &args, &output_path, &spec, &config.http)?;
+
+    if let Some((path, state)) = apple_books_state {
+        state.save(&path)?;
+    }
+
+    if fail_on_lost_books && lost_books > 0 {
+        return Err(KindleError::BooksFailed(lost_books).into());
+    }
+
+    Ok(())
+}
+
+/// Where the output goes without `-o`; the JSON one is the library that
+/// filtered exports merge in
+fn default_output_path(format: OutputFormat) -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("readingsync")
+        .join("library")
+        .with_extension(format.extension())
+}
+
+/// Merge fresh books with the previous export and write the output
+fn write_library(
+    books: Vec<readingsync::Book>,
+    args: &Args,
+    output_path: &Path,
+    spec: &FilterSpec,
+    http: &HttpConfig,
+) -> Result<(), Error> {
+    // Merge previously exported libraries so highlights that have since disappeared
+    // upstream (e.g. past Kindle's clipping limit) are kept. A filtered export only holds
+    // a subset, so filtered runs merge in the full library instead.
+    let existing_path = if spec.is_empty() {
+        output_path.to_path_buf()
+    } else {
+        default_output_path(OutputFormat::Json)
+    };
+    let mut inputs = vec![books];
+    if args.format == OutputFormat::Json && !args.no_merge_existing && existing_path.exists() {
+        let mut previous = Library::load(&existing_path)?.books;
+        let deleted = merge::mark_deleted_upstream(&inputs[0], &mut previous);
+        if deleted > 0 {
+            eprintln!("{} highlights no longer present upstream (kept)", deleted);
+        }
+        inputs.push(previous);
+        if args.verbose {
+            eprintln!("Merged existing library from {}", existing_path.display());
+        }
+    }
+    for path in &args.merge_files {
+        inputs.push(Library::load(path)?.books);
+        if args.verbose {
+            eprintln!("Merged library from {}", path.display());
+        }
+    }
+    let merge_options = merge::MergeOptions {
+        strict_dedupe: args.strict_dedupe,
+    };
+    let mut books = if inputs.len() > 1 {
+        merge::merge_books_with(inputs, merge_options)
+    } else {
+        inputs.pop().unwrap_or_default()
+    };
+
+    // Sources spell titles differently, e.g. with an edition's subtitle
+    if !args.no_fuzzy_merge {
+        let merged = merge::fuzzy_merge(&mut books, merge_options);
+        if args.verbose && merged > 0 {
+            eprintln!("Merged {} books with similar titles into others", merged);
+        }
+    }
+
+    if let Some(ref dir) = args.download_covers {
+        let relative_to = output_path.parent().unwrap_or(Path::new(""));
+        let downloads = covers::download_covers(&mut books, dir, relative_to, http)?;
+        if args.verbose || !downloads.failed.is_empty() {
+            eprintln!(
+                "Downloaded {} covers ({} already present)",
+                downloads.downloaded, downloads.reused
+            );
+        }
+        for (title, error) in &downloads.failed {
+            eprintln!("  Could not download cover for {}: {}", title, error);
+        }
+    }
+
+    if args.dedupe_overlaps {
+        let removed = merge::dedupe_overlaps(&mut books);
+        if args.verbose {
+            eprintln!("Collapsed {} overlapping highlights", removed);
+        }
+    }
+
+    // Create library
+    let full_library = Library {
+        exported_at: Utc::now(),
+        books,
+    };
+
+    // Apply filters and sort into a stable order
+    let mut library = full_library.filter(spec);
+    library.sort_canonical();
+
+    if args.format != OutputFormat::Json && !args.include_bookmarks {
+        for book in &mut library.books {
+            book.highlights.retain(|h| h.kind != HighlightKind::Bookmark);
+        }
+    }
+
+    if args.format != OutputFormat::Json && !args.include_deleted {
+        for book in &mut library.books {
+            book.highlights.retain(|h| !h.deleted_upstream);
+        }
+    }
+
+    let keep_context = match args.format {
+        OutputFormat::Json => !args.no_context,
+        _ => args.include_context,
+    };
+    if !keep_context {
+        for highlight in library.books.iter_mut().flat_map(|b| &mut b.highlights) {
+            highlight.context = None;
+        }
+    }
+
+    // Summary
+    let total_highlights = library.highlight_count();
+    let kindle_count = library
+        .books
+        .iter()
+        .filter(|b| b.sources.contains(&Source::Kindle))
+        .count();
+    let apple_count = library
+        .books
+        .iter()
+        .filter(|b| b.sources.contains(&Source::AppleBooks))
+        .count();
+
+    if spec.is_empty() {
+        eprintln!(
+            "\nExported {} books ({} Kindle, {} Apple Books) with {} total highlights",
+            library.books.len(),
+            kindle_count,
+            apple_count,
+            total_highlights
+        );
+    } else {
+        eprintln!(
+            "\nExported {} of {} books ({} Kindle, {} Apple Books) with {} of {} total highlights",
+            library.books.len(),
+            full_library.books.len(),
+            kindle_count,
+            apple_count,
+            total_highlights,
+            full_library.highlight_count()
+        );
+    }
+
+    // Ensure output directory exists
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Write output
+    match args.format {
+        OutputFormat::Json => {
+            if args.canonical {
+                reuse_exported_at(&mut library, output_path);
+            }
+            fs::write(output_path, to_json(&library, args.pretty)?)?;
+        }
+        OutputFormat::Epub => {
+            fs::write(output_path, export::to_epub(&library)?)?;
+        }
+        OutputFormat::Analytics => {
+            export::to_analytics(&library, output_path)?;
+        }
+        OutputFormat::Tiddlywiki => {
+            let tiddlers = export::to_tiddlywiki(&library);
+            fs::write(output_path, to_json(&tiddlers, args.pretty)?)?;
+        }
+        OutputFormat::Journal => {
+            let tz = args.timezone.unwrap_or(chrono_tz::UTC);
+            fs::write(output_path, export::to_journal(&library, tz))?;
+        }
+        OutputFormat::Log => {
+            let tz = args.timezone.unwrap_or(chrono_tz::UTC);
+            fs::write(output_path, export::to_reading_log(&library, tz))?;
+        }
+        OutputFormat::Textbundle => {
+            export::to_textbundles(&library, output_path)?;
+        }
+        OutputFormat::Clippings => {
+            fs::write(output_path, export::to_clippings(&library))?;
+        }
+        OutputFormat::Graphml => {
+            fs::write(output_path, export::to_graphml(&library))?;
+        }
+        OutputFormat::Notion => {
+            export::to_notion(&library, output_path)?;
+        }
+        OutputFormat::Quotes => {
+            let options = export::QuoteOptions {
+                min_chars: args.quotes.min_chars,
+                max_chars: args.quotes.max_chars,
+                seed: args.quotes.seed,
+                complete_sentences: args.quotes.complete_sentences,
+            };
+            let quotes = export::to_quotes(&library, &options);
+            fs::write(output_path, to_json(&quotes, args.pretty)?)?;
+        }
+    }
+
+    eprintln!("Written to {}", output_path.display());
+
+    // Incremental Apple Books passes apply their changes to this file, which now holds
+    // only part of the library (or isn't one); the next pass has to read everything
+    let complete = args.format == OutputFormat::Json && !args.no_merge_existing && spec.is_empty();
+    if !complete && (args.format == OutputFormat::Json || output_path.extension() == Some("json".as_ref())) {
+        apple_books::incremental::invalidate(output_path)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a window size such as "1280x900"
+fn parse_window_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, e.g. 1280x900, got {:?}", value))?;
+    let parse = |n: &str| n.trim().parse::<u32>().map_err(|e| format!("invalid window size {:?}: {}", value, e));
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_concurrency(value: &str) -> Result<usize, String> {
+    let max = kindle::concurrent::MAX_CONCURRENCY;
+    match value.parse::<usize>() {
+        Ok(n) if (1..=max).contains(&n) => Ok(n),
+        _ => Err(format!("expected a number from 1 to {}, got {:?}", max, value)),
+    }
+}
+
+/// Parse a date filter bound, either RFC3339 or a bare YYYY-MM-DD in the given timezone
+fn parse_date_bound(value: &str, tz: chrono_tz::Tz, time: NaiveTime) -> Result<DateTime<Utc>, Error> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| tz.from_local_datetime(&date.and_time(time)).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| ConfigError::InvalidValue(format!("Invalid date: {}", value)).into())
+}
+
+/// Last representable instant of a day
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap()
+}
+
+/// Reuse the previous export's timestamp when its books are unchanged
+fn reuse_exported_at(library: &mut Library, path: &Path) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    let Ok(previous) = serde_json::from_str::<Library>(&content) else {
+        return;
+    };
+
+    if serde_json::to_value(&previous.books).ok() == serde_json::to_value(&library.books).ok() {
+        library.exported_at = previous.exported_at;
+    }
+}
+
+/// Serialize a value to JSON, optionally pretty-printed
+fn to_json<T: serde::Serialize>(value: &T, pretty: bool) -> Result<String, Error> {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)?
+    } else {
+        serde_json::to_string(value)?
+    };
+    Ok(json)
+}
+
+/// Whether `--format` was given on the command line rather than left at its default
+fn format_given(matches: &clap::ArgMatches) -> bool {
+    let given = |m: &clap::ArgMatches| m.value_source("format") == Some(clap::parser::ValueSource::CommandLine);
+    given(matches) || matches.subcommand().is_some_and(|(_, sub)| given(sub))
+}
+
+/// A row of `kindle --list-only`
+#[derive(serde::Serialize)]
+struct ListedBook {
+    #[serde(flatten)]
+    entry: kindle::NotebookEntry,
+    /// Highlights as of the last sync, if the book was synced before
+    highlights: Option<usize>,
+    /// Whether the "last annotated" date differs from the last sync, so a sync would scrape it
+    changed: bool,
+}
+
+/// Log in to, check or delete the persisted Kindle browser session
+fn run_kindle_session(session: &KindleSession, args: &KindleSyncArgs, config: &Config) -> Result<(), Error> {
+    let settings = &config.kindle.browser;
+    let user_data_dir = args.user_data_dir.clone().or_else(|| settings.user_data_dir.clone());
+
+    // A WebDriver browser only keeps a login in a profile it was given
+    if args.backend == BrowserBackend::Webdriver && user_data_dir.is_none() {
+        if let KindleSession::Logout { .. } = session {
+            println!("No stored session: the WebDriver backend only keeps one in a --user-data-dir profile");
+            return Ok(());
+        }
+        return Err(KindleError::SessionNotPersisted.into());
+    }
+    let profile_dir = user_data_dir.unwrap_or_else(kindle::browser::default_profile_dir);
+
+    match session {
+        KindleSession::Login => {
+            let browser_config = kindle::BrowserConfig {
+                headless: false,
+                interactive: true,
+                ..args.browser_config(&config.kindle)?
+            };
+            let scraper = kindle::KindleBrowserScraper::with_session_persistence(browser_config)?;
+            scraper.login()?;
+            println!("Logged in; session stored in {}", profile_dir.display());
+        }
+        KindleSession::Status => {
+            // Never prompt: a sign-in page means the session is gone
+            let browser_config = kindle::BrowserConfig {
+                headless: true,
+                interactive: false,
+                ..args.browser_config(&config.kindle)?
+            };
+            let scraper = kindle::KindleBrowserScraper::with_session_persistence(browser_config)?;
+            scraper.login()?;
+            println!("Kindle session is active ({})", profile_dir.display());
+        }
+        KindleSession::Logout { yes } => {
+            if !profile_dir.exists() {
+                println!("No stored session at {}", profile_dir.display());
+                return Ok(());
+            }
+            if !yes {
+                eprint!("Delete the stored Kindle session in {}? [y/N] ", profile_dir.display());
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                    println!("Kept the stored session");
+                    return Ok(());
+                }
+            }
+            fs::remove_dir_all(&profile_dir)?;
+            println!("Deleted the stored session in {}", profile_dir.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// List the Kindle library with `--list-only`, without opening any book
+fn run_kindle_list(args: &KindleSyncArgs, config: &Config, json: bool) -> Result<(), Error> {
+    let browser_config = args.browser_config(&config.kindle)?;
+    let scraper = kindle::KindleBrowserScraper::with_session_persistence(browser_config)?;
+    let (entries, unmatched) = scraper.list_books(&args.selection())?;
+
+    for pattern in &unmatched {
+        eprintln!("--only {:?} matched no book in the Kindle library", pattern);
+    }
+
+    let state = kindle::sync_state::SyncState::load(&kindle::sync_state::default_state_path())?;
+    let books: Vec<ListedBook> = entries
+        .into_iter()
+        .map(|entry| ListedBook {
+            highlights: state.books.get(&entry.asin).map(|s| s.book.highlights.len()),
+            changed: state.unchanged(&entry.asin, entry.last_annotated.as_deref()).is_none(),
+            entry,
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&books)?);
+        return Ok(());
+    }
+
+    let width = |cells: &mut dyn Iterator<Item = usize>, header: &str| cells.max().unwrap_or(0).max(header.len());
+    let title_width = width(&mut books.iter().map(|b| b.entry.title.chars().count().min(50)), "Title");
+    let author_width = width(&mut books.iter().map(|b| b.entry.author.as_deref().unwrap_or("").chars().count().min(30)), "Author");
+
+    println!(
+        "{:<tw$}  {:<aw$}  {:<10}  {:<20}  {:>10}",
+        "Title", "Author", "ASIN", "Last annotated", "Highlights",
+        tw = title_width,
+        aw = author_width
+    );
+    for book in &books {
+        let highlights = match book.highlights {
+            Some(count) if book.changed => format!("{}*", count),
+            Some(count) => count.to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<tw$}  {:<aw$}  {:<10}  {:<20}  {:>10}",
+            truncate(&book.entry.title, 50),
+            truncate(book.entry.author.as_deref().unwrap_or(""), 30),
+            book.entry.asin,
+            book.entry.last_annotated.as_deref().unwrap_or(""),
+            highlights,
+            tw = title_width,
+            aw = author_width
+        );
+    }
+
+    let changed = books.iter().filter(|b| b.changed).count();
+    eprintln!("\n{} books, {} annotated since the last sync (* = count from the last sync)", books.len(), changed);
+    Ok(())
+}
+
+/// Shorten text to at most `max` characters, ending in "…" when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+/// Run Kindle browser-based sync, returning the books and how many failed to scrape
+fn run_kindle_browser_sync(
+    args: &KindleSyncArgs,
+    config: &Config,
+    report_path: Option<&Path>,
+    verbose: bool,
+) -> Result<(Vec<readingsync::Book>, usize), Error> {
+    eprintln!("Starting Kindle sync via browser...");
+
+    let scraper = kindle::KindleBrowserScraper::with_session_persistence(args.browser_config(&config.kindle)?)
+        .map_err(Error::Kindle)?;
+
+    run_kindle_sync_cycle(&scraper, args, report_path, verbose, None)
+}
+
+/// List the books a Kindle scrape gave up on
+fn print_failed_books(failed: &[kindle::retry::FailedBook]) {
+    eprintln!("\nFailed to scrape {} books:", failed.len());
+    for book in failed {
+        eprintln!(
+            "  - {} ({}) after {} attempts: {}",
+            book.title, book.asin, book.attempts, book.error
+        );
+    }
+}
+
+/// One incremental sync with an open browser, saving the sync state and report
+///
+/// Returns the books and how many failed. `stop` ends the sync after the books in
+/// progress.
+fn run_kindle_sync_cycle(
+    scraper: &kindle::KindleBrowserScraper,
+    args: &KindleSyncArgs,
+    report_path: Option<&Path>,
+    verbose: bool,
+    stop: Option<&AtomicBool>,
+) -> Result<(Vec<readingsync::Book>, usize), Error> {
+    // Each scraped book is checkpointed so an interrupted sync can be resumed
+    let checkpoint_path = kindle::checkpoint::default_checkpoint_path();
+    let mut checkpoint = match kindle::checkpoint::Checkpoint::pending(&checkpoint_path) {
+        Some(saved) if args.resume => {
+            eprintln!("Resuming interrupted sync with {} books already scraped", saved);
+            kindle::checkpoint::Checkpoint::resume(&checkpoint_path)?
+        }
+        Some(saved) => {
+            eprintln!(
+                "An interrupted sync left {} scraped books; starting over (pass --resume to continue it)",
+                saved
+            );
+            kindle::checkpoint::Checkpoint::create(&checkpoint_path)?
+        }
+        None => {
+            if args.resume {
+                eprintln!("No interrupted sync to resume; starting a new one");
+            }
+            kindle::checkpoint::Checkpoint::create(&checkpoint_path)?
+        }
+    };
+
+    // Books not annotated since the last sync are reused from the state file
+    let state_path = kindle::sync_state::default_state_path();
+    let mut state = if args.full {
+        kindle::sync_state::SyncState::default()
+    } else {
+        kindle::sync_state::SyncState::load(&state_path)?
+    };
+
+    let mut observer = kindle::progress::default_observer(verbose);
+    let options = kindle::ScrapeOptions {
+        state: Some(&mut state),
+        checkpoint: Some(&mut checkpoint),
+        selection: args.selection(),
+        stop,
+    };
+    let outcome = scraper.scrape_with(options, observer.as_mut()).map_err(Error::Kindle)?;
+
+    for pattern in &outcome.unmatched {
+        eprintln!("--only {:?} matched no book in the Kindle library", pattern);
+    }
+
+    state.save(&state_path)?;
+
+    if let (Some(path), Some(report)) = (report_path, &outcome.report) {
+        report.save(path)?;
+        if verbose {
+            eprintln!("Wrote scrape report to {}", path.display());
+        }
+    }
+
+    // Keep the checkpoint while books are missing so --resume can retry just those
+    if outcome.failed.is_empty() {
+        checkpoint.finish()?;
+    } else {
+        print_failed_books(&outcome.failed);
+        eprintln!("Run again with --resume to retry only those");
+    }
+
+    let truncated: Vec<&str> = outcome.books.iter().filter(|b| b.truncated).map(|b| b.title.as_str()).collect();
+    if !truncated.is_empty() {
+        eprintln!("\nAmazon shows only some highlights for {} books (publisher limit):", truncated.len());
+        for title in &truncated {
+            eprintln!("  - {}", title);
+        }
+        eprintln!(
+            "Import My Clippings.txt from the device with `readingsync clippings <PATH>` into the same output to fill the gaps"
+        );
+    }
+
+    if verbose {
+        let highlight_count: usize = outcome.books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", outcome.books.len(), highlight_count);
+        if outcome.resumed > 0 {
+            eprintln!("  {} books taken from the interrupted sync", outcome.resumed);
+        }
+    }
+    if outcome.skipped > 0 {
+        eprintln!("skipped {} unchanged books (use --full to re-scrape them)", outcome.skipped);
+    }
+
+    Ok((outcome.books, outcome.failed.len()))
+}
+
+/// Set once SIGINT or SIGTERM arrives during `kindle --watch`
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Turn SIGINT and SIGTERM into a request to stop, so the browser can be closed
+/// cleanly instead of dying with the process and leaving its profile locked
+///
+/// The handler puts the default back, so a second Ctrl-C still stops at once.
+#[cfg(unix)]
+fn install_shutdown_handler() {
+    extern "C" fn request_shutdown(signal: libc::c_int) {
+        SHUTDOWN.store(true, Ordering::SeqCst);
+        // SAFETY: signal() is async-signal-safe
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+
+    // SAFETY: the handler only stores to an atomic and calls signal()
+    unsafe {
+        libc::signal(libc::SIGINT, request_shutdown as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, request_shutdown as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_shutdown_handler() {}
+
+/// Print a line prefixed with the local time, for logs of long-running syncs
+fn log_line(message: &str) {
+    eprintln!("[{}] {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+/// How often the browser is pinged between syncs, which also keeps its connection,
+/// which drops after 30 idle seconds, open
+const WATCH_KEEPALIVE: Duration = Duration::from_secs(10);
+
+/// Sync the Kindle library every `--interval` with one long-lived browser
+///
+/// Each cycle is an incremental scrape merged into the output like a normal run. A
+/// browser that stops answering is relaunched with the same profile before the next
+/// cycle, and a failed cycle is logged and retried on schedule. SIGINT or SIGTERM
+/// finishes the books in progress, writes what was synced and closes the browser.
+fn run_kindle_watch(
+    kindle_args: &KindleSyncArgs,
+    config: &Config,
+    args: &Args,
+    output_path: &Path,
+    spec: &FilterSpec,
+) -> Result<(), Error> {
+    install_shutdown_handler();
+    let report_path = kindle_args.report.then(|| kindle::report::report_path(output_path));
+    log_line(&format!("Syncing the Kindle library every {}; stop with Ctrl-C", format_interval(kindle_args.interval)));
+
+    let mut scraper: Option<kindle::KindleBrowserScraper> = None;
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        if scraper.as_ref().is_some_and(|s| !s.is_alive()) {
+            log_line("The browser stopped responding; relaunching it");
+            scraper = None;
+        }
+        if scraper.is_none() {
+            match kindle::KindleBrowserScraper::with_session_persistence(kindle_args.browser_config(&config.kindle)?) {
+                Ok(launched) => scraper = Some(launched),
+                Err(e) => log_line(&format!("Could not launch the browser: {}", e)),
+            }
+        }
+
+        if let Some(current) = &scraper {
+            let cycle = run_kindle_sync_cycle(current, kindle_args, report_path.as_deref(), args.verbose, Some(&SHUTDOWN))
+                .and_then(|(books, failed)| {
+                    let highlights: usize = books.iter().map(|b| b.highlights.len()).sum();
+                    let count = books.len();
+                    write_library(books, args, output_path, spec, &config.http)?;
+                    Ok((count, highlights, failed))
+                });
+            match cycle {
+                Ok((books, highlights, 0)) => {
+                    log_line(&format!("Synced {} books with {} highlights", books, highlights));
+                }
+                Ok((books, highlights, failed)) => log_line(&format!(
+                    "Synced {} books with {} highlights; {} failed",
+                    books, highlights, failed
+                )),
+                Err(e) => log_line(&format!("Sync failed: {}", e)),
+            }
+        }
+
+        // Sleep until the next cycle, a little at a time so a signal is noticed
+        let next = Instant::now() + kindle_args.interval;
+        let mut last_ping = Instant::now();
+        while !SHUTDOWN.load(Ordering::SeqCst) && Instant::now() < next {
+            std::thread::sleep(Duration::from_secs(1).min(next.saturating_duration_since(Instant::now())));
+            if last_ping.elapsed() >= WATCH_KEEPALIVE {
+                last_ping = Instant::now();
+                if scraper.as_ref().is_some_and(|s| !s.is_alive()) {
+                    log_line("The browser stopped responding; it will be relaunched for the next sync");
+                    scraper = None;
+                }
+            }
+        }
+    }
+
+    // Dropping the scraper asks Chrome to close
+    drop(scraper);
+    log_line("Stopped; the browser is closed");
+    Ok(())
+}
+
+/// Parse a duration such as "90s", "30m", "6h" or "1d"
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a duration such as 30m, 6h or 1d, got {:?}", value))?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit in {:?}; use s, m, h or d", value)),
+    };
+    if number == 0 {
+        return Err("the interval must be longer than 0".to_string());
+    }
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Render an interval the way it is usually given, e.g. "6h"
+fn format_interval(interval: Duration) -> String {
+    let seconds = interval.as_secs();
+    [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m")]
+        .iter()
+        .find(|(unit, _)| seconds.is_multiple_of(*unit))
+        .map(|(unit, suffix)| format!("{}{}", seconds / unit, suffix))
+        .unwrap_or_else(|| format!("{}s", seconds))
+}
+
+/// Where to look for the Apple Books databases: `[apple_books]` patterns, then the built-in locations
+fn apple_books_search(config: &Config) -> apple_books::DatabaseSearch {
+    apple_books::DatabaseSearch::with_extra(
+        &config.apple_books.library_db_patterns,
+        &config.apple_books.annotation_db_patterns,
+    )
+}
+
+/// Print the Apple Books database candidates for `apple-books --list-databases`
+fn run_apple_books_list(config: &Config) {
+    let search = apple_books_search(config);
+    let databases = [
+        ("Library", config.apple_books.library_db.as_ref(), &search.library),
+        ("Annotation", config.apple_books.annotation_db.as_ref(), &search.annotation),
+    ];
+
+    for (name, configured, patterns) in databases {
+        println!("{} databases:", name);
+        if let Some(path) = configured {
+            println!("  * {} (set in config)", path.display());
+        }
+        let candidates = apple_books::find_candidates(patterns);
+        for pattern in patterns {
+            println!("  {}", pattern);
+            let matched: Vec<_> = candidates.iter().filter(|c| &c.pattern == pattern).collect();
+            if matched.is_empty() {
+                println!("      (nothing found)");
+            }
+            for candidate in matched {
+                // Every match is read, unless the config names a file
+                let chosen = configured.is_none();
+                let modified = candidate
+                    .modified
+                    .map(|time| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                println!(
+                    "    {} {} (modified {})",
+                    if chosen { "*" } else { " " },
+                    candidate.path.display(),
+                    modified
+                );
+            }
+        }
+    }
+}
+
+/// Run Apple Books export
+///
+/// With a state path and a previous export, only annotations changed since the last run
+/// are read and applied to that export, unless `full`. Returns the state to save once
+/// the output is written.
+fn run_apple_books_export(
+    mut options: apple_books::AppleBooksOptions,
+    state_path: Option<&Path>,
+    output_path: &Path,
+    full: bool,
+    verbose: bool,
+) -> Result<(Vec<readingsync::Book>, Option<apple_books::incremental::IncrementalState>), Error> {
+    use apple_books::incremental::{self, IncrementalState};
+
+    if verbose {
+        match &options.ios_backup {
+            Some(backup) => eprintln!("Extracting Apple Books from backup {}...", backup.display()),
+            None => eprintln!("Extracting from Apple Books..."),
+        }
+    }
+
+    let previous_state = match state_path {
+        Some(path) if !full && output_path.exists() => IncrementalState::load(path)?,
+        _ => None,
+    };
+    options.modified_after = previous_state.as_ref().map(|state| state.last_modified);
+    let mut extracted = apple_books::extract(&options)?;
+    // Another database than last time (e.g. a different Mac's library) needs a full pass
+    if let Some(state) = &previous_state {
+        if extracted.report.incremental && extracted.report.annotation_dbs.first() != Some(&state.annotation_db) {
+            options.modified_after = None;
+            extracted = apple_books::extract(&options)?;
+        }
+    }
+    // Unchanged books come from the previous export; one it lacks needs a full pass
+    if extracted.report.incremental {
+        let previous = Library::load(output_path)?.books;
+        let delta = std::mem::take(&mut extracted.books);
+        match incremental::apply_delta(&previous, delta, options.include_deleted) {
+            Some(books) => extracted.books = books,
+            None => {
+                if verbose {
+                    eprintln!("Changed annotations belong to books not in the previous export; reading all");
+                }
+                options.modified_after = None;
+                extracted = apple_books::extract(&options)?;
+            }
+        }
+    }
+    let apple_books::AppleBooksExtract { books, report } = extracted;
+
+    if report.orphaned > 0 {
+        eprintln!(
+            "Note: {} highlights belong to books with no title in the Apple Books library (e.g. in iCloud but not downloaded); they are exported under \"Unknown book (<asset ID>)\"",
+            report.orphaned
+        );
+    }
 
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Sync highlights from Kindle via browser (recommended)
-    #[command(name = "kindle")]
-    KindleSync {
-        /// Amazon region: us, uk, de, fr, jp, etc.
-        #[arg(long, default_value = "us")]
-        region: String,
+    if verbose {
+        for (name, paths) in [("Library", &report.library_dbs), ("Annotations", &report.annotation_dbs)] {
+            for path in paths {
+                eprintln!("{} database: {}", name, path.display());
+            }
+        }
+        if let Some(since) = options.modified_after.filter(|_| report.incremental) {
+            eprintln!("Reading annotations changed since {} (use --full to read all)", since.with_timezone(&Local));
+        }
+        eprintln!(
+            "Read {} annotations ({} deleted); skipped {} from samples, {} empty and {} found in a newer database",
+            report.annotations,
+            report.deleted,
+            report.skipped_samples,
+            report.skipped_empty,
+            report.duplicate_annotations
+        );
+    }
 
-        /// Run browser in headless mode (no visible window)
-        #[arg(long)]
-        headless: bool,
-    },
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
 
-    /// Export from Apple Books only
-    #[command(name = "apple-books")]
-    AppleBooks,
+    // A pass that found no changes keeps the last date
+    let carried = previous_state.filter(|_| report.incremental).map(|state| state.last_modified);
+    let state = state_path.and_then(|_| {
+        Some(IncrementalState {
+            // The newest, which is the only one read incrementally
+            annotation_db: report.annotation_dbs.into_iter().next()?,
+            last_modified: report.latest_modification.max(carried)?,
+        })
+    });
 
-    /// Legacy: use My Clippings.txt file from Kindle device
-    #[command(name = "clippings")]
-    Clippings {
-        /// Path to My Clippings.txt file
-        path: PathBuf,
-    },
+    Ok((books, state))
 }
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+/// Run My Clippings.txt import
+fn run_clippings_import(
+    paths: &[PathBuf],
+    timezone: chrono_tz::Tz,
+    title_aliases: &BTreeMap<String, String>,
+    strict: bool,
+    show_warnings: bool,
+    verbose: bool,
+) -> Result<Vec<readingsync::Book>, Error> {
+    let files = kindle::clippings::clippings_files(paths)?;
+    let mut book_lists = Vec::new();
+    let mut skipped = 0;
+    let mut partial = 0;
+
+    for file in &files {
+        if verbose {
+            eprintln!("Parsing Kindle clippings from {}...", file.display());
+        }
+
+        let report = kindle::clippings::parse_clippings_report(file, timezone)?;
+
+        if strict {
+            if let Some(warning) = report.warnings.first() {
+                return Err(KindleError::ClippingsParseError(format!("{}: {}", file.display(), warning)).into());
+            }
+        }
+
+        for warning in &report.warnings {
+            if show_warnings {
+                eprintln!("{}: {}", file.display(), warning);
+            }
+            if warning.skipped {
+                skipped += 1;
+            } else {
+                partial += 1;
+            }
+        }
+
+        if verbose && report.duplicates > 0 {
+            eprintln!("  dropped {} duplicate entries", report.duplicates);
+        }
+
+        let books = report.books;
+
+        if verbose {
+            let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+            eprintln!("  {} books with {} highlights", books.len(), highlight_count);
+        }
+
+        book_lists.push(books);
+    }
+
+    if !show_warnings && skipped + partial > 0 {
+        eprintln!(
+            "skipped {} malformed entries ({} more partly parsed), run with --show-warnings for details",
+            skipped, partial
+        );
+    }
+
+    let mut books = kindle::clippings::apply_title_aliases(merge::merge_books(book_lists), title_aliases);
+
+    // Adjusting a highlight on the device writes a new clipping for it
+    let removed = merge::dedupe_overlaps(&mut books);
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+        if removed > 0 {
+            eprintln!("  collapsed {} re-highlighted passages", removed);
+        }
+    }
+
+    for book in books.iter().filter(|b| b.unavailable_highlights > 0) {
+        eprintln!(
+            "{} highlights unavailable due to clipping limit for {}",
+            book.unavailable_highlights, book.title
+        );
     }
+
+    Ok(books)
 }
 
-fn run() -> Result<(), Error> {
-    let args = Args::parse();
+/// Run Kobo database import
+fn run_kobo_import(path: Option<PathBuf>, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Extracting from Kobo...");
+    }
 
-    // Load config
-    let config = Config::load_default();
+    let books = kobo::extract_full(path).map_err(Error::Kobo)?;
 
-    // Determine output path
-    let output_path = args.output.unwrap_or_else(|| {
-        dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("readingsync")
-            .join("library.json")
-    });
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
 
-    if args.verbose {
-        eprintln!("Output path: {}", output_path.display());
+    Ok(books)
+}
+
+/// Run Google Play Books Takeout import
+fn run_play_books_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Play Books notes from: {}", path.display());
     }
 
-    // Handle commands
-    let books = match args.command {
-        Some(Commands::KindleSync { region, headless }) => {
-            run_kindle_browser_sync(&region, headless, args.verbose)?
-        }
-        Some(Commands::AppleBooks) => {
-            run_apple_books_export(&config, args.verbose)?
-        }
-        Some(Commands::Clippings { path }) => {
-            run_clippings_import(&path, args.verbose)?
+    let books = play_books::extract_full(path).map_err(Error::PlayBooks)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run Pocket import from an export file or the API
+fn run_pocket_import(
+    path: Option<PathBuf>,
+    consumer_key: Option<String>,
+    access_token: Option<String>,
+    include_unannotated: bool,
+    http: &HttpConfig,
+    verbose: bool,
+) -> Result<Vec<readingsync::Book>, Error> {
+    let books = match (path, consumer_key, access_token) {
+        (Some(path), _, _) => {
+            if verbose {
+                eprintln!("Importing Pocket export: {}", path.display());
+            }
+            pocket::parse_export(&path, include_unannotated)?
         }
-        None => {
-            // Default: run Kindle browser sync
-            eprintln!("No command specified. Running Kindle sync...");
-            eprintln!("(Use --help to see all options)\n");
-            run_kindle_browser_sync("us", false, args.verbose)?
+        (None, Some(consumer_key), Some(access_token)) => {
+            if verbose {
+                eprintln!("Fetching from the Pocket API...");
+            }
+            pocket::fetch_api(&consumer_key, &access_token, include_unannotated, http)?
         }
+        _ => return Err(Error::Pocket(PocketError::MissingCredentials)),
     };
 
-    // Create library
-    let library = Library {
-        exported_at: Utc::now(),
-        books,
-    };
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} articles with {} highlights", books.len(), highlight_count);
+    }
 
-    // Summary
-    let total_highlights: usize = library.books.iter().map(|b| b.highlights.len()).sum();
-    let kindle_count = library
-        .books
-        .iter()
-        .filter(|b| b.sources.contains(&Source::Kindle))
-        .count();
-    let apple_count = library
-        .books
-        .iter()
-        .filter(|b| b.sources.contains(&Source::AppleBooks))
-        .count();
+    Ok(books)
+}
 
-    eprintln!(
-        "\nExported {} books ({} Kindle, {} Apple Books) with {} total highlights",
-        library.books.len(),
-        kindle_count,
-        apple_count,
-        total_highlights
-    );
+/// Run Instapaper CSV import
+fn run_instapaper_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Instapaper highlights from: {}", path.display());
+    }
 
-    // Ensure output directory exists
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent)?;
+    let books = instapaper::parse_csv(path)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} articles with {} highlights", books.len(), highlight_count);
     }
 
-    // Write output
-    let json = if args.pretty {
-        serde_json::to_string_pretty(&library)?
+    Ok(books)
+}
+
+/// Run KOReader sidecar import
+fn run_koreader_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Searching for KOReader metadata in: {}", path.display());
+    }
+
+    let books = koreader::extract_full(path)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run Moon+ Reader annotation import
+fn run_moon_reader_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Moon+ Reader annotations from: {}", path.display());
+    }
+
+    let import = moon_reader::extract(path)?;
+
+    if import.skipped > 0 {
+        eprintln!("Warning: skipped {} unrecognized annotation records", import.skipped);
+    }
+
+    if verbose {
+        let highlight_count: usize = import.books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", import.books.len(), highlight_count);
+    }
+
+    Ok(import.books)
+}
+
+/// Run Calibre library import
+fn run_calibre_import(library: Option<PathBuf>, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    let library = library
+        .or_else(calibre::default_library_path)
+        .unwrap_or_else(|| PathBuf::from("Calibre Library"));
+
+    if verbose {
+        eprintln!("Extracting from Calibre library: {}", library.display());
+    }
+
+    let books = calibre::extract(&library)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run the cookie-based Kindle notebook scraper, returning the books and how many failed
+fn run_kindle_cookies_sync(
+    source: &kindle::scraper::CookieSource,
+    region: Option<String>,
+    scrape_config: &kindle::scraper::LegacyScrapeConfig,
+    config: &Config,
+    report_path: Option<&Path>,
+    verbose: bool,
+) -> Result<(Vec<readingsync::Book>, usize), Error> {
+    let code = region.as_deref().unwrap_or(&config.kindle.region);
+    let region = if code.eq_ignore_ascii_case(AUTO_REGION) {
+        None
     } else {
-        serde_json::to_string(&library)?
+        Some(kindle::scraper::LegacyAmazonRegion::from_code(code)?)
     };
 
-    fs::write(&output_path, json)?;
+    if verbose {
+        let notebook = match &region {
+            Some(region) => format!("the Kindle notebook ({})", region.notebook_url),
+            None => "the Kindle notebook".to_string(),
+        };
+        match source {
+            kindle::scraper::CookieSource::File(path) => {
+                eprintln!("Fetching {} with cookies from {}", notebook, path.display())
+            }
+            kindle::scraper::CookieSource::Browser(browser) => {
+                eprintln!("Fetching {} with {}'s cookies", notebook, browser.name())
+            }
+        }
+    }
 
-    eprintln!("Written to {}", output_path.display());
+    let mut observer = kindle::progress::default_observer(verbose);
+    let outcome = kindle::scraper::scrape_notebook(source, region.as_ref(), scrape_config, observer.as_mut())?;
 
-    Ok(())
+    if let (Some(path), Some(report)) = (report_path, &outcome.report) {
+        report.save(path)?;
+        if verbose {
+            eprintln!("Wrote scrape report to {}", path.display());
+        }
+    }
+
+    if !outcome.failed.is_empty() {
+        print_failed_books(&outcome.failed);
+    }
+
+    if verbose {
+        let highlight_count: usize = outcome.books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", outcome.books.len(), highlight_count);
+    }
+
+    Ok((outcome.books, outcome.failed.len()))
 }
 
-/// Run Kindle browser-based sync
-fn run_kindle_browser_sync(region: &str, headless: bool, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
-    eprintln!("Starting Kindle sync via browser...");
+/// Run Kindle desktop app local cache import
+fn run_kindle_app_import(
+    path: Option<PathBuf>,
+    catalog: Option<PathBuf>,
+    verbose: bool,
+) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Reading Kindle app local annotations...");
+    }
 
-    let region = kindle::AmazonRegion::from_code(region).map_err(Error::Kindle)?;
+    let import = kindle::local::extract(path, catalog)?;
 
-    let config = kindle::BrowserConfig {
-        headless,
-        region,
-        user_data_dir: None, // Will use default with session persistence
-        timeout_secs: 30,
-    };
+    if import.highlights_without_text > 0 {
+        eprintln!(
+            "Note: {} highlights skipped; the Kindle app doesn't store highlight text locally",
+            import.highlights_without_text
+        );
+    }
+    if import.skipped_records > 0 || import.unreadable_files > 0 {
+        eprintln!(
+            "Warning: skipped {} unrecognized records and {} unreadable files",
+            import.skipped_records, import.unreadable_files
+        );
+    }
+
+    if verbose {
+        let highlight_count: usize = import.books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} notes", import.books.len(), highlight_count);
+    }
+
+    Ok(import.books)
+}
+
+/// Run Hypothesis API import
+fn run_hypothesis_import(
+    token: &str,
+    user: Option<&str>,
+    http: &HttpConfig,
+    verbose: bool,
+) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Fetching annotations from Hypothesis...");
+    }
+
+    let books = hypothesis::fetch(token, user, http)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} documents with {} annotations", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
 
-    let scraper = kindle::KindleBrowserScraper::with_session_persistence(config)
-        .map_err(|e| Error::Kindle(e))?;
+/// Run Readwise CSV import
+fn run_readwise_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Readwise export: {}", path.display());
+    }
 
-    let books = scraper.scrape_all().map_err(Error::Kindle)?;
+    let books = readwise::parse_csv(path)?;
 
     if verbose {
         let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
@@ -171,16 +2186,13 @@ fn run_kindle_browser_sync(region: &str, headless: bool, verbose: bool) -> Resul
     Ok(books)
 }
 
-/// Run Apple Books export
-fn run_apple_books_export(config: &Config, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+/// Run Libby export import
+fn run_libby_import(path: &Path, include_all: bool, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
     if verbose {
-        eprintln!("Extracting from Apple Books...");
+        eprintln!("Importing Libby export: {}", path.display());
     }
 
-    let books = apple_books::extract_full(
-        config.apple_books.library_db.clone(),
-        config.apple_books.annotation_db.clone(),
-    ).map_err(Error::AppleBooks)?;
+    let books = libby::parse_export(path, include_all)?;
 
     if verbose {
         let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
@@ -190,13 +2202,94 @@ fn run_apple_books_export(config: &Config, verbose: bool) -> Result<Vec<readings
     Ok(books)
 }
 
-/// Run My Clippings.txt import
-fn run_clippings_import(path: &PathBuf, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+/// Run Goodreads CSV import
+fn run_goodreads_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Goodreads export: {}", path.display());
+    }
+
+    let books = goodreads::parse_csv(path)?;
+
+    if verbose {
+        let finished_count = books.iter().filter(|b| b.finished == Some(true)).count();
+        eprintln!("Found {} books, {} finished", books.len(), finished_count);
+    }
+
+    Ok(books)
+}
+
+/// Run Bookcision JSON import
+fn run_bookcision_import(path: &Path, verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    if verbose {
+        eprintln!("Importing Bookcision export: {}", path.display());
+    }
+
+    let books = kindle::bookcision::parse_export(path)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run Kindle notebook export import
+fn run_notebook_export_import(paths: &[PathBuf], verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
+    let mut books = Vec::new();
+
+    for path in paths {
+        if verbose {
+            eprintln!("Parsing notebook export: {}", path.display());
+        }
+
+        books.push(vec![kindle::notebook_export::parse_html(path)?]);
+    }
+
+    let books = merge::merge_books(books);
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run mapped annotation JSON import
+fn run_json_import(
+    path: &Path,
+    preset: Option<&str>,
+    mapping: Option<&Path>,
+    verbose: bool,
+) -> Result<Vec<readingsync::Book>, Error> {
+    let mapping = match (preset, mapping) {
+        (_, Some(mapping)) => annotated_json::JsonMapping::from_file(mapping)?,
+        (Some(preset), None) => annotated_json::JsonMapping::preset(preset)?,
+        (None, None) => return Err(ConfigError::InvalidValue("Pass --preset or --mapping".to_string()).into()),
+    };
+
+    if verbose {
+        eprintln!("Importing annotation JSON: {}", path.display());
+    }
+
+    let books = annotated_json::parse_file(path, &mapping)?;
+
+    if verbose {
+        let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
+        eprintln!("Found {} books with {} highlights", books.len(), highlight_count);
+    }
+
+    Ok(books)
+}
+
+/// Run an external import command
+fn run_command_import(command: &[String], verbose: bool) -> Result<Vec<readingsync::Book>, Error> {
     if verbose {
-        eprintln!("Parsing Kindle clippings from {}...", path.display());
+        eprintln!("Running import command: {}", command.join(" "));
     }
 
-    let books = kindle::parse_clippings(path).map_err(Error::Kindle)?;
+    let books = import_cmd::run(command)?;
 
     if verbose {
         let highlight_count: usize = books.iter().map(|b| b.highlights.len()).sum();
@@ -1,15 +1,30 @@
-use crate::model::{Book, Highlight};
+use crate::model::{normalize_author, parse_position_range, Book, Highlight, HighlightKind, Source};
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// How books and their highlights are matched when merging
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeOptions {
+    /// Only merge highlights whose normalized texts are identical, leaving near-duplicates
+    /// from different sources apart (`--strict-dedupe`)
+    pub strict_dedupe: bool,
+}
 
 /// Merge books from multiple sources, deduplicating by book ID and highlight text
 pub fn merge_books(book_lists: Vec<Vec<Book>>) -> Vec<Book> {
+    merge_books_with(book_lists, MergeOptions::default())
+}
+
+/// `merge_books` with the given options
+pub fn merge_books_with(book_lists: Vec<Vec<Book>>, options: MergeOptions) -> Vec<Book> {
     let mut books_by_id: HashMap<String, Book> = HashMap::new();
 
     for books in book_lists {
         for book in books {
             match books_by_id.get_mut(&book.id) {
                 Some(existing) => {
-                    merge_into_book(existing, book);
+                    merge_into_book(existing, book, options);
                 }
                 None => {
                     books_by_id.insert(book.id.clone(), book);
@@ -21,13 +36,59 @@ pub fn merge_books(book_lists: Vec<Vec<Book>>) -> Vec<Book> {
     let mut books: Vec<Book> = books_by_id.into_values().collect();
 
     // Sort books by title
-    books.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+    books.sort_by_key(|b| b.title.to_lowercase());
 
     books
 }
 
+/// Flag highlights in `previous` that a fresh scrape no longer has
+///
+/// Only books present in `fresh` are compared, and within them only highlights whose
+/// sources the fresh books all came from, so books and sources the run didn't touch are
+/// left alone. A previous book is compared with the fresh book with its ID and with any
+/// by the same author titled like one of its `aliases`, since `fuzzy_merge` kept only
+/// one of their IDs. Books the source showed only partly (`truncated`) are skipped too.
+/// Flagged highlights stay in the library when `previous` is merged; one that turns up
+/// again is unflagged by the merge. Returns how many highlights were newly flagged.
+pub fn mark_deleted_upstream(fresh: &[Book], previous: &mut [Book]) -> usize {
+    let fresh_by_id: HashMap<&str, &Book> = fresh.iter().map(|book| (book.id.as_str(), book)).collect();
+    let mut fresh_by_title: HashMap<&str, Vec<&Book>> = HashMap::new();
+    for book in fresh {
+        fresh_by_title.entry(book.title.as_str()).or_default().push(book);
+    }
+    let author_key = |book: &Book| book.author.as_deref().map(|a| normalize_author(a).to_lowercase());
+    let mut flagged = 0;
+
+    for book in previous {
+        let mut fresh_books: Vec<&Book> = fresh_by_id.get(book.id.as_str()).copied().into_iter().collect();
+        for alias in &book.aliases {
+            let by_alias = fresh_by_title.get(alias.as_str()).into_iter().flatten();
+            fresh_books.extend(by_alias.filter(|b| b.id != book.id && author_key(b) == author_key(book)));
+        }
+
+        let sources: Vec<&Source> = fresh_books.iter().filter(|b| !b.truncated).flat_map(|b| &b.sources).collect();
+        if sources.is_empty() {
+            continue;
+        }
+
+        let present: HashSet<String> = fresh_books.iter().flat_map(|b| &b.highlights).map(dedup_key).collect();
+        for highlight in &mut book.highlights {
+            if highlight.deleted_upstream
+                || !highlight.sources.iter().all(|s| sources.contains(&s))
+                || present.contains(&dedup_key(highlight))
+            {
+                continue;
+            }
+            highlight.deleted_upstream = true;
+            flagged += 1;
+        }
+    }
+
+    flagged
+}
+
 /// Merge a book into an existing book entry
-fn merge_into_book(existing: &mut Book, other: Book) {
+fn merge_into_book(existing: &mut Book, other: Book, options: MergeOptions) {
     // Merge sources
     for source in other.sources {
         if !existing.sources.contains(&source) {
@@ -35,6 +96,59 @@ fn merge_into_book(existing: &mut Book, other: Book) {
         }
     }
 
+    // IDs match "Fitzgerald, F. Scott" with "F. Scott Fitzgerald"; show the latter
+    if other.author.as_deref().is_some_and(|author| is_nicer_author(author, existing.author.as_deref())) {
+        existing.author = other.author;
+    }
+
+    if existing.url.is_none() {
+        existing.url = other.url;
+    }
+
+    if existing.isbn.is_none() {
+        existing.isbn = other.isbn;
+    }
+
+    if existing.original_title.is_none() {
+        existing.original_title = other.original_title;
+    }
+
+    for alias in other.aliases {
+        if alias != existing.title && !existing.aliases.contains(&alias) {
+            existing.aliases.push(alias);
+        }
+    }
+
+    if existing.cover_url.is_none() {
+        existing.cover_url = other.cover_url;
+    }
+
+    if existing.document_type.is_none() {
+        existing.document_type = other.document_type;
+    }
+
+    if existing.language.is_none() {
+        existing.language = other.language;
+    }
+
+    if existing.genre.is_none() {
+        existing.genre = other.genre;
+    }
+
+    for (source, id) in other.source_ids {
+        existing.source_ids.entry(source).or_insert(id);
+    }
+
+    for collection in other.collections {
+        if !existing.collections.contains(&collection) {
+            existing.collections.push(collection);
+        }
+    }
+
+    // Snapshots of the same clippings file repeat placeholders, so don't add them up
+    existing.unavailable_highlights = existing.unavailable_highlights.max(other.unavailable_highlights);
+    existing.truncated |= other.truncated;
+
     // Merge finished status (true from any source wins)
     if other.finished == Some(true) {
         existing.finished = Some(true);
@@ -49,71 +163,590 @@ fn merge_into_book(existing: &mut Book, other: Book) {
         _ => {}
     }
 
-    // Merge highlights, deduplicating by text
-    let existing_texts: HashSet<String> = existing
-        .highlights
-        .iter()
-        .map(|h| normalize_text(&h.text))
-        .collect();
+    // Furthest progress and most recent opening, from whichever source has them
+    if other.progress > existing.progress {
+        existing.progress = other.progress;
+    }
+    if other.last_opened_at > existing.last_opened_at {
+        existing.last_opened_at = other.last_opened_at;
+    }
+
+    // Merge highlights, deduplicating by ID, then by text (bookmarks by location)
+    let existing_texts: HashSet<String> = existing.highlights.iter().map(dedup_key).collect();
+    // Within one source an ID is stable (Apple Books' own, or `generate_highlight_id` of
+    // the book, text and position), so a shared ID is the same highlight even if its text
+    // was edited since; checked before the texts. Raw IDs from different sources (Calibre
+    // `annot_id`, Kobo `BookmarkID`, import-cmd IDs) can collide, so only count with a
+    // source in common
+    let mut existing_ids: HashMap<String, Vec<Source>> = HashMap::new();
+    for h in &existing.highlights {
+        existing_ids.entry(h.id.clone()).or_default().extend(h.sources.iter().cloned());
+    }
 
     for highlight in other.highlights {
-        let normalized = normalize_text(&highlight.text);
-        if !existing_texts.contains(&normalized) {
-            existing.highlights.push(highlight);
-        } else {
+        let same_id = existing_ids
+            .get(&highlight.id)
+            .is_some_and(|sources| highlight.sources.iter().any(|s| sources.contains(s)));
+        if same_id || existing_texts.contains(&dedup_key(&highlight)) {
             // If duplicate, prefer earlier created_at
             merge_duplicate_highlight(&mut existing.highlights, highlight);
+            continue;
+        }
+        // Sources cut the same passage slightly differently
+        let near_duplicate = match options.strict_dedupe {
+            true => None,
+            false => existing.highlights.iter().position(|h| is_near_duplicate(h, &highlight)),
+        };
+        match near_duplicate {
+            Some(i) => absorb_near_duplicate(&mut existing.highlights[i], highlight),
+            None => existing.highlights.push(highlight),
+        }
+    }
+
+    // Sort highlights by created_at (undated last), in reading order where dates tie
+    existing
+        .highlights
+        .sort_by_cached_key(|h| (h.created_at.is_none(), h.created_at, h.location.reading_order()));
+}
+
+/// Whether `candidate` reads better than the current author, i.e. isn't "Last, First"
+/// or a ";"-separated list when the current one is
+fn is_nicer_author(candidate: &str, current: Option<&str>) -> bool {
+    let is_raw = |author: &str| author.contains(',') || author.contains(';');
+
+    match current {
+        None => true,
+        Some(current) => is_raw(current) && !is_raw(candidate),
+    }
+}
+
+/// Loose texts of at least this many characters may differ by one edit per this many
+const NEAR_DUPLICATE_CHARS_PER_EDIT: usize = 30;
+
+/// Share of the longer loose text the shorter must cover to count as a near-duplicate
+/// when contained in it
+const NEAR_DUPLICATE_MIN_COVERAGE: f64 = 0.9;
+
+/// Whether two highlights from different sources are the same passage, cut slightly
+/// differently
+///
+/// Their `loose_text`s must be equal, or one must contain the other and cover at least
+/// `NEAR_DUPLICATE_MIN_COVERAGE` of it (an extra "the" or a missing last word), or be
+/// within one edit per `NEAR_DUPLICATE_CHARS_PER_EDIT` characters (a typo fixed in one
+/// edition). Short texts therefore have to match all but exactly. Highlights first read
+/// from one source are never near-duplicates: they were made apart on purpose.
+fn is_near_duplicate(existing: &Highlight, other: &Highlight) -> bool {
+    if existing.sources.first() == other.sources.first()
+        || existing.kind != HighlightKind::Highlight
+        || other.kind != HighlightKind::Highlight
+    {
+        return false;
+    }
+    let (a, b) = (loose_text(&existing.text), loose_text(&other.text));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+
+    let (a_len, b_len) = (a.chars().count(), b.chars().count());
+    let (shorter, longer, longer_len) = match a_len <= b_len {
+        true => (&a, &b, b_len),
+        false => (&b, &a, a_len),
+    };
+    let shorter_len = a_len.min(b_len);
+    if longer.contains(shorter.as_str()) {
+        return shorter_len as f64 >= longer_len as f64 * NEAR_DUPLICATE_MIN_COVERAGE;
+    }
+
+    let max_edits = longer_len / NEAR_DUPLICATE_CHARS_PER_EDIT;
+    if max_edits == 0 || longer_len - shorter_len > max_edits {
+        return false;
+    }
+    // A few edits leave one end alone; this keeps the distance from being computed for
+    // every pair of similarly long highlights
+    let ends = |text: &str| {
+        let chars: Vec<char> = text.chars().collect();
+        let n = chars.len().min(NEAR_DUPLICATE_CHARS_PER_EDIT / 2);
+        (chars[..n].to_vec(), chars[chars.len() - n..].to_vec())
+    };
+    let ((a_start, a_end), (b_start, b_end)) = (ends(&a), ends(&b));
+    (a_start == b_start || a_end == b_end) && strsim::levenshtein(&a, &b) <= max_edits
+}
+
+/// `normalize_text` with typographic quotes, dashes and spaces made plain, and any
+/// punctuation at either end dropped
+fn loose_text(text: &str) -> String {
+    let plain: String = text
+        .chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' | '\u{00AB}' | '\u{00BB}' => '"',
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+            '\u{00A0}' | '\u{2009}' | '\u{202F}' => ' ',
+            c => c,
+        })
+        .collect();
+    normalize_text(&plain.replace('\u{2026}', "..."))
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_string()
+}
+
+/// Fold a near-duplicate highlight into `existing`, keeping the longer text with its ID
+/// and location, every distinct note, the earliest date and both sources
+fn absorb_near_duplicate(existing: &mut Highlight, mut other: Highlight) {
+    if other.text.trim().chars().count() > existing.text.trim().chars().count() {
+        std::mem::swap(existing, &mut other);
+    }
+    let sources = std::mem::take(&mut other.sources);
+    let (context, deleted_upstream) = (other.context.take(), other.deleted_upstream);
+
+    absorb_overlapping(existing, other);
+    if existing.context.is_none() {
+        existing.context = context;
+    }
+    existing.deleted_upstream &= deleted_upstream;
+    add_sources(existing, sources);
+}
+
+/// Record that the sources of a highlight merged into this one have it too
+fn add_sources(highlight: &mut Highlight, sources: Vec<Source>) {
+    for source in sources {
+        if !highlight.sources.contains(&source) {
+            highlight.sources.push(source);
         }
     }
+}
+
+/// Collapse highlights that were adjusted on-device into their longest version
+///
+/// Kindle writes a new clipping each time a highlight is extended or shortened. Within
+/// each book, highlights whose location ranges overlap and whose normalized texts
+/// contain one another are collapsed into the longest, keeping the earliest date and
+/// every distinct note. Returns the number of highlights removed.
+pub fn dedupe_overlaps(books: &mut [Book]) -> usize {
+    books.iter_mut().map(dedupe_book_overlaps).sum()
+}
+
+fn dedupe_book_overlaps(book: &mut Book) -> usize {
+    // Longest first, so each highlight is compared against the versions that could contain it
+    let mut order: Vec<usize> = (0..book.highlights.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(normalize_text(&book.highlights[i].text).len()));
+
+    let mut highlights: Vec<Option<Highlight>> = book.highlights.drain(..).map(Some).collect();
+    let mut kept: Vec<usize> = Vec::new();
+    let mut removed = 0;
 
-    // Sort highlights by created_at
-    existing.highlights.sort_by(|a, b| {
-        match (&a.created_at, &b.created_at) {
-            (Some(a_date), Some(b_date)) => a_date.cmp(b_date),
-            (Some(_), None) => std::cmp::Ordering::Less,
-            (None, Some(_)) => std::cmp::Ordering::Greater,
-            (None, None) => std::cmp::Ordering::Equal,
+    for i in order {
+        let Some(candidate) = highlights[i].as_ref() else {
+            continue;
+        };
+        let target = kept
+            .iter()
+            .copied()
+            .find(|&k| highlights[k].as_ref().is_some_and(|longer| is_overlapping_version(longer, candidate)));
+
+        match (target, highlights[i].take()) {
+            (Some(k), Some(shorter)) => {
+                if let Some(longer) = highlights[k].as_mut() {
+                    absorb_overlapping(longer, shorter);
+                }
+                removed += 1;
+            }
+            (_, shorter) => {
+                highlights[i] = shorter;
+                kept.push(i);
+            }
         }
+    }
+
+    book.highlights = highlights.into_iter().flatten().collect();
+    removed
+}
+
+/// Whether `shorter` is an earlier or later version of the same passage as `longer`
+fn is_overlapping_version(longer: &Highlight, shorter: &Highlight) -> bool {
+    if longer.kind != HighlightKind::Highlight || shorter.kind != HighlightKind::Highlight {
+        return false;
+    }
+    // Text-less highlights (e.g. a PDF rect) are contained in anything
+    if shorter.text.trim().is_empty() {
+        return false;
+    }
+
+    // Compare locations when both have one, otherwise pages
+    let ranges = [
+        (longer.location.position.as_deref(), shorter.location.position.as_deref()),
+        (longer.location.page.as_deref(), shorter.location.page.as_deref()),
+    ]
+    .into_iter()
+    .find_map(|pair| match pair {
+        (Some(a), Some(b)) => Some((parse_position_range(a), parse_position_range(b))),
+        _ => None,
     });
+
+    let Some((Some((a_start, a_end)), Some((b_start, b_end)))) = ranges else {
+        return false;
+    };
+
+    a_start <= b_end && b_start <= a_end && normalize_text(&longer.text).contains(&normalize_text(&shorter.text))
+}
+
+/// Fold a shorter version of a passage into the longer one
+fn absorb_overlapping(longer: &mut Highlight, shorter: Highlight) {
+    match (&mut longer.note, shorter.note) {
+        (None, note) => longer.note = note,
+        (Some(existing), Some(note)) if !existing.contains(&note) => {
+            existing.push_str("\n\n");
+            existing.push_str(&note);
+        }
+        _ => {}
+    }
+
+    match (&longer.created_at, &shorter.created_at) {
+        (None, Some(_)) => longer.created_at = shorter.created_at,
+        (Some(l), Some(s)) if s < l => longer.created_at = shorter.created_at,
+        _ => {}
+    }
+
+    if longer.color.is_none() {
+        longer.color = shorter.color;
+    }
+    if longer.style.is_highlight() {
+        longer.style = shorter.style;
+    }
+}
+
+/// Title similarity (Jaro-Winkler) above which two books by the same author are merged
+const FUZZY_TITLE_THRESHOLD: f64 = 0.93;
+
+/// Merge books whose titles differ only slightly, e.g. by a subtitle, an edition note or
+/// punctuation, where the book IDs tell them apart
+///
+/// Books match when their authors normalize the same and either one title is the other
+/// with a subtitle, once bracketed notes like "(Dune, #1)" and punctuation are dropped,
+/// or the titles are similar enough. Two different subtitles don't match, and similar
+/// titles only match when their numbers, roman numerals and volume, book or part
+/// markers agree, so volumes of a series stay apart. A bare title doesn't take in a
+/// subtitled one that is a volume of a series: one whose subtitle names a volume or
+/// series ("Essays: Second Series"), whose bracketed note numbers it ("(The Hunger
+/// Games, #2)"), or whose title the author has more books under with other subtitles
+/// ("The Lord of the Rings: The Two Towers"). A book joins a group only when it matches
+/// every book in it. Each group keeps the shortest title and its ID, recording the
+/// others in `aliases`. Books without an author, such as Apple Books' untitled ones,
+/// and articles, keyed by URL, are left alone. Returns the number of books merged away.
+pub fn fuzzy_merge(books: &mut Vec<Book>, options: MergeOptions) -> usize {
+    let mut keys: Vec<Option<TitleKey>> = books.iter().map(TitleKey::new).collect();
+
+    // Titles an author has several differently subtitled books under name a series
+    let mut subtitles: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for key in keys.iter().flatten().filter(|k| !k.subtitle.is_empty()) {
+        let seen = subtitles.entry((&key.author, &key.short_title)).or_default();
+        if !seen.iter().any(|s| similar(s, &key.subtitle)) {
+            seen.push(&key.subtitle);
+        }
+    }
+    let series: HashSet<(String, String)> = subtitles
+        .into_iter()
+        .filter(|(_, seen)| seen.len() > 1)
+        .map(|((author, title), _)| (author.to_string(), title.to_string()))
+        .collect();
+    for key in keys.iter_mut().flatten() {
+        key.volume |= series.contains(&(key.author.clone(), key.short_title.clone()));
+    }
+
+    // Groups of indexes, least decorated titles first so they start the groups
+    let mut order: Vec<usize> = (0..books.len()).collect();
+    order.sort_by(|&a, &b| books[a].title.len().cmp(&books[b].title.len()).then_with(|| books[a].id.cmp(&books[b].id)));
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for i in order {
+        let joined = keys[i].as_ref().and_then(|key| {
+            groups.iter_mut().find(|group| {
+                group.iter().all(|&j| {
+                    keys[j]
+                        .as_ref()
+                        .is_some_and(|other| other.author == key.author && other.matches(key))
+                })
+            })
+        });
+        match joined {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+
+    let mut slots: Vec<Option<Book>> = books.drain(..).map(Some).collect();
+    let mut merged = 0;
+    for group in groups {
+        // The first, shortest title is the least decorated one
+        let mut group = group.into_iter().filter_map(|i| slots[i].take());
+        let Some(mut canonical) = group.next() else {
+            continue;
+        };
+        for other in group {
+            if other.title != canonical.title && !canonical.aliases.contains(&other.title) {
+                canonical.aliases.push(other.title.clone());
+            }
+            merge_into_book(&mut canonical, other, options);
+            merged += 1;
+        }
+        books.push(canonical);
+    }
+
+    books.sort_by_key(|b| b.title.to_lowercase());
+    merged
+}
+
+/// What `fuzzy_merge` compares a book by
+struct TitleKey {
+    author: String,
+    /// Lowercase title without bracketed notes, punctuation or spaces, "&" spelled "and",
+    /// so quote styles and spacing don't matter
+    title: String,
+    /// The same without a subtitle after ":"
+    short_title: String,
+    /// The subtitle after ":", empty without one
+    subtitle: String,
+    /// Whether the subtitle is that of a volume of a series, so the bare title is the
+    /// series rather than this book
+    volume: bool,
+    /// What tells volumes of a series apart: see `numbering`
+    numbering: Vec<String>,
+}
+
+impl TitleKey {
+    fn new(book: &Book) -> Option<Self> {
+        static BRACKETED: OnceLock<Regex> = OnceLock::new();
+        static SERIES_NOTE: OnceLock<Regex> = OnceLock::new();
+
+        let author = book.author.as_deref().filter(|_| book.url.is_none())?;
+        let bracketed = BRACKETED.get_or_init(|| Regex::new(r"\([^)]*\)|\[[^\]]*\]").unwrap());
+        let title = bracketed.replace_all(&book.title, " ");
+        let (short_title, subtitle) = title.split_once(':').unwrap_or((&title, ""));
+        // "(The Hunger Games, #2)" or "(Book 2)"
+        let series_note = SERIES_NOTE.get_or_init(|| Regex::new(r"(?i)#\s*\d|\b(?:book|volume|vol|part)\s+\d").unwrap());
+        let numbered = bracketed.find_iter(&book.title).any(|note| series_note.is_match(note.as_str()));
+        let key = |text: &str| {
+            text.to_lowercase()
+                .replace('&', " and ")
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        };
+
+        let title_key = key(&title);
+        if title_key.is_empty() {
+            return None;
+        }
+        Some(Self {
+            author: key(&normalize_author(author)),
+            short_title: key(short_title),
+            subtitle: key(subtitle),
+            volume: !subtitle.trim().is_empty() && (numbered || names_volume(subtitle)),
+            title: title_key,
+            numbering: numbering(&title),
+        })
+    }
+
+    fn matches(&self, other: &TitleKey) -> bool {
+        // Two different subtitles are two different books
+        if !self.subtitle.is_empty() && !other.subtitle.is_empty() && !similar(&self.subtitle, &other.subtitle) {
+            return false;
+        }
+        let subtitled = |bare: &TitleKey, full: &TitleKey| bare.title == full.short_title && !full.volume;
+
+        self.title == other.title
+            || subtitled(self, other)
+            || subtitled(other, self)
+            || (self.numbering == other.numbering && similar(&self.title, &other.title))
+    }
+}
+
+/// Whether two title keys are the same but for a typo or two
+fn similar(a: &str, b: &str) -> bool {
+    a == b || strsim::jaro_winkler(a, b) >= FUZZY_TITLE_THRESHOLD
+}
+
+/// Whether a subtitle names a volume or series, e.g. "Second Series", "Part 2" or "II"
+fn names_volume(subtitle: &str) -> bool {
+    let subtitle = subtitle.to_lowercase();
+    let words: Vec<&str> = subtitle.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let bare_number = matches!(words[..], [word] if word.chars().all(|c| c.is_ascii_digit()) || roman_value(word).is_some());
+
+    bare_number || words.iter().any(|w| *w == "series" || VOLUME_MARKERS.iter().any(|(marker, _)| marker == w))
+}
+
+/// Words that mark a volume of a series when a number follows, by their spelled-out form
+const VOLUME_MARKERS: [(&str, &str); 7] = [
+    ("volume", "volume"),
+    ("vol", "volume"),
+    ("book", "book"),
+    ("part", "part"),
+    ("pt", "part"),
+    ("tome", "tome"),
+    ("tomo", "tome"),
+];
+
+/// The numbers in a title, in order, with any volume marker before them, e.g.
+/// ["volume", "1"] for both "Vol. I" and "Volume One"
+///
+/// Digit runs count wherever they are ("3rd" is 3); roman numerals up to 89 only as
+/// whole words after a marker or ending the title or the part before a subtitle, so
+/// "I Am Legend" and "Malcolm X Speaks" have none; numbers spelled out up to twelve
+/// only as whole words after a marker.
+fn numbering(title: &str) -> Vec<String> {
+    const NUMBER_WORDS: [&str; 12] =
+        ["one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve"];
+
+    let title = title.to_lowercase();
+    // Each word, and whether it ends the title or the part before a subtitle
+    let words: Vec<(&str, bool)> = title
+        .split(':')
+        .flat_map(|part| {
+            let words: Vec<&str> = part.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+            let last = words.len().saturating_sub(1);
+            words.into_iter().enumerate().map(move |(i, word)| (word, i == last))
+        })
+        .collect();
+    let number = |word: &str, after_marker: bool, last: bool| {
+        if word.chars().any(|c| c.is_ascii_digit()) {
+            let digits: String = word.chars().filter(char::is_ascii_digit).collect();
+            let digits = digits.trim_start_matches('0');
+            return Some(if digits.is_empty() { "0".to_string() } else { digits.to_string() });
+        }
+        if let Some(value) = roman_value(word).filter(|_| after_marker || last) {
+            return Some(value.to_string());
+        }
+        let spelled = NUMBER_WORDS.iter().position(|w| *w == word).filter(|_| after_marker)?;
+        Some((spelled + 1).to_string())
+    };
+
+    let mut numbering = Vec::new();
+    let mut marker = None;
+    for (word, last) in words {
+        if let Some(number) = number(word, marker.is_some(), last) {
+            numbering.extend(marker.take().map(String::from));
+            numbering.push(number);
+            continue;
+        }
+        marker = VOLUME_MARKERS.iter().find(|(w, _)| *w == word).map(|(_, name)| *name);
+    }
+    numbering
+}
+
+/// The value of a roman numeral from "i" to "lxxxix", written the standard way
+fn roman_value(word: &str) -> Option<u32> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^(xl|l?x{0,3})(ix|iv|v?i{0,3})$").unwrap());
+    if word.is_empty() || !re.is_match(word) {
+        return None;
+    }
+
+    let digit = |c: char| match c {
+        'i' => 1,
+        'v' => 5,
+        'x' => 10,
+        _ => 50,
+    };
+    let digits: Vec<u32> = word.chars().map(digit).collect();
+    let value = digits
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| match digits.get(i + 1) {
+            Some(&next) if next > d => -(d as i32),
+            _ => d as i32,
+        })
+        .sum::<i32>();
+    Some(value as u32)
 }
 
 /// Normalize text for comparison (lowercase, collapse whitespace)
-fn normalize_text(text: &str) -> String {
+pub(crate) fn normalize_text(text: &str) -> String {
     text.to_lowercase()
         .split_whitespace()
         .collect::<Vec<&str>>()
         .join(" ")
 }
 
-/// Merge a duplicate highlight, preferring earlier created_at
-fn merge_duplicate_highlight(highlights: &mut Vec<Highlight>, other: Highlight) {
-    let normalized_other = normalize_text(&other.text);
+/// Key highlights are deduplicated by: normalized text, location for bookmarks,
+/// location and body for standalone notes, which have no text of their own, or the ID
+/// for highlights whose text the source didn't keep
+fn dedup_key(highlight: &Highlight) -> String {
+    match highlight.kind {
+        HighlightKind::Bookmark => format!(
+            "bookmark:{}:{}",
+            highlight.location.position.as_deref().unwrap_or_default(),
+            highlight.location.page.as_deref().unwrap_or_default()
+        ),
+        HighlightKind::Note => format!(
+            "note:{}:{}:{}",
+            highlight.location.position.as_deref().unwrap_or_default(),
+            highlight.location.page.as_deref().unwrap_or_default(),
+            normalize_text(highlight.body())
+        ),
+        HighlightKind::Highlight if highlight.text.trim().is_empty() => format!("untexted:{}", highlight.id),
+        HighlightKind::Highlight => normalize_text(&highlight.text),
+    }
+}
 
-    for existing in highlights.iter_mut() {
-        if normalize_text(&existing.text) == normalized_other {
-            // Prefer earlier created_at
-            match (&existing.created_at, &other.created_at) {
-                (None, Some(_)) => existing.created_at = other.created_at,
-                (Some(e), Some(o)) if o < e => existing.created_at = other.created_at,
-                _ => {}
-            }
+/// Merge a duplicate highlight into the one with its ID from a shared source, or else
+/// its dedup key, preferring earlier created_at
+fn merge_duplicate_highlight(highlights: &mut [Highlight], other: Highlight) {
+    let same_id = |h: &Highlight| h.id == other.id && h.sources.iter().any(|s| other.sources.contains(s));
+    let index = highlights.iter().position(same_id).or_else(|| {
+        let key = dedup_key(&other);
+        highlights.iter().position(|h| dedup_key(h) == key)
+    });
+    let Some(existing) = index.map(|i| &mut highlights[i]) else {
+        return;
+    };
 
-            // Merge note if existing doesn't have one
-            if existing.note.is_none() && other.note.is_some() {
-                existing.note = other.note;
-            }
+    // Prefer earlier created_at
+    match (&existing.created_at, &other.created_at) {
+        (None, Some(_)) => existing.created_at = other.created_at,
+        (Some(e), Some(o)) if o < e => existing.created_at = other.created_at,
+        _ => {}
+    }
 
-            // Add source if not present
-            // (Note: Highlight has a single source, not a vec, so we can't merge sources here)
-            break;
-        }
+    // Merge note if existing doesn't have one
+    if existing.note.is_none() && other.note.is_some() {
+        existing.note = other.note;
+    }
+
+    if existing.color.is_none() {
+        existing.color = other.color;
+    }
+    if existing.context.is_none() {
+        existing.context = other.context;
+    }
+    if existing.style.is_highlight() {
+        existing.style = other.style;
+    }
+
+    // Still present in either copy means still present upstream
+    existing.deleted_upstream &= other.deleted_upstream;
+
+    // Keep the richer location, e.g. page plus location over a page alone
+    if existing.location.position.is_none() {
+        existing.location.position = other.location.position;
     }
+    if existing.location.page.is_none() {
+        existing.location.page = other.location.page;
+    }
+    if existing.location.chapter.is_none() {
+        existing.location.chapter = other.location.chapter;
+    }
+
+    add_sources(existing, other.sources);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{generate_book_id, Location, Source};
+    use crate::model::{generate_book_id, HighlightStyle, Location, Source};
+    use chrono::{TimeZone, Utc};
+    use std::collections::BTreeMap;
 
     fn make_book(title: &str, author: Option<&str>, source: Source) -> Book {
         Book {
@@ -124,6 +757,20 @@ mod tests {
             highlights: Vec::new(),
             finished: None,
             finished_at: None,
+            url: None,
+            isbn: None,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated: false,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids: BTreeMap::new(),
+            document_type: None,
+            progress: None,
+            last_opened_at: None,
+            language: None,
+            genre: None,
         }
     }
 
@@ -135,9 +782,16 @@ mod tests {
             location: Location {
                 chapter: None,
                 position: None,
+                page: None,
+                raw: None,
             },
             created_at: None,
-            source,
+            sources: vec![source],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
         }
     }
 
@@ -164,12 +818,457 @@ mod tests {
         let mut book2 = make_book("Test Book", None, Source::Kindle);
         book2.highlights.push(make_highlight("Same highlight text", Source::Kindle));
         book2.highlights.push(make_highlight("Different highlight", Source::Kindle));
+        book2.highlights[0].context = Some("Before. Same highlight text. After.".to_string());
 
         let merged = merge_books(vec![vec![book1], vec![book2]]);
 
         assert_eq!(merged.len(), 1);
         // Should have 2 highlights: one deduplicated, one unique
         assert_eq!(merged[0].highlights.len(), 2);
+        let same = merged[0].highlights.iter().find(|h| h.text == "Same highlight text").unwrap();
+        assert_eq!(same.context.as_deref(), Some("Before. Same highlight text. After."));
+        assert_eq!(same.sources, vec![Source::AppleBooks, Source::Kindle]);
+    }
+
+    #[test]
+    fn test_merge_dedupes_by_id_before_text() {
+        // Extended in Apple Books since the last export, keeping its UUID
+        let mut fresh = make_book("Walden", None, Source::AppleBooks);
+        fresh.highlights.push(make_highlight("Simplify, simplify. Let your affairs be as two or three.", Source::AppleBooks));
+        fresh.highlights[0].id = "U1".to_string();
+        let mut previous = fresh.clone();
+        previous.highlights[0].text = "Simplify, simplify.".to_string();
+        previous.highlights[0].note = Some("Thoreau's motto".to_string());
+
+        let merged = merge_books_with(vec![vec![fresh], vec![previous]], MergeOptions { strict_dedupe: true });
+
+        assert_eq!(merged[0].highlights.len(), 1);
+        let highlight = &merged[0].highlights[0];
+        assert!(highlight.text.ends_with("two or three."));
+        assert_eq!(highlight.note.as_deref(), Some("Thoreau's motto"));
+    }
+
+    #[test]
+    fn test_merge_keeps_colliding_ids_from_different_sources() {
+        // Calibre and Kobo both number their annotations from 1
+        let mut calibre = make_book("Walden", None, Source::Calibre);
+        calibre.highlights.push(make_highlight("I went to the woods because I wished to live deliberately.", Source::Calibre));
+        calibre.highlights[0].id = "1".to_string();
+        let mut kobo = make_book("Walden", None, Source::Kobo);
+        kobo.highlights.push(make_highlight("Our life is frittered away by detail.", Source::Kobo));
+        kobo.highlights[0].id = "1".to_string();
+
+        let merged = merge_books_with(vec![vec![calibre], vec![kobo]], MergeOptions { strict_dedupe: true });
+
+        assert_eq!(merged[0].highlights.len(), 2);
+        let texts: Vec<&str> = merged[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert!(texts.contains(&"Our life is frittered away by detail."));
+    }
+
+    #[test]
+    fn test_merge_near_duplicate_highlights() {
+        let dated = |text: &str, source: Source, day: u32| {
+            let mut highlight = make_highlight(text, source);
+            highlight.created_at = Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).single();
+            highlight
+        };
+        let mut apple = make_book("Meditations", None, Source::AppleBooks);
+        apple.highlights = vec![
+            dated("\u{201C}It is not death that a man should fear.\u{201D}", Source::AppleBooks, 1),
+            dated("impediment to action advances action. What stands in the way becomes the way.", Source::AppleBooks, 2),
+            dated(
+                "You have power over your mind, not outside events. Realize this, and you will find strength.",
+                Source::AppleBooks,
+                3,
+            ),
+        ];
+        apple.highlights[1].note = Some("Stoic core".to_string());
+        let mut kindle = make_book("Meditations", None, Source::Kindle);
+        kindle.highlights = vec![
+            dated("\"It is not death that a man should fear", Source::Kindle, 10),
+            dated("The impediment to action advances action. What stands in the way becomes the way.", Source::Kindle, 11),
+            dated(
+                "You have power over your mind \u{2014} not outside events. Realise this, and you will find strength.",
+                Source::Kindle,
+                12,
+            ),
+        ];
+        kindle.highlights[1].note = Some("Marcus on obstacles".to_string());
+
+        let merged = merge_books(vec![vec![apple.clone()], vec![kindle.clone()]]);
+        let highlights = &merged[0].highlights;
+        assert_eq!(highlights.len(), 3);
+        assert!(highlights.iter().all(|h| h.sources.len() == 2));
+        // The longer text wins, with the earliest date and both notes
+        let obstacle = highlights.iter().find(|h| h.text.contains("impediment")).unwrap();
+        assert!(obstacle.text.starts_with("The impediment"));
+        assert_eq!(obstacle.sources, vec![Source::Kindle, Source::AppleBooks]);
+        assert_eq!(obstacle.created_at, Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).single());
+        assert_eq!(obstacle.note.as_deref(), Some("Marcus on obstacles\n\nStoic core"));
+
+        let strict = merge_books_with(vec![vec![apple], vec![kindle]], MergeOptions { strict_dedupe: true });
+        assert_eq!(strict[0].highlights.len(), 6);
+    }
+
+    #[test]
+    fn test_near_duplicates_keep_different_highlights_apart() {
+        let pairs = [
+            ("Yes.", "No."),
+            ("Know thyself.", "Know yourself."),
+            // Contained, but covering too little of the longer one
+            ("Love is patient", "Love is patient, love is kind"),
+            (
+                "I think, therefore I am, said the philosopher",
+                "I think, therefore I was, said the philosopher",
+            ),
+        ];
+        for (apple_text, kindle_text) in pairs {
+            let mut apple = make_book("Book", None, Source::AppleBooks);
+            apple.highlights.push(make_highlight(apple_text, Source::AppleBooks));
+            let mut kindle = make_book("Book", None, Source::Kindle);
+            kindle.highlights.push(make_highlight(kindle_text, Source::Kindle));
+
+            let merged = merge_books(vec![vec![apple], vec![kindle]]);
+            assert_eq!(merged[0].highlights.len(), 2, "{:?} / {:?}", apple_text, kindle_text);
+        }
+
+        // Highlights from one source were made apart on purpose
+        let mut first = make_book("Book", None, Source::Kindle);
+        first.highlights.push(make_highlight("To be, or not to be, that is the question", Source::Kindle));
+        let mut second = make_book("Book", None, Source::Kindle);
+        second.highlights.push(make_highlight("To be or not to be, that is the question", Source::Kindle));
+        assert_eq!(merge_books(vec![vec![first], vec![second]])[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_fills_reading_state_and_metadata() {
+        let mut book1 = make_book("Test Book", None, Source::AppleBooks);
+        book1.progress = Some(0.8);
+        book1.last_opened_at = Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let mut book2 = make_book("Test Book", None, Source::Kobo);
+        book2.progress = Some(0.5);
+        book2.language = Some("de".to_string());
+        book2.last_opened_at = Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap());
+        let book3 = make_book("Test Book", None, Source::Kindle);
+
+        let merged = merge_books(vec![vec![book1], vec![book2], vec![book3]]);
+
+        assert_eq!(merged[0].progress, Some(0.8));
+        assert_eq!(merged[0].language.as_deref(), Some("de"));
+        assert_eq!(merged[0].last_opened_at, Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_merge_keeps_standalone_notes_apart_by_location() {
+        let note = |body: &str, position: &str| {
+            let mut note = make_highlight("", Source::AppleBooks);
+            note.kind = HighlightKind::Note;
+            note.note = Some(body.to_string());
+            note.location.position = Some(position.to_string());
+            note
+        };
+
+        let mut book1 = make_book("Test Book", None, Source::AppleBooks);
+        book1.highlights.push(note("Summary", "epubcfi(/6/4!/4/2/1:0)"));
+        book1.highlights.push(note("Summary", "epubcfi(/6/8!/4/2/1:0)"));
+        let mut book2 = make_book("Test Book", None, Source::AppleBooks);
+        book2.highlights.push(note("Summary", "epubcfi(/6/8!/4/2/1:0)"));
+        book2.highlights.push(note("Another summary", "epubcfi(/6/12!/4/2/1:0)"));
+
+        let merged = merge_books(vec![vec![book1], vec![book2]]);
+
+        assert_eq!(merged[0].highlights.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_duplicate_prefers_richer_location() {
+        let mut book1 = make_book("Test Book", None, Source::Kindle);
+        book1.highlights.push(make_highlight("Same highlight text", Source::Kindle));
+        book1.highlights[0].location.page = Some("45".to_string());
+
+        let mut book2 = make_book("Test Book", None, Source::Kindle);
+        book2.highlights.push(make_highlight("Same highlight text", Source::Kindle));
+        book2.highlights[0].location.page = Some("45".to_string());
+        book2.highlights[0].location.position = Some("678-690".to_string());
+        book2.highlights[0].color = Some("blue".to_string());
+        book2.highlights[0].style = HighlightStyle::Underline;
+
+        let merged = merge_books(vec![vec![book1], vec![book2]]);
+
+        assert_eq!(merged[0].highlights.len(), 1);
+        assert_eq!(merged[0].highlights[0].color.as_deref(), Some("blue"));
+        assert_eq!(merged[0].highlights[0].style, HighlightStyle::Underline);
+        let location = &merged[0].highlights[0].location;
+        assert_eq!(location.page.as_deref(), Some("45"));
+        assert_eq!(location.position.as_deref(), Some("678-690"));
+    }
+
+    fn make_located(text: &str, position: &str, day: u32) -> Highlight {
+        let mut highlight = make_highlight(text, Source::Kindle);
+        highlight.location.position = Some(position.to_string());
+        highlight.created_at = Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).single();
+        highlight
+    }
+
+    #[test]
+    fn test_dedupe_overlaps_extend_right() {
+        let mut book = make_book("Walden", None, Source::Kindle);
+        book.highlights.push(make_located("I went to the woods", "100-102", 1));
+        book.highlights.push(make_located("I went to the woods because I wished to live deliberately", "100-106", 2));
+        book.highlights[0].note = Some("Why?".to_string());
+
+        let mut books = vec![book];
+        assert_eq!(dedupe_overlaps(&mut books), 1);
+
+        let highlight = &books[0].highlights[0];
+        assert_eq!(books[0].highlights.len(), 1);
+        assert_eq!(highlight.text, "I went to the woods because I wished to live deliberately");
+        assert_eq!(highlight.note.as_deref(), Some("Why?"));
+        assert_eq!(highlight.created_at, Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).single());
+    }
+
+    #[test]
+    fn test_dedupe_overlaps_extend_left() {
+        let mut book = make_book("Walden", None, Source::Kindle);
+        book.highlights.push(make_located("because I wished to live deliberately", "103-106", 1));
+        book.highlights.push(make_located("I went to the woods because I wished to live deliberately", "100-106", 2));
+        book.highlights.push(make_located("Simplify, simplify.", "200-201", 3));
+
+        let mut books = vec![book];
+        assert_eq!(dedupe_overlaps(&mut books), 1);
+        assert_eq!(books[0].highlights.len(), 2);
+        assert_eq!(books[0].highlights[0].text, "I went to the woods because I wished to live deliberately");
+    }
+
+    #[test]
+    fn test_dedupe_overlaps_fully_contained() {
+        let mut book = make_book("Walden", None, Source::Kindle);
+        book.highlights.push(make_located("I went to the woods because I wished to live deliberately", "100-106", 1));
+        book.highlights.push(make_located("woods because I wished", "102-104", 2));
+        book.highlights[1].note = Some("Key line".to_string());
+        // Same words elsewhere in the book are a separate highlight
+        book.highlights.push(make_located("woods", "500", 3));
+
+        let mut books = vec![book];
+        assert_eq!(dedupe_overlaps(&mut books), 1);
+        assert_eq!(books[0].highlights.len(), 2);
+        assert_eq!(books[0].highlights[0].note.as_deref(), Some("Key line"));
+        assert_eq!(books[0].highlights[1].text, "woods");
+    }
+
+    #[test]
+    fn test_untexted_highlights_stay_apart() {
+        let rect = |id: &str, position: &str| {
+            let mut highlight = make_located("", position, 1);
+            highlight.id = id.to_string();
+            highlight
+        };
+
+        let mut book1 = make_book("Test Book", None, Source::AppleBooks);
+        book1.highlights.push(rect("P1", "Page 3"));
+        book1.highlights.push(rect("P2", "Page 3"));
+        let mut book2 = make_book("Test Book", None, Source::AppleBooks);
+        book2.highlights.push(rect("P2", "Page 3"));
+        book2.highlights.push(make_located("Program to an interface", "Page 3", 2));
+
+        let mut merged = merge_books(vec![vec![book1], vec![book2]]);
+        assert_eq!(merged[0].highlights.len(), 3);
+        // Nor are they taken for part of a longer highlight on the same page
+        assert_eq!(dedupe_overlaps(&mut merged), 0);
+    }
+
+    #[test]
+    fn test_merge_orders_undated_highlights_by_position() {
+        let mut earlier = make_book("Test Book", None, Source::Kindle);
+        for (text, position) in [("third", "Location 1,500"), ("first", "Location 30")] {
+            let mut highlight = make_highlight(text, Source::Kindle);
+            highlight.location.position = Some(position.to_string());
+            earlier.highlights.push(highlight);
+        }
+
+        let mut later = make_book("Test Book", None, Source::Kindle);
+        for (text, position) in [("unplaced", None), ("second", Some("200-210"))] {
+            let mut highlight = make_highlight(text, Source::Kindle);
+            highlight.location.position = position.map(String::from);
+            later.highlights.push(highlight);
+        }
+
+        let merged = merge_books(vec![vec![earlier], vec![later]]);
+        let texts: Vec<&str> = merged[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, vec!["first", "second", "third", "unplaced"]);
+    }
+
+    #[test]
+    fn test_merge_keeps_distinct_bookmarks() {
+        let bookmark = |position: &str| {
+            let mut highlight = make_highlight("", Source::Kindle);
+            highlight.kind = HighlightKind::Bookmark;
+            highlight.location.position = Some(position.to_string());
+            highlight
+        };
+
+        let mut book1 = make_book("Test Book", None, Source::Kindle);
+        book1.highlights.push(bookmark("100"));
+
+        let mut book2 = make_book("Test Book", None, Source::Kindle);
+        book2.highlights.push(bookmark("100"));
+        book2.highlights.push(bookmark("250"));
+
+        let merged = merge_books(vec![vec![book1], vec![book2]]);
+
+        assert_eq!(merged[0].highlights.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_author_variants() {
+        let kindle = make_book("The Great Gatsby", Some("Fitzgerald, F. Scott"), Source::Kindle);
+        let apple = make_book("The Great Gatsby", Some("F. Scott Fitzgerald"), Source::AppleBooks);
+
+        for lists in [vec![vec![kindle.clone()], vec![apple.clone()]], vec![vec![apple], vec![kindle]]] {
+            let merged = merge_books(lists);
+
+            assert_eq!(merged.len(), 1);
+            assert_eq!(merged[0].author.as_deref(), Some("F. Scott Fitzgerald"));
+            assert_eq!(merged[0].sources.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_previously_seen_highlights() {
+        let mut previous = make_book("Test Book", None, Source::Kindle);
+        previous.highlights.push(make_highlight("Old highlight", Source::Kindle));
+        previous.highlights.push(make_highlight("Kept highlight", Source::Kindle));
+
+        let path = std::env::temp_dir().join(format!("readingsync_merge_{}.json", uuid::Uuid::new_v4()));
+        let library = crate::model::Library {
+            exported_at: chrono::Utc::now(),
+            books: vec![previous],
+        };
+        std::fs::write(&path, serde_json::to_string(&library).unwrap()).unwrap();
+        let existing = crate::model::Library::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // The old highlight has dropped out of the fresh scrape
+        let mut fresh = make_book("Test Book", None, Source::Kindle);
+        fresh.highlights.push(make_highlight("Kept highlight", Source::Kindle));
+        fresh.highlights.push(make_highlight("New highlight", Source::Kindle));
+
+        let merged = merge_books(vec![vec![fresh], existing.books]);
+
+        assert_eq!(merged.len(), 1);
+        let texts: Vec<&str> = merged[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts.len(), 3);
+        assert!(texts.contains(&"Old highlight"));
+        assert!(texts.contains(&"New highlight"));
+    }
+
+    /// One sync: mark what the previous library has that `fresh` lacks, then merge
+    fn sync(fresh: Vec<Book>, previous: Vec<Book>) -> (Vec<Book>, usize) {
+        let mut previous = previous;
+        let deleted = mark_deleted_upstream(&fresh, &mut previous);
+        (merge_books(vec![fresh, previous]), deleted)
+    }
+
+    fn kindle_book(texts: &[&str]) -> Book {
+        let mut book = make_book("Test Book", None, Source::Kindle);
+        book.highlights = texts.iter().map(|text| make_highlight(text, Source::Kindle)).collect();
+        book
+    }
+
+    fn deleted(book: &Book) -> Vec<&str> {
+        book.highlights.iter().filter(|h| h.deleted_upstream).map(|h| h.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_deleted_upstream_lifecycle() {
+        // Appear: both highlights are synced
+        let (library, flagged) = sync(vec![kindle_book(&["First", "Second"])], Vec::new());
+        assert_eq!(flagged, 0);
+
+        // Disappear: "Second" is gone upstream but kept, flagged
+        let (library, flagged) = sync(vec![kindle_book(&["First"])], library);
+        assert_eq!(flagged, 1);
+        assert_eq!(library[0].highlights.len(), 2);
+        assert_eq!(deleted(&library[0]), vec!["Second"]);
+
+        // Still gone: not counted again
+        let (library, flagged) = sync(vec![kindle_book(&["First"])], library);
+        assert_eq!(flagged, 0);
+        assert_eq!(deleted(&library[0]), vec!["Second"]);
+
+        // Reappear: the flag is cleared
+        let (library, flagged) = sync(vec![kindle_book(&["First", "Second"])], library);
+        assert_eq!(flagged, 0);
+        assert!(deleted(&library[0]).is_empty());
+
+        let json = serde_json::to_string(&library[0]).unwrap();
+        assert!(!json.contains("deleted_upstream"));
+    }
+
+    #[test]
+    fn test_deleted_upstream_leaves_untouched_books_and_sources() {
+        let mut previous = kindle_book(&["Kindle highlight"]);
+        previous.sources.push(Source::AppleBooks);
+        previous.highlights.push(make_highlight("Apple Books highlight", Source::AppleBooks));
+        let other = make_book("Other Book", None, Source::Kindle);
+        let mut other_previous = other.clone();
+        other_previous.highlights.push(make_highlight("Not synced this time", Source::Kindle));
+
+        // A Kindle-only sync of "Test Book" that no longer has its Kindle highlight
+        let (library, flagged) = sync(vec![kindle_book(&[])], vec![previous.clone(), other_previous]);
+        assert_eq!(flagged, 1);
+        let book = library.iter().find(|b| b.title == "Test Book").unwrap();
+        assert_eq!(deleted(book), vec!["Kindle highlight"]);
+        let other = library.iter().find(|b| b.title == "Other Book").unwrap();
+        assert!(deleted(other).is_empty());
+
+        // A truncated book is only partly shown, so nothing is flagged
+        let mut truncated = kindle_book(&[]);
+        truncated.truncated = true;
+        let (_, flagged) = sync(vec![truncated], vec![previous]);
+        assert_eq!(flagged, 0);
+    }
+
+    #[test]
+    fn test_deleted_upstream_in_fuzzily_merged_book() {
+        let apple = |texts: &[&str]| {
+            let mut book = make_book("Dune", Some("Frank Herbert"), Source::AppleBooks);
+            book.highlights = texts.iter().map(|text| make_highlight(text, Source::AppleBooks)).collect();
+            book
+        };
+        let goodreads = |texts: &[&str]| {
+            let mut book = make_book("Dune (Dune, #1)", Some("Frank Herbert"), Source::Goodreads);
+            book.highlights = texts.iter().map(|text| make_highlight(text, Source::Goodreads)).collect();
+            book
+        };
+        let mut previous = merge_books(vec![vec![apple(&["Fear is the mind-killer."])], vec![goodreads(&["The spice must flow."])]]);
+        fuzzy_merge(&mut previous, MergeOptions::default());
+        assert_eq!(previous.len(), 1);
+        assert_eq!(previous[0].aliases, vec!["Dune (Dune, #1)"]);
+
+        // Goodreads no longer has its highlight; the merged book kept Apple Books' ID
+        let (library, flagged) = sync(vec![goodreads(&[])], previous.clone());
+        assert_eq!(flagged, 1);
+        let merged = library.iter().find(|b| b.title == "Dune").unwrap();
+        assert_eq!(deleted(merged), vec!["The spice must flow."]);
+
+        // Both sources synced, each still with its highlight: nothing is flagged
+        let (_, flagged) =
+            sync(vec![apple(&["Fear is the mind-killer."]), goodreads(&["The spice must flow."])], previous);
+        assert_eq!(flagged, 0);
+    }
+
+    #[test]
+    fn test_merge_unions_collections() {
+        let mut book1 = make_book("Test Book", None, Source::AppleBooks);
+        book1.collections = vec!["Philosophy".to_string(), "Work".to_string()];
+
+        let mut book2 = make_book("Test Book", None, Source::Kindle);
+        book2.collections = vec!["Work".to_string(), "Want to Read".to_string()];
+
+        let merged = merge_books(vec![vec![book1], vec![book2]]);
+
+        assert_eq!(merged[0].collections, vec!["Philosophy", "Work", "Want to Read"]);
     }
 
     #[test]
@@ -196,4 +1295,168 @@ mod tests {
             "multiple spaces"
         );
     }
+
+    #[test]
+    fn test_fuzzy_merge_subtitle_and_last_first_author() {
+        let mut apple = make_book("Thinking, Fast and Slow", Some("Daniel Kahneman"), Source::AppleBooks);
+        apple.highlights.push(make_highlight("Nothing in life is as important as you think it is.", Source::AppleBooks));
+        let mut kindle = make_book(
+            "Thinking, Fast and Slow: International Edition",
+            Some("Kahneman, Daniel"),
+            Source::Kindle,
+        );
+        kindle.highlights.push(make_highlight("A reliable way to make people believe in falsehoods", Source::Kindle));
+
+        let mut books = merge_books(vec![vec![apple], vec![kindle]]);
+        assert_eq!(books.len(), 2);
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 1);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.title, "Thinking, Fast and Slow");
+        assert_eq!(book.id, generate_book_id("Thinking, Fast and Slow", Some("Daniel Kahneman")));
+        assert_eq!(book.aliases, vec!["Thinking, Fast and Slow: International Edition"]);
+        assert_eq!(book.author.as_deref(), Some("Daniel Kahneman"));
+        assert_eq!(book.sources, vec![Source::AppleBooks, Source::Kindle]);
+        assert_eq!(book.highlights.len(), 2);
+
+        // Merging the result again keeps the aliases without repeating them
+        let mut again = merge_books(vec![books.clone(), books]);
+        assert_eq!(fuzzy_merge(&mut again, MergeOptions::default()), 0);
+        assert_eq!(again[0].aliases.len(), 1);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_edition_notes_and_punctuation() {
+        let mut books = vec![
+            make_book("Dune", Some("Frank Herbert"), Source::AppleBooks),
+            make_book("Dune (Dune, #1)", Some("Frank Herbert"), Source::Goodreads),
+            make_book("Dune Messiah", Some("Frank Herbert"), Source::Kindle),
+            make_book("Zen and the Art of Motorcycle Maintenance", Some("Robert M. Pirsig"), Source::Kindle),
+            make_book("Zen & the Art of Motorcycle Maintenance", Some("Robert M. Pirsig"), Source::AppleBooks),
+            make_book("Man's Search for Meaning", Some("Viktor E. Frankl"), Source::Kindle),
+            make_book("Man’s Search for Meaning", Some("Viktor E. Frankl"), Source::Kobo),
+            make_book("Sapiens: A Brief History of Humankind", Some("Yuval Noah Harari"), Source::Kindle),
+            make_book("Sapiens: A Brief Histroy of Humankind", Some("Yuval Noah Harari"), Source::Kobo),
+        ];
+
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 4);
+
+        let titles: Vec<&str> = books.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Dune",
+                "Dune Messiah",
+                "Man's Search for Meaning",
+                "Sapiens: A Brief History of Humankind",
+                "Zen & the Art of Motorcycle Maintenance"
+            ]
+        );
+        assert_eq!(books[0].aliases, vec!["Dune (Dune, #1)"]);
+        assert_eq!(books[2].aliases, vec!["Man’s Search for Meaning"]);
+        assert_eq!(books[3].aliases.len(), 1);
+        assert_eq!(books[4].aliases, vec!["Zen and the Art of Motorcycle Maintenance"]);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_keeps_different_books_apart() {
+        let mut books = vec![
+            make_book("The Lord of the Rings: The Two Towers", Some("J.R.R. Tolkien"), Source::Kindle),
+            make_book("The Lord of the Rings: The Return of the King", Some("J.R.R. Tolkien"), Source::Kindle),
+            make_book("Meditations", Some("Marcus Aurelius"), Source::Kindle),
+            make_book("Meditations", Some("René Descartes"), Source::Kindle),
+            make_book("Unknown book (A1)", None, Source::AppleBooks),
+            make_book("Unknown book (B2)", None, Source::AppleBooks),
+        ];
+
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+        assert_eq!(books.len(), 6);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_keeps_series_volumes_apart_from_the_series_title() {
+        // A bare series title doesn't chain its differently subtitled volumes together
+        let mut books = vec![
+            make_book("The Lord of the Rings", Some("J.R.R. Tolkien"), Source::Kindle),
+            make_book("The Lord of the Rings: The Two Towers", Some("J.R.R. Tolkien"), Source::AppleBooks),
+            make_book("The Lord of the Rings: The Return of the King", Some("J.R.R. Tolkien"), Source::Kobo),
+        ];
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+        assert_eq!(books.len(), 3);
+
+        // Subtitles naming a series
+        let mut books = vec![
+            make_book("Essays", Some("Ralph Waldo Emerson"), Source::Kindle),
+            make_book("Essays: First Series", Some("Ralph Waldo Emerson"), Source::AppleBooks),
+            make_book("Essays: Second Series", Some("Ralph Waldo Emerson"), Source::Kobo),
+        ];
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+        assert_eq!(books.len(), 3);
+
+        // Volumes by their own subtitles, or a single one numbered by its series note
+        let mut books = vec![
+            make_book("The Hunger Games", Some("Suzanne Collins"), Source::Kindle),
+            make_book("The Hunger Games: Catching Fire", Some("Suzanne Collins"), Source::AppleBooks),
+            make_book("The Hunger Games: Mockingjay", Some("Suzanne Collins"), Source::Kobo),
+        ];
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+        let mut books = vec![
+            make_book("The Hunger Games", Some("Suzanne Collins"), Source::Kindle),
+            make_book("The Hunger Games: Catching Fire (The Hunger Games, #2)", Some("Suzanne Collins"), Source::Goodreads),
+        ];
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_groups_only_books_matching_all_members() {
+        // The middle title is similar to both others, which aren't similar to each other
+        let mut books = vec![
+            make_book("Anna Karenina", Some("Leo Tolstoy"), Source::Kindle),
+            make_book("Anna Karenin", Some("Leo Tolstoy"), Source::AppleBooks),
+            make_book("Ana Karenin", Some("Leo Tolstoy"), Source::Kobo),
+        ];
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 1);
+        assert_eq!(books.len(), 2);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_keeps_volumes_apart() {
+        let mut books = vec![
+            make_book("The Art of Computer Programming, Volume 1", Some("Donald E. Knuth"), Source::Kindle),
+            make_book("The Art of Computer Programming, Volume 2", Some("Donald E. Knuth"), Source::Kindle),
+            make_book("The Wheel of Time Book 1", Some("Robert Jordan"), Source::AppleBooks),
+            make_book("The Wheel of Time Book 2", Some("Robert Jordan"), Source::AppleBooks),
+            make_book("Principia Mathematica Vol. I", Some("Alfred North Whitehead"), Source::Calibre),
+            make_book("Principia Mathematica Vol. II", Some("Alfred North Whitehead"), Source::Calibre),
+        ];
+
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 0);
+        assert_eq!(books.len(), 6);
+    }
+
+    #[test]
+    fn test_fuzzy_merge_ignores_roman_looking_words() {
+        // A dropped "I" is a typo, not a volume number
+        let mut books = vec![
+            make_book("What I Talk About When I Talk About Running", Some("Haruki Murakami"), Source::Kindle),
+            make_book("What I Talk About When Talk About Running", Some("Haruki Murakami"), Source::AppleBooks),
+        ];
+
+        assert_eq!(fuzzy_merge(&mut books, MergeOptions::default()), 1);
+    }
+
+    #[test]
+    fn test_title_numbering() {
+        assert_eq!(numbering("Principia Mathematica Vol. I"), vec!["volume", "1"]);
+        assert_eq!(numbering("Principia Mathematica, Volume One"), vec!["volume", "1"]);
+        assert_eq!(numbering("The Wheel of Time Book 2"), vec!["book", "2"]);
+        assert_eq!(numbering("Catch-22: 50th Anniversary Edition"), vec!["22", "50"]);
+        assert_eq!(numbering("One Hundred Years of Solitude"), Vec::<String>::new());
+        assert_eq!(numbering("Star Wars IV: A New Hope"), vec!["4"]);
+        assert_eq!(numbering("I Am Legend"), Vec::<String>::new());
+        assert_eq!(numbering("Malcolm X Speaks"), Vec::<String>::new());
+        assert_eq!(roman_value("xliv"), Some(44));
+        assert_eq!(roman_value("mix"), None);
+    }
 }
@@ -1,10 +1,19 @@
+use crate::apple_books::cfi::Cfi;
+use crate::error::Error;
 use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
 
 /// The complete library export containing all books and highlights
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Library {
+    #[serde(default = "Utc::now")]
     pub exported_at: DateTime<Utc>,
+    #[serde(default)]
     pub books: Vec<Book>,
 }
 
@@ -16,39 +25,306 @@ pub struct Book {
     pub title: String,
     pub author: Option<String>,
     /// Which platforms this book was found on
+    #[serde(default)]
     pub sources: Vec<Source>,
+    #[serde(default)]
     pub highlights: Vec<Highlight>,
     pub finished: Option<bool>,
     pub finished_at: Option<DateTime<Utc>>,
+    /// Web address, for articles imported from read-later services
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<String>,
+    /// Shelves or collections the book belongs to, e.g. Apple Books' "Want to Read"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<String>,
+    /// Highlights the source holds but wouldn't export, e.g. past a Kindle clipping limit
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unavailable_highlights: usize,
+    /// The source showed only some of the book's highlights, e.g. a publisher limit on
+    /// the Kindle web notebook
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub truncated: bool,
+    /// The title exactly as the source gave it, when it was cleaned up for matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_title: Option<String>,
+    /// Titles of other books merged into this one because they look like the same book,
+    /// e.g. an edition's longer title from another source
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Cover image, a URL as scraped or a path relative to the library once downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_url: Option<String>,
+    /// The book's identifiers on each source, e.g. `"asin"` for Kindle or `"apple_books"`
+    /// for the Apple Books asset ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub source_ids: BTreeMap<String, String>,
+    /// Whether it is a book or a personal document, when the source tells them apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_type: Option<DocumentType>,
+    /// How far through the book the reader is, from 0.0 to 1.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f32>,
+    /// When the book was last opened, e.g. Apple Books' `ZLASTOPENDATE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened_at: Option<DateTime<Utc>>,
+    /// Language code as the source gives it, e.g. "en" or "de-DE"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+}
+
+/// What kind of item a `Book` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    Book,
+    /// Sent to the device rather than bought, e.g. with Send to Kindle
+    PersonalDocument,
+}
+
+fn one_or_more_sources<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Source>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Sources {
+        One(Source),
+        More(Vec<Source>),
+    }
+
+    Ok(match Sources::deserialize(deserializer)? {
+        Sources::One(source) => vec![source],
+        Sources::More(sources) => sources,
+    })
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
 }
 
 /// A single highlight or annotation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Highlight {
-    /// From source DB, or generated UUID
+    /// From the source DB (e.g. Apple Books' annotation UUID), else derived from the
+    /// book, text and position by `generate_highlight_id`
     pub id: String,
     pub text: String,
     pub note: Option<String>,
+    #[serde(default)]
     pub location: Location,
     pub created_at: Option<DateTime<Utc>>,
-    /// Which platform this highlight came from
-    pub source: Source,
+    /// Which platforms have this highlight, the one it was first read from first;
+    /// older files with a single `source` load as one entry
+    #[serde(alias = "source", deserialize_with = "one_or_more_sources")]
+    pub sources: Vec<Source>,
+    /// Highlight color, e.g. "yellow", where the source records one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Whether this is a highlighted passage or a note on its own
+    #[serde(default, skip_serializing_if = "HighlightKind::is_highlight")]
+    pub kind: HighlightKind,
+    /// How the passage is marked, where the source tells underlines from highlights
+    #[serde(default, skip_serializing_if = "HighlightStyle::is_highlight")]
+    pub style: HighlightStyle,
+    /// The passage around the highlighted text, where the source records one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Seen in an earlier sync but missing from the source since; kept in the library
+    /// as a tombstone, left out of other formats unless asked for
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deleted_upstream: bool,
+}
+
+/// Kind of annotation a highlight record holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightKind {
+    /// A highlighted passage, possibly with a note attached
+    #[default]
+    Highlight,
+    /// A note that couldn't be attached to a highlight; `note` holds it and `text` is empty
+    Note,
+    /// A bookmarked position; `text` is empty
+    Bookmark,
+}
+
+/// How a passage is marked in the book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightStyle {
+    #[default]
+    Highlight,
+    /// Underlined rather than highlighted, e.g. Apple Books' underline style
+    Underline,
+}
+
+impl HighlightStyle {
+    pub fn is_highlight(&self) -> bool {
+        *self == HighlightStyle::Highlight
+    }
+}
+
+impl Highlight {
+    /// The highlighted text, or the note body for a standalone note
+    pub fn body(&self) -> &str {
+        match self.kind {
+            HighlightKind::Note => self.note.as_deref().unwrap_or_default(),
+            _ => &self.text,
+        }
+    }
+}
+
+impl HighlightKind {
+    pub fn is_highlight(&self) -> bool {
+        *self == HighlightKind::Highlight
+    }
 }
 
 /// Location information for a highlight
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Location {
     pub chapter: Option<String>,
     /// Opaque string, format varies by source
     pub position: Option<String>,
+    /// Printed page number, for sources that record one alongside `position`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+    /// The source's own location when `position` is derived from it, e.g. an EPUB CFI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<String>,
+}
+
+impl Location {
+    /// Page and position for display, e.g. "p. 45 · loc 678-690"
+    ///
+    /// Numeric positions are shown as Kindle locations unless they're labelled as a
+    /// page ("Page 57"); anything else, such as an EPUB CFI, is shown as stored.
+    pub fn label(&self) -> Option<String> {
+        let parts: Vec<String> = self
+            .page
+            .iter()
+            .map(|page| format!("p. {}", page))
+            .chain(self.position.as_deref().map(position_label))
+            .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" · "))
+        }
+    }
+
+    /// Sort key for reading order: Kindle locations by their start, then page-based
+    /// positions by page, then positions that aren't numbers, then none at all
+    ///
+    /// Locations and pages count different things, so they're never interleaved.
+    pub fn reading_order(&self) -> (u8, u64) {
+        match self.position.as_deref() {
+            Some(position) => match parse_labelled_position(position) {
+                Some((true, start)) => (1, start),
+                Some((false, start)) => (0, start),
+                None => (2, 0),
+            },
+            None => match self.page.as_deref().and_then(parse_position_start) {
+                Some(page) => (1, page),
+                None => (3, 0),
+            },
+        }
+    }
 }
 
 /// Source platform for books and highlights
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+///
+/// Serialized as its snake_case name. Names this version doesn't know, e.g. from an
+/// external import command, are kept as `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Source {
     AppleBooks,
     Kindle,
+    Kobo,
+    GooglePlayBooks,
+    Pocket,
+    Instapaper,
+    KoReader,
+    MoonReader,
+    Calibre,
+    Hypothesis,
+    Readwise,
+    Libby,
+    Goodreads,
+    AnnotatedJson,
+    /// A source without a variant of its own
+    Other(String),
+}
+
+impl Source {
+    /// Known source for a name, or `Other` with the name as given
+    pub fn from_name(name: &str) -> Source {
+        name.parse().unwrap_or_else(|_| Source::Other(name.to_string()))
+    }
+
+    /// Serialized name of the source, as used in the JSON output
+    pub fn as_str(&self) -> &str {
+        match self {
+            Source::AppleBooks => "apple_books",
+            Source::Kindle => "kindle",
+            Source::Kobo => "kobo",
+            Source::GooglePlayBooks => "google_play_books",
+            Source::Pocket => "pocket",
+            Source::Instapaper => "instapaper",
+            Source::KoReader => "koreader",
+            Source::MoonReader => "moon_reader",
+            Source::Calibre => "calibre",
+            Source::Hypothesis => "hypothesis",
+            Source::Readwise => "readwise",
+            Source::Libby => "libby",
+            Source::Goodreads => "goodreads",
+            Source::AnnotatedJson => "annotated_json",
+            Source::Other(name) => name,
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Source {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Source::from_name(&name))
+    }
+}
+
+impl std::str::FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "apple_books" => Ok(Source::AppleBooks),
+            "kindle" => Ok(Source::Kindle),
+            "kobo" => Ok(Source::Kobo),
+            "google_play_books" | "play_books" => Ok(Source::GooglePlayBooks),
+            "pocket" => Ok(Source::Pocket),
+            "instapaper" => Ok(Source::Instapaper),
+            "koreader" => Ok(Source::KoReader),
+            "moon_reader" | "moonreader" => Ok(Source::MoonReader),
+            "calibre" => Ok(Source::Calibre),
+            "hypothesis" => Ok(Source::Hypothesis),
+            "readwise" => Ok(Source::Readwise),
+            "libby" | "overdrive" => Ok(Source::Libby),
+            "goodreads" => Ok(Source::Goodreads),
+            "annotated_json" => Ok(Source::AnnotatedJson),
+            other => Err(format!("unknown source: {}", other)),
+        }
+    }
 }
 
 impl Book {
@@ -63,8 +339,34 @@ impl Book {
             highlights: Vec::new(),
             finished: None,
             finished_at: None,
+            url: None,
+            isbn: None,
+            collections: Vec::new(),
+            unavailable_highlights: 0,
+            truncated: false,
+            original_title: None,
+            aliases: Vec::new(),
+            cover_url: None,
+            source_ids: BTreeMap::new(),
+            document_type: None,
+            progress: None,
+            last_opened_at: None,
+            language: None,
+            genre: None,
         }
     }
+
+    /// The book's Kindle ASIN, if known
+    pub fn asin(&self) -> Option<&str> {
+        self.source_ids.get("asin").map(String::as_str)
+    }
+
+    /// Put highlights in reading order, keeping the current order among equals
+    ///
+    /// See `Location::reading_order`.
+    pub fn sort_highlights_by_position(&mut self) {
+        self.highlights.sort_by_cached_key(|h| h.location.reading_order());
+    }
 }
 
 impl Library {
@@ -75,6 +377,48 @@ impl Library {
             books: Vec::new(),
         }
     }
+
+    /// Load a previously written library JSON file
+    ///
+    /// Tolerates files from older or newer versions: missing optional fields take their
+    /// defaults, unknown fields are ignored, and a bare array of books is accepted.
+    pub fn load(path: &Path) -> Result<Library, Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum LibraryFile {
+            Library(Library),
+            Books(Vec<Book>),
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| Error::LibraryRead(path.to_path_buf(), e))?;
+
+        match serde_json::from_str(&content).map_err(|e| Error::LibraryParse(path.to_path_buf(), e))? {
+            LibraryFile::Library(library) => Ok(library),
+            LibraryFile::Books(books) => Ok(Library {
+                exported_at: Utc::now(),
+                books,
+            }),
+        }
+    }
+
+    /// Sort books by ID and highlights by location then text
+    ///
+    /// Gives a stable ordering regardless of the order sources returned data in,
+    /// so repeated exports of unchanged data produce identical output.
+    pub fn sort_canonical(&mut self) {
+        self.books.sort_by(|a, b| a.id.cmp(&b.id));
+
+        for book in &mut self.books {
+            // EPUB CFIs first, in the order `apple_books` sorts them (their display
+            // positions, "ch. 13, ¶3", don't sort as strings), then reading order (see
+            // `Location::reading_order`), then other positions by their string; the key
+            // is computed once per highlight
+            book.highlights.sort_by_cached_key(|h| {
+                let cfi = h.location.raw.as_deref().and_then(Cfi::parse).map(|cfi| cfi.sort_key());
+                (cfi.is_none(), cfi, h.location.reading_order(), h.location.position.clone(), h.text.clone())
+            });
+        }
+    }
 }
 
 impl Default for Library {
@@ -83,13 +427,87 @@ impl Default for Library {
     }
 }
 
+/// Split a numeric position into whether it's a page and its number or range,
+/// e.g. "Page 57" or "Location 1,234-1,240"; unlabelled numbers are locations
+fn split_position(position: &str) -> Option<(bool, String)> {
+    // Compiled once: this runs for every highlight when sorting large libraries
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"(?i)^(page|p\.|location|loc\.?)?\s*([\d,]+(?:\s*-\s*[\d,]+)?)$").unwrap()
+    });
+    let caps = re.captures(position.trim())?;
+
+    let is_page = caps.get(1).is_some_and(|unit| unit.as_str().to_lowercase().starts_with('p'));
+    Some((is_page, caps[2].replace([',', ' '], "")))
+}
+
+/// Whether a numeric position is a page, and its start, e.g. (true, 57) for "Page 57"
+/// and (false, 1234) for "Location 1,234-1,240"
+pub fn parse_labelled_position(position: &str) -> Option<(bool, u64)> {
+    let (is_page, number) = split_position(position)?;
+    let start = number.split('-').next()?.parse().ok()?;
+    Some((is_page, start))
+}
+
+/// Display form of a position: "p. 57" for pages, "loc 1234" for numeric locations
+fn position_label(position: &str) -> String {
+    match split_position(position) {
+        Some((true, number)) => format!("p. {}", number),
+        Some((false, number)) => format!("loc {}", number),
+        None => position.to_string(),
+    }
+}
+
+/// Parse the numeric start of a position like "123-145", "Location 1,234" or "page 45"
+///
+/// Returns None for positions that aren't a plain number or range, such as EPUB CFIs.
+pub fn parse_position_start(position: &str) -> Option<u64> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:[A-Za-z.]+\s*)?([\d,]+)(?:\s*-\s*[\d,]+)?$").unwrap()
+    });
+
+    re.captures(position.trim())
+        .and_then(|caps| caps.get(1))
+        .and_then(|m| m.as_str().replace(',', "").parse().ok())
+}
+
+/// Parse a position like "123-145" or "Location 1,234" into an inclusive numeric range
+///
+/// A single number gives a one-position range. Abbreviated ends written by older
+/// Kindles ("1234-38") are expanded using the start's leading digits.
+pub fn parse_position_range(position: &str) -> Option<(u64, u64)> {
+    // Compiled once: merging compares the ranges of every pair of nearby highlights
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:[A-Za-z.]+\s*)?([\d,]+)(?:\s*-\s*([\d,]+))?$").unwrap()
+    });
+    let caps = re.captures(position.trim())?;
+
+    let start_digits = caps[1].replace(',', "");
+    let start: u64 = start_digits.parse().ok()?;
+
+    let Some(end) = caps.get(2) else {
+        return Some((start, start));
+    };
+    let end_digits = end.as_str().replace(',', "");
+    let mut end: u64 = end_digits.parse().ok()?;
+
+    if end < start && end_digits.len() < start_digits.len() {
+        let prefix = &start_digits[..start_digits.len() - end_digits.len()];
+        end = format!("{}{}", prefix, end_digits).parse().ok()?;
+    }
+
+    Some((start, end.max(start)))
+}
+
 /// Generate a book ID from title and author
 /// Uses SHA256(lowercase(title + author))[:16]
 pub fn generate_book_id(title: &str, author: Option<&str>) -> String {
     use sha2::{Digest, Sha256};
 
     let normalized_title = title.trim().to_lowercase();
-    let normalized_author = author.map(|a| a.trim().to_lowercase()).unwrap_or_default();
+    let normalized_author = author.map(|a| normalize_author(a).to_lowercase()).unwrap_or_default();
 
     let input = format!("{}{}", normalized_title, normalized_author);
     let hash = Sha256::digest(input.as_bytes());
@@ -98,6 +516,82 @@ pub fn generate_book_id(title: &str, author: Option<&str>) -> String {
     hex::encode(&hash[..8])
 }
 
+/// Normalize an author string for matching across sources
+///
+/// Splits multiple authors on ";" and " & ", turns a "Last, First" name into
+/// "First Last", drops honorifics like "Dr." or "PhD" and sorts the names, so
+/// "Fitzgerald, F. Scott" and "F. Scott Fitzgerald" normalize the same.
+pub fn normalize_author(author: &str) -> String {
+    const PREFIXES: [&str; 8] = ["dr", "prof", "professor", "sir", "dame", "mr", "mrs", "ms"];
+    const SUFFIXES: [&str; 5] = ["phd", "ph.d", "md", "dphil", "esq"];
+
+    let is = |word: &str, list: &[&str]| list.contains(&word.trim_end_matches('.').to_lowercase().as_str());
+
+    let mut names: Vec<String> = author
+        .split(';')
+        .flat_map(|part| part.split(" & "))
+        .map(|name| {
+            // Comma-separated pieces: "Last, First", plus any "Jr." or "PhD" pieces
+            let mut pieces = Vec::new();
+            let mut generational = None;
+            for piece in name.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                if is(piece, &SUFFIXES) {
+                    continue;
+                } else if is(piece, &["jr", "sr"]) {
+                    generational = Some(piece);
+                } else {
+                    pieces.push(piece);
+                }
+            }
+            if let [last, first] = pieces[..] {
+                pieces = vec![first, last];
+            }
+            pieces.extend(generational);
+
+            let joined = pieces.join(" ");
+            let words: Vec<&str> = joined.split_whitespace().collect();
+            let start = words.iter().take_while(|w| is(w, &PREFIXES)).count();
+            let end = words.len() - words[start..].iter().rev().take_while(|w| is(w, &SUFFIXES)).count();
+
+            words[start..end].join(" ")
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    names.sort_by_key(|name| name.to_lowercase());
+    names.join("; ")
+}
+
+/// Generate a book ID for a web article from its URL
+/// Keyed on the URL so articles never merge with same-titled books
+pub fn generate_article_id(url: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let normalized_url = url.trim().trim_end_matches('/');
+
+    let input = format!("url:{}", normalized_url);
+    let hash = Sha256::digest(input.as_bytes());
+
+    hex::encode(&hash[..8])
+}
+
+/// Generate a stable highlight ID for sources without native IDs
+/// Uses SHA256(book_id + normalized text + position)[:16]
+pub fn generate_highlight_id(book_id: &str, text: &str, position: Option<&str>) -> String {
+    use sha2::{Digest, Sha256};
+
+    let normalized_text = text
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let input = format!("{}\n{}\n{}", book_id, normalized_text, position.unwrap_or_default());
+    let hash = Sha256::digest(input.as_bytes());
+
+    hex::encode(&hash[..8])
+}
+
 /// Simple hex encoding for the hash
 mod hex {
     pub fn encode(bytes: &[u8]) -> String {
@@ -108,6 +602,7 @@ mod hex {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn test_generate_book_id() {
@@ -123,6 +618,309 @@ mod tests {
         assert_eq!(id1.len(), 16);
     }
 
+    #[test]
+    fn test_generate_highlight_id() {
+        let id1 = generate_highlight_id("abc", "Some  highlighted text", Some("123-145"));
+        let id2 = generate_highlight_id("abc", "some highlighted text", Some("123-145"));
+        let id3 = generate_highlight_id("abc", "some highlighted text", Some("200"));
+
+        assert_eq!(id1, id2);
+        assert_ne!(id1, id3);
+        assert_eq!(id1.len(), 16);
+    }
+
+    #[test]
+    fn test_generate_article_id() {
+        let id1 = generate_article_id("https://example.com/essay/");
+        let id2 = generate_article_id("https://example.com/essay");
+
+        assert_eq!(id1, id2);
+        assert_ne!(id1, generate_book_id("https://example.com/essay", None));
+    }
+
+    #[test]
+    fn test_parse_position_start() {
+        assert_eq!(parse_position_start("123-145"), Some(123));
+        assert_eq!(parse_position_start("45"), Some(45));
+        assert_eq!(parse_position_start("Location 1,234"), Some(1234));
+        assert_eq!(parse_position_start("Page 57"), Some(57));
+        assert_eq!(parse_position_start("epubcfi(/6/4[chap01]!/4/2/1:0)"), None);
+    }
+
+    #[test]
+    fn test_parse_position_range() {
+        assert_eq!(parse_position_range("123-145"), Some((123, 145)));
+        assert_eq!(parse_position_range("145"), Some((145, 145)));
+        assert_eq!(parse_position_range("Location 1,234-1,240"), Some((1234, 1240)));
+        assert_eq!(parse_position_range("1234-38"), Some((1234, 1238)));
+        assert_eq!(parse_position_range("epubcfi(/6/4[chap01]!/4/2/1:0)"), None);
+    }
+
+    #[test]
+    fn test_location_label() {
+        let location = |page: Option<&str>, position: Option<&str>| Location {
+            chapter: None,
+            position: position.map(String::from),
+            page: page.map(String::from),
+            raw: None,
+        };
+
+        assert_eq!(location(None, Some("Location 1,234")).label().as_deref(), Some("loc 1234"));
+        assert_eq!(location(None, Some("123-145")).label().as_deref(), Some("loc 123-145"));
+        assert_eq!(location(None, Some("Page 57")).label().as_deref(), Some("p. 57"));
+        assert_eq!(location(Some("12"), Some("Location 100")).label().as_deref(), Some("p. 12 · loc 100"));
+        assert_eq!(location(None, Some("Page xii")).label().as_deref(), Some("Page xii"));
+        assert_eq!(location(None, Some("3:1520")).label().as_deref(), Some("3:1520"));
+        assert_eq!(location(None, None).label(), None);
+    }
+
+    #[test]
+    fn test_sort_highlights_by_position() {
+        let mut book = Book::new("Walden".to_string(), None);
+        for (text, page, position) in [
+            ("unplaced", None, None),
+            ("cfi", None, Some("epubcfi(/6/4!/4/2/1:0)")),
+            ("page 9", None, Some("Page 9")),
+            ("loc 1,234", None, Some("Location 1,234")),
+            ("page 3", Some("3"), None),
+            ("loc 200", None, Some("200-210")),
+            ("loc 1,234 again", None, Some("1234")),
+        ] {
+            book.highlights.push(Highlight {
+                id: text.to_string(),
+                text: text.to_string(),
+                note: None,
+                location: Location {
+                    chapter: None,
+                    position: position.map(String::from),
+                    page: page.map(String::from),
+                    raw: None,
+                },
+                created_at: None,
+                sources: vec![Source::Kindle],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+
+        book.sort_highlights_by_position();
+        let order: Vec<&str> = book.highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(
+            order,
+            vec!["loc 200", "loc 1,234", "loc 1,234 again", "page 3", "page 9", "cfi", "unplaced"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_sources_read_old_single_source() {
+        let old: Highlight = serde_json::from_str(r#"{"id": "h1", "text": "t", "note": null, "created_at": null, "source": "kindle"}"#).unwrap();
+        assert_eq!(old.sources, vec![Source::Kindle]);
+
+        let json = serde_json::to_value(&old).unwrap();
+        assert_eq!(json["sources"], serde_json::json!(["kindle"]));
+        assert!(json.get("source").is_none());
+
+        let new: Highlight =
+            serde_json::from_str(r#"{"id": "h1", "text": "t", "note": null, "created_at": null, "sources": ["kindle", "apple_books"]}"#)
+                .unwrap();
+        assert_eq!(new.sources, vec![Source::Kindle, Source::AppleBooks]);
+    }
+
+    #[test]
+    fn test_canonical_output_is_stable() {
+        use crate::kindle::clippings::parse_clippings_content;
+
+        let content = r#"
+Walden (Henry David Thoreau)
+- Your Highlight on Location 200-210 | Added on Monday, January 1, 2024
+
+I went to the woods because I wished to live deliberately.
+==========
+The Great Gatsby (F. Scott Fitzgerald)
+- Your Highlight on Location 1500-1510 | Added on Monday, January 1, 2024
+
+So we beat on, boats against the current.
+==========
+The Great Gatsby (F. Scott Fitzgerald)
+- Your Highlight on Location 30-45 | Added on Monday, January 1, 2024
+
+In my younger and more vulnerable years my father gave me some advice.
+==========
+"#;
+
+        let export = || {
+            let mut library = Library::new();
+            library.books = parse_clippings_content(content).unwrap();
+            library.exported_at = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+            library.sort_canonical();
+            serde_json::to_string_pretty(&library).unwrap()
+        };
+
+        let first = export();
+        let second = export();
+        assert_eq!(first, second);
+
+        // Highlights are ordered numerically, not lexicographically
+        let first = first.find("In my younger").unwrap();
+        let second = export().find("So we beat on").unwrap();
+        assert!(first < second);
+    }
+
+    #[test]
+    fn test_canonical_order_keeps_pages_after_locations() {
+        let mut book = Book::new("Walden".to_string(), None);
+        for (text, position) in [("page 3", "Page 3"), ("loc 900", "900"), ("loc 40", "Location 40")] {
+            book.highlights.push(Highlight {
+                id: text.to_string(),
+                text: text.to_string(),
+                note: None,
+                location: Location {
+                    chapter: None,
+                    position: Some(position.to_string()),
+                    page: None,
+                    raw: None,
+                },
+                created_at: None,
+                sources: vec![Source::Kindle],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+
+        let mut library = Library::new();
+        library.books.push(book);
+        library.sort_canonical();
+        let order: Vec<&str> = library.books[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(order, vec!["loc 40", "loc 900", "page 3"]);
+    }
+
+    #[test]
+    fn test_canonical_order_follows_cfis() {
+        let mut book = Book::new("Walden".to_string(), None);
+        for raw in ["epubcfi(/6/26!/4/2/1:0)", "epubcfi(/6/4!/4/20/1:0)", "epubcfi(/6/4!/4/10/1:0)"] {
+            let position = Cfi::parse(raw).unwrap().to_string();
+            book.highlights.push(Highlight {
+                id: raw.to_string(),
+                text: position.clone(),
+                note: None,
+                location: Location {
+                    chapter: None,
+                    position: Some(position),
+                    page: None,
+                    raw: Some(raw.to_string()),
+                },
+                created_at: None,
+                sources: vec![Source::AppleBooks],
+                color: None,
+                kind: HighlightKind::Highlight,
+                style: HighlightStyle::Highlight,
+                context: None,
+                deleted_upstream: false,
+            });
+        }
+
+        let mut library = Library::new();
+        library.books.push(book);
+        library.sort_canonical();
+        let order: Vec<&str> = library.books[0].highlights.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(order, vec!["ch. 2, ¶5", "ch. 2, ¶10", "ch. 13, ¶1"]);
+    }
+
+    #[test]
+    fn test_load_tolerates_older_files() {
+        let path = std::env::temp_dir().join(format!("readingsync_library_{}.json", uuid::Uuid::new_v4()));
+
+        // Older export: no sources, no highlight locations, plus an unknown field
+        std::fs::write(
+            &path,
+            r#"{"exported_at":"2023-01-01T00:00:00Z","version":1,"books":[
+                {"id":"abc","title":"Walden","author":null,"highlights":[
+                    {"id":"h1","text":"Simplify, simplify.","note":null,"created_at":null,"source":"kindle"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let library = Library::load(&path).unwrap();
+
+        assert_eq!(library.exported_at, Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(library.books.len(), 1);
+        assert!(library.books[0].sources.is_empty());
+        assert_eq!(library.books[0].highlights[0].location.position, None);
+        assert_eq!(library.books[0].highlights[0].style, HighlightStyle::Highlight);
+
+        // A bare array of books
+        std::fs::write(&path, r#"[{"id":"abc","title":"Walden","author":null}]"#).unwrap();
+        let library = Library::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(library.books.len(), 1);
+        assert!(library.books[0].highlights.is_empty());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_json() {
+        let path = std::env::temp_dir().join(format!("readingsync_library_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not json").unwrap();
+
+        let result = Library::load(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(result, Err(Error::LibraryParse(_, _))));
+    }
+
+    #[test]
+    fn test_highlight_style_serde() {
+        let mut highlight: Highlight = serde_json::from_str(
+            r#"{"id":"h1","text":"Simplify, simplify.","note":null,"created_at":null,"source":"apple_books"}"#,
+        )
+        .unwrap();
+        // Plain highlights leave the field out, as they did before it existed
+        assert!(!serde_json::to_string(&highlight).unwrap().contains("style"));
+
+        highlight.style = HighlightStyle::Underline;
+        let json = serde_json::to_string(&highlight).unwrap();
+        assert!(json.contains(r#""style":"underline""#));
+        assert_eq!(serde_json::from_str::<Highlight>(&json).unwrap().style, HighlightStyle::Underline);
+    }
+
+    #[test]
+    fn test_source_serde() {
+        assert_eq!(serde_json::to_string(&Source::KoReader).unwrap(), "\"koreader\"");
+        assert_eq!(serde_json::from_str::<Source>("\"apple_books\"").unwrap(), Source::AppleBooks);
+
+        let other: Source = serde_json::from_str("\"my_ereader\"").unwrap();
+        assert_eq!(other, Source::Other("my_ereader".to_string()));
+        assert_eq!(serde_json::to_string(&other).unwrap(), "\"my_ereader\"");
+    }
+
+    #[test]
+    fn test_normalize_author() {
+        assert_eq!(normalize_author("Fitzgerald, F. Scott"), "F. Scott Fitzgerald");
+        assert_eq!(normalize_author("  F. Scott   Fitzgerald "), "F. Scott Fitzgerald");
+        assert_eq!(normalize_author("Dr. Oliver Sacks"), "Oliver Sacks");
+        assert_eq!(normalize_author("Sacks, Oliver, MD"), "Oliver Sacks");
+        assert_eq!(normalize_author("Martin Luther King, Jr."), "Martin Luther King Jr.");
+        assert_eq!(
+            normalize_author("Pratchett, Terry;Gaiman, Neil"),
+            normalize_author("Neil Gaiman & Terry Pratchett")
+        );
+        assert_eq!(normalize_author("Terry Pratchett & Neil Gaiman"), "Neil Gaiman; Terry Pratchett");
+    }
+
+    #[test]
+    fn test_generate_book_id_normalizes_author() {
+        assert_eq!(
+            generate_book_id("The Great Gatsby", Some("Fitzgerald, F. Scott")),
+            generate_book_id("The Great Gatsby", Some("F. Scott Fitzgerald"))
+        );
+    }
+
     #[test]
     fn test_generate_book_id_no_author() {
         let id1 = generate_book_id("Some Book", None);
@@ -0,0 +1,216 @@
+use crate::error::MoonReaderError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::DateTime;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions of Moon+ Reader annotation exports
+const EXTENSIONS: [&str; 3] = ["an", "po", "mrexpt"];
+
+/// Number of lines in one annotation record
+const RECORD_FIELDS: usize = 15;
+
+/// Books imported from Moon+ Reader, with a count of records that couldn't be read
+#[derive(Debug, Default)]
+pub struct MoonReaderImport {
+    pub books: Vec<Book>,
+    pub skipped: usize,
+}
+
+/// Import a Moon+ Reader annotation export file, or every export file in a directory
+pub fn extract(path: &Path) -> Result<MoonReaderImport, MoonReaderError> {
+    if !path.exists() {
+        return Err(MoonReaderError::NotFound(path.to_path_buf()));
+    }
+
+    let mut files = Vec::new();
+    if path.is_dir() {
+        collect_exports(path, &mut files)?;
+        files.sort();
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut import = MoonReaderImport::default();
+
+    for file in files {
+        let content = fs::read_to_string(&file).map_err(|e| MoonReaderError::ReadError(file.clone(), e))?;
+        let parsed = parse_export(&content, &title_from_filename(&file));
+
+        import.skipped += parsed.skipped;
+        for book in parsed.books {
+            match import.books.iter_mut().find(|b| b.id == book.id) {
+                Some(existing) => existing.highlights.extend(book.highlights),
+                None => import.books.push(book),
+            }
+        }
+    }
+
+    Ok(import)
+}
+
+fn collect_exports(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), MoonReaderError> {
+    let entries = fs::read_dir(dir).map_err(|e| MoonReaderError::ReadError(dir.to_path_buf(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| MoonReaderError::ReadError(dir.to_path_buf(), e))?.path();
+
+        if path.is_dir() {
+            collect_exports(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Book title from an export file name, e.g. "Dune.epub.an" -> "Dune"
+fn title_from_filename(path: &Path) -> String {
+    let mut title = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+
+    for ext in [".epub", ".pdf", ".mobi", ".azw3", ".fb2", ".txt"] {
+        if title.to_lowercase().ends_with(ext) {
+            title.truncate(title.len() - ext.len());
+        }
+    }
+
+    title
+}
+
+/// Parse the contents of an annotation export
+///
+/// The file starts with a short header, then one record per annotation, each introduced
+/// by a `#` line and holding one field per line: id, book title, book path, lowercase
+/// path, chapter, split index, position, length, color, time (ms), bookmark, note, text,
+/// underline, strikethrough. Line breaks inside text are written as `<BR>`.
+///
+/// Each record's book title is used when present, falling back to `fallback_title`.
+/// Records with an unexpected shape are counted in `skipped`.
+pub fn parse_export(content: &str, fallback_title: &str) -> MoonReaderImport {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut import = MoonReaderImport::default();
+
+    // The first chunk is the header
+    for record in content.split("\n#\n").skip(1) {
+        let fields: Vec<&str> = record.trim_end_matches('\n').split('\n').collect();
+
+        if fields.len() != RECORD_FIELDS {
+            import.skipped += 1;
+            continue;
+        }
+
+        let text = decode(fields[12]);
+        let note = decode(fields[11]);
+
+        // Plain bookmarks carry no text
+        if text.is_empty() && note.is_empty() {
+            continue;
+        }
+
+        let title = fields[1].trim();
+        let title = if title.is_empty() { fallback_title } else { title };
+
+        let book = match import.books.iter_mut().position(|b| b.title == title) {
+            Some(index) => &mut import.books[index],
+            None => {
+                let mut book = Book::new(title.to_string(), None);
+                book.sources = vec![Source::MoonReader];
+                import.books.push(book);
+                import.books.last_mut().unwrap()
+            }
+        };
+
+        let position = format!("{}:{}", fields[4].trim(), fields[6].trim());
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, &text, Some(&position)),
+            text,
+            note: if note.is_empty() { None } else { Some(note) },
+            location: Location {
+                chapter: None,
+                position: Some(position),
+                page: None,
+                raw: None,
+            },
+            created_at: fields[9]
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .and_then(DateTime::from_timestamp_millis),
+            sources: vec![Source::MoonReader],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    import
+}
+
+/// Decode a record field: `<BR>` line breaks, surrounding whitespace trimmed
+fn decode(field: &str) -> String {
+    field.replace("<BR>", "\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn record(title: &str, time: &str, note: &str, text: &str) -> String {
+        format!(
+            "#\n1\n{title}\n/sdcard/Books/{title}.epub\n/sdcard/books/{title}.epub\n3\n0\n1520\n42\n-256\n{time}\n\n{note}\n{text}\n0\n0\n"
+        )
+    }
+
+    #[test]
+    fn test_parse_export() {
+        let content = format!(
+            "0\nindent:false\ntrim:false\n{}{}{}#\nshort\nrecord\n",
+            record("Dune", "1700000000000", "", "Fear is the<BR>mind-killer."),
+            record("Dune", "1700000100000", "Litany", "I must not fear."),
+            record("Dune", "1700000200000", "", ""),
+        );
+
+        let import = parse_export(&content, "fallback");
+
+        assert_eq!(import.skipped, 1);
+        assert_eq!(import.books.len(), 1);
+
+        let book = &import.books[0];
+        assert_eq!(book.title, "Dune");
+        assert_eq!(book.sources, vec![Source::MoonReader]);
+        assert_eq!(book.highlights.len(), 2);
+        assert_eq!(book.highlights[0].text, "Fear is the\nmind-killer.");
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("3:1520"));
+        assert_eq!(book.highlights[0].created_at, Utc.timestamp_opt(1700000000, 0).single());
+        assert_eq!(book.highlights[1].note.as_deref(), Some("Litany"));
+    }
+
+    #[test]
+    fn test_title_from_filename() {
+        assert_eq!(title_from_filename(Path::new("/exports/Dune.epub.an")), "Dune");
+        assert_eq!(title_from_filename(Path::new("Walden.po")), "Walden");
+    }
+
+    #[test]
+    fn test_extract_directory() {
+        let dir = std::env::temp_dir().join(format!("readingsync_moon_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Walden.epub.an"), format!("0\n{}", record("", "1700000000000", "", "Simplify."))).unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let import = extract(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(import.books.len(), 1);
+        assert_eq!(import.books[0].title, "Walden");
+    }
+}
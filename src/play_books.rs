@@ -0,0 +1,302 @@
+use crate::error::PlayBooksError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Import Google Play Books notes from a Takeout export
+///
+/// Accepts either the `Play Books` folder (notes are read from its `Notes` subfolder)
+/// or any directory of exported note documents. Files that aren't Play Books notes
+/// are skipped.
+pub fn extract_full(path: &Path) -> Result<Vec<Book>, PlayBooksError> {
+    if !path.is_dir() {
+        return Err(PlayBooksError::DirectoryNotFound(path.to_path_buf()));
+    }
+
+    let notes_dir = path.join("Notes");
+    let root = if notes_dir.is_dir() { notes_dir } else { path.to_path_buf() };
+
+    let mut files = Vec::new();
+    collect_html_files(&root, &mut files)?;
+    files.sort();
+
+    let mut books_by_id: HashMap<String, Book> = HashMap::new();
+
+    for file in files {
+        let html = fs::read_to_string(&file).map_err(|e| PlayBooksError::ReadError(file.clone(), e))?;
+
+        let Some(book) = parse_notes_document(&html, &file) else {
+            continue;
+        };
+
+        match books_by_id.get_mut(&book.id) {
+            Some(existing) => existing.highlights.extend(book.highlights),
+            None => {
+                books_by_id.insert(book.id.clone(), book);
+            }
+        }
+    }
+
+    Ok(books_by_id.into_values().collect())
+}
+
+/// Recursively collect `.html`/`.htm` files under a directory
+fn collect_html_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), PlayBooksError> {
+    let entries = fs::read_dir(dir).map_err(|e| PlayBooksError::ReadError(dir.to_path_buf(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| PlayBooksError::ReadError(dir.to_path_buf(), e))?.path();
+
+        if path.is_dir() {
+            collect_html_files(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse one exported notes document into a book
+///
+/// Returns None when the document contains no highlight blocks (i.e. isn't a notes file).
+///
+/// Both Docs layouts are handled: the newer one wraps each highlight in its own table
+/// under chapter headings, the older one uses a single table with chapter rows between
+/// highlight rows. In both, a highlight row holds the quoted text, an optional note and
+/// the date, with the page number in a separate cell.
+pub fn parse_notes_document(html: &str, path: &Path) -> Option<Book> {
+    let document = Html::parse_document(html);
+
+    let title = document_title(&document, path);
+    let author = document_author(&document);
+
+    let mut book = Book::new(title, author);
+    book.sources = vec![Source::GooglePlayBooks];
+
+    let blocks = Selector::parse("h1, h2, h3, tr").unwrap();
+    let cells = Selector::parse("td").unwrap();
+    let nested_table = Selector::parse("table").unwrap();
+
+    let mut chapter: Option<String> = None;
+
+    for element in document.select(&blocks) {
+        if element.value().name() != "tr" {
+            let heading = element_text(element);
+            if !heading.is_empty() && heading != book.title && !heading.starts_with("Notes from") {
+                chapter = Some(heading);
+            }
+            continue;
+        }
+
+        // Only innermost rows carry highlight content
+        if element.select(&nested_table).next().is_some() {
+            continue;
+        }
+
+        let mut lines = Vec::new();
+        let mut page = None;
+
+        for cell in element.select(&cells) {
+            let cell_lines = cell_lines(cell);
+            if let [only] = cell_lines.as_slice() {
+                if only.chars().all(|c| c.is_ascii_digit()) {
+                    page = Some(only.clone());
+                    continue;
+                }
+            }
+            lines.extend(cell_lines);
+        }
+
+        let date_index = lines.iter().position(|line| parse_date(line).is_some());
+
+        let Some(date_index) = date_index else {
+            // Older layout: a single-cell row without a date is a chapter heading
+            if let [heading] = lines.as_slice() {
+                chapter = Some(heading.clone());
+            }
+            continue;
+        };
+
+        let created_at = parse_date(&lines[date_index]);
+        lines.remove(date_index);
+
+        let mut lines = lines.into_iter();
+        let Some(text) = lines.next() else {
+            continue;
+        };
+        let note = lines.collect::<Vec<_>>().join("\n");
+        let position = page.map(|p| format!("Page {}", p));
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, &text, position.as_deref()),
+            text,
+            note: if note.is_empty() { None } else { Some(note) },
+            location: Location {
+                chapter: chapter.clone(),
+                position,
+                page: None,
+                raw: None,
+            },
+            created_at,
+            sources: vec![Source::GooglePlayBooks],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    if book.highlights.is_empty() {
+        return None;
+    }
+
+    Some(book)
+}
+
+/// Book title from the document title ("Notes from “Title”"), falling back to the file name
+fn document_title(document: &Html, path: &Path) -> String {
+    let selector = Selector::parse("title").unwrap();
+    let raw = document
+        .select(&selector)
+        .next()
+        .map(element_text)
+        .filter(|t| !t.is_empty())
+        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_default();
+
+    let title = raw.strip_prefix("Notes from").unwrap_or(&raw);
+    title
+        .trim()
+        .trim_matches(['"', '“', '”', '\''])
+        .trim()
+        .to_string()
+}
+
+/// Author from the "by Author" line in the document header
+fn document_author(document: &Html) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^(?i:by)\s+(.+)$").unwrap());
+    let selector = Selector::parse("p, span, h2, h3, div").unwrap();
+
+    document
+        .select(&selector)
+        .map(element_text)
+        .find_map(|text| re.captures(&text).map(|c| c[1].trim().to_string()))
+}
+
+/// Non-empty text lines of a table cell: its paragraphs, or the whole cell text
+fn cell_lines(cell: ElementRef) -> Vec<String> {
+    let paragraphs = Selector::parse("p").unwrap();
+
+    let lines: Vec<String> = cell.select(&paragraphs).map(element_text).collect();
+    let lines = if lines.is_empty() { vec![element_text(cell)] } else { lines };
+
+    lines.into_iter().filter(|l| !l.is_empty()).collect()
+}
+
+/// Element text with whitespace collapsed
+fn element_text(element: ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse Play Books note dates, e.g. "March 5, 2021" or "Mar 5, 2021"
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    ["%B %d, %Y", "%b %d, %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value.trim(), format).ok())
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const NEW_FORMAT: &str = r#"<html><head><title>Notes from “Middlemarch”</title></head><body>
+        <p><span>Middlemarch</span></p>
+        <p><span>by George Eliot</span></p>
+        <h2>Chapter 1</h2>
+        <table><tr>
+            <td><p>Miss Brooke had that kind of beauty.</p><p>Great opening</p><p>March 5, 2021</p></td>
+            <td><p><a href="https://play.google.com/books/reader?id=x&pg=GBS.PA3">3</a></p></td>
+        </tr></table>
+        <h2>Chapter 2</h2>
+        <table><tr>
+            <td><p>Her mind was theoretic.</p><p>Mar 6, 2021</p></td>
+            <td><p>10</p></td>
+        </tr></table>
+    </body></html>"#;
+
+    const OLD_FORMAT: &str = r#"<html><head><title>Notes from "Walden"</title></head><body>
+        <div>by Henry David Thoreau</div>
+        <table>
+            <tr><td>Economy</td></tr>
+            <tr><td>I went to the woods because I wished to live deliberately.</td><td>April 1, 2019</td><td>88</td></tr>
+        </table>
+    </body></html>"#;
+
+    #[test]
+    fn test_parse_new_format() {
+        let book = parse_notes_document(NEW_FORMAT, Path::new("Middlemarch.html")).unwrap();
+
+        assert_eq!(book.title, "Middlemarch");
+        assert_eq!(book.author.as_deref(), Some("George Eliot"));
+        assert_eq!(book.sources, vec![Source::GooglePlayBooks]);
+        assert_eq!(book.highlights.len(), 2);
+
+        let first = &book.highlights[0];
+        assert_eq!(first.text, "Miss Brooke had that kind of beauty.");
+        assert_eq!(first.note.as_deref(), Some("Great opening"));
+        assert_eq!(first.location.chapter.as_deref(), Some("Chapter 1"));
+        assert_eq!(first.location.position.as_deref(), Some("Page 3"));
+        assert_eq!(first.created_at, Utc.with_ymd_and_hms(2021, 3, 5, 0, 0, 0).single());
+
+        assert_eq!(book.highlights[1].location.chapter.as_deref(), Some("Chapter 2"));
+        assert_eq!(book.highlights[1].note, None);
+    }
+
+    #[test]
+    fn test_parse_old_format() {
+        let book = parse_notes_document(OLD_FORMAT, Path::new("Walden.html")).unwrap();
+
+        assert_eq!(book.title, "Walden");
+        assert_eq!(book.author.as_deref(), Some("Henry David Thoreau"));
+        assert_eq!(book.highlights.len(), 1);
+        assert_eq!(book.highlights[0].location.chapter.as_deref(), Some("Economy"));
+        assert_eq!(book.highlights[0].location.position.as_deref(), Some("Page 88"));
+    }
+
+    #[test]
+    fn test_extract_from_takeout_layout() {
+        let dir = std::env::temp_dir().join(format!("readingsync_play_books_{}", uuid::Uuid::new_v4()));
+        let notes = dir.join("Notes");
+        fs::create_dir_all(&notes).unwrap();
+        fs::write(notes.join("Notes from Middlemarch.html"), NEW_FORMAT).unwrap();
+        fs::write(notes.join("readme.txt"), "not a note").unwrap();
+        fs::write(dir.join("Library.html"), "<html><body><table><tr><td>x</td></tr></table></body></html>").unwrap();
+
+        let books = extract_full(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "Middlemarch");
+    }
+
+    #[test]
+    fn test_skips_documents_without_highlights() {
+        let html = "<html><head><title>My Library</title></head><body><p>Nothing here</p></body></html>";
+        assert!(parse_notes_document(html, Path::new("x.html")).is_none());
+    }
+}
@@ -0,0 +1,428 @@
+use crate::config::HttpConfig;
+use crate::error::PocketError;
+use crate::model::{generate_article_id, generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const API_URL: &str = "https://getpocket.com/v3/get";
+
+/// A saved article, normalized from either the export or the API
+#[derive(Debug, Default)]
+struct Article {
+    url: String,
+    title: Option<String>,
+    author: Option<String>,
+    highlights: Vec<ArticleHighlight>,
+}
+
+#[derive(Debug)]
+struct ArticleHighlight {
+    id: Option<String>,
+    quote: String,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// Article entry in the export's `annotations/*.json` files
+#[derive(Debug, Deserialize)]
+struct ExportArticle {
+    url: String,
+    title: Option<String>,
+    #[serde(default)]
+    highlights: Vec<ExportHighlight>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportHighlight {
+    quote: String,
+    created_at: Option<i64>,
+}
+
+/// Row of the export's saves CSV (`part_*.csv`)
+#[derive(Debug, Deserialize)]
+struct ExportSave {
+    title: Option<String>,
+    url: String,
+}
+
+/// Item in a `/v3/get` API response
+#[derive(Debug, Deserialize)]
+struct ApiItem {
+    resolved_title: Option<String>,
+    given_title: Option<String>,
+    resolved_url: Option<String>,
+    given_url: Option<String>,
+    authors: Option<HashMap<String, ApiAuthor>>,
+    domain_metadata: Option<ApiDomain>,
+    #[serde(default)]
+    annotations: Vec<ApiAnnotation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAuthor {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiDomain {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAnnotation {
+    annotation_id: Option<String>,
+    quote: String,
+    created_at: Option<String>,
+}
+
+/// Import highlights from a Pocket export (the ZIP or an extracted annotations JSON file)
+///
+/// Articles without highlights are skipped unless `include_unannotated` is set. Only the
+/// ZIP carries the full list of saves, so a bare JSON file yields annotated articles only.
+pub fn parse_export(path: &Path, include_unannotated: bool) -> Result<Vec<Book>, PocketError> {
+    if !path.exists() {
+        return Err(PocketError::FileNotFound(path.to_path_buf()));
+    }
+
+    let is_zip = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+
+    let articles = if is_zip {
+        read_export_zip(path)?
+    } else {
+        let content = fs::read_to_string(path).map_err(PocketError::ReadError)?;
+        parse_annotations_json(&content)?
+    };
+
+    Ok(articles_to_books(articles, include_unannotated))
+}
+
+/// Fetch saved articles and their highlights from the Pocket API
+pub fn fetch_api(
+    consumer_key: &str,
+    access_token: &str,
+    include_unannotated: bool,
+    http: &HttpConfig,
+) -> Result<Vec<Book>, PocketError> {
+    let body = serde_json::json!({
+        "consumer_key": consumer_key,
+        "access_token": access_token,
+        "state": "all",
+        "detailType": "complete",
+        "annotations": 1,
+    });
+
+    let response = crate::http::blocking_client(http)?
+        .post(API_URL)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Accept", "application/json")
+        .body(body.to_string())
+        .send()
+        .map_err(|e| crate::http::request_error(&e, API_URL, http))?;
+
+    if !response.status().is_success() {
+        let reason = response
+            .headers()
+            .get("X-Error")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .unwrap_or_else(|| response.status().to_string());
+        return Err(PocketError::ApiError(reason));
+    }
+
+    let articles = parse_api_response(&response.text()?)?;
+    Ok(articles_to_books(articles, include_unannotated))
+}
+
+/// Read annotations JSON and saves CSV files from the export ZIP
+fn read_export_zip(path: &Path) -> Result<Vec<Article>, PocketError> {
+    let file = fs::File::open(path).map_err(PocketError::ReadError)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    let mut annotated = Vec::new();
+    let mut saves = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_lowercase();
+
+        if !name.ends_with(".json") && !name.ends_with(".csv") {
+            continue;
+        }
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content).map_err(PocketError::ReadError)?;
+
+        if name.ends_with(".json") {
+            annotated.extend(parse_annotations_json(&content)?);
+        } else {
+            saves.extend(parse_saves_csv(&content)?);
+        }
+    }
+
+    // Saves without an annotations entry become unannotated articles
+    let annotated_urls: HashSet<String> = annotated.iter().map(|a| a.url.clone()).collect();
+    annotated.extend(saves.into_iter().filter(|s| !annotated_urls.contains(&s.url)));
+
+    Ok(annotated)
+}
+
+fn parse_annotations_json(content: &str) -> Result<Vec<Article>, PocketError> {
+    let entries: Vec<ExportArticle> = serde_json::from_str(content)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| Article {
+            url: entry.url,
+            title: entry.title,
+            author: None,
+            highlights: entry
+                .highlights
+                .into_iter()
+                .map(|h| ArticleHighlight {
+                    id: None,
+                    quote: h.quote,
+                    created_at: h.created_at.and_then(|ts| DateTime::from_timestamp(ts, 0)),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+fn parse_saves_csv(content: &str) -> Result<Vec<Article>, PocketError> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut articles = Vec::new();
+
+    for row in reader.deserialize() {
+        let save: ExportSave = row?;
+        articles.push(Article {
+            url: save.url,
+            title: save.title,
+            ..Default::default()
+        });
+    }
+
+    Ok(articles)
+}
+
+fn parse_api_response(content: &str) -> Result<Vec<Article>, PocketError> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+
+    // The API returns `"list": []` instead of an empty object when nothing is saved
+    let items: HashMap<String, ApiItem> = match value.get("list") {
+        Some(list) if list.is_object() => serde_json::from_value(list.clone())?,
+        _ => HashMap::new(),
+    };
+
+    let mut articles: Vec<Article> = items
+        .into_values()
+        .filter_map(|item| {
+            let url = item.resolved_url.filter(|u| !u.is_empty()).or(item.given_url)?;
+
+            // Prefer the byline, falling back to the site name
+            let byline = item.authors.map(|authors| {
+                let mut names: Vec<String> = authors.into_values().map(|a| a.name).collect();
+                names.sort();
+                names.join(", ")
+            });
+            let author = byline
+                .filter(|a| !a.is_empty())
+                .or_else(|| item.domain_metadata.and_then(|d| d.name));
+
+            Some(Article {
+                url,
+                title: item.resolved_title.filter(|t| !t.is_empty()).or(item.given_title),
+                author,
+                highlights: item
+                    .annotations
+                    .into_iter()
+                    .map(|a| ArticleHighlight {
+                        id: a.annotation_id,
+                        quote: a.quote,
+                        created_at: a.created_at.as_deref().and_then(parse_api_date),
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    articles.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(articles)
+}
+
+/// Parse API annotation dates, e.g. "2021-03-05 14:22:10"
+fn parse_api_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+/// Convert articles to books, keyed by URL so they stay distinct from same-titled books
+fn articles_to_books(articles: Vec<Article>, include_unannotated: bool) -> Vec<Book> {
+    articles
+        .into_iter()
+        .filter(|article| include_unannotated || !article.highlights.is_empty())
+        .map(|article| {
+            let id = generate_article_id(&article.url);
+            let title = article
+                .title
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| article.url.clone());
+
+            let highlights = article
+                .highlights
+                .into_iter()
+                .filter(|h| !h.quote.trim().is_empty())
+                .map(|h| Highlight {
+                    id: h.id.unwrap_or_else(|| generate_highlight_id(&id, &h.quote, None)),
+                    text: h.quote.trim().to_string(),
+                    note: None,
+                    location: Location {
+                        chapter: None,
+                        position: None,
+                        page: None,
+                        raw: None,
+                    },
+                    created_at: h.created_at,
+                    sources: vec![Source::Pocket],
+                    color: None,
+                    kind: HighlightKind::Highlight,
+                    style: HighlightStyle::Highlight,
+                    context: None,
+                    deleted_upstream: false,
+                })
+                .collect();
+
+            Book {
+                id,
+                title,
+                author: article.author,
+                sources: vec![Source::Pocket],
+                highlights,
+                finished: None,
+                finished_at: None,
+                url: Some(article.url),
+                isbn: None,
+                collections: Vec::new(),
+                unavailable_highlights: 0,
+                truncated: false,
+                original_title: None,
+                aliases: Vec::new(),
+                cover_url: None,
+                source_ids: BTreeMap::new(),
+                document_type: None,
+                progress: None,
+                last_opened_at: None,
+                language: None,
+                genre: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::io::Write;
+
+    const ANNOTATIONS_JSON: &str = r#"[
+        {
+            "url": "https://example.com/essay",
+            "title": "On Essays",
+            "highlights": [{"quote": "Essays are attempts.", "created_at": 1700000000}]
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse_annotations_json() {
+        let books = articles_to_books(parse_annotations_json(ANNOTATIONS_JSON).unwrap(), false);
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "On Essays");
+        assert_eq!(books[0].sources, vec![Source::Pocket]);
+        assert_eq!(books[0].highlights[0].text, "Essays are attempts.");
+        assert_eq!(books[0].highlights[0].created_at, Utc.timestamp_opt(1700000000, 0).single());
+    }
+
+    #[test]
+    fn test_parse_api_response() {
+        let response = r#"{
+            "status": 1,
+            "list": {
+                "1": {
+                    "resolved_title": "A Long Read",
+                    "resolved_url": "https://news.example.com/long-read",
+                    "authors": {"9": {"name": "Jane Doe"}},
+                    "domain_metadata": {"name": "Example News"},
+                    "annotations": [
+                        {"annotation_id": "abc", "quote": "A key passage.", "created_at": "2021-03-05 14:22:10"}
+                    ]
+                },
+                "2": {
+                    "given_title": "Saved For Later",
+                    "given_url": "https://blog.example.org/post",
+                    "domain_metadata": {"name": "Example Blog"}
+                }
+            }
+        }"#;
+
+        let books = articles_to_books(parse_api_response(response).unwrap(), false);
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].author.as_deref(), Some("Jane Doe"));
+        assert_eq!(books[0].highlights[0].id, "abc");
+        assert_eq!(books[0].highlights[0].created_at, Utc.with_ymd_and_hms(2021, 3, 5, 14, 22, 10).single());
+
+        let all = articles_to_books(parse_api_response(response).unwrap(), true);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].author.as_deref(), Some("Example Blog"));
+
+        // An empty account returns a list array rather than an object
+        assert!(parse_api_response(r#"{"status": 2, "list": []}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_export_zip_includes_unannotated_saves() {
+        let path = std::env::temp_dir().join(format!("readingsync_pocket_{}.zip", uuid::Uuid::new_v4()));
+        {
+            let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("annotations/part_000000.json", options).unwrap();
+            zip.write_all(ANNOTATIONS_JSON.as_bytes()).unwrap();
+            zip.start_file("part_000000.csv", options).unwrap();
+            zip.write_all(
+                b"title,url,time_added,tags,status\n\
+                  On Essays,https://example.com/essay,1700000000,,archive\n\
+                  Unread,https://example.com/unread,1700000001,,unread\n",
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+
+        let annotated = parse_export(&path, false).unwrap();
+        let all = parse_export(&path, true).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(annotated.len(), 1);
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|b| b.title == "Unread" && b.highlights.is_empty()));
+    }
+
+    #[test]
+    fn test_articles_stay_distinct_from_books() {
+        let article = Article {
+            url: "https://example.com/walden".to_string(),
+            title: Some("Walden".to_string()),
+            ..Default::default()
+        };
+        let books = articles_to_books(vec![article], true);
+
+        assert_ne!(books[0].id, Book::new("Walden".to_string(), None).id);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::ReadwiseError;
+use crate::model::{generate_highlight_id, Book, Highlight, HighlightKind, HighlightStyle, Location, Source};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Row of Readwise's CSV export
+#[derive(Debug, Deserialize)]
+struct ReadwiseRow {
+    #[serde(rename = "Highlight")]
+    highlight: String,
+    #[serde(rename = "Book Title")]
+    title: String,
+    #[serde(rename = "Book Author", default)]
+    author: String,
+    #[serde(rename = "Note", default)]
+    note: String,
+    #[serde(rename = "Location", default)]
+    location: String,
+    #[serde(rename = "Location Type", default)]
+    location_type: String,
+    #[serde(rename = "Highlighted at", default)]
+    highlighted_at: String,
+}
+
+/// Parse a Readwise CSV export into books
+///
+/// Book IDs come from title and author like every other source, so highlights that
+/// reached Readwise from Kindle or Apple Books merge with a fresh sync of the same book.
+pub fn parse_csv(path: &Path) -> Result<Vec<Book>, ReadwiseError> {
+    if !path.exists() {
+        return Err(ReadwiseError::FileNotFound(path.to_path_buf()));
+    }
+
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut books: Vec<Book> = Vec::new();
+    let mut index_by_id: HashMap<String, usize> = HashMap::new();
+
+    for row in reader.deserialize() {
+        let row: ReadwiseRow = row?;
+
+        let text = row.highlight.trim();
+        let title = row.title.trim();
+        if text.is_empty() || title.is_empty() {
+            continue;
+        }
+
+        let author = Some(row.author.trim()).filter(|a| !a.is_empty());
+        let book = Book::new(title.to_string(), author.map(String::from));
+
+        let index = *index_by_id.entry(book.id.clone()).or_insert_with(|| {
+            books.push(Book {
+                sources: vec![Source::Readwise],
+                ..book
+            });
+            books.len() - 1
+        });
+
+        let book = &mut books[index];
+        let position = format_position(&row.location, &row.location_type);
+        let note = row.note.trim();
+
+        book.highlights.push(Highlight {
+            id: generate_highlight_id(&book.id, text, position.as_deref()),
+            text: text.to_string(),
+            note: if note.is_empty() { None } else { Some(note.to_string()) },
+            location: Location {
+                chapter: None,
+                position,
+                page: None,
+                raw: None,
+            },
+            created_at: parse_timestamp(&row.highlighted_at),
+            sources: vec![Source::Readwise],
+            color: None,
+            kind: HighlightKind::Highlight,
+            style: HighlightStyle::Highlight,
+            context: None,
+            deleted_upstream: false,
+        });
+    }
+
+    Ok(books)
+}
+
+/// Combine Location and Location Type, e.g. ("1234", "location") -> "Location 1234"
+fn format_position(location: &str, location_type: &str) -> Option<String> {
+    let location = location.trim();
+    if location.is_empty() {
+        return None;
+    }
+
+    match location_type.trim().to_lowercase().as_str() {
+        "location" => Some(format!("Location {}", location)),
+        "page" => Some(format!("Page {}", location)),
+        _ => Some(location.to_string()),
+    }
+}
+
+/// Parse "Highlighted at" values, e.g. "2020-05-03 14:22:00" or RFC 3339
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|dt| dt.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kindle::clippings::parse_clippings_content;
+    use crate::merge::merge_books;
+    use chrono::TimeZone;
+
+    const EXPORT: &str = "Highlight,Book Title,Book Author,Amazon Book ID,Note,Color,Tags,Location Type,Location,Highlighted at,Document tags\n\
+        \"So we beat on, boats against the current.\",The Great Gatsby,F. Scott Fitzgerald,B000FC0PDA,,yellow,,location,1500,2020-05-03 14:22:00,\n\
+        \"In my younger and more vulnerable years\",The Great Gatsby,F. Scott Fitzgerald,B000FC0PDA,Opening,yellow,,page,1,,\n";
+
+    fn write_export() -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("readingsync_readwise_{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, EXPORT).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let path = write_export();
+        let books = parse_csv(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(books.len(), 1);
+        let book = &books[0];
+        assert_eq!(book.sources, vec![Source::Readwise]);
+        assert_eq!(book.highlights.len(), 2);
+
+        let first = &book.highlights[0];
+        assert_eq!(first.location.position.as_deref(), Some("Location 1500"));
+        assert_eq!(first.created_at, Utc.with_ymd_and_hms(2020, 5, 3, 14, 22, 0).single());
+
+        let second = &book.highlights[1];
+        assert_eq!(second.location.position.as_deref(), Some("Page 1"));
+        assert_eq!(second.note.as_deref(), Some("Opening"));
+        assert_eq!(second.created_at, None);
+    }
+
+    #[test]
+    fn test_merges_with_kindle_highlight() {
+        let path = write_export();
+        let readwise = parse_csv(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let kindle = parse_clippings_content(
+            "The Great Gatsby (F. Scott Fitzgerald)\n\
+             - Your Highlight on Location 1500-1510 | Added on Sunday, May 3, 2020 2:22:00 PM\n\
+             \n\
+             So we beat on, boats against the current.\n\
+             ==========\n",
+        )
+        .unwrap();
+
+        let merged = merge_books(vec![kindle, readwise]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sources, vec![Source::Kindle, Source::Readwise]);
+        assert_eq!(merged[0].highlights.len(), 2);
+        assert_eq!(
+            merged[0]
+                .highlights
+                .iter()
+                .filter(|h| h.text == "So we beat on, boats against the current.")
+                .count(),
+            1
+        );
+    }
+}